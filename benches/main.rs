@@ -26,7 +26,7 @@ mod benches {
     (check-sat)
     (get-model)
     "#;
-    let smt2 = solver_with_symbolic::parse(input);
+    let smt2 = solver_with_symbolic::parse(input).unwrap();
 
     b.iter(move || solver_with_symbolic::check_sat(smt2.clone()));
   }
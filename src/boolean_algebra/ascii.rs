@@ -0,0 +1,232 @@
+use super::{BoolAlg, NoElement, Predicate};
+use crate::transducer::term::{FunctionTerm, Lambda};
+use crate::util::Domain;
+use std::marker::PhantomData;
+
+/** the number of ASCII characters */
+pub const ASCII: u32 = 128;
+
+/** whether the character is ASCII, i.e. an AsciiSet can have it */
+pub fn is_ascii<D: Domain>(c: &D) -> bool {
+  let c: char = c.clone().into();
+  (c as u32) < ASCII
+}
+
+/**
+ * a predicate over ASCII characters as a 128-entry bitset,
+ * so that and, or and not are a few word operations.
+ * no character out of ASCII satisfies it, so it agrees with Predicate only when all strings are ASCII.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsciiSet<D: Domain> {
+  bits: [u64; 2],
+  domain: PhantomData<D>,
+}
+impl<D: Domain> AsciiSet<D> {
+  fn with_bits(bits: [u64; 2]) -> Self {
+    AsciiSet {
+      bits,
+      domain: PhantomData,
+    }
+  }
+
+  /** the set of ASCII characters satisfying f */
+  pub fn from_fn(f: impl Fn(&D) -> bool) -> Self {
+    let mut bits = [0; 2];
+    for code in 0..ASCII {
+      if f(&D::from(code as u8 as char)) {
+        bits[code as usize / 64] |= 1 << (code % 64);
+      }
+    }
+    Self::with_bits(bits)
+  }
+
+  fn contains_code(&self, code: u32) -> bool {
+    code < ASCII && (self.bits[code as usize / 64] & (1 << (code % 64))) != 0
+  }
+
+  /** the characters in the ascending order */
+  pub fn iter(&self) -> impl Iterator<Item = D> + '_ {
+    (0..ASCII)
+      .filter(move |code| self.contains_code(*code))
+      .map(|code| D::from(code as u8 as char))
+  }
+
+  pub fn len(&self) -> usize {
+    self
+      .bits
+      .iter()
+      .map(|word| word.count_ones() as usize)
+      .sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.bits == [0; 2]
+  }
+
+  /**
+   * the equivalent predicate assuming all strings are ASCII.
+   * sets of more than half of ASCII are the complement of the rest, so that
+   * get_one of the predicate prefers letters as it does for complements.
+   */
+  pub fn to_predicate(&self) -> Predicate<D> {
+    if self.len() > ASCII as usize / 2 {
+      self.not().ranges().not()
+    } else {
+      self.ranges()
+    }
+  }
+
+  /** the union of ranges of the characters */
+  fn ranges(&self) -> Predicate<D> {
+    let mut predicate = Predicate::bot();
+    let mut code = 0;
+    while code < ASCII {
+      if !self.contains_code(code) {
+        code += 1;
+        continue;
+      }
+      let start = code;
+      while self.contains_code(code) {
+        code += 1;
+      }
+      let left = D::from(start as u8 as char);
+      predicate = predicate.or(&if code == start + 1 {
+        Predicate::char(left)
+      } else {
+        /* the right bound of ranges is exclusive */
+        Predicate::range(Some(left), Some(D::from(code as u8 as char)))
+      });
+    }
+    predicate
+  }
+}
+impl<D: Domain> From<&Predicate<D>> for AsciiSet<D> {
+  fn from(predicate: &Predicate<D>) -> Self {
+    Self::from_fn(|c| predicate.denote(c))
+  }
+}
+impl<D: Domain> From<Predicate<D>> for AsciiSet<D> {
+  fn from(predicate: Predicate<D>) -> Self {
+    Self::from(&predicate)
+  }
+}
+impl<D: Domain> BoolAlg for AsciiSet<D> {
+  type Domain = D;
+  type Term = Lambda<Self>;
+  type GetOne = D;
+
+  fn char(a: Self::Domain) -> Self {
+    Self::from_fn(|c| *c == a)
+  }
+
+  fn and(&self, other: &Self) -> Self {
+    Self::with_bits([self.bits[0] & other.bits[0], self.bits[1] & other.bits[1]])
+  }
+
+  fn or(&self, other: &Self) -> Self {
+    Self::with_bits([self.bits[0] | other.bits[0], self.bits[1] | other.bits[1]])
+  }
+
+  fn not(&self) -> Self {
+    Self::with_bits([!self.bits[0], !self.bits[1]])
+  }
+
+  fn top() -> Self {
+    Self::with_bits([u64::MAX; 2])
+  }
+
+  fn bot() -> Self {
+    Self::with_bits([0; 2])
+  }
+
+  /** the inverse image by f, i.e. { x | self(f(x)) } */
+  fn with_lambda(&self, f: &Self::Term) -> Self {
+    if *f == Lambda::identity() {
+      self.clone()
+    } else {
+      Self::from_fn(|c| self.denote(f.apply(c)))
+    }
+  }
+
+  fn image(&self, f: &Self::Term) -> Self {
+    let image = self
+      .iter()
+      .map(|c| D::clone(f.apply(&c)))
+      .collect::<Vec<_>>();
+    Self::from_fn(|c| image.contains(c))
+  }
+
+  fn denote(&self, arg: &Self::Domain) -> bool {
+    let c: char = arg.clone().into();
+    self.contains_code(c as u32)
+  }
+
+  fn cardinality(&self) -> usize {
+    self.len()
+  }
+
+  fn satisfiable(&self) -> bool {
+    !self.is_empty()
+  }
+
+  fn get_one(self) -> Result<Self::GetOne, NoElement> {
+    /* printable ones first, so that models are readable */
+    let printable = self.iter().find(|c| {
+      let c: char = c.clone().into();
+      c.is_ascii_graphic()
+    });
+    printable.or_else(|| self.iter().next()).ok_or(NoElement)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  type Ascii = AsciiSet<char>;
+  type Prd = Predicate<char>;
+
+  #[test]
+  fn operations() {
+    let lower = Ascii::from(Prd::range(Some('a'), Some('{')));
+    let vowels = Ascii::from(Prd::in_set("aeiou".chars()));
+    assert_eq!(lower.len(), 26);
+    assert_eq!(lower.and(&vowels), vowels);
+    assert_eq!(lower.or(&vowels), lower);
+    assert_eq!(lower.and(&vowels.not()).len(), 21);
+    assert_eq!(Ascii::top().len(), 128);
+    assert!(!Ascii::char('é').satisfiable());
+    assert!(!Ascii::top().denote(&'é'));
+    assert_eq!(Ascii::all_char().len(), 127);
+
+    let upper = Lambda::<Ascii>::function(vec![(lower.clone(), 'X')]);
+    assert_eq!(
+      Ascii::char('X').with_lambda(&upper),
+      lower.or(&Ascii::char('X'))
+    );
+    assert_eq!(vowels.image(&upper), Ascii::char('X'));
+    assert_eq!(Ascii::char('\n').or(&vowels).get_one().unwrap(), 'a');
+  }
+
+  #[test]
+  fn to_predicate() {
+    let set = Ascii::from(
+      Prd::range(Some('0'), Some(':'))
+        .or(&Prd::char('_'))
+        .or(&Prd::range(Some('x'), None)),
+    );
+    let predicate = set.to_predicate();
+    for code in 0..ASCII {
+      let c = code as u8 as char;
+      assert_eq!(predicate.denote(&c), set.denote(&c), "{:?}", c);
+    }
+    assert!(predicate.denote(&'9'));
+    assert!(!predicate.denote(&'é'));
+    assert_eq!(Ascii::bot().to_predicate(), Prd::bot());
+
+    let letters = Ascii::all_char().to_predicate();
+    assert!(!letters.denote(&'#'));
+    assert_eq!(letters.get_one().unwrap(), 'a');
+  }
+}
@@ -0,0 +1,304 @@
+use super::{BoolAlg, NoElement, Predicate};
+use crate::transducer::term::{FunctionTerm, Lambda};
+use crate::util::Domain;
+use std::{cell::RefCell, collections::BTreeSet, marker::PhantomData, rc::Rc};
+
+/** the largest number of classes a ClassSet can have */
+pub const MAX_CLASSES: usize = 128;
+
+/**
+ * a partition of characters into intervals between boundaries,
+ * so that predicates whose bounds are all boundaries are unions of classes.
+ * the i-th class is the characters from the i-th boundary to the next one.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Classes {
+  /** code points in the ascending order, starting with 0 */
+  boundaries: Vec<u32>,
+}
+impl Classes {
+  /** the classes split at the code points, where a character c is split by c and c + 1 */
+  pub fn new(boundaries: impl IntoIterator<Item = u32>) -> Self {
+    let mut boundaries: BTreeSet<_> = boundaries.into_iter().collect();
+    boundaries.insert(0);
+    Classes {
+      boundaries: boundaries.into_iter().collect(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.boundaries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.boundaries.is_empty()
+  }
+
+  fn class_of(&self, code: u32) -> usize {
+    match self.boundaries.binary_search(&code) {
+      Ok(i) => i,
+      Err(i) => i - 1,
+    }
+  }
+
+  /** the smallest character of the class, which stands for the others */
+  fn representative<D: Domain>(&self, class: usize) -> Option<D> {
+    /* surrogates are skipped */
+    (self.boundaries[class]..self.end(class).unwrap_or(u32::MAX))
+      .find_map(std::char::from_u32)
+      .map(D::from)
+  }
+
+  fn end(&self, class: usize) -> Option<u32> {
+    self.boundaries.get(class + 1).copied()
+  }
+
+  /** run f with the classes, which ClassSet refers to until f returns */
+  pub fn scope<T>(self, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Rc<Classes>>);
+    impl Drop for Restore {
+      fn drop(&mut self) {
+        let previous = self.0.take();
+        CLASSES.with(|classes| *classes.borrow_mut() = previous);
+      }
+    }
+    let previous = CLASSES.with(|classes| classes.replace(Some(Rc::new(self))));
+    let _restore = Restore(previous);
+    f()
+  }
+
+  fn current() -> Rc<Classes> {
+    CLASSES.with(|classes| {
+      classes
+        .borrow()
+        .clone()
+        .expect("ClassSet is used out of Classes::scope")
+    })
+  }
+}
+
+thread_local! {
+  static CLASSES: RefCell<Option<Rc<Classes>>> = const { RefCell::new(None) };
+}
+
+/**
+ * a predicate as a bitset over the classes in scope, see Classes::scope,
+ * so that and, or and not are a few word operations while the classes are few.
+ * it is exact for predicates whose bounds are boundaries of the classes.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassSet<D: Domain> {
+  bits: u128,
+  domain: PhantomData<D>,
+}
+impl<D: Domain> ClassSet<D> {
+  fn with_bits(bits: u128) -> Self {
+    ClassSet {
+      bits,
+      domain: PhantomData,
+    }
+  }
+
+  /** all classes in scope, i.e. top */
+  fn mask() -> u128 {
+    match Classes::current().len() {
+      MAX_CLASSES => u128::MAX,
+      len => (1 << len) - 1,
+    }
+  }
+
+  /** the set of classes whose representatives satisfy f */
+  pub fn from_fn(f: impl Fn(&D) -> bool) -> Self {
+    let classes = Classes::current();
+    assert!(classes.len() <= MAX_CLASSES, "too many classes");
+    let bits = (0..classes.len())
+      .filter(|class| classes.representative(*class).is_some_and(|c| f(&c)))
+      .fold(0, |bits, class| bits | 1 << class);
+    Self::with_bits(bits)
+  }
+
+  fn contains(&self, class: usize) -> bool {
+    class < MAX_CLASSES && self.bits & (1 << class) != 0
+  }
+
+  pub fn len(&self) -> usize {
+    self.bits.count_ones() as usize
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.bits == 0
+  }
+
+  /**
+   * the equivalent predicate over the classes in scope.
+   * sets of more than half of the classes are the complement of the rest,
+   * so that get_one of the predicate prefers letters as it does for complements.
+   */
+  pub fn to_predicate(&self) -> Predicate<D> {
+    if self.len() > Classes::current().len() / 2 {
+      self.not().ranges().not()
+    } else {
+      self.ranges()
+    }
+  }
+
+  /**
+   * the union of ranges of the classes, where adjacent ones are merged.
+   * if the domain orders the separator apart from its code point, as CharWrap does,
+   * its class is taken separately and bounds at its code point skip it.
+   */
+  fn ranges(&self) -> Predicate<D> {
+    let classes = Classes::current();
+    let separator: char = D::separator().into();
+    let separator = separator as u32;
+    let apart = std::char::from_u32(separator + 1).is_some_and(|c| D::separator() > D::from(c));
+    let to_domain = |code: u32| match apart && code == separator {
+      true => std::char::from_u32(separator + 1).map(D::from),
+      false => std::char::from_u32(code).map(D::from),
+    };
+    let is_separator = |class: usize| {
+      apart && classes.boundaries[class] == separator && classes.end(class) == Some(separator + 1)
+    };
+    let mut predicate = Predicate::bot();
+    let mut class = 0;
+    while class < classes.len() {
+      if !self.contains(class) {
+        class += 1;
+        continue;
+      }
+      if is_separator(class) {
+        predicate = predicate.or(&Predicate::char(D::separator()));
+        class += 1;
+        continue;
+      }
+      let start = class;
+      while class < classes.len() && self.contains(class) && !is_separator(class) {
+        class += 1;
+      }
+      let left = to_domain(classes.boundaries[start]);
+      /* the right bound of ranges is exclusive */
+      let right = classes.end(class - 1).and_then(to_domain);
+      predicate = predicate.or(&Predicate::range(left, right));
+    }
+    predicate
+  }
+}
+impl<D: Domain> From<&Predicate<D>> for ClassSet<D> {
+  fn from(predicate: &Predicate<D>) -> Self {
+    Self::from_fn(|c| predicate.denote(c))
+  }
+}
+impl<D: Domain> From<Predicate<D>> for ClassSet<D> {
+  fn from(predicate: Predicate<D>) -> Self {
+    Self::from(&predicate)
+  }
+}
+impl<D: Domain> BoolAlg for ClassSet<D> {
+  type Domain = D;
+  type Term = Lambda<Self>;
+  type GetOne = D;
+
+  fn char(a: Self::Domain) -> Self {
+    let c: char = a.into();
+    Self::with_bits(1 << Classes::current().class_of(c as u32))
+  }
+
+  fn and(&self, other: &Self) -> Self {
+    Self::with_bits(self.bits & other.bits)
+  }
+
+  fn or(&self, other: &Self) -> Self {
+    Self::with_bits(self.bits | other.bits)
+  }
+
+  fn not(&self) -> Self {
+    Self::with_bits(!self.bits & Self::mask())
+  }
+
+  fn top() -> Self {
+    Self::with_bits(Self::mask())
+  }
+
+  fn bot() -> Self {
+    Self::with_bits(0)
+  }
+
+  /** the inverse image by f, which is exact if f is constant on each class */
+  fn with_lambda(&self, f: &Self::Term) -> Self {
+    if *f == Lambda::identity() {
+      self.clone()
+    } else {
+      Self::from_fn(|c| self.denote(f.apply(c)))
+    }
+  }
+
+  fn denote(&self, arg: &Self::Domain) -> bool {
+    let c: char = arg.clone().into();
+    self.contains(Classes::current().class_of(c as u32))
+  }
+
+  fn satisfiable(&self) -> bool {
+    !self.is_empty()
+  }
+
+  fn get_one(self) -> Result<Self::GetOne, NoElement> {
+    let classes = Classes::current();
+    (0..classes.len())
+      .filter(|class| self.contains(*class))
+      .find_map(|class| classes.representative(class))
+      .ok_or(NoElement)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  type Class = ClassSet<char>;
+  type Prd = Predicate<char>;
+
+  fn lower() -> Prd {
+    Prd::range(Some('a'), Some('{'))
+  }
+
+  #[test]
+  fn operations() {
+    let classes = Classes::new(['a' as u32, '{' as u32, 'x' as u32, 'y' as u32]);
+    assert_eq!(classes.len(), 5);
+    classes.scope(|| {
+      let lower = Class::from(lower());
+      let x = Class::char('x');
+      assert_eq!(lower.len(), 3);
+      assert_eq!(lower.and(&x), x);
+      assert_eq!(lower.and(&x.not()).len(), 2);
+      assert!(lower.and(&x.not()).denote(&'z'));
+      assert!(!lower.and(&x.not()).denote(&'x'));
+      assert!(!lower.denote(&'0'));
+      assert_eq!(Class::top().len(), 5);
+      assert_eq!(Class::top().not(), Class::bot());
+      assert_eq!(x.get_one().unwrap(), 'x');
+    });
+  }
+
+  #[test]
+  fn to_predicate() {
+    let classes = Classes::new(['0' as u32, ':' as u32, '_' as u32, '`' as u32]);
+    classes.scope(|| {
+      let set = Class::from(Prd::range(Some('0'), Some(':')).or(&Prd::char('_')));
+      let predicate = set.to_predicate();
+      for c in ['/', '0', '9', ':', '_', '`', 'é'] {
+        assert_eq!(predicate.denote(&c), set.denote(&c), "{:?}", c);
+      }
+      let rest = set.not().to_predicate();
+      assert!(rest.denote(&'é'));
+      assert!(!rest.denote(&'5'));
+      assert_eq!(rest.get_one().unwrap(), 'a');
+    });
+  }
+
+  #[test]
+  #[should_panic]
+  fn out_of_scope() {
+    Class::top();
+  }
+}
@@ -1,9 +1,12 @@
+pub mod ascii;
+pub mod classes;
+
 use crate::transducer::term::{FunctionTerm, Lambda};
-use crate::util::Domain;
+use crate::util::{hash_of, Domain};
 use std::{
-  collections::BTreeSet,
+  collections::{BTreeSet, HashMap},
   fmt::{self, Debug},
-  hash::Hash,
+  hash::{Hash, Hasher},
 };
 
 #[derive(Debug, Clone)]
@@ -47,6 +50,18 @@ pub trait BoolAlg: Debug + Eq + Hash + Clone {
     Self::char(Self::Domain::separator()).not()
   }
 
+  /**
+   * over-approximation of { f(x) | self(x) }.
+   * the default gives up except for identity.
+   */
+  fn image(&self, f: &Self::Term) -> Self {
+    if *f == Self::Term::identity() {
+      self.clone()
+    } else {
+      Self::boolean(self.satisfiable())
+    }
+  }
+
   fn boolean(b: bool) -> Self {
     if b {
       Self::top()
@@ -62,9 +77,21 @@ pub trait BoolAlg: Debug + Eq + Hash + Clone {
   /** apply argument to self and return the result */
   fn denote(&self, arg: &Self::Domain) -> bool;
 
+  /** number of elements satisfying self, assuming bytes as the alphabet like get_one does */
+  fn cardinality(&self) -> usize {
+    (0..=u8::MAX)
+      .filter(|i| self.denote(&(*i as char).into()))
+      .count()
+  }
+
   fn satisfiable(&self) -> bool;
 
   fn get_one(self) -> Result<Self::GetOne, NoElement>;
+
+  /** label of the predicate in DOT and reports, Debug unless it has a readable form */
+  fn label(&self) -> String {
+    format!("{:?}", self)
+  }
 }
 /** Boolean Algebra with epsilon */
 // impl<B: BoolAlg> BoolAlg for Option<B> {
@@ -117,7 +144,8 @@ pub trait BoolAlg: Debug + Eq + Hash + Clone {
 // }
 
 /** for Primitive Predicate */
-#[derive(Debug, Eq, Hash, Clone)]
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Predicate<T: Domain> {
   Bool(bool),
   Eq(T),
@@ -165,6 +193,27 @@ impl<T: Domain> PartialEq for Predicate<T> {
     }
   }
 }
+/** consistent with PartialEq, where the operands of And and Or commute */
+impl<T: Domain> Hash for Predicate<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    std::mem::discriminant(self).hash(state);
+    match self {
+      Predicate::Bool(b) => b.hash(state),
+      Predicate::Eq(e) => e.hash(state),
+      Predicate::Range { left, right } => {
+        left.hash(state);
+        right.hash(state);
+      }
+      Predicate::InSet(els) => els.hash(state),
+      Predicate::And(p, q) | Predicate::Or(p, q) => (hash_of(p) ^ hash_of(q)).hash(state),
+      Predicate::Not(p) => p.hash(state),
+      Predicate::WithLambda { p, f } => {
+        p.hash(state);
+        f.hash(state);
+      }
+    }
+  }
+}
 impl<T: Domain> Predicate<T> {
   pub fn range(left: Option<T>, right: Option<T>) -> Self {
     match (left.as_ref(), right.as_ref()) {
@@ -202,6 +251,106 @@ impl<T: Domain> Predicate<T> {
       Predicate::InSet(els)
     }
   }
+  /** { x | self(f(x)) } as a predicate without WithLambda */
+  pub fn inverse_image(&self, f: &Lambda<Self>) -> Self {
+    match (self, f) {
+      (Predicate::Bool(b), _) => Predicate::Bool(*b),
+      (Predicate::WithLambda { p, f: g }, f) => p.inverse_image(&g.clone().compose(f.clone())),
+      (p, Lambda::Id) => p.clone(),
+      (p, Lambda::Constant(c)) => Predicate::boolean(p.denote(c)),
+      (p, Lambda::Mapping(map)) => {
+        /* the first pair of a key is applied */
+        let mut keys = vec![];
+        let mut mapped = vec![];
+        for (k, v) in map {
+          if !keys.contains(k) {
+            keys.push(k.clone());
+            if p.denote(v) {
+              mapped.push(k.clone());
+            }
+          }
+        }
+        Predicate::in_set(mapped).or(&p.and(&Predicate::in_set(keys).not()))
+      }
+      (p, Lambda::Function(cases)) => {
+        /* the first case satisfied is applied */
+        let mut rest = Predicate::top();
+        let mut result = Predicate::bot();
+        for (cond, value) in cases {
+          if p.denote(value) {
+            result = result.or(&rest.and(cond));
+          }
+          rest = rest.and(&cond.not());
+        }
+        result.or(&rest.and(p))
+      }
+    }
+  }
+
+  /**
+   * elements where the truth of an atom may change.
+   * the truth of self is constant between consecutive ones, so they and the minimum decide
+   * satisfiability.
+   */
+  fn boundaries(&self, points: &mut Vec<T>) {
+    /* c and the elements following it, one of which is next to c in the order of T */
+    fn around<T: Domain>(c: &T, points: &mut Vec<T>) {
+      let c: char = c.clone().into();
+      points.extend((u32::from(c)..).filter_map(std::char::from_u32).take(3).map(T::from));
+    }
+    match self {
+      Predicate::Bool(_) => {}
+      Predicate::Eq(c) => around(c, points),
+      Predicate::Range { left, right } => points.extend(left.iter().chain(right).cloned()),
+      Predicate::InSet(elements) => elements.iter().for_each(|c| around(c, points)),
+      Predicate::And(p, q) | Predicate::Or(p, q) => {
+        p.boundaries(points);
+        q.boundaries(points);
+      }
+      Predicate::Not(p) => p.boundaries(points),
+      Predicate::WithLambda { p, f } => p.inverse_image(f).boundaries(points),
+    }
+  }
+}
+/** readable form, e.g. ('_' ∨ ['a'-'z']) where ranges are shown inclusively */
+impl<T: Domain> fmt::Display for Predicate<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn to_char<T: Domain>(a: &T) -> char {
+      a.clone().into()
+    }
+    match self {
+      Predicate::Bool(true) => write!(f, "⊤"),
+      Predicate::Bool(false) => write!(f, "⊥"),
+      Predicate::Eq(a) => write!(f, "{:?}", to_char(a)),
+      Predicate::Range { left, right } => {
+        write!(f, "[")?;
+        if let Some(l) = left {
+          write!(f, "{:?}", to_char(l))?;
+        }
+        write!(f, "-")?;
+        /* the last character below the exclusive bound, skipping surrogates */
+        if let Some(last) = right.as_ref().and_then(|r| {
+          (0..u32::from(to_char(r)))
+            .rev()
+            .find_map(std::char::from_u32)
+        }) {
+          write!(f, "{:?}", last)?;
+        }
+        write!(f, "]")
+      }
+      Predicate::InSet(elements) => {
+        let elements: Vec<_> = elements
+          .iter()
+          .map(|a| format!("{:?}", to_char(a)))
+          .collect();
+        write!(f, "{{{}}}", elements.join(", "))
+      }
+      Predicate::And(p, q) => write!(f, "({} ∧ {})", p, q),
+      Predicate::Or(p, q) => write!(f, "({} ∨ {})", p, q),
+      Predicate::Not(p) => write!(f, "¬{}", p),
+      Predicate::WithLambda { p, f: lambda } => write!(f, "{}∘{:?}", p, lambda),
+    }
+  }
 }
 impl<T: Domain> BoolAlg for Predicate<T> {
   type Domain = T;
@@ -294,8 +443,9 @@ impl<T: Domain> BoolAlg for Predicate<T> {
           right: qr,
         },
       ) => {
-        if matches!((pl, qr), (Some(l), Some(r)) if l <= r)
-          || matches!((ql, pr), (Some(l), Some(r)) if l <= r)
+        /* merged only if they overlap or touch, where None is unbounded */
+        if !matches!((pl, qr), (Some(l), Some(r)) if r < l)
+          && !matches!((ql, pr), (Some(l), Some(r)) if r < l)
         {
           let left = pl.as_ref().and_then(|pl| ql.as_ref().map(|ql| pl.min(ql)));
           let right = pr.as_ref().and_then(|pr| qr.as_ref().map(|qr| pr.max(qr)));
@@ -343,21 +493,42 @@ impl<T: Domain> BoolAlg for Predicate<T> {
     Predicate::Bool(false)
   }
 
+  /** the inverse image by f, so no WithLambda is made */
   fn with_lambda(&self, f: &Self::Term) -> Self {
+    self.inverse_image(f)
+  }
+
+  fn image(&self, f: &Self::Term) -> Self {
     match f {
       Lambda::Id => self.clone(),
-      Lambda::Constant(c) => Predicate::boolean(self.denote(c)),
-      f => match self {
-        Predicate::Bool(b) => Predicate::boolean(*b),
-        Predicate::WithLambda { p, f: f2 } => Predicate::WithLambda {
-          p: p.clone(),
-          f: f.clone().compose(f2.clone()),
-        },
-        _ => Predicate::WithLambda {
-          p: Box::new(self.clone()),
-          f: f.clone(),
-        },
-      },
+      Lambda::Constant(c) => {
+        if self.satisfiable() {
+          Predicate::char(c.clone())
+        } else {
+          Predicate::bot()
+        }
+      }
+      Lambda::Mapping(map) => {
+        let keys = Predicate::InSet(map.iter().map(|(k, _)| k.clone()).collect());
+        map
+          .iter()
+          .filter(|(k, _)| self.denote(k))
+          .fold(self.and(&keys.not()), |acc, (_, v)| {
+            acc.or(&Predicate::char(v.clone()))
+          })
+      }
+      Lambda::Function(cases) => {
+        /* the first case satisfied is applied */
+        let mut rest = self.clone();
+        let mut result = Predicate::bot();
+        for (cond, value) in cases {
+          if rest.and(cond).satisfiable() {
+            result = result.or(&Predicate::char(value.clone()));
+          }
+          rest = rest.and(&cond.not());
+        }
+        result.or(&rest)
+      }
     }
   }
 
@@ -378,7 +549,14 @@ impl<T: Domain> BoolAlg for Predicate<T> {
   }
 
   fn satisfiable(&self) -> bool {
-    !matches!(self, Predicate::Bool(false))
+    match self {
+      Predicate::Bool(b) => *b,
+      p => {
+        let mut points = vec![T::from('\0')];
+        p.boundaries(&mut points);
+        points.iter().any(|c| p.denote(c))
+      }
+    }
   }
 
   // use z3?...
@@ -406,6 +584,10 @@ impl<T: Domain> BoolAlg for Predicate<T> {
         .ok_or(NoElement)
     }
   }
+
+  fn label(&self) -> String {
+    self.to_string()
+  }
 }
 
 struct SatisfiableSet<D: Domain> {
@@ -475,13 +657,56 @@ impl<D: Domain> From<Predicate<D>> for SatisfiableSet<D> {
         let p1: Self = (*p1).into();
         let p2: Self = (*p2).into();
 
+        /* empty included stands for any element */
+        let included = if p1.included.is_empty() {
+          p2.included
+        } else if p2.included.is_empty() {
+          p1.included
+        } else {
+          p1.included.intersection(&p2.included).cloned().collect()
+        };
         Self {
-          included: p1.included.intersection(&p2.included).cloned().collect(),
+          included,
           excluded: p1.excluded.union(&p2.excluded).cloned().collect(),
           satisfiable: p1.satisfiable && p2.satisfiable,
         }
       }
-      Predicate::Or(p1, p2) => p1.not().and(&p2.not()).not().into(),
+      Predicate::Or(p1, p2) => {
+        let p1: Self = (*p1).into();
+        let p2: Self = (*p2).into();
+
+        if !p1.satisfiable {
+          p2
+        } else if !p2.satisfiable {
+          p1
+        } else if p1.included.is_empty() && p2.included.is_empty() {
+          Self {
+            excluded: p1.excluded.intersection(&p2.excluded).cloned().collect(),
+            ..Default::default()
+          }
+        } else if p1.included.is_empty() || p2.included.is_empty() {
+          let (any, some) = if p1.included.is_empty() {
+            (p1, p2)
+          } else {
+            (p2, p1)
+          };
+          let some: BTreeSet<_> = some.included.difference(&some.excluded).cloned().collect();
+          Self {
+            excluded: any.excluded.difference(&some).cloned().collect(),
+            ..Default::default()
+          }
+        } else {
+          Self {
+            included: p1
+              .included
+              .difference(&p1.excluded)
+              .chain(p2.included.difference(&p2.excluded))
+              .cloned()
+              .collect(),
+            ..Default::default()
+          }
+        }
+      }
       Predicate::Not(p) => {
         let p: Self = (*p).into();
 
@@ -494,10 +719,83 @@ impl<D: Domain> From<Predicate<D>> for SatisfiableSet<D> {
           p
         }
       }
-      Predicate::WithLambda { p: _, f: _ } => unimplemented!(),
+      Predicate::WithLambda { p, f } => p.inverse_image(&f).into(),
+    }
+  }
+}
+
+/**
+ * deduplicated predicates referred by indices,
+ * so that repeated guards like all_char are stored once.
+ */
+#[derive(Debug, Clone)]
+pub struct PredicatePool<B: BoolAlg> {
+  predicates: Vec<B>,
+  indices: HashMap<B, usize>,
+}
+impl<B: BoolAlg> Default for PredicatePool<B> {
+  fn default() -> Self {
+    Self {
+      predicates: vec![],
+      indices: HashMap::new(),
     }
   }
 }
+impl<B: BoolAlg> PredicatePool<B> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /** index of the predicate, registering it if it is new */
+  pub fn intern(&mut self, predicate: B) -> usize {
+    if let Some(idx) = self.indices.get(&predicate) {
+      return *idx;
+    }
+    let idx = self.predicates.len();
+    self.predicates.push(B::clone(&predicate));
+    self.indices.insert(predicate, idx);
+    idx
+  }
+
+  /** replace predicates in keys of transitions with their indices */
+  pub fn intern_transition<S: Eq + Hash, T>(
+    &mut self,
+    transition: HashMap<(S, B), T>,
+  ) -> HashMap<(S, usize), T> {
+    transition
+      .into_iter()
+      .map(|((p, phi), target)| ((p, self.intern(phi)), target))
+      .collect()
+  }
+
+  /** inverse of intern_transition */
+  pub fn resolve_transition<S: Eq + Hash, T>(
+    &self,
+    transition: HashMap<(S, usize), T>,
+  ) -> HashMap<(S, B), T> {
+    transition
+      .into_iter()
+      .map(|((p, idx), target)| ((p, B::clone(self.get(idx))), target))
+      .collect()
+  }
+
+  /** panic if the index isn't given by this pool */
+  pub fn get(&self, idx: usize) -> &B {
+    &self.predicates[idx]
+  }
+
+  pub fn len(&self) -> usize {
+    self.predicates.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.predicates.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &B> {
+    self.predicates.iter()
+  }
+}
 
 #[cfg(test)]
 mod tests {
@@ -583,6 +881,35 @@ mod tests {
     assert!(!eq.denote(z));
   }
 
+  #[test]
+  fn or_ranges() {
+    let a_c = Prd::range(Some('a'), Some('d'));
+    let x_z = Prd::range(Some('x'), Some('{'));
+
+    /* disjoint ones are kept apart in either order */
+    for disjoint in [a_c.or(&x_z), x_z.or(&a_c)] {
+      assert!(disjoint.denote(&'b'));
+      assert!(disjoint.denote(&'y'));
+      assert!(!disjoint.denote(&'m'));
+    }
+
+    /* touching ones are merged, since the right end is excluded */
+    let d_z = Prd::range(Some('d'), Some('{'));
+    assert_eq!(a_c.or(&d_z), Prd::range(Some('a'), Some('{')));
+    assert_eq!(d_z.or(&a_c), Prd::range(Some('a'), Some('{')));
+    let e_z = Prd::range(Some('e'), Some('{'));
+    assert!(!a_c.or(&e_z).denote(&'d'));
+
+    /* None is unbounded */
+    let from_a = a_c.or(&Prd::range(Some('c'), None));
+    assert_eq!(from_a, Prd::range(Some('a'), None));
+    let below_d = Prd::range(None, Some('d'));
+    assert_eq!(below_d.or(&Prd::range(Some('c'), None)), Prd::Bool(true));
+    let apart = Prd::range(None, Some('b')).or(&Prd::range(Some('x'), None));
+    assert!(apart.denote(&'a') && apart.denote(&'z'));
+    assert!(!apart.denote(&'m'));
+  }
+
   #[test]
   fn in_set() {
     let avd = Prd::in_set(['a', 'v', 'd']);
@@ -596,6 +923,48 @@ mod tests {
     assert!(!avd.denote(&'i'));
   }
 
+  #[test]
+  fn inverse_image() {
+    let digit = Prd::range(Some('0'), Some(':'));
+    let map = Lambda::Mapping(vec![('a', '1'), ('b', 'x'), ('a', 'y')]);
+    let fnc = Lambda::Function(vec![
+      (Box::new(Prd::range(Some('a'), Some('g'))), '0'),
+      (Box::new(Prd::char('a')), 'z'),
+    ]);
+
+    for (f, accepted, rejected) in [
+      (map, vec!['a', '5'], vec!['b', 'y']),
+      (fnc, vec!['a', 'f', '7'], vec!['g', 'z']),
+    ] {
+      let inverse = digit.inverse_image(&f);
+      for c in accepted {
+        assert!(inverse.denote(&c));
+      }
+      for c in rejected {
+        assert!(!inverse.denote(&c));
+      }
+
+      /* get_one works through WithLambda made by hand */
+      let with_lambda = Prd::WithLambda {
+        p: Box::new(digit.clone()),
+        f: f.clone(),
+      };
+      let one = with_lambda.get_one().unwrap();
+      assert!(digit.denote(f.apply(&one)));
+    }
+
+    let nested = Prd::WithLambda {
+      p: Box::new(Prd::WithLambda {
+        p: Box::new(Prd::char('c')),
+        f: Lambda::Mapping(vec![('b', 'c')]),
+      }),
+      f: Lambda::Mapping(vec![('a', 'b')]),
+    };
+    assert_eq!(nested.clone().get_one().unwrap(), 'a');
+    assert!(nested.inverse_image(&Lambda::Constant('a')).satisfiable());
+    assert!(!nested.inverse_image(&Lambda::Constant('z')).satisfiable());
+  }
+
   #[test]
   fn with_lambda() {
     let cond_x = Prd::char('x');
@@ -636,4 +1005,26 @@ mod tests {
     assert!(!cond_num.denote(&'p'));
     assert!(!cond_num.denote(&'a'));
   }
+
+  #[test]
+  fn display() {
+    assert_eq!(Prd::top().to_string(), "⊤");
+    assert_eq!(Prd::char('a').label(), "'a'");
+    assert_eq!(Prd::range(Some('a'), Some('{')).to_string(), "['a'-'z']");
+    assert_eq!(Prd::range(None, Some('0')).to_string(), "[-'/']");
+    assert_eq!(Prd::in_set(['x', 'y']).to_string(), "{'x', 'y'}");
+    assert_eq!(
+      Prd::Or(
+        Box::new(Prd::char('_')),
+        Box::new(Prd::Not(Box::new(Prd::char('\n'))))
+      )
+      .to_string(),
+      r"('_' ∨ ¬'\n')"
+    );
+    /* surrogates are skipped below the bound */
+    assert_eq!(
+      Prd::range(Some('a'), Some('\u{e000}')).to_string(),
+      r"['a'-'\u{d7ff}']"
+    );
+  }
 }
@@ -0,0 +1,542 @@
+use crate::boolean_algebra::{Predicate, PredicatePool};
+use crate::pipeline::{Pipeline, PipelineStep};
+use crate::regular::symbolic_automata::Sfa;
+use crate::smt2::Smt2;
+use crate::state::State;
+use crate::transducer::term::Lambda;
+use crate::util::{numbering, Domain};
+use crate::{solve_cases, undecided_if_ignored, SolverResult, UnconstrainedPolicy};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
+/**
+ * a pre-image computed so far, which lets a long solve resume from it.
+ * it belongs to the case of Smt2::case_split of the script the key is made from.
+ */
+#[derive(Debug, Clone)]
+pub struct Checkpoint<D: Domain, S: State> {
+  pub key: u64,
+  pub case: usize,
+  /** the number of pre-images already computed */
+  pub applied: usize,
+  pub sfa: Sfa<D, S>,
+}
+
+/**
+ * the key of checkpoints of the script, i.e. the FNV-1a hash of its bytes,
+ * which unlike DefaultHasher stays the same across processes and builds
+ */
+pub fn key(input: &str) -> u64 {
+  input.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+    (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+  })
+}
+
+fn write_char<D: Domain>(text: &mut String, c: &D) {
+  let c: char = c.clone().into();
+  write!(text, " {}", c as u32).unwrap();
+}
+
+fn write_predicate<D: Domain>(text: &mut String, phi: &Predicate<D>) {
+  match phi {
+    Predicate::Bool(b) => text.push_str(if *b { " T" } else { " F" }),
+    Predicate::Eq(c) => {
+      text.push_str(" (eq");
+      write_char(text, c);
+      text.push(')');
+    }
+    Predicate::Range { left, right } => {
+      text.push_str(" (range");
+      for bound in [left, right] {
+        match bound {
+          Some(c) => write_char(text, c),
+          None => text.push_str(" _"),
+        }
+      }
+      text.push(')');
+    }
+    Predicate::InSet(elements) => {
+      text.push_str(" (set");
+      elements.iter().for_each(|c| write_char(text, c));
+      text.push(')');
+    }
+    Predicate::And(p, q) | Predicate::Or(p, q) => {
+      text.push_str(if let Predicate::And(..) = phi {
+        " (and"
+      } else {
+        " (or"
+      });
+      write_predicate(text, p);
+      write_predicate(text, q);
+      text.push(')');
+    }
+    Predicate::Not(p) => {
+      text.push_str(" (not");
+      write_predicate(text, p);
+      text.push(')');
+    }
+    Predicate::WithLambda { p, f } => {
+      text.push_str(" (lambda");
+      write_predicate(text, p);
+      match f {
+        Lambda::Id => text.push_str(" id"),
+        Lambda::Constant(c) => {
+          text.push_str(" (const");
+          write_char(text, c);
+          text.push(')');
+        }
+        Lambda::Mapping(m) => {
+          text.push_str(" (map");
+          for (from, to) in m {
+            write_char(text, from);
+            write_char(text, to);
+          }
+          text.push(')');
+        }
+        Lambda::Function(cases) => {
+          text.push_str(" (fun");
+          for (cond, value) in cases {
+            text.push_str(" (");
+            write_predicate(text, cond);
+            write_char(text, value);
+            text.push(')');
+          }
+          text.push(')');
+        }
+      }
+      text.push(')');
+    }
+  }
+}
+
+/** tokens of a predicate, i.e. parentheses and atoms */
+fn tokenize(s: &str) -> Vec<&str> {
+  let mut tokens = vec![];
+  let mut start = None;
+  for (i, c) in s.char_indices() {
+    if c == '(' || c == ')' || c.is_whitespace() {
+      if let Some(start) = start.take() {
+        tokens.push(&s[start..i]);
+      }
+      if !c.is_whitespace() {
+        tokens.push(&s[i..i + 1]);
+      }
+    } else if start.is_none() {
+      start = Some(i);
+    }
+  }
+  if let Some(start) = start {
+    tokens.push(&s[start..]);
+  }
+  tokens
+}
+
+struct Reader<'a> {
+  tokens: Vec<&'a str>,
+  pos: usize,
+}
+impl<'a> Reader<'a> {
+  fn next(&mut self) -> Option<&'a str> {
+    let token = self.tokens.get(self.pos).copied();
+    self.pos += 1;
+    token
+  }
+
+  fn peek(&self) -> Option<&'a str> {
+    self.tokens.get(self.pos).copied()
+  }
+
+  fn expect(&mut self, token: &str) -> Option<()> {
+    (self.next()? == token).then_some(())
+  }
+
+  fn char<D: Domain>(&mut self) -> Option<D> {
+    let code: u32 = self.next()?.parse().ok()?;
+    Some(D::from(char::from_u32(code)?))
+  }
+
+  /** characters until the closing parenthesis, which is consumed */
+  fn chars<D: Domain>(&mut self) -> Option<Vec<D>> {
+    let mut chars = vec![];
+    while self.peek()? != ")" {
+      chars.push(self.char()?);
+    }
+    self.expect(")")?;
+    Some(chars)
+  }
+
+  fn predicate<D: Domain>(&mut self) -> Option<Predicate<D>> {
+    match self.next()? {
+      "T" => return Some(Predicate::Bool(true)),
+      "F" => return Some(Predicate::Bool(false)),
+      "(" => {}
+      _ => return None,
+    }
+    let phi = match self.next()? {
+      "eq" => Predicate::Eq(self.char()?),
+      "range" => {
+        let mut bound = || match self.peek()? {
+          "_" => {
+            self.next();
+            Some(None)
+          }
+          _ => Some(Some(self.char()?)),
+        };
+        let left = bound()?;
+        let right = bound()?;
+        Predicate::Range { left, right }
+      }
+      "set" => return Some(Predicate::InSet(self.chars()?)),
+      "and" => Predicate::And(Box::new(self.predicate()?), Box::new(self.predicate()?)),
+      "or" => Predicate::Or(Box::new(self.predicate()?), Box::new(self.predicate()?)),
+      "not" => Predicate::Not(Box::new(self.predicate()?)),
+      "lambda" => {
+        let p = Box::new(self.predicate()?);
+        let f = self.lambda()?;
+        Predicate::WithLambda { p, f }
+      }
+      _ => return None,
+    };
+    self.expect(")")?;
+    Some(phi)
+  }
+
+  fn lambda<D: Domain>(&mut self) -> Option<Lambda<Predicate<D>>> {
+    match self.next()? {
+      "id" => return Some(Lambda::Id),
+      "(" => {}
+      _ => return None,
+    }
+    match self.next()? {
+      "const" => {
+        let c = self.char()?;
+        self.expect(")")?;
+        Some(Lambda::Constant(c))
+      }
+      "map" => {
+        let chars = self.chars()?;
+        let pairs = chars
+          .chunks(2)
+          .map(|pair| match pair {
+            [from, to] => Some((D::clone(from), D::clone(to))),
+            _ => None,
+          })
+          .collect::<Option<_>>()?;
+        Some(Lambda::Mapping(pairs))
+      }
+      "fun" => {
+        let mut cases = vec![];
+        while self.peek()? != ")" {
+          self.expect("(")?;
+          let cond = self.predicate()?;
+          let value = self.char()?;
+          self.expect(")")?;
+          cases.push((Box::new(cond), value));
+        }
+        self.expect(")")?;
+        Some(Lambda::Function(cases))
+      }
+      _ => None,
+    }
+  }
+}
+
+impl<D: Domain, S: State> Checkpoint<D, S> {
+  /**
+   * a line-based text, i.e. the header, the number of states with the initial one,
+   * final states, the pool of predicates and a transition per line referring to them by index,
+   * where characters are code points.
+   */
+  pub fn to_text(&self) -> String {
+    let numbers = numbering(self.sfa.states.iter());
+    let mut text = String::new();
+    writeln!(
+      text,
+      "checkpoint {:x} {} {}",
+      self.key, self.case, self.applied
+    )
+    .unwrap();
+    writeln!(
+      text,
+      "states {} {}",
+      numbers.len(),
+      numbers[&self.sfa.initial_state]
+    )
+    .unwrap();
+    let mut finals: Vec<_> = self.sfa.final_states.iter().map(|s| numbers[s]).collect();
+    finals.sort_unstable();
+    text.push_str("final");
+    finals.iter().for_each(|s| write!(text, " {}", s).unwrap());
+    text.push('\n');
+    /* predicates are interned in the order of their texts so that the text is deterministic */
+    let mut transitions: Vec<_> = self
+      .sfa
+      .transition
+      .iter()
+      .map(|((source, phi), targets)| {
+        let mut predicate = String::new();
+        write_predicate(&mut predicate, phi);
+        let mut targets: Vec<_> = targets.iter().map(|t| numbers[t]).collect();
+        targets.sort_unstable();
+        (predicate, numbers[source], targets, phi)
+      })
+      .collect();
+    transitions.sort_by(|(p1, s1, t1, _), (p2, s2, t2, _)| (p1, s1, t1).cmp(&(p2, s2, t2)));
+    let mut pool = PredicatePool::new();
+    let lines: Vec<_> = transitions
+      .iter()
+      .map(|(_, source, targets, phi)| {
+        let mut line = format!("t {}", source);
+        targets.iter().for_each(|t| write!(line, " {}", t).unwrap());
+        write!(line, " : {}", pool.intern(Predicate::clone(phi))).unwrap();
+        line
+      })
+      .collect();
+    for phi in pool.iter() {
+      text.push('p');
+      write_predicate(&mut text, phi);
+      text.push('\n');
+    }
+    lines
+      .iter()
+      .for_each(|line| writeln!(text, "{}", line).unwrap());
+    text
+  }
+
+  /** the checkpoint of to_text, or None if the text is broken */
+  pub fn from_text(text: &str) -> Option<Self> {
+    let mut lines = text.lines();
+    let header: Vec<_> = lines.next()?.split_whitespace().collect();
+    let (key, case, applied) = match header[..] {
+      ["checkpoint", key, case, applied] => (
+        u64::from_str_radix(key, 16).ok()?,
+        case.parse().ok()?,
+        applied.parse().ok()?,
+      ),
+      _ => return None,
+    };
+    let (len, initial): (usize, usize) =
+      match lines.next()?.split_whitespace().collect::<Vec<_>>()[..] {
+        ["states", len, initial] => (len.parse().ok()?, initial.parse().ok()?),
+        _ => return None,
+      };
+    let states: Vec<S> = (0..len).map(|_| S::new()).collect();
+    let state = |i: &str| -> Option<S> { states.get(i.parse::<usize>().ok()?).cloned() };
+
+    let finals = lines.next()?.strip_prefix("final")?;
+    let final_states = finals
+      .split_whitespace()
+      .map(state)
+      .collect::<Option<HashSet<_>>>()?;
+    let mut pool = PredicatePool::new();
+    let mut transition = HashMap::new();
+    for line in lines {
+      if let Some(phi) = line.strip_prefix('p') {
+        let mut reader = Reader {
+          tokens: tokenize(phi),
+          pos: 0,
+        };
+        /* a predicate written twice would shift the indices after it */
+        if pool.intern(reader.predicate()?) + 1 != pool.len() {
+          return None;
+        }
+        continue;
+      }
+      let (head, idx) = line.strip_prefix("t ")?.split_once(" :")?;
+      let mut head = head.split_whitespace();
+      let source = state(head.next()?)?;
+      let targets = head.map(state).collect::<Option<Vec<_>>>()?;
+      let idx: usize = idx.trim().parse().ok()?;
+      if idx >= pool.len() {
+        return None;
+      }
+      transition.insert((source, idx), targets);
+    }
+
+    let initial_state = states.get(initial)?.clone();
+    let sfa = Sfa::from_pooled(
+      states.into_iter().collect(),
+      initial_state,
+      final_states,
+      &pool,
+      transition,
+    );
+    Some(Checkpoint {
+      key,
+      case,
+      applied,
+      sfa,
+    })
+  }
+}
+
+/**
+ * decide the problem case by case through Pipeline, writing a checkpoint to path
+ * after the product and each pre-image are computed.
+ * if path has a checkpoint of the same script, solving resumes from it,
+ * skipping the cases before it since they have been unsatisfiable.
+ * cases with length constraints are solved by solve_cases without checkpoints,
+ * and so is the satisfiable case again to find a model if the problem needs one.
+ */
+pub fn solve<D: Domain, S: State>(
+  input: &str,
+  problem: Smt2<D, S>,
+  unconstrained: UnconstrainedPolicy,
+  path: &Path,
+) -> SolverResult {
+  let key = key(input);
+  let mut saved = std::fs::read_to_string(path)
+    .ok()
+    .and_then(|text| Checkpoint::<D, S>::from_text(&text))
+    .filter(|checkpoint| checkpoint.key == key);
+
+  let cases = match problem.case_split() {
+    Ok(cases) => cases,
+    Err(err) => return SolverResult::ResourceExhausted(err),
+  };
+  for (case, (_, problem)) in cases.into_iter().enumerate() {
+    if saved.as_ref().is_some_and(|checkpoint| case < checkpoint.case) {
+      continue;
+    }
+    if !problem.length_constraints().is_empty() {
+      match solve_cases(problem, unconstrained, |_| {}).result {
+        SolverResult::Unsat => continue,
+        result => return result,
+      }
+    }
+    let to_model = problem.needs_model().then(|| problem.clone());
+    let ignored = problem.ignored().clone();
+    let mut pipeline = match saved.take() {
+      Some(checkpoint) if checkpoint.case == case => Pipeline::resume(problem, checkpoint),
+      _ => Pipeline::new(problem),
+    };
+    while let Some(step) = pipeline.next() {
+      if let PipelineStep::ProductBuilt { .. } | PipelineStep::PreImageComputed { .. } = step {
+        let checkpoint = pipeline.checkpoint(key, case).unwrap();
+        if let Err(err) = std::fs::write(path, checkpoint.to_text()) {
+          diagnostic!("failed to write {}: {}", path.display(), err);
+        }
+      }
+    }
+    if pipeline.is_unsat() != Some(true) {
+      return match to_model {
+        Some(problem) => solve_cases(problem, unconstrained, |_| {}).result,
+        None => undecided_if_ignored(SolverResult::Sat, &ignored),
+      };
+    }
+  }
+  SolverResult::Unsat
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::boolean_algebra::BoolAlg;
+  use crate::parse;
+  use crate::regular::regex::Regex;
+  use crate::state::StateImpl;
+  use crate::util::CharWrap;
+
+  #[test]
+  fn text() {
+    let sfa = Regex::seq("ab")
+      .or(Regex::range(Some('0'), Some('9')).star())
+      .inter(Regex::element('a').not())
+      .to_sfa::<StateImpl>();
+    let checkpoint = Checkpoint {
+      key: key("(check-sat)"),
+      case: 1,
+      applied: 2,
+      sfa,
+    };
+    let text = checkpoint.to_text();
+    let restored = Checkpoint::<char, StateImpl>::from_text(&text).unwrap();
+    assert_eq!(restored.to_text(), text);
+    for word in ["ab", "", "0123", "a", "b", "ab0"] {
+      let word: Vec<_> = word.chars().collect();
+      assert_eq!(restored.sfa.run(&word), checkpoint.sfa.run(&word));
+    }
+
+    let phi = Predicate::WithLambda {
+      p: Box::new(Predicate::InSet(vec!['x', 'y']).or(&Predicate::Bool(false))),
+      f: Lambda::Function(vec![
+        (Box::new(Predicate::range(Some('a'), None)), 'x'),
+        (Box::new(Predicate::char('(')), 'y'),
+      ]),
+    };
+    let mut written = String::new();
+    write_predicate(&mut written, &phi);
+    let mut reader = Reader {
+      tokens: tokenize(&written),
+      pos: 0,
+    };
+    assert_eq!(reader.predicate::<char>(), Some(phi));
+
+    /* keys are fixed by the script alone */
+    assert_eq!(key(""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(key("a"), 0xaf63_dc4c_8601_ec8c);
+
+    assert!(Checkpoint::<char, StateImpl>::from_text("checkpoint 0 0").is_none());
+    assert!(Checkpoint::<char, StateImpl>::from_text(&text.replace("p (eq", "p (eq x")).is_none());
+    assert!(Checkpoint::<char, StateImpl>::from_text(&text.replace(" : 0", " : 99")).is_none());
+
+    /* repeated predicates like all_char are written once */
+    let sfa = Regex::all()
+      .star()
+      .concat(Regex::seq("a"))
+      .concat(Regex::all().star())
+      .to_sfa::<StateImpl>();
+    let text = Checkpoint { sfa, ..checkpoint }.to_text();
+    let count = |prefix: &str| text.lines().filter(|line| line.starts_with(prefix)).count();
+    assert!(count("p") < count("t "), "{}", text);
+  }
+
+  #[test]
+  fn resume() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (= x2 (str.reverse x1)))
+      (assert (str.in.re x2 (str.to.re "baba")))
+      (check-sat)
+      "#;
+    let path = std::env::temp_dir().join(format!("checkpoint-{}.txt", std::process::id()));
+    let result = solve(input, parse(input).unwrap(), UnconstrainedPolicy::default(), &path);
+    assert_eq!(result, SolverResult::Sat);
+    let text = std::fs::read_to_string(&path).unwrap();
+    let checkpoint = Checkpoint::<CharWrap, StateImpl>::from_text(&text).unwrap();
+    assert_eq!((checkpoint.key, checkpoint.case), (key(input), 0));
+    assert_eq!(checkpoint.applied, 2);
+    assert!(!checkpoint.sfa.is_empty());
+
+    /* the saved pre-image is used instead of solving again */
+    let checkpoint = Checkpoint {
+      sfa: Regex::range(None, None).to_sfa(),
+      ..checkpoint
+    };
+    std::fs::write(&path, checkpoint.to_text()).unwrap();
+    let result = solve(input, parse(input).unwrap(), UnconstrainedPolicy::default(), &path);
+    assert_eq!(result, SolverResult::Unsat);
+
+    /* the one of another script is ignored */
+    let checkpoint = Checkpoint {
+      key: key("(check-sat)"),
+      ..checkpoint
+    };
+    std::fs::write(&path, checkpoint.to_text()).unwrap();
+    let result = solve(input, parse(input).unwrap(), UnconstrainedPolicy::default(), &path);
+    assert_eq!(result, SolverResult::Sat);
+
+    /* a model is found for (get-model) */
+    let input = format!("{}(get-model)", input);
+    let result = solve(&input, parse(&input).unwrap(), UnconstrainedPolicy::default(), &path);
+    match result {
+      SolverResult::Model(model) => assert_eq!(model["x0"], "ab"),
+      result => panic!("{:?}", result),
+    }
+    std::fs::remove_file(&path).unwrap();
+  }
+}
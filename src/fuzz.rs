@@ -0,0 +1,158 @@
+/*
+ * entry points for fuzzing, to be wrapped by cargo-fuzz harnesses like
+ *   fuzz_target!(|data: &[u8]| { let _ = fuzz_pipeline(data); });
+ * each of them takes arbitrary bytes and returns an error instead of panicking,
+ * so a panic found by a fuzzer is a bug of the crate.
+ */
+use crate::limits::ResourceExhausted;
+use crate::regular::regex::{Regex, RegexParseError};
+use crate::regular::symbolic_automata::Sfa;
+use crate::smt2::{ParseError, Smt2, Warning};
+use crate::state::StateImpl;
+use crate::util::CharWrap;
+use crate::{check_sat, Problem, SolverResult};
+use std::fmt::Display;
+use std::str::Utf8Error;
+
+/** inputs longer than this are rejected so that a fuzzer doesn't spend its time on huge ones */
+pub const MAX_INPUT_LEN: usize = 1 << 12;
+
+/** why a fuzzing input was rejected */
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuzzError {
+  TooLong(usize),
+  Utf8(Utf8Error),
+  Parse(ParseError),
+  Regex(RegexParseError),
+  /** the input is well-formed but too large to solve */
+  ResourceExhausted(ResourceExhausted),
+}
+impl Display for FuzzError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FuzzError::TooLong(len) => {
+        write!(f, "input of {} bytes is longer than {}", len, MAX_INPUT_LEN)
+      }
+      FuzzError::Utf8(err) => write!(f, "{}", err),
+      FuzzError::Parse(err) => write!(f, "{}", err),
+      FuzzError::Regex(err) => write!(f, "{}", err),
+      FuzzError::ResourceExhausted(err) => write!(f, "{}", err),
+    }
+  }
+}
+impl std::error::Error for FuzzError {}
+impl From<Utf8Error> for FuzzError {
+  fn from(err: Utf8Error) -> Self {
+    FuzzError::Utf8(err)
+  }
+}
+impl From<ParseError> for FuzzError {
+  fn from(err: ParseError) -> Self {
+    FuzzError::Parse(err)
+  }
+}
+impl From<RegexParseError> for FuzzError {
+  fn from(err: RegexParseError) -> Self {
+    FuzzError::Regex(err)
+  }
+}
+impl From<ResourceExhausted> for FuzzError {
+  fn from(err: ResourceExhausted) -> Self {
+    FuzzError::ResourceExhausted(err)
+  }
+}
+
+fn text(data: &[u8]) -> Result<&str, FuzzError> {
+  if data.len() > MAX_INPUT_LEN {
+    return Err(FuzzError::TooLong(data.len()));
+  }
+  Ok(std::str::from_utf8(data)?)
+}
+
+/** parse data as an SMT-LIB script */
+pub fn fuzz_parse(data: &[u8]) -> Result<Problem, FuzzError> {
+  Ok(Smt2::parse(text(data)?)?)
+}
+
+/** parse data as a pattern of Regex::parse and build its automaton */
+pub fn fuzz_regex(data: &[u8]) -> Result<Sfa<CharWrap, StateImpl>, FuzzError> {
+  let reg: Regex<CharWrap> = Regex::parse(text(data)?)?;
+  ResourceExhausted::check("to_sfa", reg.estimate_sfa_size(), || {
+    format!("the pattern {:?}", String::from_utf8_lossy(data))
+  })?;
+  Ok(reg.to_sfa())
+}
+
+/** parse and solve data as an SMT-LIB script */
+pub fn fuzz_pipeline(data: &[u8]) -> Result<SolverResult, FuzzError> {
+  let problem = fuzz_parse(data)?;
+  for warning in problem.warnings() {
+    if let Warning::LargeMembership(var, size) = warning {
+      ResourceExhausted::check("to_sfa", *size, || format!("the membership of {}", var))?;
+    }
+  }
+  match check_sat(problem) {
+    SolverResult::ResourceExhausted(err) => Err(err.into()),
+    result => Ok(result),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rejects() {
+    assert!(matches!(fuzz_parse(&[0xff, 0xfe]), Err(FuzzError::Utf8(_))));
+    assert!(matches!(
+      fuzz_parse(&vec![b' '; MAX_INPUT_LEN + 1]),
+      Err(FuzzError::TooLong(_))
+    ));
+    assert!(matches!(fuzz_parse(b"(assert (str.in.re x0 re.all))"), Err(FuzzError::Parse(_))));
+    let script = br#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.replace_re_all x0 (str.to.re "") "b")))
+      (check-sat)
+    "#;
+    assert!(matches!(fuzz_pipeline(script), Err(FuzzError::Parse(_))));
+    assert!(matches!(fuzz_regex(b"a(b"), Err(FuzzError::Regex(_))));
+    assert!(matches!(
+      fuzz_regex(b"((a{1000}){1000}){1000}"),
+      Err(FuzzError::ResourceExhausted(_))
+    ));
+  }
+
+  #[test]
+  fn large_numerals() {
+    /* an index beyond STATE_LIMIT is abstracted rather than built into an SST */
+    let script = br#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.substr x0 99999999999999 2)))
+      (assert (str.in.re x1 (str.to.re "ab")))
+      (check-sat)
+    "#;
+    assert!(matches!(fuzz_pipeline(script), Ok(SolverResult::Unknown(_))));
+    let script = String::from_utf8_lossy(script).replace("99999999999999", &"9".repeat(40));
+    assert!(matches!(
+      fuzz_pipeline(script.as_bytes()),
+      Ok(SolverResult::Unknown(_))
+    ));
+  }
+
+  #[test]
+  fn accepts() {
+    let sfa = fuzz_regex(b"a(b|c)*").unwrap();
+    let word: Vec<_> = "abc".chars().map(CharWrap::from).collect();
+    assert!(sfa.run(&word));
+    let script = br#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.replace_re_all x0 (str.to.re "a") "b")))
+      (assert (str.in.re x1 (str.to.re "bb")))
+      (check-sat)
+    "#;
+    assert_eq!(fuzz_pipeline(script), Ok(SolverResult::Sat));
+  }
+}
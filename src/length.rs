@@ -0,0 +1,598 @@
+use crate::boolean_algebra::{BoolAlg, Predicate};
+use crate::limits::STATE_LIMIT;
+use crate::regular::symbolic_automata::Sfa;
+use crate::regular::regex::Regex;
+use crate::smt2::{Constraint, IntAtom, Relation, Smt2, TransductionOp};
+use crate::state::State;
+use crate::transducer::{
+  sst_factory::SstBuilder,
+  term::VariableImpl,
+};
+use crate::util::Domain;
+use std::{
+  collections::{BTreeSet, HashMap},
+  convert::TryFrom,
+};
+
+/**
+ * values of an atom the constraints leave unbounded are searched up to this many,
+ * e.g. lengths of an infinite membership or Int variables without a bound.
+ */
+pub const LENGTH_BOUND: usize = 16;
+
+/** values of str.to_int are taken from at most this many words of a finite membership */
+const TO_INT_WORDS: usize = 1 << 10;
+
+/** lengths of a defined variable are computed from at most this many lengths of its arguments */
+const DEFINED_LENGTHS: usize = 1 << 10;
+
+/** the length abstraction of a membership is computed over words up to this length */
+const PERIOD_LENGTHS: usize = 1 << 12;
+
+/** at most this many values between the bounds of an atom are searched */
+const BOUNDED_VALUES: usize = 1 << 10;
+
+/** the bounds of the atoms are tightened by the constraints at most this many times */
+const PROPAGATION_ROUNDS: usize = 32;
+
+/** the least and the greatest values, where None is unbounded */
+type Bounds = (Option<i64>, Option<i64>);
+
+/**
+ * a semilinear set of integers, i.e. a union of linear sets base + period * k for k >= 0,
+ * where the period is 0 for a single value and negative for values going down.
+ */
+#[derive(Debug, PartialEq, Clone)]
+pub struct Semilinear(Vec<(i64, i64)>);
+impl Semilinear {
+  pub fn values(values: impl IntoIterator<Item = i64>) -> Self {
+    Semilinear(values.into_iter().map(|value| (value, 0)).collect())
+  }
+
+  pub fn at_least(min: i64) -> Self {
+    Semilinear(vec![(min, 1)])
+  }
+
+  pub fn integers() -> Self {
+    Semilinear(vec![(0, 1), (-1, -1)])
+  }
+
+  pub fn contains(&self, value: i64) -> bool {
+    self
+      .0
+      .iter()
+      .any(|linear| Self::range(*linear, (Some(value), Some(value))).is_some())
+  }
+
+  /** the values if there are finitely many */
+  pub fn finite(&self) -> Option<Vec<i64>> {
+    let values: BTreeSet<i64> = self
+      .0
+      .iter()
+      .map(|(base, period)| if *period == 0 { Some(*base) } else { None })
+      .collect::<Option<_>>()?;
+    Some(values.into_iter().collect())
+  }
+
+  /**
+   * the first and the last values of the linear set within bounds, in the order of its period,
+   * where the last one is None if it's unbounded.
+   */
+  fn range((base, period): (i64, i64), (min, max): Bounds) -> Option<(i64, Option<i64>)> {
+    let within =
+      |value: i64| min.is_none_or(|min| min <= value) && max.is_none_or(|max| value <= max);
+    if period == 0 {
+      return if within(base) { Some((base, Some(base))) } else { None };
+    }
+
+    let (base, period) = (base as i128, period as i128);
+    let (step, direction) = (period.abs(), period.signum());
+    /* the first and the last numbers of periods, from the bound met first in the direction */
+    let (start, end) = if period > 0 { (min, max) } else { (max, min) };
+    let first = start.map_or(0, |start| -(-direction * (start as i128 - base)).div_euclid(step));
+    let first = first.max(0);
+    let last = end.map(|end| (direction * (end as i128 - base)).div_euclid(step));
+    if last.is_some_and(|last| last < first) {
+      return None;
+    }
+    Some((
+      i64::try_from(base + period * first).ok()?,
+      last.and_then(|last| i64::try_from(base + period * last).ok()),
+    ))
+  }
+
+  /** the least and the greatest values within bounds, or None if no value is */
+  fn hull(&self, bounds: Bounds) -> Option<Bounds> {
+    let ranges: Vec<_> = self
+      .0
+      .iter()
+      .filter_map(|linear| Self::range(*linear, bounds).map(|range| (linear.1, range)))
+      .collect();
+    if ranges.is_empty() {
+      return None;
+    }
+    let (mut min, mut max) = (Some(i64::MAX), Some(i64::MIN));
+    for (period, (first, last)) in ranges {
+      let (least, greatest) = if period < 0 {
+        (last, Some(first))
+      } else {
+        (Some(first), last)
+      };
+      min = min.zip(least).map(|(a, b)| a.min(b));
+      max = max.zip(greatest).map(|(a, b)| a.max(b));
+    }
+    Some((min, max))
+  }
+
+  /**
+   * values within bounds, the nearest to 0 first, and whether they are all of them.
+   * at most limit values are taken from a linear set unbounded within them.
+   */
+  fn within(&self, bounds: Bounds, limit: usize) -> (Vec<i64>, bool) {
+    let mut values = BTreeSet::new();
+    let mut complete = true;
+    for (base, period) in &self.0 {
+      let (first, last) = match Self::range((*base, *period), bounds) {
+        Some(range) => range,
+        None => continue,
+      };
+      let until_last = |value: i64| {
+        last.is_none_or(|last| (last as i128 - value as i128) * *period as i128 >= 0)
+      };
+      let mut next = Some(first);
+      let limit = if last.is_some() { BOUNDED_VALUES } else { limit };
+      for _ in 0..limit {
+        match next {
+          Some(value) if until_last(value) => {
+            values.insert(value);
+            next = if *period == 0 { None } else { value.checked_add(*period) };
+          }
+          _ => break,
+        }
+      }
+      complete &= match next {
+        Some(value) => *period != 0 && last.is_some() && !until_last(value),
+        None => true,
+      };
+    }
+    let mut values: Vec<_> = values.into_iter().collect();
+    values.sort_by_key(|value| (value.unsigned_abs(), *value < 0));
+    (values, complete)
+  }
+}
+
+/** the membership of the variable, narrowed by its definition if it's a string constant */
+fn language<D: Domain, S: State>(smt2: &Smt2<D, S>, idx: usize) -> Option<Regex<D>> {
+  let constant = smt2.filter_sl(idx).and_then(|sl_cons| {
+    sl_cons
+      .constraint()
+      .0
+      .iter()
+      .map(|op| match op {
+        TransductionOp::Str(s) => Some(s.as_str()),
+        _ => None,
+      })
+      .collect::<Option<String>>()
+      .map(|s: String| Regex::seq(&s))
+  });
+  match (smt2.membership(idx), constant) {
+    (Some(reg), Some(constant)) => Some(reg.inter(constant)),
+    (reg, constant) => reg.or(constant),
+  }
+}
+
+/**
+ * values the atom may take apart from the length constraints, i.e. the length abstraction of
+ * the membership, restricted to the lengths its definition gives if it's a defined variable.
+ * values of str.to_int are the ones of the words of a finite membership, read digit by digit,
+ * so they aren't bounded by their magnitude, and otherwise the bounds of the value give them.
+ * values of str.count are the ones a counting register takes over the membership.
+ * the others are only bounded by what the function may give, e.g. -1 for str.indexof.
+ */
+fn domain<D: Domain, S: State>(smt2: &Smt2<D, S>, atom: IntAtom, bound: usize) -> Semilinear {
+  match atom {
+    IntAtom::Len(idx) => {
+      let lengths = language(smt2, idx)
+        .and_then(|reg| reg.to_sfa::<S>().length_abstraction(PERIOD_LENGTHS))
+        .map(|linears| {
+          Semilinear(
+            linears
+              .into_iter()
+              .map(|(base, period)| (base as i64, period as i64))
+              .collect(),
+          )
+        })
+        .unwrap_or_else(|| Semilinear::at_least(0));
+      match defined_lengths(smt2, idx, bound) {
+        Some(defined) => {
+          Semilinear::values(defined.into_iter().filter(|len| lengths.contains(*len)))
+        }
+        None => lengths,
+      }
+    }
+    IntAtom::ToInt(idx) => match language(smt2, idx).and_then(|reg| to_int_values(&reg.to_sfa::<S>()))
+    {
+      Some(values) => Semilinear::values(values),
+      None => Semilinear::at_least(-1),
+    },
+    IntAtom::IndexOf(_, _) => Semilinear::at_least(-1),
+    IntAtom::Count(idx, pattern) => {
+      let values = language(smt2, idx).and_then(|reg| {
+        let register = VariableImpl::new();
+        let sst = SstBuilder::<D, S, VariableImpl>::count_occurrences(
+          &smt2.count_patterns()[pattern],
+          &VariableImpl::new(),
+          &register,
+        );
+        match sst
+          .counter_values(&register, &reg.to_sfa(), bound)
+          .expect("occurrences are counted up")
+        {
+          (values, true) => Some(values),
+          (_, false) => None,
+        }
+      });
+      values.map_or_else(|| Semilinear::at_least(0), Semilinear::values)
+    }
+    IntAtom::Var(_) => Semilinear::integers(),
+  }
+}
+
+/**
+ * all the lengths the definition of the variable gives over the lengths of its arguments,
+ * through the length relation of its transducer.
+ * None if it isn't defined, the relation doesn't exist,
+ * or the lengths of some argument aren't finitely many or too many.
+ */
+fn defined_lengths<D: Domain, S: State>(
+  smt2: &Smt2<D, S>,
+  idx: usize,
+  bound: usize,
+) -> Option<Vec<i64>> {
+  let transduction = smt2.filter_sl(idx)?.constraint();
+  let mut vars = transduction.vars();
+  vars.sort_unstable();
+  vars.dedup();
+  /* the transducer reads the segments up to the last argument and outputs them followed by the value */
+  let segments = vars.last().map_or(0, |last| last + 1);
+  let relation = SstBuilder::<D, S, VariableImpl>::init()
+    .generate(segments, transduction)
+    .length_relation()?;
+
+  let mut assignments = vec![vec![0; segments]];
+  for var in vars {
+    let lengths = domain(smt2, IntAtom::Len(var), bound).finite()?;
+    if assignments.len() * lengths.len() > DEFINED_LENGTHS {
+      return None;
+    }
+    assignments = assignments
+      .iter()
+      .flat_map(|assignment| {
+        lengths.iter().map(move |len| {
+          let mut assignment = assignment.clone();
+          assignment[var] = *len as usize;
+          assignment
+        })
+      })
+      .collect();
+  }
+  let lengths: BTreeSet<i64> = assignments
+    .iter()
+    .map(|lens| {
+      let copied: usize = lens.iter().map(|len| len + 1).sum();
+      relation.eval(lens) - copied as i64 - 1
+    })
+    .collect();
+  Some(lengths.into_iter().collect())
+}
+
+/**
+ * values of str.to_int over the words of a finite language, reading each path digit by digit,
+ * or None if the language is infinite or has more than TO_INT_WORDS numerals.
+ */
+fn to_int_values<D: Domain, S: State>(sfa: &Sfa<D, S>) -> Option<Vec<i64>> {
+  let digits = ('0'..='9').fold(Predicate::bot(), |phi, c| phi.or(&Predicate::char(D::from(c))));
+  let mut values = BTreeSet::new();
+  for path in sfa.enumerate_paths()? {
+    /* the empty word and words with a non-digit are -1 */
+    if path.is_empty()
+      || path
+        .iter()
+        .any(|phi| phi.and(&Predicate::all_char()).and(&digits.not()).satisfiable())
+    {
+      values.insert(-1);
+    }
+    let mut numbers = vec![0i64];
+    for phi in &path {
+      let mut next = vec![];
+      for d in (0..10).filter(|d| phi.denote(&D::from(char::from(b'0' + *d as u8)))) {
+        for n in &numbers {
+          next.push(n.checked_mul(10)?.checked_add(d)?);
+        }
+      }
+      if next.len() > TO_INT_WORDS {
+        return None;
+      }
+      numbers = next;
+    }
+    if !path.is_empty() {
+      values.extend(numbers);
+    }
+  }
+  Some(values.into_iter().collect())
+}
+
+/** a length constraint over the indices of its atoms, i.e. the sum of the terms and the constant */
+struct Linear {
+  relation: Relation,
+  terms: Vec<(usize, i128)>,
+  constant: i128,
+}
+
+/** the least and the greatest values of coefficient times an atom within bounds */
+fn term_bounds(coefficient: i128, (min, max): Bounds) -> (Option<i128>, Option<i128>) {
+  let (min, max) = (
+    min.map(|min| coefficient * min as i128),
+    max.map(|max| coefficient * max as i128),
+  );
+  if coefficient < 0 {
+    (max, min)
+  } else {
+    (min, max)
+  }
+}
+
+/**
+ * bounds of the atoms within their domains which the constraints and the values given imply,
+ * or None if some atom has no value left.
+ * each bound of a term is derived from the bounds of the others in the constraint,
+ * e.g. x + y = 7 bounds x by 7 if y is a length, in at most PROPAGATION_ROUNDS rounds.
+ */
+fn propagate(
+  linears: &[Linear],
+  atoms: &[IntAtom],
+  domains: &[Semilinear],
+  values: &HashMap<IntAtom, i64>,
+) -> Option<Vec<Bounds>> {
+  let mut bounds = atoms
+    .iter()
+    .zip(domains)
+    .map(|(atom, domain)| match values.get(atom) {
+      Some(value) => Some((Some(*value), Some(*value))),
+      None => domain.hull((None, None)),
+    })
+    .collect::<Option<Vec<_>>>()?;
+  let violated = |linear: &Linear| match linear.relation {
+    Relation::Eq => linear.constant != 0,
+    Relation::Le => linear.constant > 0,
+  };
+  if linears.iter().any(|linear| linear.terms.is_empty() && violated(linear)) {
+    return None;
+  }
+
+  for _ in 0..PROPAGATION_ROUNDS {
+    let mut changed = false;
+    for linear in linears {
+      for (j, (i, coefficient)) in linear.terms.iter().enumerate() {
+        /* the least and the greatest sums of the other terms */
+        let (mut least, mut greatest) = (Some(linear.constant), Some(linear.constant));
+        for (k, (other, c)) in linear.terms.iter().enumerate() {
+          if k != j {
+            let (min, max) = term_bounds(*c, bounds[*other]);
+            least = least.zip(min).map(|(a, b)| a + b);
+            greatest = greatest.zip(max).map(|(a, b)| a + b);
+          }
+        }
+        /* coefficient * atom is at most -least, and at least -greatest for an equation */
+        let at_most = least.map(|least| -least);
+        let at_least = greatest
+          .filter(|_| linear.relation == Relation::Eq)
+          .map(|greatest| -greatest);
+        let (upper, lower) = if *coefficient > 0 {
+          (
+            at_most.map(|n| n.div_euclid(*coefficient)),
+            at_least.map(|n| -(-n).div_euclid(*coefficient)),
+          )
+        } else {
+          (
+            at_least.map(|n| -n.div_euclid(-coefficient)),
+            at_most.map(|n| (-n).div_euclid(-coefficient)),
+          )
+        };
+
+        let (min, max) = bounds[*i];
+        let clamp = |n: i128| n.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+        let min = match (min, lower.map(clamp)) {
+          (Some(min), Some(lower)) => Some(min.max(lower)),
+          (min, lower) => min.or(lower),
+        };
+        let max = match (max, upper.map(clamp)) {
+          (Some(max), Some(upper)) => Some(max.min(upper)),
+          (max, upper) => max.or(upper),
+        };
+        let tightened = domains[*i].hull((min, max))?;
+        if tightened != bounds[*i] {
+          bounds[*i] = tightened;
+          changed = true;
+        }
+      }
+    }
+    if !changed {
+      break;
+    }
+  }
+  Some(bounds)
+}
+
+/** what search found, where NotFound means no assignment satisfies the constraints */
+#[derive(Debug, PartialEq)]
+pub enum Search<T> {
+  Found(T),
+  NotFound,
+  /** no assignment tried is satisfiable, but some unbounded values are left */
+  Bounded,
+}
+
+/**
+ * search assignments to the atoms of the length constraints,
+ * calling solve with each one satisfying all of them until it gives some result.
+ * each atom takes the values of its domain within the bounds the constraints imply
+ * over the values assigned so far, so partial assignments are pruned as soon as some atom has none,
+ * and an atom bounded on both sides is searched entirely, e.g. a length forced by an equation.
+ * values unbounded in some direction are searched up to bound of them.
+ * indexes and str.count of a variable of a known length are searched up to that length.
+ */
+pub fn search<D: Domain, S: State, T>(
+  smt2: &Smt2<D, S>,
+  bound: usize,
+  mut solve: impl FnMut(&HashMap<IntAtom, i64>) -> Option<T>,
+) -> Search<T> {
+  let mut atoms: Vec<_> = smt2
+    .length_constraints()
+    .iter()
+    .flat_map(|int_cons| int_cons.atoms())
+    .collect();
+  /* Int variables last, since they are often forced by the others */
+  atoms.sort_by_key(|atom| (matches!(atom, IntAtom::Var(_)), *atom));
+  atoms.dedup();
+  let domains: Vec<_> = atoms
+    .iter()
+    .map(|atom| domain(smt2, *atom, bound))
+    .collect();
+  let indices: HashMap<IntAtom, usize> =
+    atoms.iter().enumerate().map(|(i, atom)| (*atom, i)).collect();
+  let linears: Vec<_> = smt2
+    .length_constraints()
+    .iter()
+    .map(|int_cons| {
+      let mut coefficients: HashMap<usize, i128> = HashMap::new();
+      let mut constant = 0;
+      for term in int_cons.terms() {
+        match term.atom() {
+          Some(atom) => {
+            *coefficients.entry(indices[&atom]).or_default() += term.coefficient() as i128
+          }
+          None => constant += term.coefficient() as i128,
+        }
+      }
+      let mut terms: Vec<_> = coefficients.into_iter().filter(|(_, c)| *c != 0).collect();
+      terms.sort_unstable();
+      Linear {
+        relation: int_cons.relation(),
+        terms,
+        constant,
+      }
+    })
+    .collect();
+
+  let mut values = HashMap::new();
+  let mut exhaustive = true;
+  match assign(&atoms, &domains, &linears, bound, &mut values, &mut exhaustive, &mut solve) {
+    Some(found) => Search::Found(found),
+    None if exhaustive => Search::NotFound,
+    None => Search::Bounded,
+  }
+}
+
+fn assign<T>(
+  atoms: &[IntAtom],
+  domains: &[Semilinear],
+  linears: &[Linear],
+  bound: usize,
+  values: &mut HashMap<IntAtom, i64>,
+  exhaustive: &mut bool,
+  solve: &mut impl FnMut(&HashMap<IntAtom, i64>) -> Option<T>,
+) -> Option<T> {
+  let bounds = propagate(linears, atoms, domains, values)?;
+
+  /* the atom of the fewest values, preferring the ones searched entirely */
+  let next = (0..atoms.len())
+    .filter(|i| !values.contains_key(&atoms[*i]))
+    .map(|i| {
+      let (min, mut max) = bounds[i];
+      let len = atoms[i]
+        .string_var()
+        .and_then(|idx| values.get(&IntAtom::Len(idx)));
+      if let (IntAtom::IndexOf(_, _) | IntAtom::Count(_, _), Some(len)) = (atoms[i], len) {
+        max = Some(max.map_or(*len, |max| max.min(*len)));
+      }
+      (i, domains[i].within((min, max), bound + 1))
+    })
+    .min_by_key(|(_, (candidates, complete))| (!complete, candidates.len()));
+  let (i, (candidates, complete)) = match next {
+    Some(next) => next,
+    None => return solve(values),
+  };
+  *exhaustive &= complete;
+
+  let atom = atoms[i];
+  for value in candidates {
+    /* lengths, indexes and counts are fixed by automata of about that many states */
+    let size = match atom {
+      IntAtom::Len(_) | IntAtom::IndexOf(_, _) | IntAtom::Count(_, _) => value,
+      IntAtom::Var(_) | IntAtom::ToInt(_) => 0,
+    };
+    if size > STATE_LIMIT as i64 {
+      *exhaustive = false;
+      continue;
+    }
+    values.insert(atom, value);
+    if let Some(result) = assign(atoms, domains, linears, bound, values, exhaustive, solve) {
+      return Some(result);
+    }
+  }
+  values.remove(&atom);
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parse;
+
+  #[test]
+  fn semilinear() {
+    /* 1 and 4 + 3k, e.g. a(aaa)* with a redundant state */
+    let lengths = Semilinear(vec![(1, 0), (4, 3)]);
+    assert!(lengths.contains(1) && lengths.contains(7) && !lengths.contains(5));
+    assert_eq!(lengths.finite(), None);
+    assert_eq!(lengths.hull((Some(2), None)), Some((Some(4), None)));
+    assert_eq!(lengths.hull((Some(2), Some(9))), Some((Some(4), Some(7))));
+    assert_eq!(lengths.hull((Some(2), Some(3))), None);
+    assert_eq!(lengths.within((Some(1000), None), 3), (vec![1000, 1003, 1006], false));
+    assert_eq!(lengths.within((None, Some(10)), 3), (vec![1, 4, 7, 10], true));
+
+    let integers = Semilinear::integers();
+    assert_eq!(integers.hull((None, None)), Some((None, None)));
+    assert_eq!(integers.within((None, None), 2), (vec![0, 1, -1, -2], false));
+    assert_eq!(integers.within((Some(-2), Some(1)), 2), (vec![0, 1, -1, -2], true));
+    assert_eq!(Semilinear::values(vec![3, 1, 3]).finite(), Some(vec![1, 3]));
+  }
+
+  #[test]
+  fn search() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const i Int)
+      (assert (str.in.re x0 (re.* (str.to.re "ab"))))
+      (assert (= i (+ (str.len x0) (str.len x1))))
+      (assert (>= (str.len x0) 3))
+      (assert (< i 6))
+      (check-sat)
+      "#;
+    let smt2 = parse(input).unwrap();
+    let mut found = vec![];
+    let result: Search<()> = super::search(&smt2, LENGTH_BOUND, |values| {
+      found.push((
+        values[&IntAtom::Len(0)],
+        values[&IntAtom::Len(1)],
+        values[&IntAtom::Var(0)],
+      ));
+      None
+    });
+    /* x0 has even lengths, so it is 4 and i at most 5 */
+    assert_eq!(result, Search::NotFound);
+    assert_eq!(found, vec![(4, 0, 4), (4, 1, 5)]);
+  }
+}
@@ -1,14 +1,43 @@
-mod boolean_algebra;
+/** eprintln! of a diagnostic, which is dropped without the `std` feature */
+macro_rules! diagnostic {
+  ($($arg:tt)*) => {{
+    #[cfg(feature = "std")]
+    eprintln!($($arg)*);
+    #[cfg(not(feature = "std"))]
+    let _ = format_args!($($arg)*);
+  }};
+}
+
+pub mod boolean_algebra;
+pub mod checkpoint;
+pub mod fuzz;
+pub mod length;
+pub mod limits;
+#[cfg(feature = "macros")]
+mod macros;
+pub mod pipeline;
+pub mod random;
 pub mod regular;
+pub mod report;
 pub mod smt2;
-mod state;
+pub mod snapshot;
+pub mod solver;
+pub mod state;
 pub mod transducer;
 mod util;
 
-use smt2::{Constraint, Smt2};
-use state::{State, StateImpl, StateMachine};
-use std::collections::HashMap;
-use transducer::{sst_factory::SstBuilder, term::VariableImpl};
+use boolean_algebra::{BoolAlg, Predicate};
+use length::{Search, LENGTH_BOUND};
+use limits::ResourceExhausted;
+use random::{Rng, SplitMix64};
+use regular::{
+  regex::{Regex, Strategy},
+  symbolic_automata::Sfa,
+};
+use smt2::{Constraint, IntAtom, Smt2, TraceEntry, Transduction, Warning};
+use state::{State, StateCounter, StateImpl, StateMachine};
+use std::{collections::HashMap, fmt::Write, sync::Mutex};
+use transducer::{sst::Sst, sst_factory::SstBuilder, term::VariableImpl};
 use util::{CharWrap, Domain};
 
 #[derive(Debug, PartialEq)]
@@ -16,30 +45,293 @@ pub enum SolverResult {
   Sat,
   Model(HashMap<String, String>),
   Unsat,
+  /** some construction diverged, so the problem is undecided */
+  ResourceExhausted(ResourceExhausted),
+  /** solving took longer than the timeout, so the problem is undecided */
+  Timeout(std::time::Duration),
+  /** the model of an abstraction doesn't satisfy the problem, so it is undecided */
+  Unknown(String),
 }
 
-pub fn check_sat<D: Domain, S: State>(mut smt2: Smt2<D, S>) -> SolverResult {
-  let mut sfa = smt2.emit_sfa();
+pub type Problem = Smt2<CharWrap, StateImpl>;
+pub type Outcome = SolverResult;
 
-  let builder: SstBuilder<D, S, VariableImpl> = SstBuilder::init();
+/** value given to variables without any constraint in a model */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnconstrainedPolicy {
+  #[default]
+  Empty,
+  ShortestNonEmpty,
+  Random,
+}
+impl UnconstrainedPolicy {
+  pub fn value(&self) -> String {
+    match self {
+      UnconstrainedPolicy::Empty => String::new(),
+      UnconstrainedPolicy::ShortestNonEmpty => String::from("a"),
+      UnconstrainedPolicy::Random => {
+        let mut rng = SplitMix64::from_entropy();
+        let len = 1 + rng.below(8);
+        (0..len)
+          .map(|_| (b'a' + rng.below(26) as u8) as char)
+          .collect()
+      }
+    }
+  }
+}
+
+/**
+ * a problem translated into machines.
+ * sfa is the product of all regular constraints,
+ * and ssts are straight-line constraints in the order they are applied as pre-image,
+ * which is chosen by Smt2::elimination_order.
+ */
+#[derive(Debug, Clone)]
+pub struct CompiledProblem<D: Domain, S: State> {
+  smt2: Smt2<D, S>,
+  sfa: Sfa<D, S>,
+  ssts: Vec<Sst<D, S, VariableImpl>>,
+  elimination_order: Vec<String>,
+  /** the problem before slicing, used to give values to the sliced variables */
+  original: Smt2<D, S>,
+  sliced: Vec<String>,
+  unconstrained: UnconstrainedPolicy,
+}
+impl<D: Domain, S: State> CompiledProblem<D, S> {
+  pub fn with_unconstrained(mut self, policy: UnconstrainedPolicy) -> Self {
+    self.unconstrained = policy;
+    self
+  }
+
+  pub fn problem(&self) -> &Smt2<D, S> {
+    &self.smt2
+  }
+
+  pub fn sfa(&self) -> &Sfa<D, S> {
+    &self.sfa
+  }
+
+  pub fn ssts(&self) -> &Vec<Sst<D, S, VariableImpl>> {
+    &self.ssts
+  }
+
+  /** defined variables in the order their pre-images are applied */
+  pub fn elimination_order(&self) -> &Vec<String> {
+    &self.elimination_order
+  }
+
+  /** variables out of the cone of influence, which no machine is built for */
+  pub fn sliced(&self) -> &Vec<String> {
+    &self.sliced
+  }
+
+  /**
+   * intersect the product of memberships with an automaton over the value of var,
+   * which must not be sliced, before pre-images are applied.
+   */
+  pub fn restrict(&mut self, var: &str, sfa: Sfa<D, S>) {
+    assert!(
+      self.smt2.vars().iter().any(|v| v == var),
+      "{} is not in the compiled problem",
+      var
+    );
+    let restriction = self
+      .smt2
+      .vars()
+      .iter()
+      .map(|v| {
+        if v == var {
+          sfa.clone()
+        } else {
+          Sfa::default()
+        }
+      })
+      .reduce(|result, sfa| result.chain(sfa))
+      .map(|sfa| sfa.finish())
+      .expect("no string variable");
+    self.sfa = std::mem::take(&mut self.sfa).inter(restriction);
+  }
+}
+
+/**
+ * machines built by compile_with, kept across check-sats so that the constraints of outer
+ * scopes aren't built again after (push) and (pop).
+ * SSTs are keyed by their definitions and products by the memberships of all the variables.
+ */
+#[derive(Debug)]
+pub struct MachineCache<D: Domain, S: State> {
+  ssts: Mutex<Vec<CachedSst<D, S>>>,
+  products: Mutex<Vec<CachedProduct<D, S>>>,
+}
+/** the SST of the definition of the variable */
+type CachedSst<D, S> = (usize, Transduction<D, S>, Sst<D, S, VariableImpl>);
+/** the product of the memberships of each variable */
+type CachedProduct<D, S> = (Vec<Option<Regex<D>>>, Sfa<D, S>);
+impl<D: Domain, S: State> MachineCache<D, S> {
+  pub fn new() -> Self {
+    MachineCache {
+      ssts: Mutex::new(vec![]),
+      products: Mutex::new(vec![]),
+    }
+  }
+
+  /** the numbers of SSTs and products kept */
+  pub fn counts(&self) -> (usize, usize) {
+    (
+      self.ssts.lock().unwrap().len(),
+      self.products.lock().unwrap().len(),
+    )
+  }
+
+  fn sst(&self, idx: usize, transduction: &Transduction<D, S>) -> Sst<D, S, VariableImpl> {
+    let mut ssts = self.ssts.lock().unwrap();
+    if let Some((_, _, sst)) = ssts
+      .iter()
+      .find(|(i, t, _)| *i == idx && t == transduction)
+    {
+      return sst.clone();
+    }
+    let sst = SstBuilder::init().generate(idx, transduction);
+    ssts.push((idx, transduction.clone(), sst.clone()));
+    sst
+  }
+
+  fn product(&self, smt2: &Smt2<D, S>) -> Sfa<D, S> {
+    if smt2.is_contradictory() {
+      return smt2.emit_sfa();
+    }
+    let memberships: Vec<_> = (0..smt2.vars().len())
+      .map(|idx| smt2.membership(idx))
+      .collect();
+    let mut products = self.products.lock().unwrap();
+    if let Some((_, sfa)) = products.iter().find(|(m, _)| *m == memberships) {
+      return sfa.clone();
+    }
+    let sfa = smt2.emit_sfa();
+    products.push((memberships, sfa.clone()));
+    sfa
+  }
+}
+impl<D: Domain, S: State> Default for MachineCache<D, S> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+pub fn compile<D: Domain, S: State>(original: Smt2<D, S>) -> CompiledProblem<D, S> {
+  compile_with(original, &MachineCache::new())
+}
+
+/** compile taking the machines built before from cache */
+pub fn compile_with<D: Domain, S: State>(
+  original: Smt2<D, S>,
+  cache: &MachineCache<D, S>,
+) -> CompiledProblem<D, S> {
+  let (smt2, sliced) = original.slice();
+  let sliced = sliced
+    .into_iter()
+    .map(|idx| original.vars()[idx].clone())
+    .collect();
+  let order = smt2.elimination_order();
+  let elimination_order = order.iter().map(|idx| smt2.vars()[*idx].clone()).collect();
+  let smt2 = smt2.reorder(&order);
+  let sfa = cache.product(&smt2);
+
+  let ssts = smt2
+    .sl_constraints()
+    .into_iter()
+    .rev()
+    .map(|sl_cons| {
+      #[cfg(test)]
+      {
+        eprintln!("sl_cons: {:?}", sl_cons);
+      }
+      cache.sst(sl_cons.idx(), sl_cons.constraint())
+    })
+    .collect();
+
+  CompiledProblem {
+    smt2,
+    sfa,
+    ssts,
+    elimination_order,
+    original,
+    sliced,
+    unconstrained: UnconstrainedPolicy::default(),
+  }
+}
+
+/** outcome of solving with the diagnostics found on the way */
+#[derive(Debug, PartialEq)]
+pub struct SolveReport {
+  pub result: Outcome,
+  pub warnings: Vec<Warning>,
+  /** defined variables in the order their pre-images were applied */
+  pub elimination_order: Vec<String>,
+  /** variables out of the cone of influence */
+  pub sliced: Vec<String>,
+  /** rewrites and approximations of the problem actually solved */
+  pub trace: Vec<TraceEntry>,
+}
+
+pub fn solve<D: Domain, S: State>(compiled: CompiledProblem<D, S>) -> Outcome {
+  solve_report(compiled).result
+}
+
+pub fn solve_report<D: Domain, S: State>(compiled: CompiledProblem<D, S>) -> SolveReport {
+  let warnings = compiled.problem().warnings().clone();
+  let elimination_order = compiled.elimination_order().clone();
+  let sliced = compiled.sliced().clone();
+  let trace = compiled.problem().trace().clone();
+  let ignored = compiled.problem().ignored().clone();
+  let result = undecided_if_ignored(solve_compiled(compiled), &ignored);
+  SolveReport {
+    result,
+    warnings,
+    elimination_order,
+    sliced,
+    trace,
+  }
+}
+
+/**
+ * a satisfying assignment of a problem without the ignored assertions may violate them,
+ * so it is SolverResult::Unknown if some is ignored, while unsat is kept
+ */
+pub(crate) fn undecided_if_ignored(result: SolverResult, ignored: &[String]) -> SolverResult {
+  match (result, ignored.first()) {
+    (SolverResult::Sat | SolverResult::Model(_), Some(term)) => SolverResult::Unknown(format!(
+      "the unsupported assertion {} is ignored",
+      term
+    )),
+    (result, _) => result,
+  }
+}
+
+fn solve_compiled<D: Domain, S: State>(compiled: CompiledProblem<D, S>) -> Outcome {
+  let CompiledProblem {
+    smt2,
+    mut sfa,
+    ssts,
+    original,
+    unconstrained,
+    ..
+  } = compiled;
 
-  for sl_cons in smt2.sl_constraints().into_iter().rev() {
+  for sst in &ssts {
     if sfa.final_set().is_empty() {
       break;
     }
 
     #[cfg(test)]
     {
-      eprintln!("sl_cons: {:?}", sl_cons);
       eprintln!("sfa: {:?}", sfa);
     }
-    let sst = builder.generate(sl_cons.idx(), sl_cons.constraint());
-    #[cfg(test)]
-    {
-      //eprintln!("generated sst: {:?}", sst);
-    }
 
-    sfa = sfa.pre_image(sst);
+    sfa = match sfa.try_pre_image(sst.clone()) {
+      Ok(sfa) => sfa,
+      Err(err) => return SolverResult::ResourceExhausted(err),
+    };
   }
 
   #[cfg(test)]
@@ -47,51 +339,840 @@ pub fn check_sat<D: Domain, S: State>(mut smt2: Smt2<D, S>) -> SolverResult {
     eprintln!("sfa: {:#?}", sfa);
   }
 
-  if smt2.get_model() {
-    if let Some(path) = sfa.accepted_path() {
+  let path = if smt2.shortest() {
+    sfa.shortest_path()
+  } else {
+    witness(sfa)
+  };
+  /* a model of abstracted integer arguments is checked even if it isn't asked for */
+  if smt2.needs_model() || original.is_abstracted() {
+    if let Some(path) = path {
       #[cfg(test)]
       {
         eprintln!("accepted path {:?}", path);
       }
-      SolverResult::Model(smt2.to_model(path))
+      let model = decode_model(&smt2, &ssts, path, unconstrained);
+      let mut model = complete_sliced(&original, model, unconstrained);
+      if !original.check_abstracted(&mut model) {
+        SolverResult::Unknown(String::from(
+          "no value of the abstracted integer arguments satisfies the model",
+        ))
+      } else if smt2.needs_model() {
+        SolverResult::Model(model)
+      } else {
+        SolverResult::Sat
+      }
     } else {
       SolverResult::Unsat
     }
+  } else if path.is_some() {
+    SolverResult::Sat
   } else {
-    if sfa
-      .reachables(sfa.initial_state())
+    SolverResult::Unsat
+  }
+}
+
+/** accepted path of the final automaton, searched by threads with the `parallel` feature */
+fn witness<D: Domain, S: State>(sfa: Sfa<D, S>) -> Option<Vec<Predicate<D>>> {
+  #[cfg(feature = "parallel")]
+  {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    sfa.accepted_path_parallel(threads)
+  }
+  #[cfg(not(feature = "parallel"))]
+  {
+    sfa.accepted_path()
+  }
+}
+
+/**
+ * over-approximation of the values of each variable in the declaration order,
+ * obtained by forward images along straight-line constraints.
+ * unlike the pre-image, it needs no regular constraint on defined variables.
+ */
+pub fn infer_values<D: Domain, S: State>(smt2: Smt2<D, S>) -> Vec<(String, Sfa<D, S>)> {
+  let builder: SstBuilder<D, S, VariableImpl> = SstBuilder::init();
+  let mut values: Vec<Sfa<D, S>> = vec![];
+  let straight_line = smt2.straight_line();
+
+  for idx in 0..smt2.vars().len() {
+    let reg = straight_line
+      .membership(idx)
+      .map(|reg| reg.clone().to_sfa());
+    let value = match straight_line.definition(idx) {
+      Some(sl_cons) => {
+        let input = values
+          .iter()
+          .cloned()
+          .reduce(|result, sfa| result.chain(sfa))
+          .map(|sfa| sfa.finish())
+          .unwrap_or_else(|| Regex::Epsilon.to_sfa());
+        let image = builder.generate(idx, sl_cons.constraint()).image(&input);
+        let value =
+          SstBuilder::<D, S, VariableImpl>::project(idx, &VariableImpl::new()).image(&image);
+        match reg {
+          Some(reg) => value.inter(reg),
+          None => value,
+        }
+      }
+      None => reg.unwrap_or_default(),
+    };
+    values.push(value);
+  }
+
+  smt2.vars().iter().cloned().zip(values).collect()
+}
+
+/**
+ * decode the accepted path into the values of free variables,
+ * then replay ssts on them so that defined variables also get their values.
+ * unconstrained variables are given by the policy instead of the path.
+ */
+fn decode_model<D: Domain, S: State>(
+  smt2: &Smt2<D, S>,
+  ssts: &[Sst<D, S, VariableImpl>],
+  path: Vec<Predicate<D>>,
+  unconstrained: UnconstrainedPolicy,
+) -> HashMap<String, String> {
+  let mut input: Vec<D> = path.into_iter().map(|phi| phi.get_one().unwrap()).collect();
+
+  /* ssts are in the order applied as pre-image */
+  for sst in ssts.iter().rev() {
+    input = sst
+      .run(&input)
       .into_iter()
-      .find(|s| sfa.final_set().contains(s))
-      .is_some()
-    {
-      SolverResult::Sat
+      .next()
+      .expect("replaying straight-line constraints failed");
+  }
+
+  let mut model: HashMap<String, String> = input
+    .split(|c| *c == D::separator())
+    .zip(smt2.vars())
+    .map(|(value, var)| {
+      (
+        var.clone(),
+        value
+          .iter()
+          .map(|c| -> char { D::clone(c).into() })
+          .collect(),
+      )
+    })
+    .collect();
+
+  for idx in smt2.unconstrained_vars() {
+    model.insert(smt2.vars()[idx].clone(), unconstrained.value());
+  }
+
+  model
+}
+
+/**
+ * give values to the variables sliced away from the model.
+ * defined ones are evaluated after the ones they refer to,
+ * and the others are given by the policy.
+ */
+fn complete_sliced<D: Domain, S: State>(
+  original: &Smt2<D, S>,
+  model: HashMap<String, String>,
+  unconstrained: UnconstrainedPolicy,
+) -> HashMap<String, String> {
+  let vars = original.vars();
+  let mut values: HashMap<usize, String> = (0..vars.len())
+    .filter_map(|idx| {
+      if let Some(value) = model.get(&vars[idx]) {
+        Some((idx, value.clone()))
+      } else if original.filter_sl(idx).is_none() {
+        Some((idx, unconstrained.value()))
+      } else {
+        None
+      }
+    })
+    .collect();
+
+  let mut pending: Vec<_> = original
+    .sl_constraints()
+    .iter()
+    .filter(|sl_cons| !values.contains_key(&sl_cons.idx()))
+    .collect();
+  while let Some(i) = pending.iter().position(|sl_cons| {
+    sl_cons
+      .constraint()
+      .vars()
+      .iter()
+      .all(|idx| values.contains_key(idx))
+  }) {
+    let sl_cons = pending.remove(i);
+    let value = sl_cons.constraint().apply(&values);
+    values.insert(sl_cons.idx(), value);
+  }
+
+  values
+    .into_iter()
+    .map(|(idx, value)| (vars[idx].clone(), value))
+    .collect()
+}
+
+/**
+ * solve the problem for each assignment to Bool variables until some case is satisfiable.
+ * inspect is called with each compiled case, and models include the values of Bool variables.
+ */
+pub fn solve_cases<D: Domain, S: State>(
+  problem: Smt2<D, S>,
+  unconstrained: UnconstrainedPolicy,
+  mut inspect: impl FnMut(&CompiledProblem<D, S>),
+) -> SolveReport {
+  solve_cases_with(problem, unconstrained, &MachineCache::new(), |compiled| {
+    inspect(compiled)
+  })
+}
+
+/**
+ * solve_cases where prepare may modify each compiled case before it is solved,
+ * and machines are taken from cache
+ */
+pub(crate) fn solve_cases_with<D: Domain, S: State>(
+  problem: Smt2<D, S>,
+  unconstrained: UnconstrainedPolicy,
+  cache: &MachineCache<D, S>,
+  mut prepare: impl FnMut(&mut CompiledProblem<D, S>),
+) -> SolveReport {
+  let cases = match problem.case_split() {
+    Ok(cases) => cases,
+    Err(err) => {
+      return SolveReport {
+        result: SolverResult::ResourceExhausted(err),
+        warnings: problem.warnings().clone(),
+        elimination_order: vec![],
+        sliced: vec![],
+        trace: problem.trace().clone(),
+      }
+    }
+  };
+  let mut report = None;
+  for (assignment, case) in cases {
+    let checked =
+      ResourceExhausted::check_cancelled("case split").and_then(|()| case.check_limits());
+    let mut case_report = if let Err(err) = checked {
+      SolveReport {
+        result: SolverResult::ResourceExhausted(err),
+        warnings: case.warnings().clone(),
+        elimination_order: vec![],
+        sliced: vec![],
+        trace: case.trace().clone(),
+      }
+    } else if case.length_constraints().is_empty() {
+      let mut compiled = compile_with(case, cache).with_unconstrained(unconstrained);
+      prepare(&mut compiled);
+      solve_report(compiled)
     } else {
-      SolverResult::Unsat
+      solve_lengths(case, unconstrained, cache, &mut prepare)
+    };
+    if let SolverResult::Model(model) = &mut case_report.result {
+      model.extend(
+        assignment
+          .into_iter()
+          .map(|(var, value)| (var, value.to_string())),
+      );
+    }
+    let decided = matches!(
+      case_report.result,
+      SolverResult::Sat | SolverResult::Model(_)
+    );
+    /* an undecided case leaves the problem undecided unless another one is satisfiable */
+    let undecided = matches!(
+      report,
+      Some(SolveReport {
+        result: SolverResult::ResourceExhausted(_)
+          | SolverResult::Timeout(_)
+          | SolverResult::Unknown(_),
+        ..
+      })
+    );
+    if decided || !undecided {
+      report = Some(case_report);
+    }
+    if decided {
+      break;
+    }
+  }
+  report.expect("no case to solve")
+}
+
+/**
+ * solve the problem for each assignment to lengths, indexes, str.to_int and str.count satisfying its length constraints,
+ * fixing them by regular constraints, until some one is satisfiable.
+ * values the constraints leave unbounded are searched only up to LENGTH_BOUND of them,
+ * so it gives up with ResourceExhausted rather than Unsat if some value out of the bound is left.
+ */
+fn solve_lengths<D: Domain, S: State>(
+  problem: Smt2<D, S>,
+  unconstrained: UnconstrainedPolicy,
+  cache: &MachineCache<D, S>,
+  prepare: &mut impl FnMut(&mut CompiledProblem<D, S>),
+) -> SolveReport {
+  let found = length::search(&problem, LENGTH_BOUND, |values| {
+    let mut compiled =
+      compile_with(problem.with_values(values), cache).with_unconstrained(unconstrained);
+    prepare(&mut compiled);
+    let mut report = solve_report(compiled);
+    match &mut report.result {
+      /* the search stops once the thread is cancelled */
+      SolverResult::Unsat if !limits::is_cancelled() => return None,
+      SolverResult::Model(model) => {
+        for (atom, value) in values {
+          if let IntAtom::Var(idx) = atom {
+            model.insert(problem.int_vars()[*idx].clone(), value.to_string());
+          }
+        }
+      }
+      SolverResult::Sat
+      | SolverResult::Unsat
+      | SolverResult::ResourceExhausted(_)
+      | SolverResult::Timeout(_)
+      | SolverResult::Unknown(_) => {}
     }
+    Some(report)
+  });
+
+  let result = match found {
+    Search::Found(report) => return report,
+    Search::NotFound => SolverResult::Unsat,
+    Search::Bounded => SolverResult::ResourceExhausted(ResourceExhausted {
+      construction: "length search",
+      detail: String::from("no assignment within the bound satisfies the length constraints"),
+      limit: LENGTH_BOUND,
+      unit: "values",
+    }),
+  };
+  SolveReport {
+    result,
+    warnings: problem.warnings().clone(),
+    elimination_order: vec![],
+    sliced: vec![],
+    trace: problem.trace().clone(),
   }
 }
 
-pub fn parse(input: &str) -> Smt2<CharWrap, StateImpl> {
-  let smt2 = Smt2::parse(input).unwrap();
+pub fn check_sat<D: Domain, S: State>(smt2: Smt2<D, S>) -> SolverResult {
+  solve_cases(smt2, UnconstrainedPolicy::default(), |_| {}).result
+}
+
+pub fn parse(input: &str) -> Result<Problem, smt2::ParseError> {
+  parse_with(input, false)
+}
+
+/**
+ * parse input, failing on commands unknown to the parser if strict,
+ * and on problems the solver can't take.
+ */
+pub fn parse_with(input: &str, strict: bool) -> Result<Problem, smt2::ParseError> {
+  let smt2 = Smt2::parse_with(input, strict)?;
   #[cfg(test)]
   {
     println!("{:?}", smt2);
   }
-  smt2
+  Ok(smt2)
+}
+
+/** options given from the command line */
+#[derive(Debug, Default, Clone)]
+pub struct RunOption {
+  /** print over-approximated values of each variable before solving */
+  pub infer_values: bool,
+  /** print non-fatal diagnostics to stderr */
+  pub warnings: bool,
+  /** print the size of machines and languages before solving */
+  pub stats: bool,
+  pub unconstrained: UnconstrainedPolicy,
+  /** fail on commands unknown to the parser instead of skipping them */
+  pub strict: bool,
+  /** print the trace of rewrites and approximations to stderr */
+  pub explain: bool,
+  /** print the dependency graph of variables in DOT instead of solving */
+  pub dump_deps: bool,
+  /** write an HTML report of the problem, the machines and the result to the path */
+  pub report: Option<std::path::PathBuf>,
+  /** how memberships are built, where the plan of Strategy::Auto is printed to stderr */
+  pub strategy: Strategy,
+  /** print the solving plan of dry_run instead of solving */
+  pub dry_run: bool,
+  /**
+   * checkpoint pre-images to the path while solving, and resume from the one there.
+   * only sat or unsat is printed since no model is built.
+   */
+  pub checkpoint: Option<std::path::PathBuf>,
+  /** assume all strings are ASCII, building the product over bitsets (see Smt2::with_ascii) */
+  pub ascii: bool,
+  /** build the product over bitsets of the character classes (see Smt2::with_bitsets) */
+  pub bitsets: bool,
+  /** give models of the minimal length (see Smt2::with_shortest) */
+  pub shortest: bool,
+  /** how states are numbered while running, where PerRun makes runs reproducible */
+  pub states: StateCounter,
+  /** directory to write DOT files of the machines of each case into, e.g. case0_sfa.dot */
+  pub dot: Option<std::path::PathBuf>,
+  /** print the assignment of a model even without (get-model) */
+  pub model: bool,
+  /** give up with unknown after the duration, see run_with */
+  pub timeout: Option<std::time::Duration>,
+}
+
+fn print_values<D: Domain, S: State>(values: Vec<(String, Sfa<D, S>)>) {
+  for (var, sfa) in values {
+    let states = sfa.states().len();
+    match sfa.witness() {
+      Some(word) => {
+        let example: String = word.into_iter().map(|c| -> char { c.into() }).collect();
+        println!("{}: {} states, e.g. {:?}", var, states, example);
+      }
+      None => println!("{}: no value", var),
+    }
+  }
+}
+
+/** length up to which the number of words is shown in statistics */
+const STATS_MAX_LEN: usize = 8;
+
+fn print_stats<D: Domain, S: State>(compiled: &CompiledProblem<D, S>) {
+  println!("sfa: {} states", compiled.sfa().states().len());
+  for (i, sst) in compiled.ssts().iter().enumerate() {
+    println!(
+      "sst {}: {} states, {} variables",
+      i,
+      sst.states().len(),
+      sst.variables().len()
+    );
+  }
+  if !compiled.sliced().is_empty() {
+    println!("sliced: {}", compiled.sliced().join(", "));
+  }
+  for (idx, var) in compiled.problem().vars().iter().enumerate() {
+    if let Some(reg) = compiled.problem().membership(idx) {
+      println!(
+        "{}: star height {}, depth {}, {} literals, up to {} states estimated",
+        var,
+        reg.star_height(),
+        reg.depth(),
+        reg.literal_count(),
+        reg.estimate_sfa_size()
+      );
+      let sfa: Sfa<D, S> = reg.to_sfa();
+      println!(
+        "{}: {} words, {:?} words per length",
+        var,
+        sfa.cardinality(),
+        sfa.words_per_length(STATS_MAX_LEN)
+      );
+    }
+  }
+}
+
+/**
+ * the solving plan of each case without building any machine, i.e. the warnings,
+ * sliced variables, the memberships of the product with their estimated sizes and constructions,
+ * and the definitions in the order their pre-images are computed.
+ */
+pub fn dry_run<D: Domain, S: State>(problem: &Smt2<D, S>) -> String {
+  let mut plan = String::new();
+  for warning in problem.warnings() {
+    writeln!(plan, "warning: {}", warning).unwrap();
+  }
+  let cases = match problem.case_split() {
+    Ok(cases) => cases,
+    Err(err) => {
+      writeln!(plan, "{}", err).unwrap();
+      return plan;
+    }
+  };
+  for (assignment, case) in &cases {
+    if cases.len() > 1 {
+      let assignment: Vec<_> = assignment
+        .iter()
+        .map(|(var, value)| format!("{}={}", var, value))
+        .collect();
+      writeln!(plan, "case {}", assignment.join(", ")).unwrap();
+    }
+    let (sliced, sliced_vars) = case.slice();
+    if !sliced_vars.is_empty() {
+      let names: Vec<_> = sliced_vars
+        .iter()
+        .map(|idx| case.vars()[*idx].as_str())
+        .collect();
+      writeln!(plan, "sliced: {}", names.join(", ")).unwrap();
+    }
+    let smt2 = sliced.reorder(&sliced.elimination_order());
+    for (var, construction) in smt2.plan() {
+      let idx = smt2.vars().iter().position(|v| *v == var).unwrap();
+      let size = smt2.membership(idx).unwrap().estimate_sfa_size();
+      writeln!(
+        plan,
+        "product {}: up to {} states, {}",
+        var, size, construction
+      )
+      .unwrap();
+    }
+    for sl_cons in smt2.sl_constraints().iter().rev() {
+      writeln!(
+        plan,
+        "pre-image {}: {} at {}",
+        smt2.vars()[sl_cons.idx()],
+        sl_cons.constraint().function_names().join(", "),
+        sl_cons.span()
+      )
+      .unwrap();
+    }
+    if !smt2.length_constraints().is_empty() {
+      writeln!(
+        plan,
+        "lengths: {} constraints, unbounded values searched up to {}",
+        smt2.length_constraints().len(),
+        LENGTH_BOUND
+      )
+      .unwrap();
+    }
+  }
+  plan
+}
+
+/** options of the demo subcommand */
+#[derive(Debug, Default, Clone)]
+pub struct DemoOption {
+  /** a regular expression of SMT-LIB, e.g. (re.* (str.to.re "ab")) */
+  pub regex: String,
+  /** straight-line definitions, pairs of a variable and a term of SMT-LIB, in the given order */
+  pub definitions: Vec<(String, String)>,
+  /** the variable constrained by regex, the last defined one if none */
+  pub target: Option<String>,
+  /** directory to write DOT files of machines into */
+  pub dot: Option<std::path::PathBuf>,
+}
+
+/** identifiers in an SMT-LIB term, which are not in string literals and have no dot */
+fn identifiers(term: &str) -> Vec<String> {
+  let mut in_string = false;
+  let stripped: String = term
+    .chars()
+    .map(|c| {
+      if c == '"' {
+        in_string = !in_string;
+        ' '
+      } else if in_string {
+        ' '
+      } else {
+        c
+      }
+    })
+    .collect();
+  stripped
+    .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+    .filter(|token| {
+      token.starts_with(|c: char| c.is_ascii_alphabetic())
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+    .map(String::from)
+    .collect()
+}
+
+/**
+ * SMT-LIB script of the demo.
+ * variables only referred by the definitions are declared before the defined ones.
+ */
+pub fn demo_script(option: &DemoOption) -> String {
+  let target = option
+    .target
+    .clone()
+    .or_else(|| option.definitions.last().map(|(var, _)| var.clone()))
+    .unwrap_or_else(|| String::from("x"));
+
+  let mut vars: Vec<String> = vec![];
+  for (_, term) in &option.definitions {
+    for var in identifiers(term) {
+      if !vars.contains(&var) && option.definitions.iter().all(|(v, _)| *v != var) {
+        vars.push(var);
+      }
+    }
+  }
+  vars.extend(option.definitions.iter().map(|(var, _)| var.clone()));
+  if !vars.contains(&target) {
+    vars.push(target.clone());
+  }
+
+  let mut script = String::new();
+  for var in &vars {
+    script.push_str(&format!("(declare-const {} String)\n", var));
+  }
+  for (var, term) in &option.definitions {
+    script.push_str(&format!("(assert (= {} {}))\n", var, term));
+  }
+  script.push_str(&format!(
+    "(assert (str.in.re {} {}))\n",
+    target, option.regex
+  ));
+  script.push_str("(check-sat)\n(get-model)\n");
+  script
+}
+
+/** write DOT files of the product and the transducers of compiled into dir, named after prefix */
+fn write_dot<D: Domain, S: State>(
+  dir: &std::path::Path,
+  prefix: &str,
+  compiled: &CompiledProblem<D, S>,
+) {
+  let mut files = vec![(
+    dir.join(format!("{}sfa.dot", prefix)),
+    compiled.sfa().to_dot(),
+  )];
+  for (i, sst) in compiled.ssts().iter().enumerate() {
+    files.push((dir.join(format!("{}sst{}.dot", prefix, i)), sst.to_dot()));
+  }
+  for (path, dot) in files {
+    match std::fs::write(&path, dot) {
+      Ok(()) => diagnostic!("wrote {}", path.display()),
+      Err(err) => diagnostic!("failed to write {}: {}", path.display(), err),
+    }
+  }
+}
+
+/**
+ * solve the problem of demo_script, printing the machines and a witness on the way.
+ * it fails if the regex or a definition given isn't a term of SMT-LIB the parser takes.
+ */
+pub fn demo(option: &DemoOption) -> Result<(), smt2::ParseError> {
+  let script = demo_script(option);
+  println!("{}", script);
+
+  let report = solve_cases(parse(&script)?, UnconstrainedPolicy::default(), |compiled| {
+    print_stats(compiled);
+    if let Some(dir) = &option.dot {
+      write_dot(dir, "", compiled);
+    }
+  });
+
+  match report.result {
+    SolverResult::Model(model) => {
+      let mut model: Vec<_> = model.into_iter().collect();
+      model.sort();
+      println!("witness:");
+      for (var, value) in model {
+        println!("  {} = {:?}", var, value);
+      }
+    }
+    result => println!("{:?}", result),
+  }
+  Ok(())
+}
+
+/** exit codes of run_with in the style of SMT-COMP, telling sat and unsat apart */
+pub const EXIT_UNKNOWN: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_SAT: i32 = 10;
+pub const EXIT_UNSAT: i32 = 20;
+
+pub fn run(input: &str) -> i32 {
+  run_with(input, &RunOption::default())
+}
+
+/**
+ * solve the input, print the result in SMT-LIB and return the exit code for it.
+ * with a timeout, it's solved in another thread, which is cancelled and joined if it times out.
+ */
+pub fn run_with(input: &str, option: &RunOption) -> i32 {
+  let timeout = match option.timeout {
+    Some(timeout) => timeout,
+    None => return option.states.scope(|| run_scoped(input, option)),
+  };
+  let input = input.to_owned();
+  let option = option.clone();
+  match limits::with_timeout(timeout, move || {
+    option.states.scope(|| run_scoped(&input, &option))
+  }) {
+    Some(code) => code,
+    None => {
+      println!("unknown");
+      diagnostic!("timeout after {} seconds", timeout.as_secs_f64());
+      EXIT_UNKNOWN
+    }
+  }
+}
+
+fn run_scoped(input: &str, option: &RunOption) -> i32 {
+  let problem: Problem = match Smt2::parse_with(input, option.strict) {
+    Ok(problem) => problem,
+    Err(err) => {
+      println!("(error {})", smt2::string_literal(&err.to_string()));
+      return EXIT_ERROR;
+    }
+  };
+  let needs_model = problem.needs_model();
+  let mut problem = problem
+    .with_strategy(option.strategy)
+    .with_ascii(option.ascii)
+    .with_bitsets(option.bitsets)
+    .with_shortest(option.shortest);
+  if option.model {
+    problem.set_get_model(true);
+  }
+  if option.ascii && option.warnings && !problem.is_ascii() {
+    diagnostic!("warning: --ascii is ignored since non-ASCII characters are mentioned");
+  }
+  if option.dump_deps {
+    print!("{}", problem.dependency_dot());
+    return EXIT_UNKNOWN;
+  }
+  if option.dry_run {
+    print!("{}", dry_run(&problem));
+    return EXIT_UNKNOWN;
+  }
+  if option.infer_values {
+    print_values(infer_values(problem.clone()));
+  }
+  if let Some(path) = &option.checkpoint {
+    let declared = problem.clone();
+    let result = checkpoint::solve(input, problem, option.unconstrained, path);
+    return print_response(result, &declared, needs_model);
+  }
+  if problem.checks().len() > 1 {
+    return run_checks(&problem, option);
+  }
+  let original = option.report.as_ref().map(|_| problem.clone());
+  let declared = problem.clone();
+  let mut automata = vec![];
+  let mut case = 0;
+  let solve_report = solve_cases(problem, option.unconstrained, |compiled| {
+    if option.stats {
+      print_stats(compiled);
+    }
+    if let Some(dir) = &option.dot {
+      write_dot(dir, &format!("case{}_", case), compiled);
+    }
+    case += 1;
+    if option.strategy == Strategy::Auto {
+      for (var, construction) in compiled.problem().plan() {
+        diagnostic!("plan\t{}\t{}", var, construction);
+      }
+    }
+    if option.report.is_some() {
+      automata = report::automata(compiled);
+    }
+  });
+
+  if let (Some(path), Some(original)) = (&option.report, original) {
+    let html = report::html(input, &original, &automata, &solve_report);
+    match std::fs::write(path, html) {
+      Ok(()) => diagnostic!("wrote {}", path.display()),
+      Err(err) => diagnostic!("failed to write {}: {}", path.display(), err),
+    }
+  }
+
+  let SolveReport {
+    result,
+    warnings,
+    trace,
+    ..
+  } = solve_report;
+
+  if option.warnings {
+    for warning in warnings {
+      diagnostic!("warning: {}", warning);
+    }
+  }
+  if option.explain {
+    for entry in trace {
+      diagnostic!("explain\t{}", entry);
+    }
+  }
+
+  print_response(result, &declared, needs_model)
+}
+
+/**
+ * answer each (check-sat) of a script with several ones against the assertions before it,
+ * reusing the machines built for the former ones. the exit status is of the last one.
+ */
+fn run_checks(problem: &Problem, option: &RunOption) -> i32 {
+  if option.warnings {
+    for warning in problem.warnings() {
+      diagnostic!("warning: {}", warning);
+    }
+  }
+  let cache = MachineCache::new();
+  let mut status = EXIT_UNKNOWN;
+  for check in problem.checks() {
+    let needs_model = check.needs_model();
+    let mut check = check
+      .clone()
+      .with_strategy(option.strategy)
+      .with_ascii(option.ascii)
+      .with_bitsets(option.bitsets)
+      .with_shortest(option.shortest);
+    if option.model {
+      check.set_get_model(true);
+    }
+    let result = solve_cases_with(check.clone(), option.unconstrained, &cache, |_| {}).result;
+    status = print_response(result, &check, needs_model);
+  }
+  status
 }
 
-pub fn run(input: &str) {
-  let smt2 = parse(input);
+/**
+ * print_result followed by the responses to (get-value), where the model is printed only for (get-model).
+ * it is an error if (get-model) or (get-value) is given for an unsat problem.
+ */
+fn print_response(result: SolverResult, declared: &Problem, needs_model: bool) -> i32 {
+  /* the thread waiting for this one answers unknown for the timeout */
+  if limits::is_cancelled() {
+    return EXIT_UNKNOWN;
+  }
+  if needs_model && result == SolverResult::Unsat {
+    println!("unsat");
+    println!("(error \"no model is available since the problem is unsat\")");
+    return EXIT_UNSAT;
+  }
+  let values = match &result {
+    SolverResult::Model(model) => Some(declared.value_blocks(model)),
+    _ => None,
+  };
+  let status = print_result(result, Some(declared).filter(|declared| declared.get_model()));
+  match values {
+    Some(Ok(blocks)) => print!("{}", blocks),
+    Some(Err(err)) => println!("(error {})", smt2::string_literal(&err.to_string())),
+    None => {}
+  }
+  status
+}
 
-  match check_sat(smt2) {
-    SolverResult::Sat => println!("sat"),
-    SolverResult::Unsat => println!("unsat"),
-    SolverResult::Model(var_map) => {
+/** print the response to (check-sat), and to (get-model) with a model of the declared problem */
+fn print_result(result: SolverResult, declared: Option<&Problem>) -> i32 {
+  match result {
+    SolverResult::Sat => {
+      println!("sat");
+      EXIT_SAT
+    }
+    SolverResult::Unsat => {
+      println!("unsat");
+      EXIT_UNSAT
+    }
+    SolverResult::ResourceExhausted(err) => {
+      println!("unknown");
+      diagnostic!("{}", err);
+      EXIT_UNKNOWN
+    }
+    SolverResult::Timeout(timeout) => {
+      println!("unknown");
+      diagnostic!("timeout after {} seconds", timeout.as_secs_f64());
+      EXIT_UNKNOWN
+    }
+    SolverResult::Unknown(reason) => {
+      println!("unknown");
+      diagnostic!("{}", reason);
+      EXIT_UNKNOWN
+    }
+    SolverResult::Model(model) => {
       println!("sat");
-      println!("given constraint is satisfiable with following assignment");
-      for (var, assignment) in var_map {
-        println!("{}:  {}", var, assignment);
+      if let Some(declared) = declared {
+        print!("{}", declared.model_block(&model));
       }
+      EXIT_SAT
     }
   }
 }
@@ -154,7 +1235,7 @@ mod tests {
   macro_rules! model {
     ( $($var:expr => $result:expr),* $(,)? ) => {
       SolverResult::Model(HashMap::from([$(
-        ($var.to_owned(), $result.to_owned())
+        ($var.to_owned(), $result.to_string())
       ),*]))
     };
   }
@@ -170,50 +1251,721 @@ mod tests {
       (get-model)
       "#;
 
-    assert_eq!(check_sat(parse(input)), model!["x0" => "ba","x1" => "ab"]);
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "ba","x1" => "ab"]);
   }
 
   #[test]
-  fn smt2_2_sst_replace() {
+  fn smt2_2_sst_replace_first() {
     let input = r#"
       (declare-const x0 String)
       (declare-const x1 String)
-      (assert (= x1 
-        (str.replaceallre x0 (re.union (str.to.re "a") (str.to.re "k")) "x")
-      ))
-      (assert (str.in.re x1 (str.to.re "x")))
+      (assert (= x1 (str.replace x0 "ab" "c")))
+      (assert (str.in.re x0 (re.+ (str.to.re "ab"))))
+      (assert (str.in.re x1 (str.to.re "cab")))
       (check-sat)
       (get-model)
       "#;
 
-    let model = check_sat(parse(input));
-    assert!(
-      model == model!["x0" => "a", "x1" => "x"]
-        || model == model!["x0" => "k", "x1" => "x"]
-        || model == model!["x0" => "x", "x1" => "x"]
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "abab", "x1" => "cab"]
     );
   }
 
   #[test]
-  fn smt2_2_sst_concat() {
+  fn smt2_2_sst_string_predicates() {
     let input = r#"
       (declare-const x0 String)
       (declare-const x1 String)
-      (assert (= x1 
-        (str.++ "abc" x0 "www")
-      ))
-      (assert (str.in.re x1 (str.to.re "x")))
+      (assert (= x1 (str.reverse x0)))
+      (assert (str.prefixof "ab" x0))
+      (assert (str.prefixof "ab" x1))
+      (assert (not (str.contains x0 "aa")))
       (check-sat)
       (get-model)
       "#;
 
-    assert_eq!(check_sat(parse(input)), SolverResult::Unsat);
+    match check_sat(parse(input).unwrap()) {
+      SolverResult::Model(model) => {
+        assert!(model["x0"].starts_with("ab"));
+        assert!(model["x0"].ends_with("ba"));
+        assert!(!model["x0"].contains("aa"));
+        assert_eq!(model["x1"], model["x0"].chars().rev().collect::<String>());
+      }
+      result => panic!("expected a model, got {:?}", result),
+    }
+    assert_eq!(
+      check_sat(parse(&input.replace(r#""aa""#, r#""b""#)).unwrap()),
+      SolverResult::Unsat
+    );
+  }
 
+  #[test]
+  fn smt2_2_sst_length() {
     let input = r#"
       (declare-const x0 String)
       (declare-const x1 String)
-      (assert (= x1 
-        (str.++ "abc" x0 "w")
+      (declare-const i Int)
+      (assert (= x1 (str.++ x0 "c")))
+      (assert (str.in.re x0 (re.* (str.to.re "ab"))))
+      (assert (= i (str.len x0)))
+      (assert (> i 2))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "abab", "x1" => "ababc", "i" => "4"]
+    );
+    assert_eq!(
+      check_sat(parse(&input.replace("(> i 2)", "(= i 3)")).unwrap()),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_length_bound() {
+    /* lengths are searched from the bounds the constraints give */
+    let input = r#"
+      (declare-fun x () String)
+      (assert (> (str.len x) 20))
+      (check-sat)
+      (get-model)
+      "#;
+    match check_sat(parse(input).unwrap()) {
+      SolverResult::Model(model) => assert_eq!(model["x"].chars().count(), 21),
+      result => panic!("{:?}", result),
+    }
+    let input = r#"
+      (declare-fun x () String)
+      (assert (str.in.re x (re.* (str.to.re "ab"))))
+      (assert (>= (str.len x) 40))
+      (check-sat)
+      (get-model)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x" => "ab".repeat(20)]);
+    let large = input.replace("(>= (str.len x) 40)", "(> (str.len x) 1000)");
+    match check_sat(parse(&large).unwrap()) {
+      SolverResult::Model(model) => assert_eq!(model["x"], "ab".repeat(501)),
+      result => panic!("{:?}", result),
+    }
+
+    assert_eq!(
+      check_sat(parse(&input.replace(">=", "=")).unwrap()),
+      model!["x" => "ab".repeat(20)]
+    );
+    assert_eq!(
+      check_sat(parse(&input.replace("(>= (str.len x) 40)", "(= (str.len x) 41)")).unwrap()),
+      SolverResult::Unsat
+    );
+    let input = r#"
+      (declare-const x String)
+      (declare-const i Int)
+      (assert (= i (str.indexof x "b" 0)))
+      (assert (= i 30))
+      (check-sat)
+      (get-model)
+      "#;
+    match check_sat(parse(input).unwrap()) {
+      SolverResult::Model(model) => {
+        assert_eq!(model["x"].find('b'), Some(30));
+        assert_eq!(model["i"], "30");
+      }
+      result => panic!("{:?}", result),
+    }
+    match check_sat(parse(&input.replace("(= i 30)", "(>= i 30)")).unwrap()) {
+      SolverResult::Model(model) => assert_eq!(model["x"].find('b'), Some(30)),
+      result => panic!("{:?}", result),
+    }
+
+    /* lengths unbounded by the constraints are unknown rather than unsat out of the bound */
+    let input = r#"
+      (declare-const x String)
+      (declare-const y String)
+      (assert (str.in.re x (re.* (str.to.re "ab"))))
+      (assert (str.in.re y (re.* (str.to.re "ab"))))
+      (assert (= (str.len x) (+ (str.len y) 1)))
+      (check-sat)
+      "#;
+    assert!(matches!(
+      check_sat(parse(input).unwrap()),
+      SolverResult::ResourceExhausted(_)
+    ));
+    let input = r#"
+      (declare-const x String)
+      (declare-const y String)
+      (assert (= (+ (str.len x) (str.len y)) 7))
+      (assert (= (str.len x) (* 2 (str.len y))))
+      (check-sat)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+  }
+
+  #[test]
+  fn smt2_2_sst_large_index() {
+    /* the automaton of a large index is too large to build */
+    let input = r#"
+      (declare-const x String)
+      (assert (= (str.at x 99999999999999) "a"))
+      (check-sat)
+      "#;
+    assert!(matches!(
+      check_sat(parse(input).unwrap()),
+      SolverResult::ResourceExhausted(_)
+    ));
+    let input = r#"
+      (declare-const x String)
+      (assert (str.in.re x ((_ re.loop 99999999999 99999999999) (str.to.re "a"))))
+      (check-sat)
+      "#;
+    assert!(matches!(
+      check_sat(parse(input).unwrap()),
+      SolverResult::ResourceExhausted(_)
+    ));
+  }
+
+  #[test]
+  fn smt2_2_sst_at_indexof() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (re.* (re.union (str.to.re "a") (str.to.re "b")))))
+      (assert (= x1 (str.at x0 1)))
+      (assert (str.in.re x1 (str.to.re "a")))
+      (assert (= (str.indexof x0 "b" 0) 2))
+      (assert (= (str.len x0) 3))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "aab", "x1" => "a"]);
+    assert_eq!(
+      check_sat(parse(&input.replace(r#""b" 0) 2"#, r#""b" 2) 1"#)).unwrap()),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_count() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (set-option :str-extensions true)
+      (assert (str.in.re x0 (re.* (re.union (str.to.re "a") (str.to.re ",")))))
+      (assert (str.prefixof "a" x0))
+      (assert (= x1 (str.++ x0 ",")))
+      (assert (<= (str.count x1 ",") 3))
+      (assert (= (str.len x1) 4))
+      (assert (= (str.count x0 ",") 2))
+      (assert (= (str.len x0) 3))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "a,,", "x1" => "a,,,"]);
+    assert_eq!(
+      check_sat(parse(&input.replace(r#"x1 ",") 3"#, r#"x1 ",") 2"#)).unwrap()),
+      SolverResult::Unsat
+    );
+
+    /* the membership has two occurrences, so no more are searched for */
+    let input = r#"
+      (declare-const x String)
+      (set-option :str-extensions true)
+      (assert (str.in.re x (re.++ (str.to.re "a,a,") (re.* (str.to.re "a")))))
+      (assert (> (str.count x ",") 2))
+      (check-sat)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+    assert_eq!(
+      check_sat(parse(&input.replace("(> ", "(>= ")).unwrap()),
+      SolverResult::Sat
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_defined_lengths() {
+    /* x1 is 5 or 7 long, which the length relation of its definition tells */
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (re.union (str.to.re "ab") (str.to.re "abc"))))
+      (assert (= x1 (str.++ x0 x0 "a")))
+      (assert (> (str.len x1) 7))
+      (check-sat)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+    assert_eq!(
+      check_sat(parse(&format!("{}(get-model)", input.replace("7))", "6))"))).unwrap()),
+      model!["x0" => "abc", "x1" => "abcabca"]
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_char_at() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (re.* (re.union (str.to.re "a") (str.to.re "b")))))
+      (assert (= x1 (str.++ x0 "a")))
+      (assert (= (str.at x0 1) "b"))
+      (assert (= "a" (str.at x1 0)))
+      (assert (= (str.len x0) 2))
+      (check-sat)
+      (get-model)
+      "#;
+
+    let problem = parse(input).unwrap();
+    assert_eq!(problem.sl_constraints().len(), 1);
+    assert_eq!(check_sat(problem), model!["x0" => "ab", "x1" => "aba"]);
+    assert_eq!(
+      check_sat(parse(&input.replace(r#"(str.at x0 1) "b""#, r#"(str.at x0 2) "b""#)).unwrap()),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_int_conversions() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const i Int)
+      (assert (= (str.to_int x0) 12))
+      (assert (= (str.len x0) 3))
+      (assert (= i (+ (str.to_int x0) 1)))
+      (assert (= x1 (str.from_int i)))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "012", "x1" => "13", "i" => "13"]
+    );
+
+    /* values out of the bound are searched by the digits of the words */
+    let input = r#"
+      (declare-const x String)
+      (declare-const i Int)
+      (assert (= i (str.to_int x)))
+      (assert (= i 19))
+      (check-sat)
+      (get-model)
+      "#;
+    match check_sat(parse(input).unwrap()) {
+      SolverResult::Model(model) => {
+        assert_eq!(model["x"].trim_start_matches('0'), "19");
+        assert_eq!(model["i"], "19");
+      }
+      result => panic!("{:?}", result),
+    }
+    /* and from the bounds of the value without a membership, or with an infinite one */
+    let input = r#"
+      (declare-const x String)
+      (declare-const i Int)
+      (assert (= i (str.to_int x)))
+      (assert (> i 41))
+      (assert (< i 43))
+      (check-sat)
+      (get-model)
+      "#;
+    match check_sat(parse(input).unwrap()) {
+      SolverResult::Model(model) => {
+        assert_eq!(model["x"].trim_start_matches('0'), "42");
+        assert_eq!(model["i"], "42");
+      }
+      result => panic!("{:?}", result),
+    }
+    let digits = r#"(assert (str.in.re x (re.+ (re.range "0" "9"))))"#;
+    let input = input.replace("(check-sat)", &format!("{}\n(check-sat)", digits));
+    match check_sat(parse(&input.replace("41", "12345").replace("43", "12347")).unwrap()) {
+      SolverResult::Model(model) => assert_eq!(model["i"], "12346"),
+      result => panic!("{:?}", result),
+    }
+    assert_eq!(
+      check_sat(parse(&input.replace("43", "42")).unwrap()),
+      SolverResult::Unsat
+    );
+    let input = r#"
+      (declare-const x String)
+      (declare-const i Int)
+      (assert (= x (str.from_int i)))
+      (assert (= x "42"))
+      (check-sat)
+      (get-model)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x" => "42", "i" => "42"]);
+    assert_eq!(
+      check_sat(parse(&input.replace(r#"(= x "42")"#, r#"(str.in.re x (re.union (str.to.re "42") (str.to.re "042")))"#)).unwrap()),
+      model!["x" => "42", "i" => "42"]
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_auto_strategy() {
+    use regular::regex::Construction;
+
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (re.union (str.to.re "ab") (str.to.re "ba") (str.to.re "abab"))))
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (str.in.re x1 (re.inter
+        (re.* (str.to.re "ab"))
+        (re.* (re.union (str.to.re "a") (str.to.re "b")))
+        (re.+ (str.to.re "abab")))))
+      (check-sat)
+      (get-model)
+      "#;
+    let problem = parse(input).unwrap().with_strategy(Strategy::Auto);
+    assert_eq!(
+      problem.plan(),
+      vec![
+        (String::from("x0"), Construction::Trie),
+        (String::from("x1"), Construction::SmallestFirst)
+      ]
+    );
+
+    match check_sat(problem) {
+      SolverResult::Model(model) => {
+        assert!(["ab", "abab"].contains(&model["x0"].as_str()));
+        assert_eq!(model["x1"], model["x0"].repeat(2));
+      }
+      result => panic!("expected a model, got {:?}", result),
+    }
+    assert_eq!(
+      check_sat(
+        parse(&input.replace("(re.+", "(re.inter (str.to.re \"\")")).unwrap().with_strategy(Strategy::Auto)
+      ),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_comp_diff_opt() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (re.diff (re.* (str.to.re "ab")) (re.opt (str.to.re "ab")))))
+      (assert (str.in.re x0 (re.comp (str.to.re "abab"))))
+      (assert (= x1 (str.reverse x0)))
+      (check-sat)
+      (get-model)
+      "#;
+
+    match check_sat(parse(input).unwrap()) {
+      SolverResult::Model(model) => {
+        let x0 = &model["x0"];
+        assert!(x0.len() >= 6 && x0.len() % 2 == 0);
+        assert_eq!(*x0, "ab".repeat(x0.len() / 2));
+        assert_eq!(model["x1"], x0.chars().rev().collect::<String>());
+      }
+      result => panic!("expected a model, got {:?}", result),
+    }
+
+    /* the words with the separator don't count in complements and differences */
+    let input = r#"
+      (declare-const x0 String)
+      (assert (str.in.re x0 (re.diff (re.* re.allchar) (re.* (str.to.re "a")))))
+      (assert (str.in.re x0 (re.comp (re.++ (re.* re.allchar) (str.to.re "b") (re.* re.allchar)))))
+      (assert (not (str.in.re x0 (re.comp (re.* re.allchar)))))
+      (check-sat)
+      (get-model)
+      "#;
+    match check_sat(parse(input).unwrap()) {
+      SolverResult::Model(model) => {
+        let x0 = &model["x0"];
+        assert!(x0.chars().any(|c| c != 'a'), "{:?}", x0);
+        assert!(!x0.contains(['b', '#']), "{:?}", x0);
+      }
+      result => panic!("expected a model, got {:?}", result),
+    }
+  }
+
+  #[test]
+  fn smt2_2_sst_negated_universal() {
+    let input = r#"
+      (declare-const x String)
+      (assert (str.in.re x (re.comp (re.* re.allchar))))
+      (check-sat)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+
+    let input = r#"
+      (declare-const x String)
+      (assert (not (str.in.re x (re.* re.allchar))))
+      (check-sat)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+  }
+
+  #[test]
+  fn smt2_2_sst_loop() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 ((_ re.loop 2 3) (str.to.re "ab"))))
+      (assert (= x1 (str.++ x0 "c")))
+      (assert (str.in.re x1 (re.++ ((_ re.^ 3) (str.to.re "ab")) (str.to.re "c"))))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "ababab", "x1" => "abababc"]
+    );
+    assert_eq!(
+      check_sat(parse(&input.replace("re.loop 2 3", "re.loop 1 2")).unwrap()),
+      SolverResult::Unsat
+    );
+    assert_eq!(
+      check_sat(parse(&input.replace(
+        "((_ re.loop 2 3) (str.to.re \"ab\"))",
+        "(re.loop (str.to.re \"ab\") 3)"
+      )).unwrap()),
+      model!["x0" => "ababab", "x1" => "abababc"]
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_alphabet() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (set-option :alphabet (x0 "[ab]"))
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (str.in.re x1 (re.++ (str.to.re "a") re.allchar (str.to.re "a") re.allchar)))
+      (assert (str.in.re x0 (re.comp (str.to.re "ab"))))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "aa", "x1" => "aaaa"]
+    );
+    assert_eq!(
+      check_sat(parse(&input.replace("[ab]", "b")).unwrap()),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_ascii() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "!")))
+      (assert (str.in.re x0 (re.+ (re.range "a" "c"))))
+      (assert (str.in.re x1 (re.++ (re.comp (str.to.re "a")) (str.to.re "!"))))
+      (assert (str.in.re x1 (re.++ re.allchar (re.* re.allchar))))
+      (check-sat)
+      (get-model)
+      "#;
+
+    let problem = parse(input).unwrap().with_ascii(true);
+    assert!(problem.is_ascii());
+    match check_sat(problem) {
+      SolverResult::Model(model) => {
+        assert_eq!(model["x1"], format!("{}!", model["x0"]));
+        assert!(model["x0"].chars().all(|c| c == 'a' || c == 'b'));
+        assert_ne!(model["x0"], "a");
+      }
+      result => panic!("unexpected {:?}", result),
+    }
+    let unsat = input.replace("(re.+ (re.range \"a\" \"c\"))", "(str.to.re \"a\")");
+    assert_eq!(
+      check_sat(parse(&unsat).unwrap().with_ascii(true)),
+      SolverResult::Unsat
+    );
+
+    /* solved as usual since x1 has a non-ASCII character */
+    let input = input.replace("\"!\"", "\"\u{e9}\"");
+    let problem = parse(&input).unwrap().with_ascii(true);
+    assert!(!problem.is_ascii());
+    assert!(matches!(check_sat(problem), SolverResult::Model(_)));
+  }
+
+  #[test]
+  fn smt2_2_sst_bitsets() {
+    use boolean_algebra::classes::MAX_CLASSES;
+
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "é")))
+      (assert (str.in.re x0 (re.+ (re.range "a" "c"))))
+      (assert (str.in.re x1 (re.++ (re.comp (str.to.re "a")) (str.to.re "é"))))
+      (assert (str.in.re x1 (re.++ re.allchar (re.* re.allchar))))
+      (check-sat)
+      (get-model)
+      "#;
+
+    let problem = parse(input).unwrap().with_bitsets(true);
+    assert!(problem.classes().len() <= MAX_CLASSES);
+    match check_sat(problem) {
+      SolverResult::Model(model) => {
+        assert_eq!(model["x1"], format!("{}é", model["x0"]));
+        assert!(model["x0"].chars().all(|c| c == 'a' || c == 'b'));
+        assert_ne!(model["x0"], "a");
+      }
+      result => panic!("unexpected {:?}", result),
+    }
+    let unsat = input.replace("(re.+ (re.range \"a\" \"c\"))", "(str.to.re \"a\")");
+    assert_eq!(
+      check_sat(parse(&unsat).unwrap().with_bitsets(true)),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_shortest() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 x0 "c")))
+      (assert (str.in.re x0 (re.union (str.to.re "abcabc") (re.+ (str.to.re "ab")))))
+      (check-sat)
+      (get-model)
+      "#;
+
+    match check_sat(parse(input).unwrap().with_shortest(true)) {
+      SolverResult::Model(model) => {
+        assert_eq!(model["x0"], "ab");
+        assert_eq!(model["x1"], "ababc");
+      }
+      result => panic!("unexpected {:?}", result),
+    }
+  }
+
+  #[test]
+  fn dry_run() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (declare-const x3 String)
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (= x2 (str.reverse x1)))
+      (assert (= x3 (str.++ x0 "a")))
+      (assert (str.in.re x2 (str.to.re "abab")))
+      (check-sat)
+      "#;
+    assert_eq!(
+      super::dry_run(&parse(input).unwrap()),
+      "sliced: x3\n\
+       product x2: up to 8 states, inductive\n\
+       pre-image x2: str.reverse at 7:7\n\
+       pre-image x1: str.++ at 6:7\n"
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_substr() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (str.to.re "abcd")))
+      (assert (= x1 (str.substr x0 1 2)))
+      (assert (str.in.re x1 (str.to.re "bc")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "abcd", "x1" => "bc"]
+    );
+    assert_eq!(
+      check_sat(parse(&input.replace("\"bc\"", "\"cd\"")).unwrap()),
+      SolverResult::Unsat
+    );
+
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const i Int)
+      (assert (str.in.re x0 (str.to.re "abcd")))
+      (assert (= x1 (str.substr x0 i 2)))
+      (assert (str.in.re x1 (str.to.re "cd")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(check_sat(parse(&input.replace("(get-model)", "")).unwrap()), SolverResult::Sat);
+    /* the abstracted index is searched in the model */
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "abcd", "x1" => "cd", "i" => "2"]
+    );
+    assert_eq!(
+      check_sat(parse(&input.replace("(check-sat)", "(assert (= i 1))\n(check-sat)")).unwrap()),
+      SolverResult::Unsat
+    );
+    assert!(matches!(
+      check_sat(parse(&input.replace("x0 i 2", "x0 (+ i 1) 2")).unwrap()),
+      SolverResult::Unknown(_)
+    ));
+
+    /* negative arguments take nothing */
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (str.to.re "abcd")))
+      (assert (= x1 (str.substr x0 (- 1) 2)))
+      (check-sat)
+      (get-model)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "abcd", "x1" => ""]);
+    assert_eq!(
+      check_sat(parse(&input.replace("(- 1) 2", "1 (- 2)")).unwrap()),
+      model!["x0" => "abcd", "x1" => ""]
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_replace() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 
+        (str.replaceallre x0 (re.union (str.to.re "a") (str.to.re "k")) "x")
+      ))
+      (assert (str.in.re x1 (str.to.re "x")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    let model = check_sat(parse(input).unwrap());
+    assert!(
+      model == model!["x0" => "a", "x1" => "x"]
+        || model == model!["x0" => "k", "x1" => "x"]
+        || model == model!["x0" => "x", "x1" => "x"]
+    );
+  }
+
+  #[test]
+  fn smt2_2_sst_concat() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 
+        (str.++ "abc" x0 "www")
+      ))
+      (assert (str.in.re x1 (str.to.re "x")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 
+        (str.++ "abc" x0 "w")
       ))
       (assert (str.in.re x1
         (
@@ -226,7 +1978,7 @@ mod tests {
 
     assert!({
       let mut result = false;
-      let model = check_sat(parse(input));
+      let model = check_sat(parse(input).unwrap());
       for i in 0..=5 {
         let x0 = format!("a{}", "w".repeat(i));
         if model == model!["x0" => x0, "x1" => format!("{}{}{}", "abc", x0, "w")] {
@@ -252,7 +2004,7 @@ mod tests {
       (get-model)
       "#;
 
-    assert_eq!(check_sat(parse(input)), SolverResult::Unsat);
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
 
     let input = r#"
       (declare-const x0 String)
@@ -266,7 +2018,7 @@ mod tests {
       "#;
 
     assert_eq!(
-      check_sat(parse(input)),
+      check_sat(parse(input).unwrap()),
       model!["x0" => "ba", "x1" => "ab", "x2" => "aba"]
     );
   }
@@ -291,7 +2043,437 @@ mod tests {
       (get-model)
       "#;
 
-    assert_eq!(check_sat(parse(input)), SolverResult::Unsat);
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+  }
+
+  #[test]
+  fn multiple_memberships_on_defined_variable() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "a")))
+      (assert (str.in.re x1 (re.* (re.union (str.to.re "a") (str.to.re "b")))))
+      (assert (str.in.re x1 (re.++ (re.* re.allchar) (str.to.re "b") (re.* re.allchar))))
+      (assert (str.in.re x1 (re.++ re.allchar re.allchar re.allchar)))
+      (check-sat)
+      (get-model)
+      "#;
+
+    let model = check_sat(parse(input).unwrap());
+    assert!(["ab", "ba", "bb"]
+      .iter()
+      .any(|x0| model == model!["x0" => x0, "x1" => format!("{}a", x0)]));
+
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "a")))
+      (assert (str.in.re x1 (re.* (re.union (str.to.re "a") (str.to.re "b")))))
+      (assert (str.in.re x1 (re.++ (re.* re.allchar) (str.to.re "b") (re.* re.allchar))))
+      (assert (str.in.re x1 (re.++ re.allchar (str.to.re "a"))))
+      (assert (str.in.re x0 (str.to.re "a")))
+      (check-sat)
+      "#;
+
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Unsat);
+  }
+
+  #[test]
+  fn unconstrained_policy() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (= x1 (str.reverse x0)))
+      (assert (str.in.re x1 (str.to.re "ab")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      solve(compile(parse(input).unwrap())),
+      model!["x0" => "ba", "x1" => "ab", "x2" => ""]
+    );
+    assert_eq!(
+      solve(compile(parse(input).unwrap()).with_unconstrained(UnconstrainedPolicy::ShortestNonEmpty)),
+      model!["x0" => "ba", "x1" => "ab", "x2" => "a"]
+    );
+    if let SolverResult::Model(model) =
+      solve(compile(parse(input).unwrap()).with_unconstrained(UnconstrainedPolicy::Random))
+    {
+      assert!(!model["x2"].is_empty());
+    } else {
+      unreachable!();
+    }
+  }
+
+  #[test]
+  fn model_of_intermediate_variables() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (declare-const x3 String)
+      (assert (= x1 (str.replaceallre x0 (str.to.re "a") "b")))
+      (assert (= x2 (str.++ x1 x0)))
+      (assert (= x3 (str.reverse x2)))
+      (assert (str.in.re x3 (str.to.re "axbx")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      check_sat(parse(input).unwrap()),
+      model!["x0" => "xa", "x1" => "xb", "x2" => "xbxa", "x3" => "axbx"]
+    );
+  }
+
+  #[test]
+  fn definitions_out_of_declaration_order() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (= x1 (str.++ x2 "b")))
+      (assert (= x2 (str.++ x0 "a")))
+      (assert (str.in.re x1 (str.to.re "cab")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    let compiled = compile(parse(input).unwrap());
+    assert_eq!(compiled.elimination_order(), &vec!["x1", "x2"]);
+    assert_eq!(
+      solve(compiled),
+      model!["x0" => "c", "x1" => "cab", "x2" => "ca"]
+    );
+  }
+
+  #[test]
+  fn check_sat_assuming() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const b Bool)
+      (assert (! (str.in.re x0 (re.+ (str.to.re "a"))) :named a1))
+      (assert (=> b (str.in.re x0 (str.to.re "aa"))))
+      (check-sat-assuming (b))
+      (get-model)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "aa", "b" => true]);
+    let input = input.replace("(b)", "((not b) a1)");
+    match check_sat(parse(&input).unwrap()) {
+      SolverResult::Model(model) => {
+        assert_eq!(model["b"], "false");
+        assert!(!model["x0"].is_empty() && model["x0"].chars().all(|c| c == 'a'));
+      }
+      result => panic!("expected a model, got {:?}", result),
+    }
+    let input = input.replace("((not b) a1)", "((not a1))");
+    assert_eq!(check_sat(parse(&input).unwrap()), SolverResult::Unsat);
+    let input = input.replace("((not a1))", "(a2)");
+    assert!(matches!(
+      Smt2::<CharWrap, StateImpl>::parse(&input),
+      Err(smt2::ParseError::UnknownVariable { .. })
+    ));
+  }
+
+  #[test]
+  fn ite_terms() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (ite (str.in.re x0 (str.to.re "a")) (str.++ x0 "b") "c")))
+      (assert (or (str.in.re x0 (str.to.re "a")) (str.in.re x0 (str.to.re "d"))))
+      (assert (str.in.re x1 (str.to.re "c")))
+      (check-sat)
+      (get-model)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "d", "x1" => "c"]);
+    let input = input.replace(r#"(str.to.re "c")))"#, r#"(str.to.re "ab")))"#);
+    assert_eq!(check_sat(parse(&input).unwrap()), model!["x0" => "a", "x1" => "ab"]);
+
+    let input = r#"
+      (declare-const x0 String)
+      (assert (ite (str.prefixof "a" x0) (str.in.re x0 (str.to.re "ab")) (str.suffixof "c" x0)))
+      (assert (not (str.in.re x0 (str.to.re "ab"))))
+      (assert (str.in.re x0 (re.union (str.to.re "ac") (str.to.re "bc"))))
+      (check-sat)
+      (get-model)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "bc"]);
+  }
+
+  #[test]
+  fn ite_terms_too_many_cases() {
+    /* each Bool variable doubles the cases */
+    let declarations: String = (0..20)
+      .map(|i| format!("(declare-const b{} Bool)", i))
+      .collect();
+    let assertions: String = (0..20)
+      .map(|i| format!(r#"(assert (ite b{} (str.prefixof "a" x) (str.suffixof "a" x)))"#, i))
+      .collect();
+    let input = format!("(declare-const x String) {} {} (check-sat)", declarations, assertions);
+    match check_sat(parse(&input).unwrap()) {
+      SolverResult::ResourceExhausted(err) => assert_eq!(err.construction, "case split"),
+      result => panic!("{:?}", result),
+    }
+
+    /* so does each ite on a membership */
+    let assertions: String = (0..20)
+      .map(|i| {
+        format!(
+          r#"(assert (ite (str.in.re x (str.to.re "{}")) (str.prefixof "a" x) (str.suffixof "b" x)))"#,
+          i
+        )
+      })
+      .collect();
+    let input = format!("(declare-const x String) {} (check-sat)", assertions);
+    match check_sat(parse(&input).unwrap()) {
+      SolverResult::ResourceExhausted(err) => {
+        assert_eq!(err.construction, "case split");
+        assert_eq!(err.limit, smt2::MAX_CASES);
+      }
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn constant_definitions() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x0 "ab"))
+      (assert (= x1 ""))
+      (assert (str.in.re x0 (re.* (str.to.re "ab"))))
+      (assert (str.in.re x1 (re.* (str.to.re "a"))))
+      (check-sat)
+      (get-model)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), model!["x0" => "ab", "x1" => ""]);
+    let input = input.replace(r#"(str.to.re "a")"#, r#"(str.to.re "b")"#);
+    let input = input.replace(r#"(= x1 "")"#, r#"(= x1 "ba")"#);
+    assert_eq!(check_sat(parse(&input).unwrap()), SolverResult::Unsat);
+    /* nothing is left after slicing */
+    let input = r#"
+      (declare-const x0 String)
+      (assert (= x0 "ab"))
+      (check-sat)
+      "#;
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Sat);
+  }
+
+  #[test]
+  fn slice_out_of_cone_of_influence() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (declare-const x3 String)
+      (assert (= x1 (str.++ x0 "a")))
+      (assert (= x2 (str.++ x3 x1)))
+      (assert (= x3 (str.reverse x0)))
+      (assert (str.in.re x1 (str.to.re "ba")))
+      (check-sat)
+      (get-model)
+      "#;
+
+    let compiled = compile(parse(input).unwrap());
+    assert_eq!(compiled.sliced(), &vec!["x2", "x3"]);
+    assert_eq!(compiled.ssts().len(), 1);
+    assert_eq!(
+      solve(compiled),
+      model!["x0" => "b", "x1" => "ba", "x2" => "bba", "x3" => "b"]
+    );
+  }
+
+  #[test]
+  fn boolean_constant_assertions() {
+    let input = r#"
+      (declare-const x0 String)
+      (assert true)
+      (assert (str.in.re x0 (str.to.re "ab")))
+      (check-sat)
+      "#;
+
+    assert_eq!(check_sat(parse(input).unwrap()), SolverResult::Sat);
+    assert_eq!(
+      check_sat(parse(&input.replace("true", "false")).unwrap()),
+      SolverResult::Unsat
+    );
+    assert_eq!(
+      check_sat(parse("(assert false) (check-sat)").unwrap()),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn ignored_assertions() {
+    let input = r#"
+      (declare-const x String)
+      (declare-const y String)
+      (assert (str.in.re x (str.to.re "b")))
+      (assert (str.< x "a"))
+      (check-sat)
+      "#;
+    /* a model of the rest may violate the ignored assertion */
+    assert!(matches!(
+      check_sat(parse(input).unwrap()),
+      SolverResult::Unknown(_)
+    ));
+    let pinned = r#"
+      (declare-const x String)
+      (declare-const y String)
+      (assert (str.in.re x (str.to.re "a")))
+      (assert (str.in.re y (str.to.re "a")))
+      (assert (not (= y x)))
+      (check-sat)
+      "#;
+    assert!(matches!(
+      check_sat(parse(pinned).unwrap()),
+      SolverResult::Unknown(_)
+    ));
+
+    /* while the rest being unsat is enough */
+    let unsat = input.replace(
+      "(assert (str.<",
+      "(assert (str.in.re x (str.to.re \"c\"))) (assert (str.<",
+    );
+    assert_eq!(check_sat(parse(&unsat).unwrap()), SolverResult::Unsat);
+
+    /* the assertion ignored in a popped scope is gone */
+    let scoped = input.replace("(assert (str.<", "(push 1) (assert (str.<");
+    let problem = parse(&format!("{} (pop 1) (check-sat)", scoped)).unwrap();
+    assert!(matches!(
+      check_sat(problem.checks()[0].clone()),
+      SolverResult::Unknown(_)
+    ));
+    assert_eq!(check_sat(problem.checks()[1].clone()), SolverResult::Sat);
+  }
+
+  #[test]
+  fn bool_variables() {
+    let input = r#"
+      (declare-const b Bool)
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "a")))
+      (assert (=> b (str.in.re x1 (str.to.re "ba"))))
+      (assert (=> (not b) (str.in.re x0 (str.to.re "c"))))
+      (check-sat)
+      (get-model)
+      "#;
+
+    assert_eq!(
+      check_sat(parse(&format!("{} (assert (not b))", input)).unwrap()),
+      model!["b" => "false", "x0" => "c", "x1" => "ca"]
+    );
+    assert_eq!(
+      check_sat(parse(&format!("{} (assert b)", input)).unwrap()),
+      model!["b" => "true", "x0" => "b", "x1" => "ba"]
+    );
+    assert_eq!(
+      check_sat(parse(&format!("{} (assert b) (assert (not b))", input)).unwrap()),
+      SolverResult::Unsat
+    );
+  }
+
+  #[test]
+  fn demo_script() {
+    let option = DemoOption {
+      regex: String::from(r#"(str.to.re "abab")"#),
+      definitions: vec![
+        (String::from("y"), String::from(r#"(str.++ x x)"#)),
+        (
+          String::from("z"),
+          String::from(r#"(str.replaceallre y (str.to.re "c") "b")"#),
+        ),
+      ],
+      ..Default::default()
+    };
+    let script = super::demo_script(&option);
+    assert!(script.starts_with("(declare-const x String)\n(declare-const y String)\n"));
+    assert!(script.contains(r#"(assert (str.in.re z (str.to.re "abab")))"#));
+
+    match check_sat(parse(&script).unwrap()) {
+      SolverResult::Model(model) => {
+        assert!(["ab", "ac"].contains(&model["x"].as_str()));
+        assert_eq!(model["z"], "abab");
+      }
+      result => panic!("unexpected {:?}", result),
+    }
+
+    let option = DemoOption {
+      regex: String::from(r#"(str.to.re "a")"#),
+      ..Default::default()
+    };
+    assert_eq!(
+      check_sat(parse(&super::demo_script(&option)).unwrap()),
+      model!["x" => "a"]
+    );
+
+    /* a regex the parser doesn't take is an error, not a panic */
+    let option = DemoOption {
+      regex: String::from("(re.bogus x)"),
+      ..Default::default()
+    };
+    assert!(parse(&super::demo_script(&option)).is_err());
+  }
+
+  #[test]
+  fn compile_then_solve() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (= x1 (str.reverse x0)))
+      (assert (= x2 (str.++ x1 "a")))
+      (assert (str.in.re x2 (str.to.re "aba")))
+      (check-sat)
+      "#;
+
+    let compiled = compile(parse(input).unwrap());
+    assert_eq!(compiled.ssts().len(), 2);
+    assert_eq!(compiled.problem().vars().len(), 3);
+    assert_eq!(
+      solve_report(compiled.clone()),
+      SolveReport {
+        result: SolverResult::Sat,
+        warnings: vec![],
+        elimination_order: vec![String::from("x2"), String::from("x1")],
+        sliced: vec![],
+        trace: vec![],
+      }
+    );
+    assert_eq!(solve(compiled), check_sat(parse(input).unwrap()));
+  }
+
+  #[test]
+  fn infer_values_without_sink() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (str.in.re x0 (re.union (str.to.re "ab") (str.to.re "c"))))
+      (assert (= x1 (str.reverse x0)))
+      (assert (= x2 (str.++ x1 "a")))
+      (check-sat)
+      "#;
+
+    let values: HashMap<_, _> = infer_values(parse(input).unwrap()).into_iter().collect();
+    assert_eq!(values.len(), 3);
+    for (var, case, expected) in [
+      ("x0", "ab", true),
+      ("x0", "ba", false),
+      ("x1", "ba", true),
+      ("x1", "c", true),
+      ("x1", "ab", false),
+      ("x2", "baa", true),
+      ("x2", "ca", true),
+      ("x2", "ba", false),
+    ] {
+      let input: Vec<CharWrap> = case.chars().map(|c| CharWrap::from(c)).collect();
+      assert_eq!(values[var].run(&input), expected);
+    }
   }
 
   #[test]
@@ -310,7 +2492,7 @@ mod tests {
       (check-sat)
       (get-model)
       "#;
-    eprintln!("{:?}", check_sat(parse(input)));
+    eprintln!("{:?}", check_sat(parse(input).unwrap()));
     unreachable!();
   }
 }
@@ -0,0 +1,171 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+#[cfg(feature = "std")]
+use std::sync::mpsc;
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc,
+};
+use std::time::Duration;
+
+/** the number of states a fixpoint construction may build before it is taken as diverging */
+pub const STATE_LIMIT: usize = 100_000;
+
+/**
+ * a fixpoint construction built more states than STATE_LIMIT,
+ * which is reported instead of running for a degenerate input.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceExhausted {
+  /** the construction which diverged, e.g. pre-image */
+  pub construction: &'static str,
+  /** the machines it was applied to */
+  pub detail: String,
+  pub limit: usize,
+  /** what the limit counts, e.g. states */
+  pub unit: &'static str,
+}
+impl ResourceExhausted {
+  /**
+   * whether a construction with the number of states should give up,
+   * which it also does once the thread is cancelled, see with_timeout
+   */
+  pub(crate) fn check(
+    construction: &'static str,
+    states: usize,
+    detail: impl FnOnce() -> String,
+  ) -> Result<(), Self> {
+    Self::check_cancelled(construction)?;
+    if states > STATE_LIMIT {
+      Err(ResourceExhausted {
+        construction,
+        detail: detail(),
+        limit: STATE_LIMIT,
+        unit: "states",
+      })
+    } else {
+      Ok(())
+    }
+  }
+
+  /** whether a construction should give up since the thread is cancelled */
+  pub(crate) fn check_cancelled(construction: &'static str) -> Result<(), Self> {
+    if is_cancelled() {
+      Err(ResourceExhausted {
+        construction,
+        detail: String::from("cancelled after the timeout"),
+        limit: 0,
+        unit: "states",
+      })
+    } else {
+      Ok(())
+    }
+  }
+}
+impl Display for ResourceExhausted {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} exceeded {} {} ({})",
+      self.construction, self.limit, self.unit, self.detail
+    )
+  }
+}
+impl std::error::Error for ResourceExhausted {}
+
+thread_local! {
+  /** set once whoever waits for the current thread gives up on it */
+  static CANCELLED: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/** whether the current thread runs with_timeout which has already timed out */
+pub(crate) fn is_cancelled() -> bool {
+  CANCELLED.with(|cancelled| {
+    cancelled
+      .borrow()
+      .as_ref()
+      .is_some_and(|cancelled| cancelled.load(Ordering::Relaxed))
+  })
+}
+
+/**
+ * run f in another thread, giving None if it takes longer than timeout.
+ * the thread is then cancelled, so that constructions give up at their next check
+ * by ResourceExhausted, and joined. a panic of f before the timeout is passed on.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn with_timeout<T: Send + 'static>(
+  timeout: Duration,
+  f: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+  let cancelled = Arc::new(AtomicBool::new(false));
+  let (sender, receiver) = mpsc::channel();
+  let worker = {
+    let cancelled = Arc::clone(&cancelled);
+    std::thread::Builder::new()
+      /* as large as the main thread, since constructions recurse on regexes */
+      .stack_size(8 << 20)
+      .spawn(move || {
+        CANCELLED.with(|current| *current.borrow_mut() = Some(cancelled));
+        let _ = sender.send(f());
+      })
+      .expect("failed to spawn a solver thread")
+  };
+  match receiver.recv_timeout(timeout) {
+    Ok(result) => {
+      let _ = worker.join();
+      Some(result)
+    }
+    Err(mpsc::RecvTimeoutError::Timeout) => {
+      cancelled.store(true, Ordering::Relaxed);
+      /* what it ends with no longer matters */
+      let _ = worker.join();
+      None
+    }
+    /* disconnected if f panicked, which is passed on */
+    Err(mpsc::RecvTimeoutError::Disconnected) => match worker.join() {
+      Err(panic) => std::panic::resume_unwind(panic),
+      Ok(()) => unreachable!("the thread ended without a result"),
+    },
+  }
+}
+
+/** without the `std` feature no thread is spawned, so f runs to the end whatever the timeout */
+#[cfg(not(feature = "std"))]
+pub(crate) fn with_timeout<T>(_timeout: Duration, f: impl FnOnce() -> T) -> Option<T> {
+  Some(f())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn check() {
+    assert!(ResourceExhausted::check("pre-image", STATE_LIMIT, || unreachable!()).is_ok());
+    let err = ResourceExhausted::check("pre-image", STATE_LIMIT + 1, || String::from("an SFA"))
+      .unwrap_err();
+    assert_eq!(err.construction, "pre-image");
+    assert_eq!(
+      err.to_string(),
+      format!("pre-image exceeded {} states (an SFA)", STATE_LIMIT)
+    );
+  }
+
+  #[cfg(feature = "std")]
+  #[test]
+  fn with_timeout() {
+    assert_eq!(super::with_timeout(Duration::from_secs(10), || 1), Some(1));
+
+    /* a construction checking its states stops soon after the timeout */
+    let start = std::time::Instant::now();
+    let result = super::with_timeout(Duration::from_millis(50), || loop {
+      if let Err(err) = ResourceExhausted::check("pre-image", 0, String::new) {
+        return err;
+      }
+    });
+    assert_eq!(result, None);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(!is_cancelled());
+  }
+}
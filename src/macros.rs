@@ -0,0 +1,137 @@
+/*
+ * declarative construction of small machines for tests and examples, with the `macros` feature.
+ * states are made by State::new, so the state type comes from the type of the result, e.g.
+ *   let ab: Sfa<char, StateImpl> = sfa! {
+ *     { q0, q1, q2 },
+ *     { -> q0, (q0, Predicate::char('a')) -> [q1], (q1, Predicate::char('b')) -> [q2] },
+ *     { q2 }
+ *   };
+ */
+
+/**
+ * a symbolic automaton from its states, the initial state and transitions, and final states.
+ * states are identifiers bound to new states, which predicates and targets may refer to.
+ */
+#[macro_export]
+macro_rules! sfa {
+  ( { $( $state:ident ),+ $(,)? },
+    {
+      -> $initial:ident
+      $(, ($source:ident, $predicate:expr) -> [$( $target:ident ),*] )* $(,)?
+    },
+    { $( $final_state:ident ),* $(,)? }
+  ) => {{
+    let mut states = ::std::collections::HashSet::new();
+    $(
+      let $state = $crate::state::State::new();
+      states.insert(::std::clone::Clone::clone(&$state));
+    )+
+    let transition = ::std::collections::HashMap::from([
+      $( (
+        (::std::clone::Clone::clone(&$source), $predicate),
+        vec![$( ::std::clone::Clone::clone(&$target) ),*],
+      ) ),*
+    ]);
+    let final_states =
+      ::std::collections::HashSet::from([$( ::std::clone::Clone::clone(&$final_state) ),*]);
+    $crate::regular::symbolic_automata::SymFa::new(states, $initial, final_states, transition)
+  }};
+}
+
+/** updates of variables for a transition of sst!, e.g. update! { x -> vec![UpdateComp::X(x.clone())] } */
+#[macro_export]
+macro_rules! update {
+  ( $( $var:ident -> $seq:expr ),* $(,)? ) => {
+    ::std::collections::HashMap::from([ $( (::std::clone::Clone::clone(&$var), $seq) ),* ])
+  };
+}
+
+/**
+ * a symbolic streaming string transducer as sfa!, given its variables,
+ * where each target is paired with update! and each final state with its output.
+ */
+#[macro_export]
+macro_rules! sst {
+  ( { $( $state:ident ),+ $(,)? },
+    $variables:expr,
+    {
+      -> $initial:ident
+      $(, ($source:ident, $predicate:expr) -> [$( ( $target:ident, $update:expr ) ),*] )* $(,)?
+    },
+    { $( $final_state:ident -> $output:expr ),* $(,)? }
+  ) => {{
+    let mut states = ::std::collections::HashSet::new();
+    $(
+      let $state = $crate::state::State::new();
+      states.insert(::std::clone::Clone::clone(&$state));
+    )+
+    let transition = ::std::collections::HashMap::from([
+      $( (
+        (::std::clone::Clone::clone(&$source), $predicate),
+        vec![$( (::std::clone::Clone::clone(&$target), $update) ),*],
+      ) ),*
+    ]);
+    let output_function = ::std::collections::HashMap::from([
+      $( (::std::clone::Clone::clone(&$final_state), $output) ),*
+    ]);
+    $crate::transducer::sst::SymSst::new(
+      states,
+      $variables,
+      $initial,
+      output_function,
+      transition,
+    )
+  }};
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    boolean_algebra::{BoolAlg, Predicate},
+    regular::symbolic_automata::Sfa,
+    state::StateImpl,
+    tests::helper::chars,
+    transducer::{
+      sst::Sst,
+      term::{FunctionTerm, OutputComp, UpdateComp, VariableImpl},
+    },
+  };
+  use std::collections::HashSet;
+
+  #[test]
+  fn sfa() {
+    let a_star_b: Sfa<char, StateImpl> = sfa! {
+      { q0, q1 },
+      {
+        -> q0,
+        (q0, Predicate::char('a')) -> [q0],
+        (q0, Predicate::char('b')) -> [q1],
+      },
+      { q1 }
+    };
+    assert!(a_star_b.run(&chars("aab")));
+    assert!(!a_star_b.run(&chars("aba")));
+  }
+
+  #[test]
+  fn sst() {
+    let x = VariableImpl::new();
+    /* doubles each character */
+    let double: Sst<char, StateImpl, VariableImpl> = sst! {
+      { q0 },
+      HashSet::from([x.clone()]),
+      {
+        -> q0,
+        (q0, Predicate::all_char()) -> [(q0, update! {
+          x -> vec![
+            UpdateComp::X(x.clone()),
+            UpdateComp::F(FunctionTerm::identity()),
+            UpdateComp::F(FunctionTerm::identity()),
+          ]
+        })]
+      },
+      { q0 -> vec![OutputComp::X(x.clone())] }
+    };
+    assert_eq!(double.run(&chars("ab")), vec![chars("aabb")]);
+  }
+}
@@ -1,6 +1,44 @@
 extern crate solver_with_symbolic;
 
-use std::{env, fs::File, io::Read};
+use solver_with_symbolic::{regular::regex::Strategy, state::StateCounter, UnconstrainedPolicy};
+use std::{
+  env,
+  fs::File,
+  io::{self, Read},
+  time::Duration,
+};
+
+const USAGE: &str = "\
+usage: solver_with_symbolic [OPTION]... [FILE]...
+       solver_with_symbolic demo --regex RE [--define VAR=TERM]... [--target VAR] [--dot DIR]
+
+solve the SMT-LIB problem in FILEs, or in the standard input if none or - is given.
+each (check-sat) is answered against the assertions before it.
+the exit status is 10 if sat, 20 if unsat, 0 if unknown and 1 on errors, for the last (check-sat).
+
+options:
+  -h, --help              print this help
+  -V, --version           print the version
+      --model             print the assignment of a model even without (get-model)
+      --verbose           print warnings and the trace of rewrites to stderr
+      --timeout SECS      give up with unknown after SECS seconds
+      --dot DIR           write DOT files of the machines of each case into DIR
+      --report PATH       write an HTML report of the problem and the result to PATH
+      --checkpoint PATH   checkpoint pre-images to PATH, resuming from the one there
+      --strict            fail on commands unknown to the parser
+      --warnings          print non-fatal diagnostics to stderr
+      --explain           print the trace of rewrites and approximations to stderr
+      --stats             print the size of machines and languages before solving
+      --infer-values      print over-approximated values of each variable before solving
+      --dump-deps         print the dependency graph of variables in DOT instead of solving
+      --dry-run           print the solving plan instead of solving
+      --ascii             assume all strings are ASCII
+      --bitsets           build the product over bitsets of character classes
+      --shortest          give models of the minimal length
+      --strategy=S        build memberships by inductive (default) or auto
+      --states=C          number states by a global (default), thread or run counter
+      --unconstrained=P   give unconstrained variables empty (default), shortest or random values
+";
 
 /**
  * TODO
@@ -9,27 +47,158 @@ use std::{env, fs::File, io::Read};
 fn main() {
   let mut args = env::args();
   args.next();
+  let mut args = args.peekable();
+  if args.peek().map(|arg| arg.as_str()) == Some("demo") {
+    args.next();
+    demo(args);
+    return;
+  }
   let mut input = String::new();
   let mut is_file_given = false;
+  let mut option = solver_with_symbolic::RunOption::default();
 
-  for arg in args {
-    if arg.starts_with("--") {
-    } else if arg.starts_with("-") {
+  while let Some(arg) = args.next() {
+    if arg == "--help" || arg == "-h" {
+      print!("{}", USAGE);
+      return;
+    } else if arg == "--version" || arg == "-V" {
+      println!("solver_with_symbolic {}", env!("CARGO_PKG_VERSION"));
+      return;
+    } else if arg == "--model" {
+      option.model = true;
+    } else if arg == "--verbose" {
+      option.warnings = true;
+      option.explain = true;
+    } else if arg == "--timeout" {
+      match args.next().map(|secs| secs.parse()) {
+        Some(Ok(secs)) => option.timeout = Some(Duration::from_secs(secs)),
+        _ => fail("--timeout needs a number of seconds"),
+      }
+    } else if arg == "--infer-values" {
+      option.infer_values = true;
+    } else if arg == "--warnings" {
+      option.warnings = true;
+    } else if arg == "--stats" {
+      option.stats = true;
+    } else if arg == "--strict" {
+      option.strict = true;
+    } else if arg == "--explain" {
+      option.explain = true;
+    } else if arg == "--dump-deps" {
+      option.dump_deps = true;
+    } else if arg == "--ascii" {
+      option.ascii = true;
+    } else if arg == "--bitsets" {
+      option.bitsets = true;
+    } else if arg == "--shortest" {
+      option.shortest = true;
+    } else if arg == "--dry-run" {
+      option.dry_run = true;
+    } else if arg == "--report" {
+      match args.next() {
+        Some(path) => option.report = Some(path.into()),
+        None => fail("--report needs a path"),
+      }
+    } else if arg == "--dot" {
+      match args.next() {
+        Some(dir) => option.dot = Some(dir.into()),
+        None => fail("--dot needs a directory"),
+      }
+    } else if arg == "--checkpoint" {
+      match args.next() {
+        Some(path) => option.checkpoint = Some(path.into()),
+        None => fail("--checkpoint needs a path"),
+      }
+    } else if let Some(strategy) = arg.strip_prefix("--strategy=") {
+      option.strategy = match strategy {
+        "inductive" => Strategy::Inductive,
+        "auto" => Strategy::Auto,
+        _ => fail(&format!("unknown strategy {}, expected inductive or auto", strategy)),
+      };
+    } else if let Some(counter) = arg.strip_prefix("--states=") {
+      option.states = match counter {
+        "global" => StateCounter::Global,
+        "thread" => StateCounter::ThreadLocal,
+        "run" => StateCounter::PerRun,
+        _ => {
+          fail(&format!(
+            "unknown counter {}, expected global, thread or run",
+            counter
+          ));
+        }
+      };
+    } else if let Some(policy) = arg.strip_prefix("--unconstrained=") {
+      option.unconstrained = match policy {
+        "empty" => UnconstrainedPolicy::Empty,
+        "shortest" => UnconstrainedPolicy::ShortestNonEmpty,
+        "random" => UnconstrainedPolicy::Random,
+        _ => {
+          fail(&format!(
+            "unknown policy {}, expected empty, shortest or random",
+            policy
+          ));
+        }
+      };
+    } else if arg == "-" {
+      if let Err(err) = io::stdin().read_to_string(&mut input) {
+        fail(&format!("failed to read the standard input for {}", err));
+      }
+      is_file_given = true;
+    } else if arg.starts_with('-') {
+      fail(&format!("unknown option {}, see --help", arg));
     } else {
-      let read_result = File::open(arg).and_then(|mut file| file.read_to_string(&mut input));
+      let read_result = File::open(&arg).and_then(|mut file| file.read_to_string(&mut input));
 
-      if read_result.is_err() {
-        println!("failed to read file for {}", read_result.unwrap_err());
-        return;
-      } else {
-        is_file_given = true;
+      if let Err(err) = read_result {
+        fail(&format!("failed to read {} for {}", arg, err));
       }
+      is_file_given = true;
+    }
+  }
+
+  if !is_file_given {
+    if let Err(err) = io::stdin().read_to_string(&mut input) {
+      fail(&format!("failed to read the standard input for {}", err));
+    }
+  }
+  std::process::exit(solver_with_symbolic::run_with(&input, &option));
+}
+
+/** print the error of the command line to stderr and exit with 1 */
+fn fail(message: &str) -> ! {
+  eprintln!("{}", message);
+  std::process::exit(solver_with_symbolic::EXIT_ERROR)
+}
+
+/**
+ * demo --regex RE [--define VAR=TERM]... [--target VAR] [--dot DIR]
+ * e.g. demo --regex '(str.to.re "abab")' --define 'y=(str.++ x x)'
+ */
+fn demo(mut args: impl Iterator<Item = String>) {
+  let mut option = solver_with_symbolic::DemoOption::default();
+
+  while let Some(arg) = args.next() {
+    let value = match args.next() {
+      Some(value) => value,
+      None => fail(&format!("{} needs a value", arg)),
+    };
+    match arg.as_str() {
+      "--regex" => option.regex = value,
+      "--define" => match value.split_once('=') {
+        Some((var, term)) => option
+          .definitions
+          .push((var.trim().to_owned(), term.trim().to_owned())),
+        None => fail(&format!("definition {} should be VAR=TERM", value)),
+      },
+      "--target" => option.target = Some(value),
+      "--dot" => option.dot = Some(value.into()),
+      _ => fail(&format!("unknown option {} for demo", arg)),
     }
   }
 
-  if is_file_given {
-    solver_with_symbolic::run(&input);
-  } else {
-    println!("no smt2 file given.");
+  if option.regex.is_empty() {
+    fail("demo needs --regex");
+  } else if let Err(err) = solver_with_symbolic::demo(&option) {
+    fail(&err.to_string());
   }
 }
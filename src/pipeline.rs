@@ -0,0 +1,198 @@
+use crate::checkpoint::Checkpoint;
+use crate::regular::symbolic_automata::Sfa;
+use crate::smt2::{Constraint, Smt2};
+use crate::state::{State, StateMachine};
+use crate::transducer::{sst::Sst, sst_factory::SstBuilder, term::VariableImpl};
+use crate::util::Domain;
+
+/** a step of solving, with the machine it produced */
+#[derive(Debug, Clone)]
+pub enum PipelineStep<D: Domain, S: State> {
+  /** the SST of the definition of var, in the order their pre-images are applied */
+  SstBuilt {
+    var: String,
+    sst: Sst<D, S, VariableImpl>,
+  },
+  /** the product of all regular constraints */
+  ProductBuilt { sfa: Sfa<D, S> },
+  /** the pre-image through the SST of the definition of var */
+  PreImageComputed { var: String, sfa: Sfa<D, S> },
+  /** whether the final automaton accepts nothing, i.e. the problem is unsatisfiable */
+  EmptinessChecked { empty: bool },
+}
+
+/**
+ * the steps compile and solve take, computed one at a time on next,
+ * so that the intermediate machines can be inspected.
+ * the problem is sliced and reordered as compile does,
+ * and must have no conditional assertion, e.g. one of the cases of Smt2::case_split.
+ */
+#[derive(Debug, Clone)]
+pub struct Pipeline<D: Domain, S: State> {
+  smt2: Smt2<D, S>,
+  ssts: Vec<Sst<D, S, VariableImpl>>,
+  /** the product, or the last pre-image */
+  sfa: Option<Sfa<D, S>>,
+  /** the number of ssts the pre-image is computed through */
+  applied: usize,
+  checked: bool,
+}
+impl<D: Domain, S: State> Pipeline<D, S> {
+  pub fn new(problem: Smt2<D, S>) -> Self {
+    let (smt2, _) = problem.slice();
+    let smt2 = smt2.reorder(&smt2.elimination_order());
+    Pipeline {
+      smt2,
+      ssts: vec![],
+      sfa: None,
+      applied: 0,
+      checked: false,
+    }
+  }
+
+  /**
+   * the pipeline continuing from the checkpoint of the same problem,
+   * where the product and the pre-images it has computed are skipped.
+   * the ssts are built again since they are cheap compared to pre-images.
+   */
+  pub fn resume(problem: Smt2<D, S>, checkpoint: Checkpoint<D, S>) -> Self {
+    let mut pipeline = Self::new(problem);
+    pipeline.sfa = Some(checkpoint.sfa);
+    pipeline.applied = checkpoint.applied;
+    pipeline
+  }
+
+  /** the checkpoint of the current automaton, if the product is built */
+  pub fn checkpoint(&self, key: u64, case: usize) -> Option<Checkpoint<D, S>> {
+    self.sfa.as_ref().map(|sfa| Checkpoint {
+      key,
+      case,
+      applied: self.applied,
+      sfa: sfa.clone(),
+    })
+  }
+
+  /** the problem sliced and reordered */
+  pub fn problem(&self) -> &Smt2<D, S> {
+    &self.smt2
+  }
+
+  /** SSTs built so far */
+  pub fn ssts(&self) -> &Vec<Sst<D, S, VariableImpl>> {
+    &self.ssts
+  }
+
+  /** the current automaton, i.e. the product or the last pre-image */
+  pub fn sfa(&self) -> Option<&Sfa<D, S>> {
+    self.sfa.as_ref()
+  }
+
+  /** the result of the emptiness check, i.e. whether the problem is unsatisfiable */
+  pub fn is_unsat(&self) -> Option<bool> {
+    match &self.sfa {
+      Some(sfa) if self.checked => Some(sfa.is_empty()),
+      _ => None,
+    }
+  }
+
+  /** the variable the i-th SST defines */
+  fn defined(&self, i: usize) -> String {
+    let sl_constraints = self.smt2.sl_constraints();
+    let sl_cons = &sl_constraints[sl_constraints.len() - 1 - i];
+    self.smt2.vars()[sl_cons.idx()].clone()
+  }
+}
+impl<D: Domain, S: State> Iterator for Pipeline<D, S> {
+  type Item = PipelineStep<D, S>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let sl_constraints = self.smt2.sl_constraints();
+    if self.ssts.len() < sl_constraints.len() {
+      /* definitions are applied in the reverse order */
+      let sl_cons = &sl_constraints[sl_constraints.len() - 1 - self.ssts.len()];
+      let builder: SstBuilder<D, S, VariableImpl> = SstBuilder::init();
+      let sst = builder.generate(sl_cons.idx(), sl_cons.constraint());
+      let var = self.defined(self.ssts.len());
+      self.ssts.push(sst.clone());
+      return Some(PipelineStep::SstBuilt { var, sst });
+    }
+
+    match self.sfa.take() {
+      None => {
+        let sfa = self.smt2.emit_sfa();
+        self.sfa = Some(sfa.clone());
+        Some(PipelineStep::ProductBuilt { sfa })
+      }
+      /* the pre-image of the empty language is empty, so the rest is skipped */
+      Some(sfa) if self.applied < self.ssts.len() && !sfa.final_set().is_empty() => {
+        let sfa = sfa.pre_image(self.ssts[self.applied].clone());
+        let var = self.defined(self.applied);
+        self.applied += 1;
+        self.sfa = Some(sfa.clone());
+        Some(PipelineStep::PreImageComputed { var, sfa })
+      }
+      Some(sfa) => {
+        let step = (!self.checked).then(|| PipelineStep::EmptinessChecked {
+          empty: sfa.is_empty(),
+        });
+        self.checked = true;
+        self.sfa = Some(sfa);
+        step
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{check_sat, parse, SolverResult};
+
+  #[test]
+  fn steps() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (= x2 (str.reverse x1)))
+      (assert (str.in.re x2 (str.to.re "baba")))
+      (check-sat)
+      "#;
+    let mut pipeline = Pipeline::new(parse(input).unwrap());
+    let steps: Vec<_> = pipeline.by_ref().collect();
+    let names: Vec<_> = steps
+      .iter()
+      .map(|step| match step {
+        PipelineStep::SstBuilt { var, .. } => format!("sst {}", var),
+        PipelineStep::ProductBuilt { .. } => String::from("product"),
+        PipelineStep::PreImageComputed { var, .. } => format!("pre-image {}", var),
+        PipelineStep::EmptinessChecked { empty } => format!("empty {}", empty),
+      })
+      .collect();
+    assert_eq!(
+      names,
+      vec![
+        "sst x2",
+        "sst x1",
+        "product",
+        "pre-image x2",
+        "pre-image x1",
+        "empty false"
+      ]
+    );
+    assert_eq!(pipeline.ssts().len(), 2);
+    assert_eq!(pipeline.is_unsat(), Some(false));
+    assert!(pipeline.next().is_none());
+
+    /* the pre-image through x1 is skipped since nothing is left */
+    let input = input.replace(
+      r#"(check-sat)"#,
+      r#"(assert (str.in.re x1 (str.to.re "ab"))) (check-sat)"#,
+    );
+    let mut pipeline = Pipeline::new(parse(&input).unwrap());
+    assert_eq!(pipeline.by_ref().count(), 5);
+    assert_eq!(pipeline.is_unsat(), Some(true));
+    assert_eq!(check_sat(parse(&input).unwrap()), SolverResult::Unsat);
+  }
+}
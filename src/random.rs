@@ -0,0 +1,37 @@
+use std::{
+  collections::hash_map::RandomState,
+  hash::{BuildHasher, Hasher},
+};
+
+/** source of random numbers for sampling, so that any generator can be plugged in */
+pub trait Rng {
+  fn next_u64(&mut self) -> u64;
+
+  /** a number in 0..n, where n must be positive */
+  fn below(&mut self, n: usize) -> usize {
+    (self.next_u64() % n as u64) as usize
+  }
+}
+
+/** SplitMix64, which is small and good enough for generating inputs */
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+impl SplitMix64 {
+  pub fn new(seed: u64) -> Self {
+    SplitMix64(seed)
+  }
+
+  /** seeded by the hasher of std, so it differs for each run */
+  pub fn from_entropy() -> Self {
+    SplitMix64(RandomState::new().build_hasher().finish())
+  }
+}
+impl Rng for SplitMix64 {
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+  }
+}
@@ -1,14 +1,19 @@
-use super::{recognizable::Recognizable, symbolic_automata::Sfa};
+use super::{
+  recognizable::Recognizable,
+  symbolic_automata::{Sfa, SymFa},
+};
 use crate::{
-  boolean_algebra::{BoolAlg, Predicate},
-  smt2,
-  state::{State, StateMachine},
+  boolean_algebra::{ascii, BoolAlg, Predicate},
+  random::Rng,
+  smt2::{self, Arity, ParseError},
+  state::{State, StateImpl, StateMachine},
   util::Domain,
 };
-use smt2parser::concrete::{Constant, Term};
+use smt2parser::concrete::{Constant, Identifier, QualIdentifier, Symbol, Term};
+use smt2parser::visitors::Index;
 use std::{
   collections::{HashMap, HashSet},
-  fmt::Debug,
+  fmt::{Debug, Display},
   hash::Hash,
 };
 
@@ -25,10 +30,45 @@ pub fn convert<D: Domain>(reg: Regex<D>) -> Regex<char> {
     Regex::Star(reg) => Regex::Star(Box::new(convert(*reg))),
     Regex::Plus(reg) => Regex::Plus(Box::new(convert(*reg))),
     Regex::Not(reg) => Regex::Not(Box::new(convert(*reg))),
+    Regex::Loop(reg, min, max) => Regex::Loop(Box::new(convert(*reg)), min, max),
   }
 }
 
+/** how an automaton is built from a regex */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Construction {
+  /** the inductive construction of to_sfa */
+  Inductive,
+  /** a trie of the words, for unions of literals */
+  Trie,
+  /** intersections from the smallest operand, giving up on the rest once empty */
+  SmallestFirst,
+}
+impl Display for Construction {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Construction::Inductive => write!(f, "inductive"),
+      Construction::Trie => write!(f, "trie"),
+      Construction::SmallestFirst => write!(f, "smallest-first"),
+    }
+  }
+}
+
+/** whether constructions are picked per regex */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+  /** always Construction::Inductive */
+  #[default]
+  Inductive,
+  /** the construction Regex::plan picks */
+  Auto,
+}
+
+/** intersections of more operands than this are built smallest first */
+const SMALLEST_FIRST_OPERANDS: usize = 3;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Regex<T: PartialOrd> {
   Empty,
   Epsilon,
@@ -41,6 +81,8 @@ pub enum Regex<T: PartialOrd> {
   Star(Box<Self>),
   Plus(Box<Self>),
   Not(Box<Self>),
+  /** between the minimum and the maximum repetitions, or at least the minimum without maximum */
+  Loop(Box<Self>, usize, Option<usize>),
 }
 impl<T: Domain> Regex<T> {
   pub fn empty() -> Self {
@@ -66,6 +108,36 @@ impl<T: Domain> Regex<T> {
       .unwrap_or(Regex::Epsilon)
   }
 
+  /** words containing s as a scattered subsequence, i.e. .*s1.*s2 ... .*sn.* */
+  pub fn subsequence(s: &str) -> Self {
+    let any = || Regex::all().star();
+    s.chars()
+      .fold(any(), |reg, c| reg.concat(Regex::element(c)).concat(any()))
+  }
+
+  /** words containing s as a substring, i.e. .*s.* */
+  pub fn superstring(s: &str) -> Self {
+    let any = || Regex::all().star();
+    any().concat(Regex::seq(s)).concat(any())
+  }
+
+  /**
+   * words whose (str.at w k) is s, i.e. k characters, the character s and anything after it.
+   * s is empty exactly for words of at most k characters, and longer ones are never at k.
+   * the k characters are a loop, so a large k is only built if it's solved.
+   */
+  pub fn char_at(k: usize, s: &str) -> Self {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+      (None, _) => Regex::all().repeat(0, Some(k)),
+      (Some(c), None) => Regex::all()
+        .repeat(k, Some(k))
+        .concat(Regex::element(c))
+        .concat(Regex::all().star()),
+      (Some(_), Some(_)) => Regex::Empty,
+    }
+  }
+
   pub fn range(start: Option<char>, end: Option<char>) -> Self {
     if start.is_none() && end.is_none() {
       Regex::Empty
@@ -80,6 +152,17 @@ impl<T: Domain> Regex<T> {
     }
   }
 
+  /** characters from left to right, both inclusive, while the right bound of range is exclusive */
+  pub fn inclusive_range(left: char, right: char) -> Self {
+    /* the next character, skipping surrogates */
+    let next = (u32::from(right) + 1..=u32::from(char::MAX)).find_map(std::char::from_u32);
+    if left == right {
+      Regex::element(left)
+    } else {
+      Regex::range(Some(left), next)
+    }
+  }
+
   pub fn concat(self, other: Regex<T>) -> Self {
     match (self, other) {
       (Regex::Empty, _) | (_, Regex::Empty) => Regex::Empty,
@@ -181,6 +264,30 @@ impl<T: Domain> Regex<T> {
     Regex::Plus(Box::new(self))
   }
 
+  /** between min and max repetitions, i.e. re.loop, or at least min without max */
+  pub fn repeat(self, min: usize, max: Option<usize>) -> Self {
+    match (self, min, max) {
+      (_, min, Some(max)) if max < min => Regex::Empty,
+      (_, 0, Some(0)) => Regex::Epsilon,
+      (Regex::Empty, 0, _) | (Regex::Epsilon, _, _) => Regex::Epsilon,
+      (Regex::Empty, _, _) => Regex::Empty,
+      (reg, 1, Some(1)) => reg,
+      (reg, 0, None) => reg.star(),
+      (reg, 1, None) => reg.plus(),
+      (reg, min, max) => Regex::Loop(Box::new(reg), min, max),
+    }
+  }
+
+  /** zero or one occurrence, i.e. re.opt */
+  pub fn opt(self) -> Self {
+    Regex::Epsilon.or(self)
+  }
+
+  /** words of self not in other, i.e. re.diff */
+  pub fn diff(self, other: Regex<T>) -> Self {
+    self.inter(other.not())
+  }
+
   pub fn not(self) -> Self {
     if let Regex::Empty = self {
       Regex::all().star()
@@ -191,10 +298,292 @@ impl<T: Domain> Regex<T> {
     }
   }
 
+  /** words of the language if it is a finite union of string literals */
+  pub fn literals(&self) -> Option<Vec<Vec<T>>> {
+    match self {
+      Regex::Epsilon => Some(vec![vec![]]),
+      Regex::Element(c) => Some(vec![vec![c.clone()]]),
+      Regex::Concat(v) => v.iter().try_fold(vec![vec![]], |words, reg| {
+        let suffixes = reg.literals()?;
+        Some(
+          words
+            .iter()
+            .flat_map(|word| {
+              suffixes.iter().map(move |suffix| {
+                let mut word = word.clone();
+                word.extend(suffix.iter().cloned());
+                word
+              })
+            })
+            .collect(),
+        )
+      }),
+      Regex::Or(v) => {
+        let mut words = vec![];
+        for reg in v {
+          for word in reg.literals()? {
+            if !words.contains(&word) {
+              words.push(word);
+            }
+          }
+        }
+        Some(words)
+      }
+      _ => None,
+    }
+  }
+
+  /**
+   * an upper bound of the number of states to_sfa builds, following its construction.
+   * products of intersections and determinization under negation make it grow fast,
+   * e.g. each nesting of re.comp can double it exponentially.
+   * saturates instead of overflowing.
+   */
+  pub fn estimate_sfa_size(&self) -> usize {
+    match self {
+      Regex::Empty | Regex::Epsilon => 1,
+      Regex::Element(_) | Regex::All | Regex::Range(_, _) => 2,
+      Regex::Concat(v) => v
+        .iter()
+        .fold(0usize, |size, reg| size.saturating_add(reg.estimate_sfa_size())),
+      Regex::Or(v) => v
+        .iter()
+        .fold(0usize, |size, reg| size.saturating_add(reg.estimate_sfa_size()))
+        .saturating_add(v.len()),
+      Regex::Inter(v) => v
+        .iter()
+        .fold(1, |size, reg| size.saturating_mul(reg.estimate_sfa_size())),
+      Regex::Star(r) | Regex::Plus(r) => r.estimate_sfa_size().saturating_add(1),
+      /* a copy for each repetition, and the star after the minimum */
+      Regex::Loop(r, min, max) => r
+        .estimate_sfa_size()
+        .saturating_add(1)
+        .saturating_mul(max.unwrap_or(min.saturating_add(1)))
+        .saturating_add(1),
+      Regex::Not(r) => {
+        let size = r.estimate_sfa_size();
+        /* subsets of states and a dead state */
+        if size < usize::BITS as usize {
+          (1usize << size).saturating_add(1)
+        } else {
+          usize::MAX
+        }
+      }
+    }
+  }
+
+  /** the maximum nesting of re.* and re.+ */
+  pub fn star_height(&self) -> usize {
+    match self {
+      Regex::Empty | Regex::Epsilon | Regex::All | Regex::Element(_) | Regex::Range(_, _) => 0,
+      Regex::Concat(v) | Regex::Or(v) | Regex::Inter(v) => {
+        v.iter().map(|reg| reg.star_height()).max().unwrap_or(0)
+      }
+      Regex::Star(r) | Regex::Plus(r) | Regex::Loop(r, _, None) => r.star_height() + 1,
+      Regex::Loop(r, _, Some(_)) => r.star_height(),
+      Regex::Not(r) => r.star_height(),
+    }
+  }
+
+  /** the height of the syntax tree, where atoms are 1 */
+  pub fn depth(&self) -> usize {
+    match self {
+      Regex::Empty | Regex::Epsilon | Regex::All | Regex::Element(_) | Regex::Range(_, _) => 1,
+      Regex::Concat(v) | Regex::Or(v) | Regex::Inter(v) => {
+        v.iter().map(|reg| reg.depth()).max().unwrap_or(0) + 1
+      }
+      Regex::Star(r) | Regex::Plus(r) | Regex::Not(r) | Regex::Loop(r, _, _) => r.depth() + 1,
+    }
+  }
+
+  /** the number of occurrences of characters and character classes */
+  pub fn literal_count(&self) -> usize {
+    match self {
+      Regex::Empty | Regex::Epsilon => 0,
+      Regex::All | Regex::Element(_) | Regex::Range(_, _) => 1,
+      Regex::Concat(v) | Regex::Or(v) | Regex::Inter(v) => {
+        v.iter().map(|reg| reg.literal_count()).sum()
+      }
+      Regex::Star(r) | Regex::Plus(r) | Regex::Not(r) | Regex::Loop(r, _, _) => r.literal_count(),
+    }
+  }
+
+  /**
+   * the number of occurrences of characters and character classes with re.loop unrolled,
+   * i.e. about as many states as building its automaton takes, unlike estimate_sfa_size
+   * which is exponential in complements.
+   */
+  pub fn unrolled_size(&self) -> usize {
+    match self {
+      Regex::Empty | Regex::Epsilon => 0,
+      Regex::All | Regex::Element(_) | Regex::Range(_, _) => 1,
+      Regex::Concat(v) | Regex::Or(v) | Regex::Inter(v) => v
+        .iter()
+        .fold(0usize, |size, reg| size.saturating_add(reg.unrolled_size())),
+      Regex::Star(r) | Regex::Plus(r) | Regex::Not(r) => r.unrolled_size(),
+      Regex::Loop(r, min, max) => r
+        .unrolled_size()
+        .saturating_mul(max.unwrap_or(min.saturating_add(1))),
+    }
+  }
+
+  /** code points where the predicates of the regex may change, see Classes */
+  pub fn boundaries(&self) -> Vec<u32> {
+    let code = |c: &T| {
+      let c: char = c.clone().into();
+      c as u32
+    };
+    match self {
+      Regex::Empty | Regex::Epsilon => vec![],
+      Regex::All => {
+        let separator = code(&T::separator());
+        vec![separator, separator + 1]
+      }
+      Regex::Element(c) => vec![code(c), code(c) + 1],
+      /* a range of the same bounds is the character */
+      Regex::Range(Some(left), Some(right)) if left == right => {
+        vec![code(left), code(left) + 1]
+      }
+      Regex::Range(left, right) => left.iter().chain(right).map(code).collect(),
+      Regex::Concat(v) | Regex::Or(v) | Regex::Inter(v) => {
+        v.iter().flat_map(|reg| reg.boundaries()).collect()
+      }
+      Regex::Star(r) | Regex::Plus(r) | Regex::Not(r) | Regex::Loop(r, _, _) => r.boundaries(),
+    }
+  }
+
+  /** a random word of the regex up to max_len, see Sfa::sample */
+  pub fn sample(&self, rng: &mut impl Rng, max_len: usize) -> Option<Vec<T>> {
+    self.clone().to_sfa::<StateImpl>().sample(rng, max_len)
+  }
+
+  /** whether every character the regex mentions is ASCII */
+  pub fn is_ascii(&self) -> bool {
+    match self {
+      Regex::Empty | Regex::Epsilon | Regex::All => true,
+      Regex::Element(c) => ascii::is_ascii(c),
+      /* the right bound is exclusive, so ranges from ASCII have some ASCII character */
+      Regex::Range(left, _) => left.as_ref().is_none_or(ascii::is_ascii),
+      Regex::Concat(v) | Regex::Or(v) | Regex::Inter(v) => v.iter().all(|reg| reg.is_ascii()),
+      Regex::Star(r) | Regex::Plus(r) | Regex::Not(r) | Regex::Loop(r, _, _) => r.is_ascii(),
+    }
+  }
+
+  /**
+   * the construction auto mode picks from the estimators.
+   * unions of literals become tries, and intersections of many operands or
+   * estimated to blow up are built smallest first.
+   */
+  pub fn plan(&self) -> Construction {
+    match self {
+      Regex::Or(_) if self.literals().is_some() => Construction::Trie,
+      Regex::Inter(v)
+        if v.len() >= SMALLEST_FIRST_OPERANDS
+          || self.estimate_sfa_size() > smt2::SFA_SIZE_THRESHOLD =>
+      {
+        Construction::SmallestFirst
+      }
+      _ => Construction::Inductive,
+    }
+  }
+
+  /** the automaton by the construction, falling back to to_sfa if it doesn't apply */
+  pub fn to_sfa_with<S: State>(self, construction: Construction) -> Sfa<T, S> {
+    self.to_symfa_with(construction)
+  }
+
+  /** to_sfa_with over any algebra as to_symfa */
+  pub fn to_symfa_with<B, S>(self, construction: Construction) -> SymFa<T, B, S>
+  where
+    B: BoolAlg<Domain = T> + From<Predicate<T>>,
+    S: State,
+  {
+    match (construction, self) {
+      (Construction::Trie, reg) => match reg.literals() {
+        Some(words) => SymFa::from_words(&words),
+        None => reg.to_symfa(),
+      },
+      (Construction::SmallestFirst, Regex::Inter(v)) => {
+        SymFa::inter_all(v.into_iter().map(|reg| reg.to_symfa()).collect())
+      }
+      (_, reg) => reg.to_symfa(),
+    }
+  }
+
+  /** whether the empty word is in the language */
+  pub fn nullable(&self) -> bool {
+    match self {
+      Regex::Empty | Regex::All | Regex::Element(_) | Regex::Range(_, _) => false,
+      Regex::Epsilon | Regex::Star(_) => true,
+      /* the empty concatenation is built into the empty automaton */
+      Regex::Concat(v) | Regex::Inter(v) => !v.is_empty() && v.iter().all(|r| r.nullable()),
+      Regex::Or(v) => v.iter().any(|r| r.nullable()),
+      Regex::Plus(r) => r.nullable(),
+      Regex::Not(r) => !r.nullable(),
+      Regex::Loop(r, min, _) => *min == 0 || r.nullable(),
+    }
+  }
+
+  /**
+   * the Brzozowski derivative by c, i.e. the language of { w | cw in self }.
+   * atoms are decided by the predicates to_sfa builds from them.
+   */
+  pub fn derivative(&self, c: &T) -> Self {
+    let atom = |phi: Predicate<T>| {
+      if phi.denote(c) {
+        Regex::Epsilon
+      } else {
+        Regex::Empty
+      }
+    };
+    match self {
+      Regex::Empty | Regex::Epsilon => Regex::Empty,
+      Regex::All => atom(Predicate::all_char()),
+      Regex::Element(a) => atom(Predicate::char(a.clone())),
+      Regex::Range(left, right) => atom(Predicate::range(left.clone(), right.clone())),
+      Regex::Concat(v) => match &v[..] {
+        [] => Regex::Empty,
+        [head, rest @ ..] => {
+          let rest = rest
+            .iter()
+            .fold(Regex::Epsilon, |result, r| result.concat(r.clone()));
+          let derivative = head.derivative(c).concat(rest.clone());
+          if head.nullable() {
+            derivative.or(rest.derivative(c))
+          } else {
+            derivative
+          }
+        }
+      },
+      Regex::Or(v) => v
+        .iter()
+        .fold(Regex::Empty, |result, r| result.or(r.derivative(c))),
+      /* built as it is, since inter simplifies with epsilon regardless of nullability */
+      Regex::Inter(v) => Regex::Inter(v.iter().map(|r| r.derivative(c)).collect()),
+      Regex::Star(r) | Regex::Plus(r) => r.derivative(c).concat(Regex::Star(r.clone())),
+      Regex::Not(r) => r.derivative(c).not(),
+      Regex::Loop(_, _, Some(0)) => Regex::Empty,
+      Regex::Loop(r, min, max) => r.derivative(c).concat(Regex::Loop(
+        r.clone(),
+        min.saturating_sub(1),
+        max.map(|max| max - 1),
+      )),
+    }
+  }
+
   /** with, thompson  --- clushkul, partial derivative */
   pub fn to_sfa<S: State>(self) -> Sfa<T, S> {
+    self.to_symfa()
+  }
+
+  /** the automaton over any algebra the predicates of atoms can be converted into */
+  pub fn to_symfa<B, S>(self) -> SymFa<T, B, S>
+  where
+    B: BoolAlg<Domain = T> + From<Predicate<T>>,
+    S: State,
+  {
     match self {
-      Regex::Empty => Sfa::empty(),
+      Regex::Empty => SymFa::empty(),
       Regex::Epsilon => super::macros::sfa! {
         { initial },
         { -> initial },
@@ -204,7 +593,7 @@ impl<T: Domain> Regex<T> {
         { initial, final_state },
         {
           -> initial,
-          (initial, Predicate::char(a)) -> [final_state]
+          (initial, B::char(a)) -> [final_state]
         },
         { final_state }
       },
@@ -212,7 +601,7 @@ impl<T: Domain> Regex<T> {
         { initial, final_state },
         {
           -> initial,
-          (initial, Predicate::all_char()) -> [final_state]
+          (initial, B::all_char()) -> [final_state]
         },
         { final_state }
       },
@@ -220,114 +609,537 @@ impl<T: Domain> Regex<T> {
         { initial, final_state },
         {
           -> initial,
-          (initial, Predicate::range(left, right)) -> [final_state]
+          (initial, B::from(Predicate::range(left, right))) -> [final_state]
         },
         { final_state }
       },
       Regex::Concat(v) => v
         .into_iter()
-        .map(|r| r.to_sfa())
+        .map(|r| r.to_symfa())
         .reduce(|res, sfa| res.concat(sfa))
-        .unwrap_or(Sfa::empty()),
-      Regex::Or(v) => v
-        .into_iter()
-        .map(|r| r.to_sfa())
-        .reduce(|res, sfa| res.or(sfa))
-        .unwrap_or(Sfa::empty()),
+        .unwrap_or(SymFa::empty()),
+      Regex::Or(v) => SymFa::or_all(v.into_iter().map(|r| r.to_symfa()).collect()),
       Regex::Inter(v) => v
         .into_iter()
-        .map(|r| r.to_sfa())
+        .map(|r| r.to_symfa())
         .reduce(|res, sfa| res.inter(sfa))
-        .unwrap_or(Sfa::empty()),
-      Regex::Star(r) => r.to_sfa().star(),
-      Regex::Plus(r) => r.to_sfa().plus(),
-      Regex::Not(r) => r.to_sfa().not(),
+        .unwrap_or(SymFa::empty()),
+      Regex::Star(r) => r.to_symfa().star(),
+      Regex::Plus(r) => r.to_symfa().plus(),
+      Regex::Loop(r, min, max) => r.to_symfa().repeat(min, max),
+      Regex::Not(r) => r.to_symfa().complement(),
     }
   }
 
-  pub fn new(term: &Term) -> Self {
+  /** the regex of a RegLan term of SMT-LIB */
+  pub fn new(term: &Term) -> Result<Self, ParseError> {
     match term {
+      /* ((_ re.loop min max) r) and ((_ re.^ n) r) */
+      Term::Application {
+        qual_identifier:
+          QualIdentifier::Simple {
+            identifier:
+              Identifier::Indexed {
+                symbol: Symbol(symbol),
+                indices,
+              },
+          },
+        arguments,
+      } => {
+        let reg = match &arguments[..] {
+          [reg] => Regex::new(reg)?,
+          _ => return Err(ParseError::arity(symbol, term, Arity::Exactly(1))),
+        };
+        let indices = indices
+          .iter()
+          .map(|index| match index {
+            Index::Numeral(n) => n.to_string().parse::<usize>().ok(),
+            Index::Symbol(_) => None,
+          })
+          .collect::<Option<Vec<_>>>()
+          .ok_or_else(|| ParseError::malformed(term, "numeral indices"))?;
+        match (&symbol[..], &indices[..]) {
+          ("re.loop", [min, max]) => Ok(reg.repeat(*min, Some(*max))),
+          ("re.^", [n]) => Ok(reg.repeat(*n, Some(*n))),
+          _ => Err(ParseError::unsupported(format!(
+            "regular expression {}",
+            term
+          ))),
+        }
+      }
       Term::Application {
         qual_identifier,
         arguments,
-      } => match &smt2::get_symbol(qual_identifier)[..] {
-        "str.to.re" => {
-          if let [term] = &arguments[..] {
-            if let Term::Constant(Constant::String(s)) = term {
-              s.chars().fold(Regex::Epsilon, |reg, c| {
+      } => {
+        let operator = smt2::get_symbol(qual_identifier);
+        let unary = || match &arguments[..] {
+          [reg] => Regex::new(reg),
+          _ => Err(ParseError::arity(operator, term, Arity::Exactly(1))),
+        };
+        let fold = |f: fn(Self, Self) -> Self| {
+          arguments
+            .iter()
+            .map(Regex::new)
+            .reduce(|reg, curr| Ok(f(reg?, curr?)))
+            .unwrap_or_else(|| Err(ParseError::arity(operator, term, Arity::AtLeast(1))))
+        };
+        match operator {
+          "str.to.re" => match &arguments[..] {
+            [Term::Constant(Constant::String(s))] => {
+              Ok(s.chars().fold(Regex::Epsilon, |reg, c| {
                 reg.concat(Regex::Element(T::from(c)))
-              })
-            } else {
-              panic!("Syntax Error")
+              }))
+            }
+            [s] => Err(ParseError::malformed(s, "a string constant")),
+            _ => Err(ParseError::arity(operator, term, Arity::Exactly(1))),
+          },
+          "re.++" => fold(Regex::concat),
+          "re.union" => fold(Regex::or),
+          "re.inter" => fold(Regex::inter),
+          "re.diff" => fold(Regex::diff),
+          "re.*" => Ok(unary()?.star()),
+          "re.+" => Ok(unary()?.plus()),
+          "re.opt" => Ok(unary()?.opt()),
+          "re.comp" => Ok(unary()?.not()),
+          /* the legacy form (re.loop r min max) */
+          "re.loop" => {
+            let bound = |bound: &Term| {
+              match bound {
+                Term::Constant(Constant::Numeral(n)) => n.to_string().parse::<usize>().ok(),
+                _ => None,
+              }
+              .ok_or_else(|| ParseError::malformed(bound, "a numeral"))
+            };
+            match &arguments[..] {
+              [reg, min] => Ok(Regex::new(reg)?.repeat(bound(min)?, None)),
+              [reg, min, max] => Ok(Regex::new(reg)?.repeat(bound(min)?, Some(bound(max)?))),
+              _ => Err(ParseError::arity(operator, term, Arity::Between(2, 3))),
             }
-          } else {
-            panic!("Syntax Error")
           }
+          /* the characters between single characters, both inclusive, and none otherwise */
+          "re.range" => match &arguments[..] {
+            [Term::Constant(Constant::String(start)), Term::Constant(Constant::String(end))] => {
+              let single = |s: &str| match s.chars().collect::<Vec<_>>()[..] {
+                [c] => Some(c),
+                _ => None,
+              };
+              Ok(match (single(start), single(end)) {
+                (Some(left), Some(right)) if left <= right => Regex::inclusive_range(left, right),
+                _ => Regex::Empty,
+              })
+            }
+            [_, _] => Err(ParseError::malformed(term, "a range of string constants")),
+            _ => Err(ParseError::arity(operator, term, Arity::Exactly(2))),
+          },
+          _ => Err(ParseError::unsupported(format!(
+            "regular expression {}",
+            term
+          ))),
         }
-        "re.++" => arguments
-          .into_iter()
-          .map(|term| Regex::new(term))
-          .reduce(|reg, curr| reg.concat(curr))
-          .expect("Syntax Error"),
-        "re.union" => arguments
-          .into_iter()
-          .map(|term| Regex::new(term))
-          .reduce(|reg, curr| reg.or(curr))
-          .expect("Syntax Error"),
-        "re.inter" => arguments
-          .into_iter()
-          .map(|term| Regex::new(term))
-          .reduce(|reg, curr| reg.inter(curr))
-          .expect("Syntax Error"),
-        "re.*" => {
-          if let [term] = &arguments[..] {
-            Regex::new(term).star()
-          } else {
-            panic!("Syntax Error")
+      }
+      Term::QualIdentifier(qi) => match smt2::get_symbol(qi) {
+        "re.nostr" => Ok(Regex::Epsilon),
+        "re.allchar" => Ok(Regex::All),
+        _ => Err(ParseError::unsupported(format!(
+          "regular expression {}",
+          term
+        ))),
+      },
+      _ => Err(ParseError::malformed(term, "a regular expression")),
+    }
+  }
+
+  /**
+   * the regex of a pattern in the familiar syntax like a(b|c)*[0-9]{2,4}, matching whole words.
+   * it has alternatives, groups, character classes, the quantifiers * + ? {n} {n,} {n,m},
+   * the escapes \d \w \s with the negated ones, \n \t \r \f \v \0 \xHH \u{H..} and escaped punctuations.
+   * anchors are errors since they have no meaning for whole words.
+   */
+  pub fn parse(pattern: &str) -> Result<Self, RegexParseError> {
+    let mut parser = RegexParser {
+      chars: pattern.chars().collect(),
+      pos: 0,
+    };
+    let reg = parser.alternatives()?;
+    match parser.peek() {
+      None => Ok(reg),
+      Some(c) => Err(RegexParseError::Unexpected(parser.pos, c)),
+    }
+  }
+
+  /**
+   * a regex separating the memberships of a from those of b, i.e. accepting all the words
+   * in every regex of a and none in every regex of b, or None if some word is in all of them.
+   * such a regex is one of a, the complement of one of b, or the intersection of a,
+   * whichever is the shortest when printed, so that it points at the constraints in conflict.
+   */
+  pub fn separate(a: &[Self], b: &[Self]) -> Option<Self> {
+    let all = |regs: &[Self]| {
+      regs
+        .iter()
+        .cloned()
+        .reduce(Regex::inter)
+        .unwrap_or_else(|| Regex::all().star())
+    };
+    let disjoint = |r1: &Self, r2: &Self| {
+      r1.clone()
+        .inter(r2.clone())
+        .to_sfa::<StateImpl>()
+        .is_empty()
+    };
+    let (in_a, in_b) = (all(a), all(b));
+    if !disjoint(&in_a, &in_b) {
+      return None;
+    }
+    let complements = b
+      .iter()
+      .filter(|reg| disjoint(reg, &in_a))
+      .map(|reg| reg.clone().not());
+    a.iter()
+      .filter(|reg| disjoint(reg, &in_b))
+      .cloned()
+      .chain(complements)
+      .chain(std::iter::once(in_a.clone()))
+      .min_by_key(|reg| reg.to_string().len())
+  }
+}
+
+/**
+ * SMT-LIB syntax, e.g. (re.++ (str.to.re "ab") (re.* re.allchar)),
+ * where ranges are shown inclusively
+ */
+impl<T: Domain> Display for Regex<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn to_char<T: Domain>(a: &T) -> char {
+      a.clone().into()
+    }
+    fn join<T: Domain>(regs: &[Regex<T>]) -> String {
+      let regs: Vec<_> = regs.iter().map(|reg| reg.to_string()).collect();
+      regs.join(" ")
+    }
+    fn word(word: &str) -> String {
+      format!("(str.to.re {})", smt2::string_literal(word))
+    }
+    match self {
+      Regex::Empty => write!(f, "re.none"),
+      Regex::Epsilon => write!(f, "{}", word("")),
+      Regex::All => write!(f, "re.allchar"),
+      Regex::Element(a) => write!(f, "{}", word(&to_char(a).to_string())),
+      Regex::Range(left, right) => {
+        let left = left.as_ref().map_or('\0', to_char);
+        /* the last character below the exclusive bound, skipping surrogates */
+        let right = right
+          .as_ref()
+          .map_or(Some(char::MAX), |r| {
+            (0..u32::from(to_char(r))).rev().find_map(std::char::from_u32)
+          })
+          .unwrap_or('\0');
+        write!(
+          f,
+          "(re.range {} {})",
+          smt2::string_literal(&left.to_string()),
+          smt2::string_literal(&right.to_string())
+        )
+      }
+      Regex::Concat(regs) => {
+        /* runs of characters are shown as words */
+        let mut parts = vec![];
+        let mut chars = String::new();
+        for reg in regs {
+          match reg {
+            Regex::Element(a) => chars.push(to_char(a)),
+            reg => {
+              if !chars.is_empty() {
+                parts.push(word(&std::mem::take(&mut chars)));
+              }
+              parts.push(reg.to_string());
+            }
           }
         }
-        "re.+" => {
-          if let [term] = &arguments[..] {
-            Regex::new(term).plus()
-          } else {
-            panic!("Syntax Error")
-          }
+        if !chars.is_empty() {
+          parts.push(word(&chars));
         }
-        "re.range" => {
-          if let [start, end] = &arguments[..] {
-            if let Term::Constant(Constant::String(start)) = start {
-              if let Term::Constant(Constant::String(end)) = end {
-                Regex::range(start.chars().next(), end.chars().next())
-              } else {
-                panic!("Syntax Error")
-              }
-            } else {
-              panic!("Syntax Error")
+        match &parts[..] {
+          [part] => write!(f, "{}", part),
+          _ => write!(f, "(re.++ {})", parts.join(" ")),
+        }
+      }
+      Regex::Or(regs) => write!(f, "(re.union {})", join(regs)),
+      Regex::Inter(regs) => write!(f, "(re.inter {})", join(regs)),
+      Regex::Star(r) => write!(f, "(re.* {})", r),
+      Regex::Plus(r) => write!(f, "(re.+ {})", r),
+      Regex::Not(r) => write!(f, "(re.comp {})", r),
+      Regex::Loop(r, min, Some(max)) => write!(f, "((_ re.loop {} {}) {})", min, max, r),
+      Regex::Loop(r, min, None) => write!(f, "(re.++ ((_ re.^ {}) {}) (re.* {}))", min, r, r),
+    }
+  }
+}
+
+/** an error of Regex::parse with the position in characters */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexParseError {
+  Unexpected(usize, char),
+  UnexpectedEnd,
+  /** ^ and $, which are meaningless since whole words are matched */
+  Anchor(usize),
+  UnknownEscape(usize, char),
+  /** a class range like [z-a] or a repetition like {4,2} */
+  InvalidRange(usize),
+}
+impl Display for RegexParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RegexParseError::Unexpected(pos, c) => write!(f, "unexpected {:?} at {}", c, pos),
+      RegexParseError::UnexpectedEnd => write!(f, "unexpected end of the pattern"),
+      RegexParseError::Anchor(pos) => write!(f, "anchor at {} is not supported", pos),
+      RegexParseError::UnknownEscape(pos, c) => write!(f, "unknown escape \\{} at {}", c, pos),
+      RegexParseError::InvalidRange(pos) => write!(f, "invalid range at {}", pos),
+    }
+  }
+}
+impl std::error::Error for RegexParseError {}
+
+
+/** a recursive descent parser of Regex::parse */
+struct RegexParser {
+  chars: Vec<char>,
+  pos: usize,
+}
+impl RegexParser {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn next(&mut self) -> Result<char, RegexParseError> {
+    let c = self.peek().ok_or(RegexParseError::UnexpectedEnd)?;
+    self.pos += 1;
+    Ok(c)
+  }
+
+  fn eat(&mut self, c: char) -> bool {
+    let eaten = self.peek() == Some(c);
+    if eaten {
+      self.pos += 1;
+    }
+    eaten
+  }
+
+  fn alternatives<T: Domain>(&mut self) -> Result<Regex<T>, RegexParseError> {
+    let mut reg = self.concatenation()?;
+    while self.eat('|') {
+      reg = reg.or(self.concatenation()?);
+    }
+    Ok(reg)
+  }
+
+  fn concatenation<T: Domain>(&mut self) -> Result<Regex<T>, RegexParseError> {
+    let mut reg = Regex::Epsilon;
+    while let Some(c) = self.peek() {
+      if c == '|' || c == ')' {
+        break;
+      }
+      reg = reg.concat(self.repetition()?);
+    }
+    Ok(reg)
+  }
+
+  fn repetition<T: Domain>(&mut self) -> Result<Regex<T>, RegexParseError> {
+    let mut reg = self.atom()?;
+    loop {
+      let pos = self.pos;
+      let (min, max) = match self.peek() {
+        Some('*') => (0, None),
+        Some('+') => (1, None),
+        Some('?') => (0, Some(1)),
+        Some('{') => {
+          self.pos += 1;
+          let min = self.number()?;
+          let max = if self.eat(',') {
+            match self.peek() {
+              Some('}') => None,
+              _ => Some(self.number()?),
             }
           } else {
-            panic!("Syntax Error")
+            Some(min)
+          };
+          match self.next()? {
+            '}' => {}
+            c => return Err(RegexParseError::Unexpected(self.pos - 1, c)),
+          }
+          if max.is_some_and(|max| max < min) {
+            return Err(RegexParseError::InvalidRange(pos));
           }
+          self.pos -= 1;
+          (min, max)
         }
-        _ => panic!("Syntax Error"),
-      },
-      Term::QualIdentifier(qi) => match &smt2::get_symbol(qi)[..] {
-        "re.nostr" => Regex::Epsilon,
-        "re.allchar" => Regex::All,
-        _ => panic!("Syntax Error"),
-      },
-      _ => panic!("Syntax Error"),
+        _ => return Ok(reg),
+      };
+      self.pos += 1;
+      /* star of a star is the star itself */
+      reg = match (reg, min, max) {
+        (reg @ Regex::Star(_), 0, None) => reg,
+        (reg, 0, Some(1)) => reg.opt(),
+        (reg, min, max) => reg.repeat(min, max),
+      };
+    }
+  }
+
+  fn number(&mut self) -> Result<usize, RegexParseError> {
+    let start = self.pos;
+    while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+      self.pos += 1;
+    }
+    let digits: String = self.chars[start..self.pos].iter().collect();
+    match (digits.parse(), self.peek()) {
+      (Ok(n), _) => Ok(n),
+      (Err(_), Some(c)) if digits.is_empty() => Err(RegexParseError::Unexpected(self.pos, c)),
+      (Err(_), None) if digits.is_empty() => Err(RegexParseError::UnexpectedEnd),
+      (Err(_), _) => Err(RegexParseError::InvalidRange(start)),
+    }
+  }
+
+  fn atom<T: Domain>(&mut self) -> Result<Regex<T>, RegexParseError> {
+    let pos = self.pos;
+    match self.next()? {
+      '(' => {
+        /* non-capturing groups are the same as the others */
+        if self.eat('?') && !self.eat(':') {
+          return Err(RegexParseError::Unexpected(self.pos - 1, '?'));
+        }
+        let reg = self.alternatives()?;
+        match self.next()? {
+          ')' => Ok(reg),
+          c => Err(RegexParseError::Unexpected(self.pos - 1, c)),
+        }
+      }
+      '[' => self.class(),
+      '.' => Ok(Regex::all()),
+      '\\' => self.escape(pos),
+      '^' | '$' => Err(RegexParseError::Anchor(pos)),
+      c @ ('*' | '+' | '?' | '{' | '}' | ']') => Err(RegexParseError::Unexpected(pos, c)),
+      c => Ok(Regex::element(c)),
+    }
+  }
+
+  /** an escape sequence after the backslash at pos, either a character or a class */
+  fn escape<T: Domain>(&mut self, pos: usize) -> Result<Regex<T>, RegexParseError> {
+    let class = |ranges: &[(char, char)]| -> Regex<T> {
+      ranges
+        .iter()
+        .fold(Regex::Empty, |reg, (l, r)| reg.or(Regex::inclusive_range(*l, *r)))
+    };
+    let digit = [('0', '9')];
+    let word = [('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+    let space = [('\t', '\r'), (' ', ' ')];
+    match self.next()? {
+      'd' => Ok(class(&digit)),
+      'w' => Ok(class(&word)),
+      's' => Ok(class(&space)),
+      'D' => Ok(Regex::all().diff(class(&digit))),
+      'W' => Ok(Regex::all().diff(class(&word))),
+      'S' => Ok(Regex::all().diff(class(&space))),
+      'b' | 'B' | 'A' | 'z' | 'Z' => Err(RegexParseError::Anchor(pos)),
+      _ => {
+        self.pos -= 1;
+        Ok(Regex::element(self.escaped_char(pos)?))
+      }
+    }
+  }
+
+  /** the character of an escape sequence after the backslash at pos */
+  fn escaped_char(&mut self, pos: usize) -> Result<char, RegexParseError> {
+    let c = self.next()?;
+    let hex = |digits: &[char]| -> Option<char> {
+      let digits: String = digits.iter().collect();
+      char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+    };
+    match c {
+      'n' => Ok('\n'),
+      't' => Ok('\t'),
+      'r' => Ok('\r'),
+      'f' => Ok('\x0C'),
+      'v' => Ok('\x0B'),
+      '0' => Ok('\0'),
+      'x' => {
+        let digits = self.chars.get(self.pos..self.pos + 2);
+        let c = digits
+          .and_then(hex)
+          .ok_or(RegexParseError::UnknownEscape(pos, 'x'))?;
+        self.pos += 2;
+        Ok(c)
+      }
+      'u' => {
+        let end = self.chars[self.pos..].iter().position(|c| *c == '}');
+        let c = match (self.eat('{'), end) {
+          (true, Some(end)) => hex(&self.chars[self.pos..self.pos + end - 1]),
+          _ => None,
+        }
+        .ok_or(RegexParseError::UnknownEscape(pos, 'u'))?;
+        self.pos += end.unwrap();
+        Ok(c)
+      }
+      c if c.is_ascii_alphanumeric() => Err(RegexParseError::UnknownEscape(pos, c)),
+      c => Ok(c),
+    }
+  }
+
+  /** a character class after [, which is negated by ^ */
+  fn class<T: Domain>(&mut self) -> Result<Regex<T>, RegexParseError> {
+    let negated = self.eat('^');
+    let mut reg = Regex::Empty;
+    let mut first = true;
+    loop {
+      let pos = self.pos;
+      let left = match self.next()? {
+        /* ] right after [ is the character itself */
+        ']' if !first => break,
+        '\\' => match self.peek() {
+          Some('d' | 'w' | 's' | 'D' | 'W' | 'S') => {
+            reg = reg.or(self.escape(pos)?);
+            first = false;
+            continue;
+          }
+          _ => self.escaped_char(pos)?,
+        },
+        c => c,
+      };
+      first = false;
+      let right = if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+        self.pos += 1;
+        match self.next()? {
+          '\\' => self.escaped_char(self.pos - 1)?,
+          c => c,
+        }
+      } else {
+        left
+      };
+      if right < left {
+        return Err(RegexParseError::InvalidRange(pos));
+      }
+      reg = reg.or(Regex::inclusive_range(left, right));
     }
+    Ok(if negated { Regex::all().diff(reg) } else { reg })
   }
 }
-impl Recognizable<char> for Regex<char> {
-  fn member(&self, _: &[char]) -> bool {
-    unimplemented!()
+/** matching by derivatives, without building automata */
+impl<T: Domain> Recognizable<T> for Regex<T> {
+  fn member(&self, input: &[T]) -> bool {
+    let mut reg = self.clone();
+    for c in input {
+      if reg == Regex::Empty {
+        return false;
+      }
+      reg = reg.derivative(c);
+    }
+    reg.nullable()
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::limits::STATE_LIMIT;
+  use crate::state::StateImpl;
 
   type Reg = Regex<char>;
 
@@ -417,6 +1229,104 @@ mod tests {
     assert_eq!(not_a, Reg::Not(Box::new(a)));
   }
 
+  #[test]
+  fn opt_diff() {
+    let ab = Reg::seq("ab");
+    assert_eq!(ab.clone().opt(), Reg::Or(vec![Reg::Epsilon, ab.clone()]));
+    assert_eq!(ab.clone().star().opt(), ab.clone().star());
+    assert_eq!(
+      ab.clone().star().diff(ab.clone()),
+      Reg::Inter(vec![ab.clone().star(), Reg::Not(Box::new(ab.clone()))])
+    );
+
+    let sfa = ab
+      .clone()
+      .star()
+      .diff(ab.clone().opt())
+      .to_sfa::<StateImpl>();
+    assert!(!sfa.run(&[]));
+    assert!(!sfa.run(&['a', 'b']));
+    assert!(sfa.run(&['a', 'b', 'a', 'b']));
+  }
+
+  #[test]
+  fn sample() {
+    use crate::random::SplitMix64;
+
+    let mut rng = SplitMix64::new(1);
+    let reg = Reg::range(Some('a'), Some('{')).plus();
+    for _ in 0..20 {
+      let word = reg.sample(&mut rng, 4).unwrap();
+      assert!((1..=4).contains(&word.len()));
+      assert!(word.iter().all(|c| c.is_ascii_lowercase()), "{:?}", word);
+    }
+    assert_eq!(Reg::Empty.sample(&mut rng, 4), None);
+    assert_eq!(Reg::seq("abc").sample(&mut rng, 2), None);
+  }
+
+  #[test]
+  fn subsequence_superstring() {
+    let word = |s: &str| s.chars().collect::<Vec<_>>();
+    let subsequence = Reg::subsequence("abc").to_sfa::<StateImpl>();
+    for s in ["abc", "xaybzc", "aabbcc", "cabc"] {
+      assert!(subsequence.run(&word(s)), "{:?}", s);
+    }
+    for s in ["", "ab", "acb", "cba"] {
+      assert!(!subsequence.run(&word(s)), "{:?}", s);
+    }
+    assert_eq!(Reg::subsequence(""), Reg::all().star());
+
+    let superstring = Reg::superstring("abc").to_sfa::<StateImpl>();
+    assert!(superstring.run(&word("xabcx")));
+    assert!(!superstring.run(&word("xaybzc")));
+  }
+
+  #[test]
+  fn char_at() {
+    let word = |s: &str| s.chars().collect::<Vec<_>>();
+    let at = Reg::char_at(2, "c").to_sfa::<StateImpl>();
+    assert!(at.run(&word("abc")));
+    assert!(at.run(&word("xxcyy")));
+    assert!(!at.run(&word("cab")));
+    assert!(!at.run(&word("ab")));
+    let empty = Reg::char_at(2, "").to_sfa::<StateImpl>();
+    assert!(empty.run(&word("")));
+    assert!(empty.run(&word("ab")));
+    assert!(!empty.run(&word("abc")));
+    assert_eq!(Reg::char_at(0, "ab"), Reg::Empty);
+    assert!(Reg::char_at(usize::MAX, "c").unrolled_size() > STATE_LIMIT);
+    assert_eq!(Reg::char_at(3, "c").unrolled_size(), 5);
+  }
+
+  #[test]
+  fn repeat() {
+    let ab = Reg::seq("ab");
+    assert_eq!(ab.clone().repeat(2, Some(1)), Reg::Empty);
+    assert_eq!(ab.clone().repeat(0, Some(0)), Reg::Epsilon);
+    assert_eq!(ab.clone().repeat(1, Some(1)), ab);
+    assert_eq!(ab.clone().repeat(0, None), ab.clone().star());
+    assert_eq!(
+      ab.clone().repeat(2, Some(3)),
+      Reg::Loop(Box::new(ab.clone()), 2, Some(3))
+    );
+
+    let accepts = |reg: &Reg, n: usize| {
+      reg
+        .clone()
+        .to_sfa::<StateImpl>()
+        .run(&"ab".repeat(n).chars().collect::<Vec<_>>())
+    };
+    let two_to_four = ab.clone().repeat(2, Some(4));
+    let at_least_two = ab.clone().repeat(2, None);
+    for n in 0..7 {
+      assert_eq!(accepts(&two_to_four, n), (2..=4).contains(&n));
+      assert_eq!(accepts(&at_least_two, n), n >= 2);
+    }
+    assert!(!two_to_four
+      .to_sfa::<StateImpl>()
+      .run(&"aba".chars().collect::<Vec<_>>()));
+  }
+
   #[test]
   fn star() {
     let abc = Reg::seq("abc");
@@ -424,10 +1334,220 @@ mod tests {
     assert_eq!(star, Reg::Star(Box::new(abc)));
   }
 
+  #[test]
+  fn estimate_sfa_size() {
+    let regexes = vec![
+      Reg::seq("abc"),
+      Reg::seq("ab").or(Reg::seq("ba")).star(),
+      Reg::seq("ab").plus().inter(Reg::all().star()),
+      Reg::seq("ab").star().not(),
+      Reg::seq("a")
+        .or(Reg::range(Some('b'), Some('d')))
+        .not()
+        .plus(),
+    ];
+    for reg in regexes {
+      let states = reg.clone().to_sfa::<StateImpl>().states().len();
+      assert!(
+        reg.estimate_sfa_size() >= states,
+        "{:?} has {} states",
+        reg,
+        states
+      );
+    }
+
+    assert_eq!(Reg::seq("abc").estimate_sfa_size(), 6);
+    /* nested negations blow up */
+    let nested = Reg::seq("abc").not().concat(Reg::element('a')).not();
+    assert_eq!(nested.estimate_sfa_size(), usize::MAX);
+  }
+
+  #[test]
+  fn metrics() {
+    let a = Reg::element('a');
+    assert_eq!((a.star_height(), a.depth(), a.literal_count()), (0, 1, 1));
+    assert_eq!(Reg::epsilon().literal_count(), 0);
+
+    /* (ab|[b-c]*)* & !(a+) */
+    let reg = Reg::seq("ab")
+      .or(Reg::range(Some('b'), Some('c')).star())
+      .star()
+      .inter(Reg::element('a').plus().not());
+    assert_eq!(reg.star_height(), 2);
+    assert_eq!(reg.depth(), 5);
+    assert_eq!(reg.literal_count(), 4);
+  }
+
+  #[test]
+  fn plan() {
+    let literals = Reg::seq("ab").or(Reg::seq("ac")).or(Reg::seq("b"));
+    assert_eq!(literals.plan(), Construction::Trie);
+    let sfa = literals
+      .clone()
+      .to_sfa_with::<StateImpl>(Construction::Trie);
+    assert_eq!(sfa.states().len(), 5);
+    for word in ["ab", "ac", "b"] {
+      assert!(sfa.run(&word.chars().collect::<Vec<_>>()));
+    }
+    for word in ["", "a", "abc", "ba"] {
+      assert!(!sfa.run(&word.chars().collect::<Vec<_>>()));
+    }
+
+    let inter = Reg::seq("ab")
+      .star()
+      .inter(Reg::all().star())
+      .inter(Reg::seq("ba").plus());
+    assert_eq!(inter.plan(), Construction::SmallestFirst);
+    let sfa = inter
+      .clone()
+      .to_sfa_with::<StateImpl>(Construction::SmallestFirst);
+    assert!(sfa.is_empty());
+
+    assert_eq!(Reg::seq("ab").star().plan(), Construction::Inductive);
+    /* not applicable */
+    let star = Reg::seq("ab").star();
+    assert_eq!(
+      star
+        .clone()
+        .to_sfa_with::<StateImpl>(Construction::SmallestFirst)
+        .states()
+        .len(),
+      star.to_sfa::<StateImpl>().states().len()
+    );
+  }
+
   #[test]
   fn plus() {
     let abc = Reg::seq("abc");
     let star = abc.clone().plus();
     assert_eq!(star, Reg::Plus(Box::new(abc)));
   }
+
+  #[test]
+  fn parse() {
+    let accepts = |pattern: &str, word: &str| {
+      let sfa = Reg::parse(pattern).unwrap().to_sfa::<StateImpl>();
+      sfa.run(&word.chars().collect::<Vec<_>>())
+    };
+    assert_eq!(
+      Reg::parse("a(b|c)*").unwrap(),
+      Reg::element('a').concat(Reg::element('b').or(Reg::element('c')).star())
+    );
+    assert!(accepts("a(b|c)*[0-9]{2,4}", "abcb123"));
+    assert!(!accepts("a(b|c)*[0-9]{2,4}", "a12345"));
+    assert!(accepts("[a-z]\\d", "z9"));
+    assert!(!accepts("[a-cx-z]", "m"));
+    assert!(accepts("(?:ab)+c?", "abab"));
+    assert!(!accepts("(?:ab)+c?", "c"));
+    assert!(accepts("x|", ""));
+    assert!(accepts("[^a-c\\d]", "z"));
+    assert!(!accepts("[^a-c\\d]", "5"));
+    assert!(accepts("[]a-]+", "]-a"));
+    assert!(accepts("\\w\\s\\.\\x41\\u{3042}", "_\t.Aあ"));
+    assert!(!accepts("\\D", "7"));
+    assert!(accepts("(a*)*", "aa"));
+    assert!(accepts(".{3,}", "abcd"));
+
+    assert_eq!(Reg::parse("^ab"), Err(RegexParseError::Anchor(0)));
+    assert_eq!(Reg::parse("ab\\b"), Err(RegexParseError::Anchor(2)));
+    assert_eq!(Reg::parse("a)"), Err(RegexParseError::Unexpected(1, ')')));
+    assert_eq!(Reg::parse("*a"), Err(RegexParseError::Unexpected(0, '*')));
+    assert_eq!(Reg::parse("(ab"), Err(RegexParseError::UnexpectedEnd));
+    assert_eq!(Reg::parse("[z-a]"), Err(RegexParseError::InvalidRange(1)));
+    assert_eq!(Reg::parse("a{4,2}"), Err(RegexParseError::InvalidRange(1)));
+    assert_eq!(
+      Reg::parse("\\q"),
+      Err(RegexParseError::UnknownEscape(0, 'q'))
+    );
+  }
+
+  #[test]
+  fn member() {
+    let words = ["", "a", "ab", "abb", "ba", "aab", "abab", "a1", "#", "zz"];
+    for pattern in [
+      "a(b|c)*",
+      "(ab)*|b+",
+      "[a-c]{2,3}",
+      "[^b]a?",
+      "(a*)*b",
+      ".+",
+      "(a|b)*b(a|b)",
+    ] {
+      let reg = Reg::parse(pattern).unwrap();
+      let sfa = reg.clone().to_sfa::<StateImpl>();
+      for word in words {
+        let word: Vec<_> = word.chars().collect();
+        assert_eq!(reg.member(&word), sfa.run(&word), "{} {:?}", pattern, word);
+      }
+    }
+
+    let ab = Reg::seq("ab");
+    assert!(ab.clone().not().member(&['a']));
+    assert!(!ab.clone().not().member(&['a', 'b']));
+    let nullable = Reg::Inter(vec![Reg::Epsilon, Reg::element('a').opt()]);
+    assert!(nullable.member(&[]));
+    assert!(ab.clone().repeat(0, Some(2)).member(&['a', 'b', 'a', 'b']));
+    assert!(!ab.repeat(2, None).member(&['a', 'b']));
+  }
+
+  #[test]
+  fn display() {
+    let show = |pattern: &str| Reg::parse(pattern).unwrap().to_string();
+    assert_eq!(show("ab.*"), r#"(re.++ (str.to.re "ab") (re.* re.allchar))"#);
+    assert_eq!(
+      show("a|[b-d\"]"),
+      r#"(re.union (str.to.re """") (str.to.re "a") (re.range "b" "d"))"#
+    );
+    assert_eq!(
+      show("(ab){2,3}x+"),
+      r#"(re.++ ((_ re.loop 2 3) (str.to.re "ab")) (re.+ (str.to.re "x")))"#
+    );
+    assert_eq!(
+      show("a{2,}"),
+      r#"(re.++ ((_ re.^ 2) (str.to.re "a")) (re.* (str.to.re "a")))"#
+    );
+    assert_eq!(
+      Reg::Not(Box::new(Reg::Empty)).to_string(),
+      "(re.comp re.none)"
+    );
+  }
+
+  #[test]
+  fn display_round_trip() {
+    use smt2parser::concrete::{Command, SyntaxBuilder};
+    use smt2parser::CommandStream;
+    let new = |term: &str| {
+      let input = format!("(assert {})", term);
+      match CommandStream::new(input.as_bytes(), SyntaxBuilder, None).next() {
+        Some(Ok(Command::Assert { term })) => Reg::new(&term).unwrap(),
+        _ => panic!("{}", term),
+      }
+    };
+    /* ranges are inclusive in both directions */
+    assert_eq!(new(r#"(re.range "0" "9")"#), Reg::range(Some('0'), Some(':')));
+    assert_eq!(new(r#"(re.range "a" "a")"#), Reg::element('a'));
+    assert_eq!(new(r#"(re.range "b" "a")"#), Reg::Empty);
+    assert_eq!(new(r#"(re.range "ab" "c")"#), Reg::Empty);
+    for pattern in ["a|[b-d\"]", "[0-9]+x?", "[^a-z]*", "(ab){2,3}|\\d"] {
+      let reg = Reg::parse(pattern).unwrap();
+      assert_eq!(new(&reg.to_string()), reg, "{}", pattern);
+    }
+  }
+
+  #[test]
+  fn separate() {
+    let parse = |patterns: &[&str]| -> Vec<Reg> {
+      patterns.iter().map(|p| Reg::parse(p).unwrap()).collect()
+    };
+    assert_eq!(
+      Reg::separate(&parse(&["a+", "[a-c]*"]), &parse(&["b.*"])),
+      Some(Reg::parse("a+").unwrap())
+    );
+    /* each of a has a word of b, but not all of them */
+    assert_eq!(
+      Reg::separate(&parse(&["a.*", ".*a"]), &parse(&["ab|ba"])),
+      Some(Reg::parse("ab|ba").unwrap().not())
+    );
+    assert_eq!(Reg::separate(&parse(&["a+"]), &parse(&["a{3}"])), None);
+  }
 }
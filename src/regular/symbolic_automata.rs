@@ -1,18 +1,23 @@
 use super::recognizable::Recognizable;
-use crate::boolean_algebra::{BoolAlg, Predicate};
+use crate::boolean_algebra::{BoolAlg, Predicate, PredicatePool};
+use crate::limits::ResourceExhausted;
+use crate::random::Rng;
 use crate::state::{self, State, StateMachine};
 use crate::transducer::{
   sst::SymSst,
   term::{OutputComp, UpdateComp, Variable},
 };
-use crate::util::{extention::MultiMap, Domain};
+use crate::util::{dot_escape, extention::MultiMap, hash_of, Domain};
 use std::{
-  collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-  fmt::Debug,
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+  fmt::{Debug, Display},
+  hash::{Hash, Hasher},
 };
 
 type Source<S, B> = (S, B);
 type Target<S> = Vec<S>;
+type Pooled<S, B> = (PredicatePool<B>, HashMap<(S, usize), Target<S>>);
+type CanonicalForm<'a, B> = Vec<(bool, Vec<(&'a B, Vec<usize>)>)>;
 
 /*
  * https://stackoverflow.com/questions/32300132/why-cant-i-store-a-value-and-a-reference-to-that-value-in-the-same-struct
@@ -24,7 +29,15 @@ type Target<S> = Vec<S>;
  * symbolic automata
  * each operation like concat, or, ... corresponds to regex's one.
  */
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(bound(
+    serialize = "B: serde::Serialize, S: serde::Serialize",
+    deserialize = "B: serde::Deserialize<'de>, S: serde::Deserialize<'de>"
+  ))
+)]
 pub struct SymFa<D, B, S>
 where
   D: Domain,
@@ -34,8 +47,40 @@ where
   pub(crate) states: HashSet<S>,
   pub(crate) initial_state: S,
   pub(crate) final_states: HashSet<S>,
+  #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_pooled"))]
   pub(crate) transition: HashMap<Source<S, B>, Target<S>>,
 }
+/**
+ * structural equality up to renaming of states,
+ * which is exact for deterministic ones (see canonical_form).
+ */
+impl<D, B, S> PartialEq for SymFa<D, B, S>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  S: State,
+{
+  fn eq(&self, other: &Self) -> bool {
+    self.canonical_form() == other.canonical_form()
+  }
+}
+impl<D, B, S> Eq for SymFa<D, B, S>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  S: State,
+{
+}
+impl<D, B, S> Hash for SymFa<D, B, S>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  S: State,
+{
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.canonical_form().hash(state)
+  }
+}
 impl<D, B, S> Default for SymFa<D, B, S>
 where
   D: Domain,
@@ -91,6 +136,200 @@ where
     sfa
   }
 
+  /**
+   * reachable states renumbered in the breadth-first order from the initial state,
+   * following transitions in the order of the hash of their predicates.
+   * each state is given by whether it is final and its transitions.
+   * the order of targets is kept, so nondeterministic machines may differ by it.
+   */
+  fn canonical_form(&self) -> CanonicalForm<'_, B> {
+    self.canonical_form_from(&self.initial_state, &self.successors())
+  }
+
+  /** transitions of each state */
+  fn successors(&self) -> HashMap<&S, Vec<(&B, &Target<S>)>> {
+    let mut successors: HashMap<_, Vec<_>> = HashMap::new();
+    for ((p, phi), target) in &self.transition {
+      successors.entry(p).or_default().push((phi, target));
+    }
+    successors
+  }
+
+  /** canonical_form of the sub-automaton whose initial state is root */
+  fn canonical_form_from<'a>(
+    &'a self,
+    root: &'a S,
+    successors: &HashMap<&'a S, Vec<(&'a B, &'a Target<S>)>>,
+  ) -> CanonicalForm<'a, B> {
+    let mut numbers = HashMap::from([(root, 0)]);
+    let mut queue = VecDeque::from([root]);
+    let mut form = vec![];
+
+    while let Some(p) = queue.pop_front() {
+      let mut transitions = successors.get(p).cloned().unwrap_or_default();
+      transitions.sort_by_key(|(phi, _)| hash_of(*phi));
+
+      let transitions = transitions
+        .into_iter()
+        .map(|(phi, target)| {
+          let target = target
+            .iter()
+            .map(|q| {
+              let n = numbers.len();
+              *numbers.entry(q).or_insert_with(|| {
+                queue.push_back(q);
+                n
+              })
+            })
+            .collect();
+          (phi, target)
+        })
+        .collect();
+      form.push((self.final_states.contains(p), transitions));
+    }
+
+    form
+  }
+
+  /** a state of self from which the sub-automaton is the same as other up to renaming */
+  fn find_shared(&self, other: &Self) -> Option<S> {
+    let form = other.canonical_form();
+    let successors = self.successors();
+    let mut candidates: Vec<&S> = self.states.iter().collect();
+    candidates.sort();
+
+    candidates
+      .into_iter()
+      .find(|q| self.has_form_from(q, &successors, &form))
+      .cloned()
+  }
+
+  /**
+   * whether canonical_form_from(root) is form.
+   * states are compared while they are numbered, so most candidates are rejected at once.
+   */
+  fn has_form_from<'a>(
+    &'a self,
+    root: &'a S,
+    successors: &HashMap<&'a S, Vec<(&'a B, &'a Target<S>)>>,
+    form: &CanonicalForm<'_, B>,
+  ) -> bool {
+    let mut numbers = HashMap::from([(root, 0)]);
+    let mut queue = VecDeque::from([root]);
+
+    for (is_final, expected) in form {
+      let p = match queue.pop_front() {
+        Some(p) => p,
+        None => return false,
+      };
+      let mut transitions = successors.get(p).cloned().unwrap_or_default();
+      if self.final_states.contains(p) != *is_final || transitions.len() != expected.len() {
+        return false;
+      }
+      transitions.sort_by_key(|(phi, _)| hash_of(*phi));
+
+      for ((phi, target), (expected_phi, expected_target)) in transitions.into_iter().zip(expected) {
+        if phi != *expected_phi || target.len() != expected_target.len() {
+          return false;
+        }
+        for (q, expected_number) in target.iter().zip(expected_target) {
+          let n = numbers.len();
+          let number = *numbers.entry(q).or_insert_with(|| {
+            queue.push_back(q);
+            n
+          });
+          if number != *expected_number {
+            return false;
+          }
+        }
+      }
+    }
+
+    queue.is_empty()
+  }
+
+  /** transitions whose predicates are replaced with indices of a shared pool */
+  pub fn pooled(&self) -> Pooled<S, B> {
+    let mut pool = PredicatePool::new();
+    let transition = pool.intern_transition(self.transition.clone());
+    (pool, transition)
+  }
+
+  /** inverse of pooled */
+  pub fn from_pooled(
+    states: HashSet<S>,
+    initial_state: S,
+    final_states: HashSet<S>,
+    pool: &PredicatePool<B>,
+    transition: HashMap<(S, usize), Target<S>>,
+  ) -> Self {
+    Self::new(
+      states,
+      initial_state,
+      final_states,
+      pool.resolve_transition(transition),
+    )
+  }
+
+  /** Graphviz DOT of the automaton, whose states are numbered in their order */
+  pub fn to_dot(&self) -> String {
+    self.to_dot_with(|_| None)
+  }
+
+  /** to_dot where each state is annotated with its origins, e.g. q3@3:5 for a label 3:5 */
+  pub fn to_dot_with_provenance<L: Display + Clone>(&self, provenance: &Provenance<S, L>) -> String {
+    self.to_dot_with(|s| {
+      let origins = provenance.origins(s);
+      (!origins.is_empty()).then(|| {
+        origins
+          .iter()
+          .map(|(label, q)| format!("{}@{}", q, label))
+          .collect::<Vec<_>>()
+          .join(", ")
+      })
+    })
+  }
+
+  /** states are named by Display, so that the same state has the same name in every dump */
+  fn to_dot_with(&self, xlabel: impl Fn(&S) -> Option<String>) -> String {
+    let mut dot = String::from("digraph sfa {\n  rankdir=LR;\n  start [shape=point];\n");
+    let mut nodes: Vec<_> = self.states.iter().collect();
+    nodes.sort();
+    for s in nodes {
+      let shape = if self.final_states.contains(s) {
+        "doublecircle"
+      } else {
+        "circle"
+      };
+      match xlabel(s) {
+        Some(label) => dot.push_str(&format!(
+          "  {} [shape={}, xlabel=\"{}\"];\n",
+          s,
+          shape,
+          dot_escape(&label)
+        )),
+        None => dot.push_str(&format!("  {} [shape={}];\n", s, shape)),
+      }
+    }
+    dot.push_str(&format!("  start -> {};\n", self.initial_state));
+
+    let mut edges: Vec<_> = self
+      .transitions()
+      .map(|(p, phi, q)| {
+        format!(
+          "  {} -> {} [label=\"{}\"];\n",
+          p,
+          q,
+          dot_escape(&phi.label())
+        )
+      })
+      .collect();
+    edges.sort();
+    dot.extend(edges);
+    dot.push_str("}\n");
+    dot
+  }
+
   pub fn run<'a>(&self, input: impl IntoIterator<Item = &'a B::Domain>) -> bool
   where
     B::Domain: 'a,
@@ -108,6 +347,24 @@ where
     )
   }
 
+  /** whether no word is accepted */
+  pub fn is_empty(&self) -> bool {
+    self.clone().accepted_path().is_none()
+  }
+
+  /**
+   * an accepted word, made of an element of each predicate along an accepted path.
+   * none if the language is empty.
+   */
+  pub fn witness(&self) -> Option<Vec<B::GetOne>> {
+    self
+      .clone()
+      .accepted_path()?
+      .into_iter()
+      .map(|phi| phi.get_one().ok())
+      .collect()
+  }
+
   pub fn accepted_path(self) -> Option<Vec<B>> {
     let mut result = None;
     let mut paths = vec![(self.initial_state(), vec![])];
@@ -142,6 +399,377 @@ where
     result
   }
 
+  /** an accepted path of the minimal length, by breadth-first search */
+  pub fn shortest_path(&self) -> Option<Vec<B>> {
+    let successors = self.successors();
+    let mut parents: HashMap<&S, Option<(&S, &B)>> = HashMap::from([(&self.initial_state, None)]);
+    let mut queue = VecDeque::from([&self.initial_state]);
+
+    while let Some(p) = queue.pop_front() {
+      if self.final_states.contains(p) {
+        let mut path = vec![];
+        let mut state = p;
+        while let Some((parent, phi)) = parents[state] {
+          path.push(phi.clone());
+          state = parent;
+        }
+        path.reverse();
+        return Some(path);
+      }
+
+      for &(phi, target) in successors.get(p).into_iter().flatten() {
+        if !phi.satisfiable() {
+          continue;
+        }
+        for q in target {
+          if !parents.contains_key(q) {
+            parents.insert(q, Some((p, phi)));
+            queue.push_back(q);
+          }
+        }
+      }
+    }
+
+    None
+  }
+
+  /** an accepted word of the minimal length, which witness doesn't guarantee */
+  pub fn shortest_witness(&self) -> Option<Vec<B::GetOne>> {
+    self
+      .shortest_path()?
+      .into_iter()
+      .map(|phi| phi.get_one().ok())
+      .collect()
+  }
+
+  /**
+   * breadth-first search of an accepted path with the `parallel` feature,
+   * where workers take chunks of each frontier one by one.
+   * the witness doesn't depend on the scheduling: it is the first shortest path
+   * in the order of states and then of the hash of predicates.
+   */
+  #[cfg(feature = "parallel")]
+  pub fn accepted_path_parallel(&self, threads: usize) -> Option<Vec<B>> {
+    use std::{
+      sync::atomic::{AtomicUsize, Ordering},
+      thread,
+    };
+    const CHUNK: usize = 64;
+
+    /* workers only see indices, so that neither S nor B has to be Sync */
+    let mut states: Vec<&S> = self.states.iter().collect();
+    states.sort();
+    let index: HashMap<&S, usize> = states.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+    let mut predicates: Vec<&B> = vec![];
+    let mut successors: Vec<Vec<(u64, usize, usize)>> = vec![vec![]; states.len()];
+    for ((p, phi), target) in &self.transition {
+      predicates.push(phi);
+      for q in target {
+        successors[index[p]].push((hash_of(phi), predicates.len() - 1, index[q]));
+      }
+    }
+    successors
+      .iter_mut()
+      .for_each(|succ| succ.sort_by_key(|(h, _, q)| (*h, *q)));
+
+    /* (parent, predicate) for visited states */
+    let mut parents: Vec<Option<Option<(usize, usize)>>> = vec![None; states.len()];
+    parents[index[&self.initial_state]] = Some(None);
+    let mut frontier = vec![index[&self.initial_state]];
+
+    while !frontier.is_empty() {
+      if let Some(f) = frontier
+        .iter()
+        .find(|p| self.final_states.contains(states[**p]))
+      {
+        let mut path = vec![];
+        let mut state = *f;
+        while let Some(Some((p, phi))) = parents[state] {
+          path.push(B::clone(predicates[phi]));
+          state = p;
+        }
+        path.reverse();
+        return Some(path);
+      }
+
+      let chunks: Vec<&[usize]> = frontier.chunks(CHUNK).collect();
+      let counter = AtomicUsize::new(0);
+      let mut expanded: Vec<Vec<(usize, usize, usize)>> = vec![vec![]; chunks.len()];
+      thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+          .map(|_| {
+            scope.spawn(|| {
+              let mut done = vec![];
+              loop {
+                let i = counter.fetch_add(1, Ordering::Relaxed);
+                let chunk = match chunks.get(i) {
+                  Some(chunk) => chunk,
+                  None => break done,
+                };
+                let succ: Vec<_> = chunk
+                  .iter()
+                  .flat_map(|p| {
+                    successors[*p]
+                      .iter()
+                      .filter(|(_, _, q)| parents[*q].is_none())
+                      .map(move |(_, phi, q)| (*p, *phi, *q))
+                  })
+                  .collect();
+                done.push((i, succ));
+              }
+            })
+          })
+          .collect();
+        for worker in workers {
+          for (i, succ) in worker.join().unwrap() {
+            expanded[i] = succ;
+          }
+        }
+      });
+
+      /* merged in the order of chunks, so the first visit wins as in the sequential search */
+      frontier = vec![];
+      for (p, phi, q) in expanded.into_iter().flatten() {
+        if parents[q].is_none() {
+          parents[q] = Some(Some((p, phi)));
+          frontier.push(q);
+        }
+      }
+    }
+
+    None
+  }
+
+  fn has_cycle(&self) -> bool {
+    let successors = |p: &S| {
+      self
+        .transition
+        .iter()
+        .filter(|((q, _), _)| *q == *p)
+        .flat_map(|(_, target)| target.iter())
+        .collect::<Vec<_>>()
+    };
+
+    let mut finished = HashSet::new();
+    for s in &self.states {
+      if finished.contains(s) {
+        continue;
+      }
+
+      let mut on_stack = HashSet::from([s]);
+      let mut stack = vec![(s, successors(s))];
+      while let Some((p, nexts)) = stack.last_mut() {
+        match nexts.pop() {
+          Some(q) => {
+            if on_stack.contains(q) {
+              return true;
+            }
+            if !finished.contains(q) {
+              on_stack.insert(q);
+              stack.push((q, successors(q)));
+            }
+          }
+          None => {
+            on_stack.remove(*p);
+            finished.insert(*p);
+            stack.pop();
+          }
+        }
+      }
+    }
+
+    false
+  }
+
+  /** whether the language is finite, i.e. no cycle remains after trimming */
+  pub fn is_finite(&self) -> bool {
+    let mut sfa = self.clone();
+    sfa.minimize();
+    !sfa.has_cycle()
+  }
+
+  /**
+   * all words of a finite language as sequences of predicates, or None if infinite.
+   * any element of each predicate can be taken,
+   * so small languages can be handled by brute force.
+   */
+  pub fn enumerate_paths(&self) -> Option<Vec<Vec<B>>> {
+    let mut sfa = self.clone();
+    sfa.minimize();
+    if sfa.has_cycle() {
+      return None;
+    }
+
+    let mut words = vec![];
+    let mut stack = vec![(&sfa.initial_state, vec![])];
+    while let Some((p, word)) = stack.pop() {
+      if sfa.final_states.contains(p) {
+        words.push(word.clone());
+      }
+
+      for ((q, phi), target) in &sfa.transition {
+        if *q == *p {
+          for r in target {
+            let mut word = word.clone();
+            word.push(phi.clone());
+            stack.push((r, word));
+          }
+        }
+      }
+    }
+
+    Some(words)
+  }
+
+  /**
+   * accepted words up to max_len in the order of length, by the breadth-first search
+   * taking an element of each satisfiable predicate by get_one, so each transition gives one word.
+   * it is lazy, e.g. for looking into constraints or generating test inputs from a specification.
+   */
+  pub fn enumerate(&self, max_len: usize) -> impl Iterator<Item = Vec<B::GetOne>> + '_ {
+    let successors = self.successors();
+    let mut queue = VecDeque::from([(&self.initial_state, vec![])]);
+    let mut visited = HashSet::new();
+    let mut words = HashSet::new();
+
+    std::iter::from_fn(move || {
+      while let Some((p, word)) = queue.pop_front() {
+        if word.len() < max_len {
+          for &(phi, target) in successors.get(p).into_iter().flatten() {
+            if let Ok(c) = B::clone(phi).get_one() {
+              let mut next = word.clone();
+              next.push(c);
+              for q in target.iter() {
+                if visited.insert((q, next.clone())) {
+                  queue.push_back((q, next.clone()));
+                }
+              }
+            }
+          }
+        }
+        if self.final_states.contains(p) && words.insert(word.clone()) {
+          return Some(word);
+        }
+      }
+      None
+    })
+  }
+
+  /**
+   * number of words of each length up to max_len, i.e. coefficients of the generating function.
+   * sets of states are tracked so that a word with several runs is counted once.
+   */
+  pub fn words_per_length(&self, max_len: usize) -> Vec<u128> {
+    let mut counts = vec![];
+    let mut frontier = HashMap::from([(BTreeSet::from([&self.initial_state]), 1u128)]);
+
+    for _ in 0..=max_len {
+      counts.push(
+        frontier
+          .iter()
+          .filter(|(set, _)| set.iter().any(|s| self.final_states.contains(*s)))
+          .fold(0u128, |acc, (_, count)| acc.saturating_add(*count)),
+      );
+
+      let mut next = HashMap::new();
+      for (set, count) in &frontier {
+        for i in 0..=u8::MAX {
+          let c: D = (i as char).into();
+          let successors: BTreeSet<_> = self
+            .transition
+            .iter()
+            .filter(|((p, phi), _)| set.contains(p) && phi.denote(&c))
+            .flat_map(|(_, target)| target.iter())
+            .collect();
+          if !successors.is_empty() {
+            let total = next.entry(successors).or_insert(0u128);
+            *total = total.saturating_add(*count);
+          }
+        }
+      }
+      frontier = next;
+    }
+
+    counts
+  }
+
+  /**
+   * lengths up to max_len of the accepted words, i.e. the length abstraction of the language.
+   * any satisfiable predicate stands for some character, so no word is enumerated.
+   */
+  pub fn lengths(&self, max_len: usize) -> Vec<usize> {
+    let mut lengths = vec![];
+    let mut current = HashSet::from([&self.initial_state]);
+    for len in 0..=max_len {
+      if current.iter().any(|s| self.final_states.contains(*s)) {
+        lengths.push(len);
+      }
+      current = self
+        .transition
+        .iter()
+        .filter(|((p, phi), _)| current.contains(p) && phi.satisfiable())
+        .flat_map(|(_, target)| target.iter())
+        .collect();
+      if current.is_empty() {
+        break;
+      }
+    }
+    lengths
+  }
+
+  /**
+   * the length abstraction of the language as linear sets base + period * k for k >= 0,
+   * where the period is 0 for a single length.
+   * the set of states reached by each length repeats eventually, and the lengths with it,
+   * or None if it doesn't within max_len.
+   */
+  pub fn length_abstraction(&self, max_len: usize) -> Option<Vec<(usize, usize)>> {
+    let mut successors: HashMap<&S, Vec<&S>> = HashMap::new();
+    for ((p, phi), target) in &self.transition {
+      if phi.satisfiable() {
+        successors.entry(p).or_default().extend(target.iter());
+      }
+    }
+
+    let mut reached: HashMap<Vec<&S>, usize> = HashMap::new();
+    let mut accepted = vec![];
+    let mut current = vec![&self.initial_state];
+    for len in 0..=max_len {
+      if let Some(start) = reached.get(&current) {
+        let period = len - start;
+        return Some(
+          (0..len)
+            .filter(|len| accepted[*len])
+            .map(|len| (len, if len < *start { 0 } else { period }))
+            .collect(),
+        );
+      }
+      accepted.push(current.iter().any(|s| self.final_states.contains(*s)));
+      let mut next: Vec<&S> = current
+        .iter()
+        .flat_map(|p| successors.get(p).into_iter().flatten().copied())
+        .collect();
+      next.sort();
+      next.dedup();
+      reached.insert(std::mem::replace(&mut current, next), len);
+    }
+    None
+  }
+
+  pub fn cardinality(&self) -> Cardinality {
+    if self.is_finite() {
+      /* the longest word is shorter than the number of states */
+      Cardinality::Finite(
+        self
+          .words_per_length(self.states.len())
+          .into_iter()
+          .fold(0, |acc, count| acc.saturating_add(count)),
+      )
+    } else {
+      Cardinality::Infinite
+    }
+  }
+
   pub fn concat(self, other: Self) -> Self {
     let Self {
       mut states,
@@ -175,7 +803,19 @@ where
     Self::new(states, initial_state, final_states, transition)
   }
 
+  /**
+   * union of the languages.
+   * when one side already contains the other as a sub-automaton (e.g. `a|ba` of
+   * machine-generated re.union chains), its states are reused instead of copied.
+   */
   pub fn or(self, other: Self) -> Self {
+    if let Some(shared) = self.find_shared(&other) {
+      return self.or_shared(shared);
+    }
+    if let Some(shared) = other.find_shared(&self) {
+      return other.or_shared(shared);
+    }
+
     let Self {
       mut states,
       initial_state: i1,
@@ -217,7 +857,271 @@ where
     Self::new(states, initial_state, final_states, transition)
   }
 
+  /** union of the language and the one from shared, which is a state of self */
+  fn or_shared(self, shared: S) -> Self {
+    if shared == self.initial_state {
+      return self;
+    }
+
+    let Self {
+      mut states,
+      initial_state: i1,
+      mut final_states,
+      mut transition,
+    } = self;
+
+    let initial_state = S::new();
+    states.insert(S::clone(&initial_state));
+    if final_states.contains(&i1) || final_states.contains(&shared) {
+      final_states.insert(S::clone(&initial_state));
+    }
+
+    let copied: Vec<_> = transition
+      .iter()
+      .filter(|((state, _), _)| *state == i1 || *state == shared)
+      .map(|((_, phi), target)| (phi.clone(), target.clone()))
+      .collect();
+    for (phi, target) in copied {
+      transition.insert_with_check((S::clone(&initial_state), phi), target);
+    }
+
+    Self::new(states, initial_state, final_states, transition)
+  }
+
+  /**
+   * union of all the languages at once.
+   * states with the same sub-automaton up to renaming are merged afterwards, so that the
+   * common suffixes of machine-generated re.union chains are shared instead of copied.
+   */
+  pub fn or_all(sfas: Vec<Self>) -> Self {
+    if sfas.len() <= 1 {
+      return sfas.into_iter().next().unwrap_or_else(Self::empty);
+    }
+
+    let initial_state = S::new();
+    let mut states = HashSet::from([S::clone(&initial_state)]);
+    let mut final_states = HashSet::new();
+    let mut transition = HashMap::new();
+
+    for sfa in sfas {
+      let Self {
+        states: s,
+        initial_state: i,
+        final_states: f,
+        transition: t,
+      } = sfa;
+
+      if f.contains(&i) {
+        final_states.insert(S::clone(&initial_state));
+      }
+      t.into_iter().for_each(|((state, phi), target)| {
+        if state == i {
+          transition.insert_with_check((S::clone(&initial_state), phi.clone()), target.clone());
+        }
+        transition.insert_with_check((state, phi), target);
+      });
+      states.extend(s);
+      final_states.extend(f);
+    }
+
+    Self::new(states, initial_state, final_states, transition).merge_bisimilar()
+  }
+
+  /**
+   * merges the states of the same block of the coarsest bisimulation,
+   * refined from the final and non-final states by the blocks of the targets of each predicate.
+   */
+  fn merge_bisimilar(self) -> Self {
+    let successors = self.successors();
+    let mut order: Vec<&S> = self.states.iter().collect();
+    order.sort();
+
+    let mut blocks: HashMap<&S, usize> = order
+      .iter()
+      .map(|q| (*q, self.final_states.contains(q) as usize))
+      .collect();
+    let mut count = 0;
+    loop {
+      let mut signatures = HashMap::new();
+      let refined: HashMap<&S, usize> = order
+        .iter()
+        .map(|q| {
+          let mut transitions: Vec<_> = successors
+            .get(q)
+            .into_iter()
+            .flatten()
+            .map(|(phi, target)| {
+              let mut target: Vec<usize> = target.iter().map(|p| blocks[p]).collect();
+              target.sort_unstable();
+              target.dedup();
+              (hash_of(*phi), *phi, target)
+            })
+            .collect();
+          transitions.sort_by(|(h1, _, t1), (h2, _, t2)| (h1, t1).cmp(&(h2, t2)));
+
+          let n = signatures.len();
+          (*q, *signatures.entry((blocks[q], transitions)).or_insert(n))
+        })
+        .collect();
+
+      blocks = refined;
+      if signatures.len() == count {
+        break;
+      }
+      count = signatures.len();
+    }
+
+    let mut representatives: HashMap<usize, &S> = HashMap::new();
+    for q in &order {
+      representatives.entry(blocks[q]).or_insert(q);
+    }
+    let representative = |q: &S| S::clone(representatives[&blocks[q]]);
+
+    let transition = self
+      .transition
+      .iter()
+      .filter(|((p, _), _)| representatives[&blocks[p]] == p)
+      .map(|((p, phi), target)| {
+        let mut target: Vec<S> = target.iter().map(representative).collect();
+        target.sort();
+        target.dedup();
+        ((S::clone(p), phi.clone()), target)
+      })
+      .collect();
+
+    Self::new(
+      representatives.values().map(|q| S::clone(q)).collect(),
+      representative(&self.initial_state),
+      self.final_states.iter().map(representative).collect(),
+      transition,
+    )
+  }
+
   pub fn inter(self, other: Self) -> Self {
+    self.product(other).0
+  }
+
+  /**
+   * intersection of all, from the smallest one and stopping at an empty product,
+   * so that large operands are never multiplied when the result is empty anyway.
+   */
+  pub fn inter_all(mut sfas: Vec<Self>) -> Self {
+    sfas.sort_by_key(|sfa| sfa.states.len());
+    let mut sfas = sfas.into_iter();
+    let mut result = match sfas.next() {
+      Some(sfa) => sfa,
+      None => return Self::default(),
+    };
+    for sfa in sfas {
+      if result.final_states.is_empty() || result.is_empty() {
+        return Self::empty();
+      }
+      result = result.inter(sfa);
+    }
+    result
+  }
+
+  /** the deterministic automaton of the finite set of words, i.e. a trie */
+  pub fn from_words(words: &[Vec<D>]) -> Self {
+    let initial_state = S::new();
+    let mut states = HashSet::from([S::clone(&initial_state)]);
+    let mut final_states = HashSet::new();
+    let mut children: HashMap<(S, D), S> = HashMap::new();
+
+    for word in words {
+      let mut state = S::clone(&initial_state);
+      for c in word {
+        state = S::clone(
+          children
+            .entry((S::clone(&state), D::clone(c)))
+            .or_insert_with(|| {
+              let child = S::new();
+              states.insert(S::clone(&child));
+              child
+            }),
+        );
+      }
+      final_states.insert(state);
+    }
+
+    let transition = children
+      .into_iter()
+      .map(|((state, c), child)| ((state, B::char(c)), vec![child]))
+      .collect();
+    Self::new(states, initial_state, final_states, transition)
+  }
+
+  /**
+   * the Levenshtein automaton of words within k edits of word, i.e. insertions,
+   * deletions and substitutions of characters, whose state (i, e) has read
+   * a word within e edits of the first i characters of word.
+   * deletions are taken ahead of the next transition, since there is no epsilon transition.
+   */
+  pub fn within_edit_distance(word: &[D], k: usize) -> Self {
+    let grid: Vec<Vec<S>> = (0..=word.len())
+      .map(|_| (0..=k).map(|_| S::new()).collect())
+      .collect();
+    let mut final_states = HashSet::new();
+    let mut transition: HashMap<_, Vec<S>> = HashMap::new();
+
+    for i in 0..=word.len() {
+      for e in 0..=k {
+        let source = &grid[i][e];
+        for deleted in 0..=(k - e).min(word.len() - i) {
+          let (i, e) = (i + deleted, e + deleted);
+          let mut add = |phi: B, target: &S| {
+            transition
+              .entry((S::clone(source), phi))
+              .or_default()
+              .push(S::clone(target))
+          };
+          if i < word.len() {
+            add(B::char(D::clone(&word[i])), &grid[i + 1][e]);
+          }
+          if e < k {
+            /* an insertion, and a substitution */
+            add(B::all_char(), &grid[i][e + 1]);
+            if i < word.len() {
+              add(B::all_char(), &grid[i + 1][e + 1]);
+            }
+          }
+          if i == word.len() {
+            final_states.insert(S::clone(source));
+          }
+        }
+      }
+    }
+
+    let initial_state = S::clone(&grid[0][0]);
+    let states = grid.into_iter().flatten().collect();
+    Self::new(states, initial_state, final_states, transition)
+  }
+
+  /**
+   * inter, keeping track of provenance.
+   * each product state originates from the origins of both of its components.
+   */
+  pub fn inter_with_provenance<L: Clone>(
+    self,
+    provenance: &Provenance<S, L>,
+    other: Self,
+    other_provenance: &Provenance<S, L>,
+  ) -> (Self, Provenance<S, L>) {
+    let (sfa, pairs) = self.product(other);
+    let origins = pairs
+      .into_iter()
+      .filter(|(s, _)| sfa.states.contains(s))
+      .map(|(s, (p, q))| {
+        let mut origins = provenance.origins(&p).to_vec();
+        origins.extend(other_provenance.origins(&q).iter().cloned());
+        (s, origins)
+      })
+      .collect();
+    (sfa, Provenance { origins })
+  }
+
+  /** intersection by the product construction, and the pair each product state comes from */
+  fn product(self, other: Self) -> (Self, HashMap<S, (S, S)>) {
     let error_msg = "Uncontrolled states exist. this will happen for developper's error";
 
     let Self {
@@ -264,17 +1168,118 @@ where
 
     let initial_state = S::clone(cartesian.get(&(&i1, &i2)).expect(error_msg));
 
-    let states = cartesian.into_values().collect();
+    let states = cartesian.values().cloned().collect();
+    let pairs = cartesian
+      .into_iter()
+      .map(|((p, q), s)| (s, (S::clone(p), S::clone(q))))
+      .collect();
 
-    Self::new(states, initial_state, final_states, transition)
+    (
+      Self::new(states, initial_state, final_states, transition),
+      pairs,
+    )
+  }
+
+  /** try_determinize which panics if it diverges */
+  pub fn determinize(self) -> Self {
+    self
+      .try_determinize()
+      .unwrap_or_else(|err| panic!("{}", err))
+  }
+
+  /**
+   * an equivalent deterministic automaton by the subset construction,
+   * where each set of states moves by the minterms of its outgoing predicates.
+   * it gives up if there are more subsets than STATE_LIMIT.
+   */
+  pub fn try_determinize(self) -> Result<Self, ResourceExhausted> {
+    let successors = self.successors();
+    let initial = BTreeSet::from([&self.initial_state]);
+    let initial_state = S::new();
+    let mut subsets = HashMap::from([(initial.clone(), S::clone(&initial_state))]);
+    let mut queue = VecDeque::from([initial]);
+    let mut final_states = HashSet::new();
+    let mut transition = HashMap::new();
+
+    while let Some(subset) = queue.pop_front() {
+      ResourceExhausted::check("determinization", subsets.len(), || {
+        format!("an SFA of {} states", self.states.len())
+      })?;
+      let source = S::clone(&subsets[&subset]);
+      if subset.iter().any(|p| self.final_states.contains(*p)) {
+        final_states.insert(S::clone(&source));
+      }
+      let outgoing: Vec<_> = subset
+        .iter()
+        .flat_map(|p| successors.get(*p).cloned().unwrap_or_default())
+        .collect();
+      for (phi, target) in Self::minterms(&outgoing) {
+        let target = S::clone(subsets.entry(target.clone()).or_insert_with(|| {
+          queue.push_back(target);
+          S::new()
+        }));
+        transition.insert((S::clone(&source), phi), vec![target]);
+      }
+    }
+
+    let states = subsets.into_values().collect();
+    Ok(Self::new(states, initial_state, final_states, transition))
+  }
+
+  /**
+   * satisfiable conjunctions of the predicates or their negations,
+   * each with the union of the targets of the predicates it implies.
+   * ones without any target are dropped, so they are partial.
+   */
+  fn minterms<'a>(outgoing: &[(&B, &'a Target<S>)]) -> Vec<(B, BTreeSet<&'a S>)> {
+    let mut minterms = vec![(B::top(), BTreeSet::new())];
+    for &(phi, target) in outgoing {
+      minterms = minterms
+        .into_iter()
+        .flat_map(|(psi, subset)| {
+          let mut union = subset.clone();
+          union.extend(target.iter());
+          [(psi.and(phi), union), (psi.and(&phi.not()), subset)]
+        })
+        .filter(|(psi, _)| psi.satisfiable())
+        .collect();
+    }
+    minterms
+      .into_iter()
+      .filter(|(_, subset)| !subset.is_empty())
+      .collect()
+  }
+
+  /** whether each state has at most one target for each character */
+  pub fn is_deterministic(&self) -> bool {
+    self.successors().values().all(|transitions| {
+      transitions.iter().enumerate().all(|(i, (phi, target))| {
+        target.len() <= 1
+          && transitions[i + 1..]
+            .iter()
+            .all(|(psi, _)| !phi.and(psi).satisfiable())
+      })
+    })
   }
 
-  pub fn not(self) -> Self {
-    let not_predicates: HashMap<_, _> = self
+  /**
+   * the automaton where each state moves to a sink state by the negation of the union of
+   * its outgoing predicates, so that every word without the separator has a run.
+   * it isn't minimized, which would remove the sink since no final state is reachable from it.
+   */
+  pub fn complete(self) -> Self {
+    let missing: Vec<_> = self
       .states
       .iter()
-      .map(|state| (S::clone(state), self.state_predicate(state).not()))
+      .map(|state| {
+        let phi = self.state_predicate(state).not().and(&B::all_char());
+        (S::clone(state), phi)
+      })
+      .filter(|(_, phi)| phi.satisfiable())
       .collect();
+    if missing.is_empty() {
+      return self;
+    }
 
     let Self {
       mut states,
@@ -282,23 +1287,165 @@ where
       final_states,
       mut transition,
     } = self;
+    let sink = S::new();
+    for (state, phi) in missing {
+      transition.insert_with_check((state, phi), [S::clone(&sink)]);
+    }
+    transition.insert((S::clone(&sink), B::all_char()), vec![S::clone(&sink)]);
+    states.insert(sink);
 
-    let mut final_states = &states - &final_states;
-    let dead_state = S::new();
+    Self {
+      states,
+      initial_state,
+      final_states,
+      transition,
+    }
+  }
 
-    states.iter().for_each(|state| {
-      transition.insert(
-        (S::clone(state), not_predicates.get(state).unwrap().clone()),
-        vec![S::clone(&dead_state)],
-      );
-    });
-    transition.insert(
-      (S::clone(&dead_state), B::top()),
-      vec![S::clone(&dead_state)],
-    );
+  /**
+   * the complement among the words without the separator,
+   * flipping the final states of the deterministic and complete automaton.
+   */
+  pub fn complement(self) -> Self {
+    let Self {
+      states,
+      initial_state,
+      final_states,
+      transition,
+    } = self.determinize().complete();
+    let final_states = &states - &final_states;
+
+    Self::new(states, initial_state, final_states, transition)
+  }
+
+  /** prefixes of the accepted words, where the states from which a final one is reachable are final */
+  pub fn prefix_closure(self) -> Self {
+    let final_states = self
+      .final_states
+      .iter()
+      .flat_map(|q| self.reachable_sources(q))
+      .cloned()
+      .collect();
+    let Self {
+      states,
+      initial_state,
+      transition,
+      ..
+    } = self;
+
+    Self::new(states, initial_state, final_states, transition)
+  }
+
+  /**
+   * suffixes of the accepted words, by a new initial state which moves as any reachable state does.
+   * it is final if some reachable state is final.
+   */
+  pub fn suffix_closure(self) -> Self {
+    let reachables: HashSet<S> = self
+      .reachables(&self.initial_state)
+      .into_iter()
+      .cloned()
+      .collect();
+    let Self {
+      mut states,
+      mut final_states,
+      mut transition,
+      ..
+    } = self;
+
+    let initial_state = S::new();
+    let entries: Vec<_> = transition
+      .iter()
+      .filter(|((p, _), _)| reachables.contains(p))
+      .map(|((_, phi), target)| ((S::clone(&initial_state), phi.clone()), target.clone()))
+      .collect();
+    for (source, target) in entries {
+      transition.insert_with_check(source, target);
+    }
+    if reachables.iter().any(|p| final_states.contains(p)) {
+      final_states.insert(S::clone(&initial_state));
+    }
+    states.insert(S::clone(&initial_state));
+
+    Self::new(states, initial_state, final_states, transition)
+  }
+
+  /** the same automaton over fresh states, so that it can be combined with self */
+  fn fresh_copy(&self) -> Self {
+    let renaming: HashMap<_, _> = self
+      .states
+      .iter()
+      .map(|state| (S::clone(state), S::new()))
+      .collect();
+    let rename = |state: &S| S::clone(&renaming[state]);
+    Self {
+      states: renaming.values().cloned().collect(),
+      initial_state: rename(&self.initial_state),
+      final_states: self.final_states.iter().map(rename).collect(),
+      transition: self
+        .transition
+        .iter()
+        .map(|((state, phi), target)| {
+          (
+            (rename(state), phi.clone()),
+            target.iter().map(rename).collect(),
+          )
+        })
+        .collect(),
+    }
+  }
 
-    states.insert(S::clone(&dead_state));
-    final_states.insert(dead_state);
+  /**
+   * between min and max repetitions, or at least min without max.
+   * copies of self are wired one after another in a single pass, linking the states ending
+   * each copy to the next one as concat does, and only the whole chain is minimized.
+   * the optional ones are nested as (r(r(r)?)?)?, so that the states ending any of them are final.
+   */
+  pub fn repeat(self, min: usize, max: Option<usize>) -> Self {
+    let initial_state = S::new();
+    let mut states = HashSet::from([S::clone(&initial_state)]);
+    let mut transition: HashMap<_, Target<S>> = HashMap::new();
+    /* the states where the copies read so far end, and the ones where the chain may end */
+    let mut ends = HashSet::from([S::clone(&initial_state)]);
+    let mut final_states = ends.clone();
+
+    let copies = match max {
+      Some(max) => max.max(min),
+      None => min + 1,
+    };
+    for k in 0..copies {
+      let copy = if max.is_none() && k == min {
+        self.fresh_copy().star()
+      } else {
+        self.fresh_copy()
+      };
+      let Self {
+        states: s,
+        initial_state: i,
+        final_states: f,
+        transition: t,
+      } = copy;
+
+      for ((state, phi), target) in t {
+        if state == i {
+          for end in &ends {
+            transition.insert_with_check((S::clone(end), phi.clone()), target.clone());
+          }
+        }
+        transition.insert_with_check((state, phi), target);
+      }
+      states.extend(s);
+
+      if !f.contains(&i) {
+        ends.clear();
+      }
+      ends.extend(f);
+      if k < min || max.is_none() {
+        final_states = ends.clone();
+      } else {
+        final_states.extend(ends.iter().cloned());
+      }
+    }
 
     Self::new(states, initial_state, final_states, transition)
   }
@@ -352,7 +1499,7 @@ where
   }
 
   pub fn pre_image_forward<V: Variable>(self, sst: SymSst<D, B, B::Term, S, V>) -> Self {
-    eprintln!("preimage");
+    diagnostic!("preimage");
     let mut states = HashMap::new();
     let mut initial_states = HashSet::new();
     let mut transition: HashMap<_, Vec<_>> = HashMap::new();
@@ -432,7 +1579,7 @@ where
       }
     }
 
-    eprintln!(
+    diagnostic!(
       "stack {:?}\n\nstart searching, {}, vars: {}",
       stack,
       stack.len(),
@@ -624,7 +1771,18 @@ where
     }
   }
 
+  /** try_pre_image which panics if it diverges */
   pub fn pre_image<V: Variable>(self, sst: SymSst<D, B, B::Term, S, V>) -> Self {
+    self
+      .try_pre_image(sst)
+      .unwrap_or_else(|err| panic!("{}", err))
+  }
+
+  /** the pre-image through sst, which gives up if it builds more states than STATE_LIMIT */
+  pub fn try_pre_image<V: Variable>(
+    self,
+    sst: SymSst<D, B, B::Term, S, V>,
+  ) -> Result<Self, ResourceExhausted> {
     #[cfg(test)]
     eprintln!("preimage");
 
@@ -718,20 +1876,19 @@ where
     }
 
     while let Some(tuple) = stack.pop() {
-      #[cfg(test)]
-      {
-        if states.len() > 5000 {
-          eprintln!("states: {:#?}", states);
-          panic!("psedo stack overflow");
-        }
-      }
+      ResourceExhausted::check("pre-image", states.len(), || {
+        format!(
+          "an SFA of {} states through an SST of {} states",
+          self.states.len(),
+          sst.states().len()
+        )
+      })?;
 
       let next = S::clone(states.get(&tuple).unwrap());
       let (q, var_map) = tuple;
 
       for ((q1, psi), target) in sst.transition() {
         'add_update: for (_, alpha) in target.into_iter().filter(|(s, _)| *s == *q) {
-          let mut phi = HashMap::new();
           let mut pre_maps: HashMap<_, Vec<_>> = HashMap::new();
 
           for (var, nexts) in &var_map {
@@ -781,63 +1938,60 @@ where
                 .collect();
 
               if possibilities.len() != 0 {
-                possibilities.into_iter().for_each(|(_, var_map, var_phi)| {
-                  let p_phi = phi.entry((*p1, *var, *p2)).or_insert(B::bot());
-                  *p_phi = var_phi.or(p_phi);
-                  pre_maps.insert_with_check(*var, [var_map]);
-                });
+                pre_maps.insert(
+                  (*var, *p1, *p2),
+                  possibilities
+                    .into_iter()
+                    .map(|(_, var_map, var_phi)| (var_map, var_phi))
+                    .collect(),
+                );
               } else {
                 continue 'add_update;
               }
             }
           }
 
-          let phi = phi
-            .into_values()
-            .reduce(|phi, p_phi| phi.and(&p_phi))
-            .unwrap_or(B::boolean(var_map.is_empty()))
-            .and(psi);
-
-          if phi.satisfiable() {
-            /* calculate each combination of pre_maps */
-            let pre_maps = {
-              let mut combination = vec![BTreeMap::new()];
-
-              for pre_maps in pre_maps.into_values() {
-                combination = combination
-                  .into_iter()
-                  .flat_map(move |map| {
-                    let mut pre_maps = pre_maps.clone();
-                    pre_maps.iter_mut().for_each(|pre_map| {
-                      pre_map.merge(map.clone());
-                    });
-
-                    pre_maps
+          /*
+           * calculate each combination of pre_maps, one for each pair of states of each variable,
+           * read by a character satisfying all of them
+           */
+          let mut combination = vec![(BTreeMap::new(), psi.clone())];
+          for choices in pre_maps.into_values() {
+            combination = combination
+              .into_iter()
+              .flat_map(|(map, phi)| {
+                choices
+                  .iter()
+                  .filter_map(|(pre_map, var_phi)| {
+                    let phi = phi.and(var_phi);
+                    phi.satisfiable().then(|| {
+                      let mut pre_map = pre_map.clone();
+                      MultiMap::merge(&mut pre_map, map.clone());
+                      (pre_map, phi)
+                    })
                   })
-                  .collect();
-              }
-
-              combination
-            };
+                  .collect::<Vec<_>>()
+              })
+              .collect();
+          }
 
-            pre_maps.into_iter().for_each(|pre_map| {
-              let tuple = (q1, pre_map);
-              let source_state = match states.get(&tuple) {
-                Some(s) => S::clone(s),
-                None => {
-                  let new_state = S::new();
-                  if !stack.contains(&tuple) {
-                    stack.push(tuple.clone());
-                  }
-                  states.insert(tuple, S::clone(&new_state));
-                  new_state
+          combination.into_iter().for_each(|(pre_map, phi)| {
+            let tuple = (q1, pre_map);
+            let source_state = match states.get(&tuple) {
+              Some(s) => S::clone(s),
+              None => {
+                let new_state = S::new();
+                if !stack.contains(&tuple) {
+                  stack.push(tuple.clone());
                 }
-              };
+                states.insert(tuple, S::clone(&new_state));
+                new_state
+              }
+            };
 
-              let source = (source_state, phi.clone());
-              transition.insert_with_check(source, [S::clone(&next)]);
-            });
-          }
+            let source = (source_state, phi);
+            transition.insert_with_check(source, [S::clone(&next)]);
+          });
         }
       }
 
@@ -877,9 +2031,9 @@ where
     }
 
     if initial_states.is_empty() {
-      Self::empty()
+      Ok(Self::empty())
     } else {
-      Self::new(states, initial_state, final_states, transition)
+      Ok(Self::new(states, initial_state, final_states, transition))
     }
   }
 
@@ -915,6 +2069,219 @@ where
       { joint }
     })
   }
+
+  /**
+   * the same automaton over another algebra, converting each predicate by f.
+   * transitions whose predicates become the same are merged.
+   */
+  pub fn map_predicates<B2>(self, f: impl Fn(&B) -> B2) -> SymFa<D, B2, S>
+  where
+    B2: BoolAlg<Domain = D>,
+  {
+    let mut transition = HashMap::new();
+    for ((source, phi), targets) in self.transition {
+      transition.insert_with_check((source, f(&phi)), targets);
+    }
+    SymFa::new(
+      self.states,
+      self.initial_state,
+      self.final_states,
+      transition,
+    )
+  }
+}
+impl<T, S> SymFa<T, Predicate<T>, S>
+where
+  T: Domain,
+  S: State,
+{
+  /**
+   * a random accepted word up to max_len, or None if there is no such one.
+   * it walks from the initial state, stopping at a final state or taking a transition
+   * uniformly among the ones from which a final state is reachable in the rest of the length,
+   * and picks a random character of the predicate, so words are sampled uniformly-ish.
+   */
+  pub fn sample(&self, rng: &mut impl Rng, max_len: usize) -> Option<Vec<T>> {
+    let distances = self.distances_to_final();
+    let mut p = &self.initial_state;
+    if distances.get(p).is_none_or(|d| *d > max_len) {
+      return None;
+    }
+
+    let mut word = vec![];
+    loop {
+      let rest = max_len - word.len();
+      let moves: Vec<_> = self
+        .transition
+        .iter()
+        .filter(|((q, _), _)| q == p)
+        .flat_map(|((_, phi), target)| target.iter().map(move |q| (phi, q)))
+        .filter(|(_, q)| distances.get(q).is_some_and(|d| *d < rest))
+        .collect();
+      let stop = self.final_states.contains(p);
+      let choice = rng.below(moves.len() + stop as usize);
+      if choice == moves.len() {
+        return Some(word);
+      }
+      let (phi, q) = moves[choice];
+      word.push(Self::sample_char(phi, rng)?);
+      p = q;
+    }
+  }
+
+  /** the length of the shortest word from each state to a final one */
+  fn distances_to_final(&self) -> HashMap<&S, usize> {
+    let mut distances: HashMap<&S, usize> = self.final_states.iter().map(|q| (q, 0)).collect();
+    let mut queue: VecDeque<&S> = self.final_states.iter().collect();
+    while let Some(q) = queue.pop_front() {
+      let d = distances[q] + 1;
+      for ((p, phi), target) in &self.transition {
+        if target.contains(q) && !distances.contains_key(p) && phi.satisfiable() {
+          distances.insert(p, d);
+          queue.push_back(p);
+        }
+      }
+    }
+    distances
+  }
+
+  /** a random character of phi, trying printable ASCII ones and then bytes before get_one */
+  fn sample_char(phi: &Predicate<T>, rng: &mut impl Rng) -> Option<T> {
+    const TRIES: usize = 16;
+    (0..2 * TRIES)
+      .map(|i| match i < TRIES {
+        true => 0x20 + rng.below(0x5f) as u8,
+        false => rng.below(0x100) as u8,
+      })
+      .map(|b| T::from(b as char))
+      .find(|c| phi.denote(c))
+      .or_else(|| phi.clone().get_one().ok())
+  }
+
+  /**
+   * equivalent automaton with merged states, renumbered in the breadth-first order,
+   * whose transitions are unions of maximal byte ranges.
+   * equivalent deterministic automata become equal (see PartialEq) after this.
+   * the alphabet is assumed to be bytes like get_one does,
+   * and characters beyond them behave like the last byte.
+   */
+  pub fn canonicalize(&self) -> Self {
+    let mut sfa = self.clone();
+    sfa.minimize();
+    let bytes: Vec<T> = (0..=u8::MAX).map(|i| (i as char).into()).collect();
+
+    let successors: HashMap<&S, Vec<Vec<&S>>> = sfa
+      .states
+      .iter()
+      .map(|p| {
+        let successors = bytes
+          .iter()
+          .map(|c| {
+            sfa
+              .transition
+              .iter()
+              .filter(|((q, phi), _)| q == p && phi.denote(c))
+              .flat_map(|(_, target)| target)
+              .collect()
+          })
+          .collect();
+        (p, successors)
+      })
+      .collect();
+
+    /* refine classes of states by the classes of successors until it gets stable */
+    let mut classes: HashMap<&S, usize> = sfa
+      .states
+      .iter()
+      .map(|p| (p, sfa.final_states.contains(p) as usize))
+      .collect();
+    let mut size = 0;
+    loop {
+      let mut signatures = HashMap::new();
+      let refined = sfa
+        .states
+        .iter()
+        .map(|p| {
+          let signature = (
+            classes[p],
+            successors[p]
+              .iter()
+              .map(|targets| targets.iter().map(|q| classes[*q]).collect::<BTreeSet<_>>())
+              .collect::<Vec<_>>(),
+          );
+          let n = signatures.len();
+          (p, *signatures.entry(signature).or_insert(n))
+        })
+        .collect();
+      classes = refined;
+      if signatures.len() == size {
+        break;
+      }
+      size = signatures.len();
+    }
+
+    let representatives: HashMap<usize, &S> = classes.iter().map(|(p, c)| (*c, *p)).collect();
+    let initial_state = S::new();
+    let mut states = HashMap::from([(classes[&sfa.initial_state], S::clone(&initial_state))]);
+    let mut queue = VecDeque::from([classes[&sfa.initial_state]]);
+    let mut final_states = HashSet::new();
+    let mut transition = HashMap::new();
+
+    while let Some(c) = queue.pop_front() {
+      let p = representatives[&c];
+      let source = S::clone(&states[&c]);
+      if sfa.final_states.contains(p) {
+        final_states.insert(S::clone(&source));
+      }
+
+      /* bytes grouped by the classes of targets, in the order of their first appearance */
+      let mut groups: Vec<(usize, Vec<usize>)> = vec![];
+      for (i, targets) in successors[p].iter().enumerate() {
+        let targets: BTreeSet<_> = targets.iter().map(|q| classes[*q]).collect();
+        for t in targets {
+          match groups.iter_mut().find(|(u, _)| *u == t) {
+            Some((_, indices)) => indices.push(i),
+            None => groups.push((t, vec![i])),
+          }
+        }
+      }
+
+      for (t, indices) in groups {
+        let target = S::clone(states.entry(t).or_insert_with(|| {
+          queue.push_back(t);
+          S::new()
+        }));
+        transition
+          .entry((S::clone(&source), Self::byte_ranges(&bytes, &indices)))
+          .or_insert_with(Vec::new)
+          .push(target);
+      }
+    }
+
+    SymFa {
+      states: states.into_values().collect(),
+      initial_state,
+      final_states,
+      transition,
+    }
+  }
+
+  /** union of maximal ranges of the indexed bytes, left open at the ends of bytes */
+  fn byte_ranges(bytes: &[T], indices: &[usize]) -> Predicate<T> {
+    let mut predicate = Predicate::bot();
+    let mut i = 0;
+    while i < indices.len() {
+      let mut j = i;
+      while j + 1 < indices.len() && indices[j + 1] == indices[j] + 1 {
+        j += 1;
+      }
+      let left = (indices[i] != 0).then(|| T::clone(&bytes[indices[i]]));
+      let right = bytes.get(indices[j] + 1).cloned();
+      predicate = predicate.or(&Predicate::range(left, right));
+      i = j + 1;
+    }
+    predicate
+  }
 }
 impl<D, B, S> Recognizable<D> for SymFa<D, B, S>
 where
@@ -952,8 +2319,71 @@ where
   state::macros::impl_state_machine!(states, initial_state, final_states, transition);
 }
 
+/** number of words in a language */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Cardinality {
+  Finite(u128),
+  Infinite,
+}
+impl Display for Cardinality {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Cardinality::Finite(n) => write!(f, "{}", n),
+      Cardinality::Infinite => f.write_str("infinite"),
+    }
+  }
+}
+
 pub type Sfa<T, S> = SymFa<T, Predicate<T>, S>;
 
+/**
+ * states of an automaton traced back to the states of the automata it is the product of,
+ * each with the label (e.g. the assertion) of the automaton it belongs to.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance<S: State, L> {
+  origins: HashMap<S, Vec<(L, S)>>,
+}
+impl<S: State, L: Clone> Provenance<S, L> {
+  /** each state of sfa originates from itself in the automaton labeled by label */
+  pub fn of<D, B>(sfa: &SymFa<D, B, S>, label: L) -> Self
+  where
+    D: Domain,
+    B: BoolAlg<Domain = D>,
+  {
+    Provenance {
+      origins: sfa
+        .states
+        .iter()
+        .map(|s| (S::clone(s), vec![(L::clone(&label), S::clone(s))]))
+        .collect(),
+    }
+  }
+
+  /** origins of the state, which are empty for unknown ones */
+  pub fn origins(&self, state: &S) -> &[(L, S)] {
+    self
+      .origins
+      .get(state)
+      .map(|origins| origins.as_slice())
+      .unwrap_or(&[])
+  }
+
+  /** labels of the automata the state originates from, without duplication */
+  pub fn labels(&self, state: &S) -> Vec<&L>
+  where
+    L: PartialEq,
+  {
+    let mut labels = vec![];
+    for (label, _) in self.origins(state) {
+      if !labels.contains(&label) {
+        labels.push(label);
+      }
+    }
+    labels
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::super::regex::Regex;
@@ -1291,6 +2721,402 @@ mod tests {
     }
   }
 
+  #[test]
+  fn finite_language() {
+    let sfa = Reg::seq("ab").or(Reg::seq("c")).to_sfa::<StateImpl>();
+    assert!(sfa.is_finite());
+    let mut words: Vec<String> = sfa
+      .enumerate_paths()
+      .unwrap()
+      .into_iter()
+      .map(|word| {
+        word
+          .into_iter()
+          .map(|phi| -> char { phi.get_one().unwrap().into() })
+          .collect()
+      })
+      .collect();
+    words.sort();
+    assert_eq!(words, vec!["ab", "c"]);
+
+    let sfa = Reg::seq("ab").star().to_sfa::<StateImpl>();
+    assert!(!sfa.is_finite());
+    assert_eq!(sfa.enumerate_paths(), None);
+
+    let sfa = Reg::seq("ab")
+      .star()
+      .inter(Reg::seq("ab"))
+      .to_sfa::<StateImpl>();
+    assert!(sfa.is_finite());
+    assert_eq!(sfa.enumerate_paths().unwrap().len(), 1);
+
+    let sfa = Reg::Empty.to_sfa::<StateImpl>();
+    assert!(sfa.is_finite());
+    assert_eq!(sfa.enumerate_paths(), Some(vec![]));
+  }
+
+  #[test]
+  fn enumerate() {
+    let words = |sfa: &Sfa<CharWrap, StateImpl>, max_len| {
+      sfa
+        .enumerate(max_len)
+        .map(|word| word.into_iter().map(|c| -> char { c.into() }).collect())
+        .collect::<Vec<String>>()
+    };
+
+    let sfa = Reg::seq("ab").star().concat(Reg::seq("c")).to_sfa();
+    assert_eq!(words(&sfa, 5), vec!["c", "abc", "ababc"]);
+    assert!(words(&sfa, 0).is_empty());
+
+    /* each word once, though some have several runs */
+    let sfa = Reg::seq("a").or(Reg::seq("ab")).star().to_sfa();
+    let mut found = words(&sfa, 2);
+    assert!(found.windows(2).all(|w| w[0].len() <= w[1].len()));
+    found.sort();
+    assert_eq!(found, vec!["", "a", "aa", "ab"]);
+    assert_eq!(sfa.enumerate(usize::MAX).nth(1).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn cardinality() {
+    let sfa = Reg::seq("ab").or(Reg::seq("c")).to_sfa::<StateImpl>();
+    assert_eq!(sfa.cardinality(), Cardinality::Finite(2));
+    assert_eq!(sfa.words_per_length(3), vec![0, 1, 1, 0]);
+
+    let sfa = Reg::seq("a").or(Reg::seq("b")).star().to_sfa::<StateImpl>();
+    assert_eq!(sfa.cardinality(), Cardinality::Infinite);
+    assert_eq!(sfa.words_per_length(3), vec![1, 2, 4, 8]);
+
+    /* "ab" has two runs but is counted once */
+    let sfa = Reg::Or(vec![Reg::seq("ab"), Reg::seq("a").concat(Reg::All)]).to_sfa::<StateImpl>();
+    let all = Predicate::<CharWrap>::all_char().cardinality() as u128;
+    assert_eq!(sfa.cardinality(), Cardinality::Finite(all));
+  }
+
+  #[test]
+  fn equality_up_to_renaming() {
+    use crate::util::hash_of;
+
+    let sfa1 = Reg::seq("ab").star().to_sfa::<StateImpl>();
+    let sfa2 = Reg::seq("ab").star().to_sfa::<StateImpl>();
+    assert_eq!(sfa1, sfa2);
+    assert_eq!(hash_of(&sfa1), hash_of(&sfa2));
+    assert_ne!(sfa1, Reg::seq("ba").star().to_sfa::<StateImpl>());
+    assert_ne!(sfa1, Reg::seq("ab").plus().to_sfa::<StateImpl>());
+
+    let set = HashSet::from([sfa1, sfa2]);
+    assert_eq!(set.len(), 1);
+  }
+
+  #[test]
+  fn witness() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("ab").or(Reg::seq("c").star()).to_sfa::<S>();
+    assert!(!sfa.is_empty());
+    let word = sfa.witness().unwrap();
+    assert!(sfa.run(&word));
+
+    let sfa = Reg::seq("ab")
+      .to_sfa::<S>()
+      .concat(Reg::range(Some('x'), Some('z')).to_sfa());
+    assert_eq!(
+      sfa.witness().map(|word| word[..2].to_vec()),
+      Some(chars("ab"))
+    );
+    assert!(sfa.run(&sfa.witness().unwrap()));
+
+    let empty = Reg::seq("a").to_sfa::<S>().inter(Reg::seq("b").to_sfa());
+    assert!(empty.is_empty());
+    assert_eq!(empty.witness(), None);
+  }
+
+  #[test]
+  fn shortest_witness() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("abcd")
+      .or(Reg::seq("ab").star().concat(Reg::seq("c")))
+      .to_sfa::<S>();
+    assert_eq!(sfa.shortest_witness(), Some(chars("c")));
+
+    let sfa = Reg::seq("xyz").concat(Reg::seq("ab").star()).to_sfa::<S>();
+    assert_eq!(sfa.shortest_witness(), Some(chars("xyz")));
+    assert_eq!(sfa.shortest_path().map(|path| path.len()), Some(3));
+
+    let empty = Reg::seq("a").to_sfa::<S>().inter(Reg::seq("b").to_sfa());
+    assert_eq!(empty.shortest_witness(), None);
+  }
+
+  #[test]
+  fn transitions() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("ab").or(Reg::seq("ac")).to_sfa::<S>();
+    let total: usize = sfa.transition.values().map(|target| target.len()).sum();
+    assert_eq!(sfa.transitions().count(), total);
+
+    let from_initial: Vec<_> = sfa.predicates_from(&sfa.initial_state).collect();
+    assert!(!from_initial.is_empty());
+    for (p, phi, q) in from_initial {
+      assert_eq!(p, &sfa.initial_state);
+      assert!(phi.denote(&CharWrap::from('a')));
+      assert!(sfa
+        .edges_between(&sfa.initial_state, q)
+        .any(|(_, psi, _)| psi == phi));
+    }
+    let finals: Vec<_> = sfa.final_states.iter().collect();
+    assert_eq!(sfa.edges_between(&sfa.initial_state, finals[0]).count(), 0);
+  }
+
+  #[test]
+  fn to_dot() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("ab").to_sfa::<S>();
+    let dot = sfa.to_dot();
+    assert!(dot.starts_with("digraph sfa {"));
+    assert!(dot.contains(&format!("start -> {};", sfa.initial_state)));
+    for q in &sfa.final_states {
+      assert!(dot.contains(&format!("  q{} [shape=doublecircle];", q.id())));
+    }
+    assert_eq!(dot.matches("doublecircle").count(), 1);
+    assert_eq!(dot.matches(" -> q").count(), 3);
+    assert!(dot.contains(r#"label="'a'""#));
+
+    let dot = Reg::range(Some('a'), Some('{'))
+      .or(Reg::seq("_"))
+      .to_sfa::<S>()
+      .to_dot();
+    assert!(dot.contains("['a'-'z']"));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn serde_round_trip() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("ab")
+      .star()
+      .concat(Reg::range(Some('x'), Some('{')))
+      .to_sfa::<S>();
+    let json = serde_json::to_string(&sfa).unwrap();
+    let loaded: Sfa<CharWrap, S> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.states, sfa.states);
+    assert_eq!(loaded.transition, sfa.transition);
+    assert!(loaded.run(&chars("ababz")));
+    assert!(!loaded.run(&chars("aba")));
+    /* new states are numbered after the loaded ones */
+    assert!(!loaded.states.contains(&S::new()));
+
+    /* every transition of all{32} refers to the one pooled all_char */
+    let all = Reg::all().repeat(32, Some(32)).to_sfa::<S>();
+    let json = serde_json::to_string(&all).unwrap();
+    let pairs = serde_json::to_string(&all.transition.iter().collect::<Vec<_>>()).unwrap();
+    assert!(json.len() < pairs.len());
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["transition"]["predicates"].as_array().unwrap().len(), 1);
+    let loaded: Sfa<CharWrap, S> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, all);
+    assert!(serde_json::from_str::<Sfa<CharWrap, S>>(&json.replace(",0,", ",1,")).is_err());
+
+    let reg = Reg::seq("ab").or(Reg::range(None, Some('0')).plus());
+    let json = serde_json::to_string(&reg).unwrap();
+    assert_eq!(serde_json::from_str::<Reg>(&json).unwrap(), reg);
+  }
+
+  #[test]
+  fn lengths() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("ab").star().concat(Reg::seq("c")).to_sfa::<S>();
+    assert_eq!(sfa.lengths(8), vec![1, 3, 5, 7]);
+    assert_eq!(Reg::seq("abc").to_sfa::<S>().lengths(8), vec![3]);
+    assert!(Reg::seq("abc").to_sfa::<S>().lengths(2).is_empty());
+
+    assert_eq!(sfa.length_abstraction(8), Some(vec![(1, 2)]));
+    assert_eq!(Reg::seq("abc").to_sfa::<S>().length_abstraction(8), Some(vec![(3, 0)]));
+    let sfa = Reg::seq("a").concat(Reg::seq("aaa").star()).to_sfa::<S>();
+    assert_eq!(sfa.length_abstraction(8), Some(vec![(1, 0), (4, 3)]));
+    let sfa = Reg::seq("aa").or(Reg::seq("aaaaa").star()).to_sfa::<S>();
+    assert_eq!(sfa.length_abstraction(8), Some(vec![(0, 0), (2, 0), (5, 5)]));
+    assert_eq!(sfa.length_abstraction(2), None);
+  }
+
+  #[test]
+  fn inter_with_provenance() {
+    type S = StateImpl;
+
+    let ab_star = Reg::seq("ab").star().to_sfa::<S>();
+    let a_b = Reg::element('a').concat(Reg::all()).star().to_sfa::<S>();
+    let (left, right) = (
+      Provenance::of(&ab_star, "left"),
+      Provenance::of(&a_b, "right"),
+    );
+    let (product, provenance) = ab_star
+      .clone()
+      .inter_with_provenance(&left, a_b.clone(), &right);
+    assert_eq!(product, ab_star.clone().inter(a_b.clone()));
+
+    for s in &product.states {
+      let origins = provenance.origins(s);
+      assert_eq!(origins.len(), 2);
+      assert!(ab_star.states.contains(&origins[0].1));
+      assert!(a_b.states.contains(&origins[1].1));
+      assert_eq!(provenance.labels(s), vec![&"left", &"right"]);
+    }
+    assert_eq!(
+      provenance.origins(&product.initial_state),
+      &[
+        ("left", S::clone(&ab_star.initial_state)),
+        ("right", S::clone(&a_b.initial_state))
+      ]
+    );
+    assert!(provenance.origins(&S::new()).is_empty());
+
+    let dot = product.to_dot_with_provenance(&provenance);
+    assert_eq!(dot.matches("xlabel").count(), product.states.len());
+    assert!(dot.contains("@left, q"));
+  }
+
+  #[test]
+  fn or_shares_subautomata() {
+    type S = StateImpl;
+
+    let abc = Reg::seq("abc").to_sfa::<S>();
+    assert_eq!(abc.clone().or(abc.clone()), abc);
+
+    /* abc is the sub-automaton after x */
+    let xabc = Reg::seq("xabc").to_sfa::<S>();
+    let union = xabc.clone().or(abc.clone());
+    assert_eq!(union.states.len(), xabc.states.len());
+    assert_eq!(abc.clone().or(xabc), union);
+    for (case, expected) in [("abc", true), ("xabc", true), ("xab", false), ("x", false)] {
+      assert_eq!(union.run(&chars(case)), expected);
+    }
+
+    /* nothing is shared */
+    let union = abc.clone().or(Reg::seq("xyz").to_sfa());
+    assert_eq!(union.states.len(), 7);
+    assert!(union.run(&chars("xyz")) && union.run(&chars("abc")));
+  }
+
+  #[test]
+  fn or_all_shares_suffixes() {
+    type S = StateImpl;
+
+    let words: Vec<String> = (0..40).map(|i| format!("w{}z", i)).collect();
+    let sfas = || words.iter().map(|w| Reg::seq(w).to_sfa::<S>());
+    let chain = sfas().reduce(|res, sfa| res.or(sfa)).unwrap();
+    let union = SymFa::or_all(sfas().collect());
+
+    /* the states after the digits, and before z, are shared by all the words */
+    assert!(union.states.len() * 2 < chain.states.len());
+    for w in &words {
+      assert!(union.run(&chars(w)));
+    }
+    for case in ["w40z", "wz", "w1", "w1zz", "z"] {
+      assert!(!union.run(&chars(case)));
+    }
+    assert!(!SymFa::or_all(vec![Reg::seq("").to_sfa::<S>(), union]).run(&chars("w")));
+  }
+
+  #[test]
+  fn canonicalize() {
+    type S = StateImpl;
+    let (a, b) = (Predicate::char(CharWrap::from('a')), Predicate::char(CharWrap::from('b')));
+
+    /* (ab)* with redundant states */
+    let sfa1 = super::super::macros::sfa! {
+      { q0, q1, q2, q3 },
+      {
+        -> q0,
+        (q0, a.clone()) -> [q1],
+        (q1, b.clone()) -> [q2],
+        (q2, a.clone()) -> [q3],
+        (q3, b.clone()) -> [q2]
+      },
+      { q0, q2 }
+    };
+    let sfa2 = super::super::macros::sfa! {
+      { q0, q1 },
+      { -> q0, (q0, a.clone()) -> [q1], (q1, b.clone()) -> [q0] },
+      { q0 }
+    };
+    assert_ne!(sfa1, sfa2);
+    let canonical = sfa1.canonicalize();
+    assert_eq!(canonical, sfa2.canonicalize());
+    assert_eq!(canonical.states.len(), 2);
+    for (case, expected) in [("", true), ("abab", true), ("aba", false), ("ba", false)] {
+      assert_eq!(canonical.run(&chars(case)), expected);
+    }
+
+    let sfa1 = Reg::seq("a").or(Reg::seq("b")).to_sfa::<S>();
+    let sfa2 = super::super::macros::sfa! {
+      { q0, q1 },
+      { -> q0, (q0, Predicate::range(Some('a'.into()), Some('c'.into()))) -> [q1] },
+      { q1 }
+    };
+    assert_eq!(sfa1.canonicalize(), sfa2.canonicalize());
+
+    let all = Reg::all().star().to_sfa::<S>().canonicalize();
+    assert!(all.run(&chars("x$")));
+    assert!(all.run(&chars("")));
+    assert_eq!(all.states.len(), 1);
+  }
+
+  #[test]
+  fn pooled() {
+    let sfa = Reg::all()
+      .star()
+      .concat(Reg::seq("a"))
+      .concat(Reg::all().star())
+      .to_sfa::<StateImpl>();
+    let (pool, transition) = sfa.pooled();
+    assert!(pool.len() < transition.len());
+    assert_eq!(
+      pool
+        .iter()
+        .filter(|phi| **phi == Predicate::all_char())
+        .count(),
+      1
+    );
+
+    let restored = Sfa::from_pooled(
+      sfa.states.clone(),
+      sfa.initial_state.clone(),
+      sfa.final_states.clone(),
+      &pool,
+      transition,
+    );
+    assert_eq!(restored, sfa);
+  }
+
+  #[test]
+  #[cfg(feature = "parallel")]
+  fn accepted_path_parallel() {
+    let sfa = Reg::all()
+      .star()
+      .concat(Reg::seq("abc"))
+      .or(Reg::seq("xy").plus())
+      .to_sfa::<StateImpl>();
+    let witness = sfa.accepted_path_parallel(1).unwrap();
+    assert_eq!(witness.len(), 2);
+    for threads in [2, 4, 8] {
+      assert_eq!(sfa.accepted_path_parallel(threads), Some(witness.clone()));
+    }
+    let word: Vec<_> = witness
+      .into_iter()
+      .map(|phi| phi.get_one().unwrap())
+      .collect();
+    assert!(sfa.run(&word));
+
+    assert_eq!(
+      Reg::Empty.to_sfa::<StateImpl>().accepted_path_parallel(4),
+      None
+    );
+  }
+
   #[test]
   fn create_sfa_and_minimize() {
     let mut transition = HashMap::new();
@@ -1527,6 +3353,161 @@ mod tests {
     }
   }
 
+  #[test]
+  fn determinize() {
+    type S = StateImpl;
+
+    let reg = Reg::seq("a")
+      .or(Reg::seq("ab"))
+      .star()
+      .concat(Reg::range(Some('a'), Some('c')));
+    let sfa = reg.to_sfa::<S>();
+    assert!(!sfa.is_deterministic());
+    let dfa = sfa.clone().determinize();
+    assert!(dfa.is_deterministic());
+    for word in ["a", "b", "aa", "ab", "aab", "abb", "abab", "ba", "", "c"] {
+      assert_eq!(dfa.run(&chars(word)), sfa.run(&chars(word)), "{:?}", word);
+    }
+    assert_eq!(dfa.clone().determinize().states.len(), dfa.states.len());
+    assert!(Reg::Empty.to_sfa::<S>().determinize().is_empty());
+  }
+
+  #[test]
+  fn complement() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("a")
+      .or(Reg::seq("ab"))
+      .star()
+      .concat(Reg::range(Some('a'), Some('c')))
+      .to_sfa::<S>();
+    let complete = sfa.clone().determinize().complete();
+    assert!(complete.is_deterministic());
+    for state in complete.states() {
+      let missing = complete.state_predicate(state).not();
+      assert!(!missing.and(&Predicate::all_char()).satisfiable());
+    }
+
+    /* sfa is nondeterministic, so flipping its final states alone is unsound */
+    let not = sfa.clone().complement();
+    for word in ["a", "b", "aa", "ab", "aab", "abb", "abab", "ba", "", "c"] {
+      assert_eq!(not.run(&chars(word)), !sfa.run(&chars(word)), "{:?}", word);
+    }
+    assert!(sfa.clone().complement().complement().run(&chars("abb")));
+    assert!(Reg::Empty.to_sfa::<S>().complement().run(&chars("abc")));
+    assert!(!Reg::Empty.to_sfa::<S>().complement().run(&chars("a#c")));
+
+    /* the words with the separator aren't in the complement of the universal language */
+    let all = Reg::All.star().to_sfa::<S>().complement();
+    assert!(all.is_empty());
+  }
+
+  #[test]
+  fn repeat() {
+    type S = StateImpl;
+
+    /* copies accepting the empty word end where the previous ones do */
+    let sfa = Reg::element('a')
+      .or(Reg::Epsilon)
+      .concat(Reg::element('b'))
+      .to_sfa::<S>()
+      .repeat(1, Some(3));
+    for (word, expected) in [
+      ("", false),
+      ("b", true),
+      ("abb", true),
+      ("ababab", true),
+      ("bbb", true),
+      ("bbbb", false),
+      ("aa", false),
+    ] {
+      assert_eq!(sfa.run(&chars(word)), expected, "{:?}", word);
+    }
+    let sfa = Reg::seq("ab").to_sfa::<S>().repeat(2, None);
+    assert!(!sfa.run(&chars("ab")));
+    assert!(sfa.run(&chars(&"ab".repeat(5))));
+    assert!(Reg::seq("ab").to_sfa::<S>().repeat(0, Some(0)).run(&chars("")));
+
+    /* the chain is built at once and minimized only once, so long ones are cheap */
+    let sfa = Reg::element('a').to_sfa::<S>().repeat(1000, Some(1000));
+    assert_eq!(sfa.states().len(), 1001);
+    assert!(sfa.run(&chars(&"a".repeat(1000))));
+    assert!(!sfa.run(&chars(&"a".repeat(999))));
+  }
+
+  #[test]
+  fn within_edit_distance() {
+    type S = StateImpl;
+
+    let sfa = Sfa::<CharWrap, S>::within_edit_distance(&chars("abc"), 1);
+    for word in [
+      "abc", "ab", "bc", "ac", "xabc", "abxc", "abcx", "xbc", "abx",
+    ] {
+      assert!(sfa.run(&chars(word)), "{:?}", word);
+    }
+    for word in ["", "a", "ba", "cba", "xxabc", "axbxc", "abcxx"] {
+      assert!(!sfa.run(&chars(word)), "{:?}", word);
+    }
+
+    let exact = Sfa::<CharWrap, S>::within_edit_distance(&chars("ab"), 0);
+    assert_eq!(exact.lengths(8), vec![2]);
+    assert!(exact.run(&chars("ab")));
+    let any = Sfa::<CharWrap, S>::within_edit_distance(&[], 2);
+    assert_eq!(any.lengths(8), vec![0, 1, 2]);
+  }
+
+  #[test]
+  fn closures() {
+    type S = StateImpl;
+
+    let sfa = Reg::seq("ab").star().concat(Reg::seq("c")).to_sfa::<S>();
+    let prefixes = sfa.clone().prefix_closure();
+    for word in ["", "a", "ab", "aba", "abc", "ababc"] {
+      assert!(prefixes.run(&chars(word)), "{:?}", word);
+    }
+    for word in ["b", "ac", "abcc", "abca"] {
+      assert!(!prefixes.run(&chars(word)), "{:?}", word);
+    }
+
+    let suffixes = sfa.suffix_closure();
+    for word in ["", "c", "bc", "abc", "babc", "ababc"] {
+      assert!(suffixes.run(&chars(word)), "{:?}", word);
+    }
+    for word in ["a", "ab", "cc", "bbc", "aabc"] {
+      assert!(!suffixes.run(&chars(word)), "{:?}", word);
+    }
+
+    let empty = Reg::Empty.to_sfa::<S>();
+    assert!(empty.clone().prefix_closure().is_empty());
+    assert!(empty.suffix_closure().is_empty());
+  }
+
+  #[test]
+  fn sample() {
+    use crate::random::SplitMix64;
+
+    let mut rng = SplitMix64::new(0);
+    let sfa = Reg::seq("ab")
+      .star()
+      .concat(Reg::seq("c"))
+      .to_sfa::<StateImpl>();
+    let mut found = HashSet::new();
+    for _ in 0..200 {
+      let word = sfa.sample(&mut rng, 5).unwrap();
+      assert!(sfa.run(&word), "{:?}", word);
+      found.insert(word);
+    }
+    assert_eq!(found.len(), 3);
+    assert_eq!(sfa.sample(&mut rng, 0), None);
+
+    /* characters are taken at random within predicates */
+    let sfa = Reg::range(Some('a'), Some('{')).to_sfa::<StateImpl>();
+    let chars: HashSet<_> = (0..100)
+      .map(|_| sfa.sample(&mut rng, 1).unwrap()[0])
+      .collect();
+    assert!(chars.len() > 1);
+  }
+
   #[test]
   fn chain() {
     let sfa: SymFa<_, _, StateImpl> = Reg::seq("pre").to_sfa().chain(Reg::seq("suf").to_sfa());
@@ -0,0 +1,217 @@
+use crate::smt2::{Constraint, Smt2, Span};
+use crate::state::{State, StateMachine};
+use crate::util::Domain;
+use crate::{CompiledProblem, SolveReport, SolverResult};
+use std::fmt::Write;
+
+/** a row of the statistics, i.e. an assertion and what it became */
+struct AssertionRow {
+  span: Span,
+  kind: &'static str,
+  var: String,
+  detail: String,
+}
+
+fn html_escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/** the rest of the line of the input from span */
+fn source_at(input: &str, span: Span) -> &str {
+  input
+    .lines()
+    .nth(span.line - 1)
+    .and_then(|line| line.get(span.column - 1..))
+    .unwrap_or("")
+    .trim_end()
+}
+
+fn assertion_rows<D: Domain, S: State>(problem: &Smt2<D, S>) -> Vec<AssertionRow> {
+  let vars = problem.vars();
+  let mut rows = vec![];
+  for sl_cons in problem.sl_constraints() {
+    rows.push(AssertionRow {
+      span: sl_cons.span(),
+      kind: "definition",
+      var: vars[sl_cons.idx()].clone(),
+      detail: sl_cons.constraint().function_names().join(", "),
+    });
+  }
+  for reg_cons in problem.reg_constraints() {
+    let sfa = reg_cons.constraint().clone().to_sfa::<S>();
+    rows.push(AssertionRow {
+      span: reg_cons.span(),
+      kind: "membership",
+      var: vars[reg_cons.idx()].clone(),
+      detail: format!("{} states", sfa.states().len()),
+    });
+  }
+  for int_cons in problem.length_constraints() {
+    rows.push(AssertionRow {
+      span: int_cons.span(),
+      kind: "length",
+      var: String::new(),
+      detail: format!("{} unknowns", int_cons.atoms().len()),
+    });
+  }
+  rows.sort_by_key(|row| row.span);
+  rows
+}
+
+/**
+ * machines of the compiled problem in DOT, titled.
+ * ssts are in the order their pre-images are applied.
+ */
+pub fn automata<D: Domain, S: State>(compiled: &CompiledProblem<D, S>) -> Vec<(String, String)> {
+  let mut automata = vec![(String::from("product"), compiled.sfa().to_dot())];
+  for (var, sst) in compiled.elimination_order().iter().zip(compiled.ssts()) {
+    automata.push((format!("definition of {}", var), sst.to_dot()));
+  }
+  automata
+}
+
+/**
+ * a self-contained HTML page with the input, statistics of each assertion,
+ * the automata as DOT source, the result with the model, warnings and the trace.
+ * no layouting is done, so the DOT source is embedded as it is.
+ */
+pub fn html<D: Domain, S: State>(
+  input: &str,
+  problem: &Smt2<D, S>,
+  automata: &[(String, String)],
+  report: &SolveReport,
+) -> String {
+  let mut html = String::new();
+  html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+  html.push_str("<title>solver report</title>\n<style>\n");
+  html.push_str("body { font-family: sans-serif; }\n");
+  html.push_str("table { border-collapse: collapse; }\n");
+  html.push_str("td, th { border: 1px solid #999; padding: 2px 8px; text-align: left; }\n");
+  html.push_str("pre { background: #f4f4f4; padding: 8px; }\n");
+  html.push_str("</style>\n</head>\n<body>\n");
+
+  html.push_str("<h1>Result</h1>\n");
+  let result = match &report.result {
+    SolverResult::Unsat => "unsat",
+    SolverResult::ResourceExhausted(_) | SolverResult::Timeout(_) | SolverResult::Unknown(_) => {
+      "unknown"
+    }
+    _ => "sat",
+  };
+  writeln!(html, "<p id=\"result\">{}</p>", result).unwrap();
+  if let SolverResult::Model(model) = &report.result {
+    let mut model: Vec<_> = model.iter().collect();
+    model.sort();
+    html.push_str("<table>\n<tr><th>variable</th><th>value</th></tr>\n");
+    for (var, value) in model {
+      writeln!(
+        html,
+        "<tr><td>{}</td><td>{:?}</td></tr>",
+        html_escape(var),
+        html_escape(value)
+      )
+      .unwrap();
+    }
+    html.push_str("</table>\n");
+  }
+
+  html.push_str("<h1>Problem</h1>\n");
+  writeln!(html, "<pre>{}</pre>", html_escape(input.trim())).unwrap();
+
+  html.push_str("<h1>Assertions</h1>\n<table>\n");
+  html.push_str(
+    "<tr><th>at</th><th>assertion</th><th>kind</th><th>variable</th><th>statistics</th></tr>\n",
+  );
+  for row in assertion_rows(problem) {
+    writeln!(
+      html,
+      "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+      row.span,
+      html_escape(source_at(input, row.span)),
+      row.kind,
+      html_escape(&row.var),
+      html_escape(&row.detail)
+    )
+    .unwrap();
+  }
+  html.push_str("</table>\n");
+
+  if !report.sliced.is_empty() {
+    writeln!(
+      html,
+      "<p>sliced: {}</p>",
+      html_escape(&report.sliced.join(", "))
+    )
+    .unwrap();
+  }
+
+  html.push_str("<h1>Automata</h1>\n");
+  for (title, dot) in automata {
+    writeln!(
+      html,
+      "<h2>{}</h2>\n<pre class=\"dot\">{}</pre>",
+      html_escape(title),
+      html_escape(dot)
+    )
+    .unwrap();
+  }
+
+  if !report.warnings.is_empty() {
+    html.push_str("<h1>Warnings</h1>\n<ul>\n");
+    for warning in &report.warnings {
+      writeln!(html, "<li>{}</li>", html_escape(&warning.to_string())).unwrap();
+    }
+    html.push_str("</ul>\n");
+  }
+
+  if !report.trace.is_empty() {
+    html.push_str("<h1>Trace</h1>\n<ul>\n");
+    for entry in &report.trace {
+      writeln!(html, "<li>{}</li>", html_escape(&entry.to_string())).unwrap();
+    }
+    html.push_str("</ul>\n");
+  }
+
+  html.push_str("</body>\n</html>\n");
+  html
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{parse, solve_cases, UnconstrainedPolicy};
+
+  #[test]
+  fn html() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "<b>")))
+      (assert (str.in.re x1 (str.to.re "a<b>")))
+      (check-sat)
+      (get-model)
+      "#;
+    let problem = parse(input).unwrap();
+    let mut machines = vec![];
+    let report = solve_cases(
+      problem.clone(),
+      UnconstrainedPolicy::default(),
+      |compiled| machines = automata(compiled),
+    );
+    let html = super::html(input, &problem, &machines, &report);
+
+    assert!(html.contains("<p id=\"result\">sat</p>"));
+    assert!(html.contains("<tr><td>x0</td><td>\"a\"</td></tr>"));
+    assert!(html.contains(
+      "<tr><td>4:7</td><td><code>(assert (= x1 (str.++ x0 &quot;&lt;b&gt;&quot;)))</code></td>\
+       <td>definition</td><td>x1</td><td>str.++</td></tr>"
+    ));
+    assert!(html.contains("<td>5:7</td>"));
+    assert!(html.contains("<h2>product</h2>"));
+    assert!(html.contains("<h2>definition of x1</h2>"));
+    assert!(!html.contains("<b>"));
+  }
+}
@@ -1,53 +1,256 @@
-use crate::boolean_algebra::BoolAlg;
+use crate::boolean_algebra::{
+  ascii::AsciiSet,
+  classes::{ClassSet, Classes, MAX_CLASSES},
+  BoolAlg, Predicate,
+};
+use crate::limits::{ResourceExhausted, STATE_LIMIT};
 use crate::regular::{
-  regex::{self, Regex},
-  symbolic_automata::Sfa,
+  regex::{self, Construction, Regex, RegexParseError, Strategy},
+  symbolic_automata::{Provenance, Sfa, SymFa},
 };
-use crate::state::State;
+use crate::state::{State, StateMachine};
 use crate::transducer::sst_factory::SstBuilder;
 use crate::transducer::{
   term::{OutputComp, VariableImpl},
   transducer::Transducer,
 };
-use crate::util::Domain;
+use crate::util::{dot_escape, Domain};
 use smt2parser::{
-  concrete::{Command, Constant, Identifier, QualIdentifier, Sort, Symbol, SyntaxBuilder, Term},
-  CommandStream, Error as Smt2ParserError, Numeral,
+  concrete::{
+    AttributeValue, Command, Constant, FunctionDec, Identifier, QualIdentifier, SExpr, Sort,
+    Symbol, SyntaxBuilder, Term,
+  },
+  CommandStream, Numeral,
+};
+use std::{
+  collections::{HashMap, HashSet},
+  fmt::{Debug, Display},
 };
-use std::{collections::HashMap, fmt::Debug};
 
 type VarIndex = usize;
 pub type Variables = Vec<String>;
 
+/** memberships estimated to build automata larger than this are warned of */
+pub const SFA_SIZE_THRESHOLD: usize = 1 << 16;
+
+/** Bool variables case_split assigns at most, since the cases are exponential in them */
+pub const MAX_BOOL_VARS: usize = 16;
+
+/** cases case_split enumerates at most, counting the disjuncts of conditional assertions */
+pub const MAX_CASES: usize = 1 << 12;
+
+/** a case of Smt2::case_split, i.e. the assignment to Bool variables and the problem under it */
+pub type Case<D, S> = (Vec<(String, bool)>, Smt2<D, S>);
+
+/** the name of the identifier, where indices and sorts are left to the callers */
 pub fn get_symbol(qi: &QualIdentifier) -> &str {
-  if let QualIdentifier::Simple {
-    identifier: Identifier::Simple {
-      symbol: Symbol(symbol),
-    },
-  } = qi
-  {
-    symbol
-  } else {
-    panic!("Unsupported: {}", qi);
+  match qi {
+    QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. } => {
+      match identifier {
+        Identifier::Simple {
+          symbol: Symbol(symbol),
+        }
+        | Identifier::Indexed {
+          symbol: Symbol(symbol),
+          ..
+        } => symbol,
+      }
+    }
+  }
+}
+
+/** the string as an SMT-LIB literal, escaping quotes and characters out of printable ASCII */
+pub fn string_literal(s: &str) -> String {
+  let mut literal = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => literal.push_str("\"\""),
+      ' '..='~' if c != '\\' => literal.push(c),
+      c => literal.push_str(&format!("\\u{{{:x}}}", c as u32)),
+    }
+  }
+  literal.push('"');
+  literal
+}
+
+fn get_var_from_str(target: &str, vars: &Variables) -> Result<VarIndex, ParseError> {
+  vars
+    .iter()
+    .position(|s| s == target)
+    .ok_or_else(|| ParseError::UnknownVariable {
+      span: Span::default(),
+      name: target.to_owned(),
+    })
+}
+
+fn get_var(qi: &QualIdentifier, vars: &Variables) -> Result<VarIndex, ParseError> {
+  get_var_from_str(get_symbol(qi), vars)
+}
+
+/** an integer unknown, i.e. an Int variable or the length of a String one */
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum IntAtom {
+  Var(VarIndex),
+  Len(VarIndex),
+  /** (str.indexof x s start) of the String variable and the IndexPattern of the index */
+  IndexOf(VarIndex, usize),
+  /** (str.to_int x) of the String variable */
+  ToInt(VarIndex),
+  /** (str.count x s) of the String variable and the pattern s of count_patterns, with extensions */
+  Count(VarIndex, usize),
+}
+impl IntAtom {
+  /** the String variable the atom depends on */
+  pub fn string_var(&self) -> Option<VarIndex> {
+    match self {
+      IntAtom::Var(_) => None,
+      IntAtom::Len(idx)
+      | IntAtom::IndexOf(idx, _)
+      | IntAtom::ToInt(idx)
+      | IntAtom::Count(idx, _) => Some(*idx),
+    }
+  }
+
+  fn renumber(&self, map: &[VarIndex]) -> Self {
+    match self {
+      IntAtom::Var(idx) => IntAtom::Var(*idx),
+      IntAtom::Len(idx) => IntAtom::Len(map[*idx]),
+      IntAtom::IndexOf(idx, pattern) => IntAtom::IndexOf(map[*idx], *pattern),
+      IntAtom::ToInt(idx) => IntAtom::ToInt(map[*idx]),
+      IntAtom::Count(idx, pattern) => IntAtom::Count(map[*idx], *pattern),
+    }
+  }
+}
+
+/** decimal digits */
+fn digit<D: Domain>() -> Regex<D> {
+  Regex::inclusive_range('0', '9')
+}
+
+/** words whose str.to_int is the value, i.e. the decimal with leading zeros, or non-numerals for -1 */
+pub fn to_int_regex<D: Domain>(value: i64) -> Regex<D> {
+  match value {
+    -1 => digit().plus().not(),
+    value if value >= 0 => Regex::element('0')
+      .star()
+      .concat(Regex::seq(&value.to_string())),
+    _ => Regex::Empty,
+  }
+}
+
+/**
+ * words with exactly value occurrences of the pattern, i.e. (avoiding pattern)*value avoiding.
+ * the pattern must be nonempty and border-free so that occurrences never overlap.
+ */
+pub fn count_regex<D: Domain>(pattern: &str, value: i64) -> Regex<D> {
+  if value < 0 {
+    return Regex::Empty;
   }
+  let any = || Regex::all().star();
+  let avoiding = || any().concat(Regex::seq(pattern)).concat(any()).not();
+  (0..value).fold(avoiding(), |reg, _| {
+    avoiding().concat(Regex::seq(pattern)).concat(reg)
+  })
+}
+
+/** whether no proper prefix of the nonempty pattern is also its suffix */
+fn is_border_free(pattern: &str) -> bool {
+  let chars: Vec<char> = pattern.chars().collect();
+  !chars.is_empty() && (1..chars.len()).all(|len| chars[..len] != chars[chars.len() - len..])
+}
+
+/** words str.from_int gives for non-negative integers, i.e. decimals without leading zeros */
+fn from_int_regex<D: Domain>() -> Regex<D> {
+  Regex::element('0').or(Regex::inclusive_range('1', '9').concat(digit().star()))
+}
+
+/** the constant arguments of str.indexof, i.e. the searched string and where to start */
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct IndexPattern {
+  pub pattern: String,
+  pub start: usize,
 }
+impl IndexPattern {
+  /** words where the index of the pattern is the value, following SMT-LIB's str.indexof */
+  pub fn regex<D: Domain>(&self, value: i64) -> Regex<D> {
+    let exactly = |n: usize| (0..n).fold(Regex::Epsilon, |reg, _| reg.concat(Regex::all()));
+    let any = || Regex::all().star();
+    let pattern = || Regex::seq(&self.pattern);
+    /* words not containing the pattern */
+    let avoiding = || any().concat(pattern()).concat(any()).not();
 
-fn get_var_from_str(target: &str, vars: &Variables) -> VarIndex {
-  if let Some(idx) = vars.iter().position(|s| s == target) {
-    idx
-  } else {
-    panic!("Variable not found")
+    if value == -1 {
+      /* shorter than start, or no occurrence from start */
+      let shorter = if self.start == 0 {
+        Regex::Empty
+      } else {
+        (1..self.start).fold(Regex::Epsilon, |reg, _| {
+          reg.concat(Regex::Epsilon.or(Regex::all()))
+        })
+      };
+      return if self.pattern.is_empty() {
+        shorter
+      } else {
+        shorter.or(exactly(self.start).concat(avoiding()))
+      };
+    }
+    if value < self.start as i64 {
+      return Regex::Empty;
+    }
+    let value = value as usize;
+    if self.pattern.is_empty() {
+      return if value == self.start {
+        exactly(self.start).concat(any())
+      } else {
+        Regex::Empty
+      };
+    }
+    /* the first occurrence from start ends the segment, so no other one is before it */
+    let segment = exactly(value - self.start)
+      .concat(pattern())
+      .inter(avoiding().concat(Regex::all()));
+    exactly(self.start).concat(segment).concat(any())
   }
 }
 
-fn get_var(qi: &QualIdentifier, vars: &Variables) -> VarIndex {
-  get_var_from_str(&get_symbol(qi), vars)
+/** the value of a term over a model, printed in SMT-LIB syntax */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Value {
+  Str(String),
+  Int(i64),
+  Bool(bool),
+}
+impl Display for Value {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Value::Str(s) => f.write_str(&string_literal(s)),
+      Value::Int(n) if *n < 0 => write!(f, "(- {})", n.unsigned_abs()),
+      Value::Int(n) => write!(f, "{}", n),
+      Value::Bool(b) => write!(f, "{}", b),
+    }
+  }
 }
 
+/** coefficient times atom, or a constant without atom */
 #[derive(Debug, PartialEq, Clone)]
 pub struct LinearTerm {
-  var: Option<usize>,
-  coefficient: Numeral,
+  atom: Option<IntAtom>,
+  coefficient: i64,
+}
+impl LinearTerm {
+  pub fn atom(&self) -> Option<IntAtom> {
+    self.atom
+  }
+
+  pub fn coefficient(&self) -> i64 {
+    self.coefficient
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Relation {
+  Eq,
+  Le,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -56,11 +259,66 @@ pub enum ReplaceTarget {
   Var(VarIndex),
 }
 impl ReplaceTarget {
-  fn from(term: &Term, vars: &Variables) -> Self {
+  fn from(term: &Term, vars: &Variables) -> Result<Self, ParseError> {
+    match term {
+      Term::Constant(Constant::String(s)) => Ok(ReplaceTarget::Str(s.clone())),
+      Term::QualIdentifier(qi) => Ok(ReplaceTarget::Var(get_var(qi, vars)?)),
+      _ => Err(ParseError::unsupported(format!("replacement {}", term))),
+    }
+  }
+}
+
+/** assignments to Int variables of abstracted integer arguments Smt2::check_abstracted tries at most */
+const INDEX_ASSIGNMENTS: usize = 1 << 12;
+
+/**
+ * an integer argument, which is abstracted away unless it's a constant.
+ * constants over STATE_LIMIT are abstracted too, since their machines would have as many states.
+ */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum IntArg {
+  Const(usize),
+  Symbolic(String),
+}
+impl IntArg {
+  fn from(term: &Term) -> Self {
+    match term {
+      Term::Constant(Constant::Numeral(n)) => n
+        .to_string()
+        .parse()
+        .ok()
+        .filter(|n| *n <= STATE_LIMIT)
+        .map_or_else(|| IntArg::Symbolic(n.to_string()), IntArg::Const),
+      term => IntArg::Symbolic(format!("{}", term)),
+    }
+  }
+
+  pub fn constant(&self) -> Option<usize> {
+    match self {
+      IntArg::Const(n) => Some(*n),
+      IntArg::Symbolic(_) => None,
+    }
+  }
+
+  /** value in a model of Int variables, where symbolic ones out of it are 0 */
+  fn value(&self, ints: &HashMap<String, i64>) -> i64 {
+    match self {
+      IntArg::Const(n) => *n as i64,
+      IntArg::Symbolic(name) => ints.get(name).copied().unwrap_or(0),
+    }
+  }
+
+  /** whether the term is a negative constant such as (- 1), out of any string */
+  fn is_negative(term: &Term) -> bool {
     match term {
-      Term::Constant(Constant::String(s)) => ReplaceTarget::Str(s.clone()),
-      Term::QualIdentifier(qi) => ReplaceTarget::Var(get_var(qi, vars)),
-      _ => panic!("Unexpected Input"),
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } => {
+        get_symbol(qual_identifier) == "-"
+          && matches!(&arguments[..], [Term::Constant(Constant::Numeral(n))] if n.to_string() != "0")
+      }
+      _ => false,
     }
   }
 }
@@ -72,6 +330,10 @@ pub enum TransductionOp<T: Domain, S: State> {
   Str(String),
   Replace(VarIndex, Regex<T>, ReplaceTarget),
   ReplaceAll(VarIndex, Regex<T>, ReplaceTarget),
+  /** (str.replace x word target), replacing only the first occurrence of word */
+  ReplaceFirst(VarIndex, String, ReplaceTarget),
+  /** (str.substr x start len) */
+  Substr(VarIndex, IntArg, IntArg),
   #[allow(dead_code)]
   UserDef(Transducer<T, S>),
 }
@@ -83,69 +345,239 @@ impl<D: Domain, S: State> Transduction<D, S> {
     Self(vec![])
   }
 
-  pub fn from(term: &Term, vars: &Variables) -> Self {
-    match term {
-      Term::QualIdentifier(qi) => Transduction(vec![TransductionOp::Var(get_var(qi, vars))]),
-      Term::Constant(Constant::String(s)) => Transduction(vec![TransductionOp::Str(s.clone())]),
+  pub fn from(term: &Term, vars: &Variables) -> Result<Self, ParseError> {
+    let (operator, arguments) = match term {
+      Term::QualIdentifier(qi) => {
+        return Ok(Transduction(vec![TransductionOp::Var(get_var(qi, vars)?)]))
+      }
+      Term::Constant(Constant::String(s)) => {
+        return Ok(Transduction(vec![TransductionOp::Str(s.clone())]))
+      }
       Term::Application {
         qual_identifier,
         arguments,
-      } => {
-        let op = get_symbol(qual_identifier);
-        match &op[..] {
-          "str.++" => Transduction(arguments.iter().fold(Vec::new(), |mut res, term| {
-            res.extend(Transduction::from(term, vars).0);
-            res
-          })),
-          "str.replaceallre" => {
-            if let [var, old, new] = &arguments[..] {
-              if let Term::QualIdentifier(qi) = var {
-                Transduction(vec![TransductionOp::ReplaceAll(
-                  get_var(qi, vars),
-                  Regex::new(old),
-                  ReplaceTarget::from(new, vars),
-                )])
-              } else {
-                panic!("Syntax error: extra arguments");
-              }
-            } else {
-              panic!("Syntax error")
-            }
+      } => (get_symbol(qual_identifier), arguments),
+      _ => return Err(ParseError::malformed(term, "a string term")),
+    };
+    let arity = match operator {
+      "str.++" => {
+        let mut ops = vec![];
+        for argument in arguments {
+          ops.extend(Transduction::from(argument, vars)?.0);
+        }
+        return Ok(Transduction(ops));
+      }
+      "str.reverse" => 1,
+      "str.at" => 2,
+      "str.replaceallre" | "str.replace_re_all" | "str.replacere" | "str.replace_re"
+      | "str.replace" | "str.substr" => 3,
+      _ => return Err(ParseError::unsupported(format!("string term {}", term))),
+    };
+    if arguments.len() != arity {
+      return Err(ParseError::arity(operator, term, Arity::Exactly(arity)));
+    }
+    let idx = match &arguments[0] {
+      Term::QualIdentifier(qi) => get_var(qi, vars)?,
+      argument => {
+        return Err(ParseError::unsupported(format!(
+          "{} of a non-variable {}",
+          operator, argument
+        )))
+      }
+    };
+    /* patterns of no word or only the empty word are rejected by SstBuilder */
+    let pattern = |term: &Term| {
+      let reg = Regex::new(term)?;
+      match reg {
+        Regex::Empty | Regex::Epsilon => Err(ParseError::unsupported(format!(
+          "{} of the pattern {}",
+          operator, term
+        ))),
+        reg => Ok(reg),
+      }
+    };
+    let op = match operator {
+      "str.replaceallre" | "str.replace_re_all" => TransductionOp::ReplaceAll(
+        idx,
+        pattern(&arguments[1])?,
+        ReplaceTarget::from(&arguments[2], vars)?,
+      ),
+      "str.replacere" | "str.replace_re" => TransductionOp::Replace(
+        idx,
+        pattern(&arguments[1])?,
+        ReplaceTarget::from(&arguments[2], vars)?,
+      ),
+      "str.replace" => match &arguments[1] {
+        Term::Constant(Constant::String(word)) => {
+          TransductionOp::ReplaceFirst(idx, word.clone(), ReplaceTarget::from(&arguments[2], vars)?)
+        }
+        pattern => {
+          return Err(ParseError::unsupported(format!(
+            "str.replace of a non-constant pattern {}",
+            pattern
+          )))
+        }
+      },
+      /* a negative start or length takes nothing */
+      "str.substr" | "str.at" if arguments[1..].iter().any(IntArg::is_negative) => {
+        TransductionOp::Str(String::new())
+      }
+      "str.substr" => TransductionOp::Substr(
+        idx,
+        IntArg::from(&arguments[1]),
+        IntArg::from(&arguments[2]),
+      ),
+      "str.at" => TransductionOp::Substr(idx, IntArg::from(&arguments[1]), IntArg::Const(1)),
+      /* str.reverse */
+      _ => TransductionOp::Reverse(idx),
+    };
+    Ok(Transduction(vec![op]))
+  }
+
+  /** variables the transduction refers to */
+  pub fn vars(&self) -> Vec<VarIndex> {
+    let mut vars = vec![];
+    for operator in &self.0 {
+      match operator {
+        TransductionOp::Var(idx)
+        | TransductionOp::Reverse(idx)
+        | TransductionOp::Substr(idx, _, _) => vars.push(*idx),
+        TransductionOp::Replace(idx, _, to)
+        | TransductionOp::ReplaceAll(idx, _, to)
+        | TransductionOp::ReplaceFirst(idx, _, to) => {
+          vars.push(*idx);
+          if let ReplaceTarget::Var(target_id) = to {
+            vars.push(*target_id);
           }
-          "str.replacere" => {
-            if let [var, old, new] = &arguments[..] {
-              if let Term::QualIdentifier(qi) = var {
-                Transduction(vec![TransductionOp::Replace(
-                  get_var(qi, vars),
-                  Regex::new(old),
-                  ReplaceTarget::from(new, vars),
-                )])
-              } else {
-                panic!("Syntax error: extra arguments");
-              }
-            } else {
-              panic!("Syntax error")
-            }
+        }
+        TransductionOp::Str(_) | TransductionOp::UserDef(_) => {}
+      }
+    }
+    vars
+  }
+
+  /** the SMT-LIB function each operator comes from, where plain strings and variables are str.++ */
+  pub(crate) fn function_names(&self) -> Vec<&'static str> {
+    let mut names = vec![];
+    for operator in &self.0 {
+      let name = match operator {
+        TransductionOp::Var(_) | TransductionOp::Str(_) => "str.++",
+        TransductionOp::Reverse(_) => "str.reverse",
+        TransductionOp::Replace(..) => "str.replace_re",
+        TransductionOp::ReplaceAll(..) => "str.replace_re_all",
+        TransductionOp::ReplaceFirst(..) => "str.replace",
+        TransductionOp::Substr(..) => "str.substr",
+        TransductionOp::UserDef(_) => "user-defined",
+      };
+      if !names.contains(&name) {
+        names.push(name);
+      }
+    }
+    names
+  }
+
+  /** whether some integer argument is abstracted away */
+  pub fn is_abstracted(&self) -> bool {
+    self.0.iter().any(|operator| match operator {
+      TransductionOp::Substr(_, start, len) => {
+        start.constant().is_none() || len.constant().is_none()
+      }
+      _ => false,
+    })
+  }
+
+  /** whether every character the transduction mentions is ASCII, where user-defined ones are not known */
+  pub fn is_ascii(&self) -> bool {
+    let target = |to: &ReplaceTarget| match to {
+      ReplaceTarget::Str(s) => s.is_ascii(),
+      ReplaceTarget::Var(_) => true,
+    };
+    self.0.iter().all(|operator| match operator {
+      TransductionOp::Var(_) | TransductionOp::Reverse(_) | TransductionOp::Substr(_, _, _) => true,
+      TransductionOp::Str(s) => s.is_ascii(),
+      TransductionOp::Replace(_, reg, to) | TransductionOp::ReplaceAll(_, reg, to) => {
+        reg.is_ascii() && target(to)
+      }
+      TransductionOp::ReplaceFirst(_, word, to) => word.is_ascii() && target(to),
+      TransductionOp::UserDef(_) => false,
+    })
+  }
+
+  /** the transduction with variables renamed by map, i.e. old index to new one */
+  fn renumber(&self, map: &[VarIndex]) -> Self {
+    let target = |to: &ReplaceTarget| match to {
+      ReplaceTarget::Var(idx) => ReplaceTarget::Var(map[*idx]),
+      ReplaceTarget::Str(s) => ReplaceTarget::Str(s.clone()),
+    };
+    Transduction(
+      self
+        .0
+        .iter()
+        .map(|operator| match operator {
+          TransductionOp::Var(idx) => TransductionOp::Var(map[*idx]),
+          TransductionOp::Reverse(idx) => TransductionOp::Reverse(map[*idx]),
+          TransductionOp::Substr(idx, start, len) => {
+            TransductionOp::Substr(map[*idx], start.clone(), len.clone())
           }
-          "str.reverse" => {
-            if let [var] = &arguments[..] {
-              if let Term::QualIdentifier(qi) = var {
-                Transduction(vec![TransductionOp::Reverse(get_var(qi, vars))])
-              } else {
-                panic!("Syntax error: extra arguments");
-              }
-            } else {
-              panic!("Syntax error")
+          TransductionOp::Replace(idx, from, to) => {
+            TransductionOp::Replace(map[*idx], from.clone(), target(to))
+          }
+          TransductionOp::ReplaceAll(idx, from, to) => {
+            TransductionOp::ReplaceAll(map[*idx], from.clone(), target(to))
+          }
+          TransductionOp::ReplaceFirst(idx, word, to) => {
+            TransductionOp::ReplaceFirst(map[*idx], word.clone(), target(to))
+          }
+          operator => operator.clone(),
+        })
+        .collect(),
+    )
+  }
+
+  /** the abstracted integer arguments, which are Int variables, large constants or other terms */
+  fn symbolic_args(&self) -> Vec<&str> {
+    let mut names = vec![];
+    for operator in &self.0 {
+      if let TransductionOp::Substr(_, start, len) = operator {
+        for arg in [start, len] {
+          if let IntArg::Symbolic(name) = arg {
+            if !names.contains(&&name[..]) {
+              names.push(&name[..]);
             }
           }
-          _ => panic!("Syntax error"),
         }
       }
-      _ => panic!("Syntax error: {:?}", term),
     }
+    names
+  }
+
+  /** the transduction where the Int variable is the value, taking nothing if it's negative */
+  fn with_int(&self, name: &str, value: i64) -> Self {
+    let arg = |arg: &IntArg| match arg {
+      IntArg::Symbolic(symbol) if symbol == name => IntArg::Const(value.max(0) as usize),
+      arg => arg.clone(),
+    };
+    Transduction(
+      self
+        .0
+        .iter()
+        .map(|operator| match operator {
+          TransductionOp::Substr(_, IntArg::Symbolic(start), _) if start == name && value < 0 => {
+            TransductionOp::Str(String::new())
+          }
+          TransductionOp::Substr(idx, start, len) => TransductionOp::Substr(*idx, arg(start), arg(len)),
+          operator => operator.clone(),
+        })
+        .collect(),
+    )
   }
 
   pub fn apply(&self, var_map: &HashMap<VarIndex, String>) -> String {
+    self.apply_with(var_map, &HashMap::new())
+  }
+
+  /** apply with the values of Int variables in integer arguments, which are 0 out of ints */
+  pub fn apply_with(&self, var_map: &HashMap<VarIndex, String>, ints: &HashMap<String, i64>) -> String {
     let mut result = String::new();
 
     for operator in &self.0 {
@@ -159,6 +591,26 @@ impl<D: Domain, S: State> Transduction<D, S> {
         TransductionOp::Reverse(idx) => {
           result.push_str(&var_map.get(&idx).unwrap().chars().rev().collect::<String>());
         }
+        TransductionOp::ReplaceFirst(idx, word, to) => {
+          let to = match to {
+            ReplaceTarget::Str(s) => s,
+            ReplaceTarget::Var(target_id) => var_map.get(target_id).unwrap(),
+          };
+          result.push_str(&var_map.get(idx).unwrap().replacen(&word[..], to, 1));
+        }
+        TransductionOp::Substr(idx, start, len) => {
+          let (start, len) = (start.value(ints), len.value(ints));
+          if start >= 0 && len > 0 {
+            result.extend(
+              var_map
+                .get(idx)
+                .unwrap()
+                .chars()
+                .skip(start as usize)
+                .take(len as usize),
+            );
+          }
+        }
         TransductionOp::Replace(idx, from, to) => {
           let to = match to {
             ReplaceTarget::Str(s) => s.chars().map(|c| OutputComp::A(char::from(c))).collect(),
@@ -210,7 +662,13 @@ pub trait Constraint {
   fn constraint(&self) -> &Self::Value;
 }
 #[derive(Debug, PartialEq, Clone)]
-pub struct StraightLineConstraint<D: Domain, S: State>(VarIndex, Transduction<D, S>);
+pub struct StraightLineConstraint<D: Domain, S: State>(VarIndex, Transduction<D, S>, Span);
+impl<D: Domain, S: State> StraightLineConstraint<D, S> {
+  /** position of the assertion in the input */
+  pub fn span(&self) -> Span {
+    self.2
+  }
+}
 impl<D: Domain, S: State> Constraint for StraightLineConstraint<D, S> {
   type Value = Transduction<D, S>;
 
@@ -222,7 +680,13 @@ impl<D: Domain, S: State> Constraint for StraightLineConstraint<D, S> {
   }
 }
 #[derive(Debug, PartialEq, Clone)]
-pub struct RegularConstraint<D: Domain>(VarIndex, Regex<D>);
+pub struct RegularConstraint<D: Domain>(VarIndex, Regex<D>, Span);
+impl<D: Domain> RegularConstraint<D> {
+  /** position of the assertion in the input */
+  pub fn span(&self) -> Span {
+    self.2
+  }
+}
 impl<D: Domain> Constraint for RegularConstraint<D> {
   type Value = Regex<D>;
 
@@ -233,31 +697,104 @@ impl<D: Domain> Constraint for RegularConstraint<D> {
     &self.1
   }
 }
+/**
+ * a problem in straight-line form.
+ * definitions are in the order of assertions,
+ * each variable has the intersection of its regular constraints as membership,
+ * and free variables are the ones without definition in the declaration order.
+ */
 #[derive(Debug, PartialEq, Clone)]
-pub struct IntLinearConstraint(VarIndex, Vec<LinearTerm>);
-impl Constraint for IntLinearConstraint {
-  type Value = Vec<LinearTerm>;
+pub struct StraightLine<'a, D: Domain, S: State> {
+  definitions: Vec<&'a StraightLineConstraint<D, S>>,
+  memberships: Vec<Option<Regex<D>>>,
+  free_vars: Vec<VarIndex>,
+}
+impl<'a, D: Domain, S: State> StraightLine<'a, D, S> {
+  pub fn definitions(&self) -> &Vec<&'a StraightLineConstraint<D, S>> {
+    &self.definitions
+  }
 
-  fn idx(&self) -> VarIndex {
+  pub fn definition(&self, idx: VarIndex) -> Option<&'a StraightLineConstraint<D, S>> {
+    self
+      .definitions
+      .iter()
+      .find(|sl_cons| sl_cons.idx() == idx)
+      .copied()
+  }
+
+  /** indexed by variables */
+  pub fn memberships(&self) -> &Vec<Option<Regex<D>>> {
+    &self.memberships
+  }
+
+  pub fn membership(&self, idx: VarIndex) -> Option<&Regex<D>> {
+    self.memberships[idx].as_ref()
+  }
+
+  pub fn free_vars(&self) -> &Vec<VarIndex> {
+    &self.free_vars
+  }
+}
+
+/** the sum of terms is equal to or at most 0 */
+#[derive(Debug, PartialEq, Clone)]
+pub struct IntLinearConstraint(Relation, Vec<LinearTerm>, Span);
+impl IntLinearConstraint {
+  pub fn relation(&self) -> Relation {
     self.0
   }
-  fn constraint(&self) -> &Self::Value {
+
+  pub fn terms(&self) -> &Vec<LinearTerm> {
     &self.1
   }
+
+  /** position of the assertion in the input */
+  pub fn span(&self) -> Span {
+    self.2
+  }
+
+  pub fn atoms(&self) -> Vec<IntAtom> {
+    let mut atoms: Vec<_> = self.1.iter().filter_map(|term| term.atom).collect();
+    atoms.sort();
+    atoms.dedup();
+    atoms
+  }
+
+  /** whether the values satisfy the constraint, or None if some atom has no value */
+  pub fn eval(&self, values: &HashMap<IntAtom, i64>) -> Option<bool> {
+    let mut sum = 0i64;
+    for term in &self.1 {
+      let value = match term.atom {
+        Some(atom) => *values.get(&atom)?,
+        None => 1,
+      };
+      sum = sum.saturating_add(term.coefficient.saturating_mul(value));
+    }
+    Some(match self.0 {
+      Relation::Eq => sum == 0,
+      Relation::Le => sum <= 0,
+    })
+  }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct SMTOption {
   check_sat: bool,
   get_model: bool,
+  /** the terms of each (get-value) as given and expanded */
+  get_values: Vec<Vec<(Term, Term)>>,
   logic: Logic,
+  /** functions out of SMT-LIB are accepted, see set_extensions */
+  extensions: bool,
 }
 impl Default for SMTOption {
   fn default() -> Self {
     SMTOption {
       check_sat: false,
       get_model: false,
+      get_values: vec![],
       logic: Logic::QuantifierFreeString,
+      extensions: false,
     }
   }
 }
@@ -279,6 +816,337 @@ impl Debug for Logic {
   }
 }
 
+/** non-fatal diagnostics found while reading a problem */
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+  /** the command is ignored */
+  UnsupportedCommand(String),
+  /** the assertion is ignored, so the problem is unknown unless the rest is unsat */
+  UnsupportedAssertion(String),
+  /** the constraint is not taken into account exactly */
+  Approximated(String),
+  /** the variable has no constraint, so any string is allowed */
+  UnconstrainedVariable(String),
+  /** the automaton of the membership of the variable may have about this many states */
+  LargeMembership(String, usize),
+}
+impl Display for Warning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Warning::UnsupportedCommand(command) => write!(f, "unsupported command ignored: {}", command),
+      Warning::UnsupportedAssertion(term) => write!(f, "unsupported assertion ignored: {}", term),
+      Warning::Approximated(reason) => write!(f, "approximated: {}", reason),
+      Warning::UnconstrainedVariable(var) => write!(f, "variable {} is unconstrained", var),
+      Warning::LargeMembership(var, size) => write!(
+        f,
+        "membership of {} may build an automaton of up to {} states",
+        var, size
+      ),
+    }
+  }
+}
+
+/** how preprocessing changed a constraint */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TraceKind {
+  /** replaced with an equivalent constraint */
+  Rewritten,
+  /** replaced with a weaker one or ignored, so a model may violate the original */
+  Approximated,
+  /** removed, since it can't affect satisfiability */
+  Eliminated,
+}
+impl Display for TraceKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TraceKind::Rewritten => write!(f, "rewritten"),
+      TraceKind::Approximated => write!(f, "approximated"),
+      TraceKind::Eliminated => write!(f, "eliminated"),
+    }
+  }
+}
+
+/**
+ * a record of preprocessing, displayed as a tab-separated line of
+ * the position of the command (- if unknown), the kind and the detail.
+ */
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraceEntry {
+  pub span: Option<Span>,
+  pub kind: TraceKind,
+  pub detail: String,
+}
+impl Display for TraceEntry {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.span {
+      Some(span) => write!(f, "{}", span)?,
+      None => write!(f, "-")?,
+    }
+    write!(
+      f,
+      "\t{}\t{}",
+      self.kind,
+      self.detail.replace(['\t', '\n'], " ")
+    )
+  }
+}
+
+/** 1-origin line and column of a command in the input */
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
+pub struct Span {
+  pub line: usize,
+  pub column: usize,
+}
+impl Span {
+  /** position of the byte offset in input, where columns count characters */
+  fn at(input: &str, offset: usize) -> Self {
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Span {
+      line: before.matches('\n').count() + 1,
+      column: before[line_start..].chars().count() + 1,
+    }
+  }
+}
+impl Default for Span {
+  fn default() -> Self {
+    Span { line: 1, column: 1 }
+  }
+}
+impl Display for Span {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}", self.line, self.column)
+  }
+}
+
+/** the number of arguments an operator takes */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Arity {
+  Exactly(usize),
+  AtLeast(usize),
+  Between(usize, usize),
+}
+impl Display for Arity {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Arity::Exactly(n) => write!(f, "{}", n),
+      Arity::AtLeast(n) => write!(f, "at least {}", n),
+      Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+    }
+  }
+}
+
+/** why an input can't be taken as a problem, at the position of the command */
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+  /** the input isn't well-formed SMT-LIB */
+  Syntax {
+    span: Span,
+    message: String,
+  },
+  /** the operator is applied to a wrong number of arguments in term */
+  Arity {
+    span: Span,
+    operator: String,
+    term: String,
+    expected: Arity,
+  },
+  /** the term isn't of the form its position needs, e.g. a string constant */
+  Malformed {
+    span: Span,
+    term: String,
+    expected: &'static str,
+  },
+  /** valid SMT-LIB which is beyond the solver, e.g. a sort or a function */
+  Unsupported {
+    span: Span,
+    feature: String,
+  },
+  UnknownVariable {
+    span: Span,
+    name: String,
+  },
+  /** the name is already declared or bound by define-fun */
+  Redeclared {
+    span: Span,
+    name: String,
+  },
+  /** the variable is defined by a second equation, where first is the former */
+  Redefined {
+    span: Span,
+    name: String,
+    first: Span,
+  },
+  /** the pattern of (set-option :alphabet ..) is rejected by Regex::parse */
+  Pattern {
+    span: Span,
+    pattern: String,
+    error: RegexParseError,
+  },
+}
+impl ParseError {
+  pub(crate) fn arity(operator: &str, term: &dyn Display, expected: Arity) -> Self {
+    ParseError::Arity {
+      span: Span::default(),
+      operator: operator.to_owned(),
+      term: term.to_string(),
+      expected,
+    }
+  }
+
+  pub(crate) fn malformed(term: &dyn Display, expected: &'static str) -> Self {
+    ParseError::Malformed {
+      span: Span::default(),
+      term: term.to_string(),
+      expected,
+    }
+  }
+
+  pub(crate) fn unsupported(feature: String) -> Self {
+    ParseError::Unsupported {
+      span: Span::default(),
+      feature,
+    }
+  }
+
+  /** position of the command the error is found in */
+  pub fn span(&self) -> Span {
+    match self {
+      ParseError::Syntax { span, .. }
+      | ParseError::Arity { span, .. }
+      | ParseError::Malformed { span, .. }
+      | ParseError::Unsupported { span, .. }
+      | ParseError::UnknownVariable { span, .. }
+      | ParseError::Redeclared { span, .. }
+      | ParseError::Redefined { span, .. }
+      | ParseError::Pattern { span, .. } => *span,
+    }
+  }
+
+  /** the error found in the command at span */
+  fn at(mut self, at: Span) -> Self {
+    match &mut self {
+      ParseError::Syntax { span, .. }
+      | ParseError::Arity { span, .. }
+      | ParseError::Malformed { span, .. }
+      | ParseError::Unsupported { span, .. }
+      | ParseError::UnknownVariable { span, .. }
+      | ParseError::Redeclared { span, .. }
+      | ParseError::Redefined { span, .. }
+      | ParseError::Pattern { span, .. } => *span = at,
+    }
+    self
+  }
+}
+impl Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: ", self.span())?;
+    match self {
+      ParseError::Syntax { message, .. } => write!(f, "Syntax error: {}", message),
+      ParseError::Arity {
+        operator,
+        term,
+        expected,
+        ..
+      } => write!(
+        f,
+        "Syntax error: {} takes {} argument(s) in {}",
+        operator, expected, term
+      ),
+      ParseError::Malformed { term, expected, .. } => {
+        write!(f, "Syntax error: expected {} but found {}", expected, term)
+      }
+      ParseError::Unsupported { feature, .. } => write!(f, "Unsupported {}", feature),
+      ParseError::UnknownVariable { name, .. } => write!(f, "Variable {} is not declared", name),
+      ParseError::Redeclared { name, .. } => write!(f, "Variable {} is already defined", name),
+      ParseError::Redefined { name, first, .. } => write!(
+        f,
+        "Variable {} is defined twice, first at {}. Only one definition per variable is supported.",
+        name, first
+      ),
+      ParseError::Pattern { pattern, error, .. } => {
+        write!(f, "{} in the pattern {:?}", error, pattern)
+      }
+    }
+  }
+}
+impl std::error::Error for ParseError {}
+
+/**
+ * top-level s-expressions of input with their offsets, skipping comments.
+ * string literals and quoted symbols may contain parentheses.
+ * text out of parentheses is split by whitespaces.
+ */
+fn split_commands(input: &str) -> Vec<(usize, &str)> {
+  let mut chunks = vec![];
+  let mut start = None;
+  let mut depth = 0;
+  let (mut in_string, mut in_symbol, mut in_comment) = (false, false, false);
+
+  for (i, c) in input.char_indices() {
+    if in_comment {
+      in_comment = c != '\n';
+    } else if in_string {
+      in_string = c != '"';
+    } else if in_symbol {
+      in_symbol = c != '|';
+    } else {
+      match c {
+        ';' => in_comment = true,
+        '"' => in_string = true,
+        '|' => in_symbol = true,
+        '(' => depth += 1,
+        ')' => depth -= 1,
+        _ => {}
+      }
+      if start.is_none() && !c.is_whitespace() && !in_comment {
+        start = Some(i);
+      }
+      if let Some(s) = start {
+        let atom_end = depth == 0 && (c.is_whitespace() || in_comment);
+        if c == ')' && depth <= 0 {
+          chunks.push((s, &input[s..i + 1]));
+          start = None;
+          depth = 0;
+        } else if atom_end {
+          chunks.push((s, input[s..i].trim_end()));
+          start = None;
+        }
+      }
+    }
+  }
+  if let Some(s) = start {
+    chunks.push((s, input[s..].trim_end()));
+  }
+
+  chunks
+}
+
+/**
+ * input where the parentheses around the terms of each (get-value (t1 ... tn)) are blanked out,
+ * since smt2parser reads (get-value t1 ... tn) instead. offsets of the commands are kept.
+ */
+fn unwrap_get_value(input: &str) -> String {
+  let mut unwrapped = input.to_owned();
+  for (offset, chunk) in split_commands(input) {
+    let body = match chunk
+      .strip_prefix("(get-value")
+      .and_then(|body| body.strip_suffix(')'))
+    {
+      Some(body) => body,
+      None => continue,
+    };
+    let open = body.len() - body.trim_start().len();
+    let close = body.trim_end().len();
+    if body[open..].starts_with('(') && body[..close].ends_with(')') && open + 1 < close {
+      let start = offset + "(get-value".len();
+      unwrapped.replace_range(start + open..start + open + 1, " ");
+      unwrapped.replace_range(start + close - 1..start + close, " ");
+    }
+  }
+  unwrapped
+}
+
 #[derive(Debug, PartialEq)]
 pub enum SolverResult<B: BoolAlg> {
   SAT,
@@ -290,273 +1158,3790 @@ pub enum SolverResult<B: BoolAlg> {
 pub struct Smt2<D: Domain, S: State> {
   sl_constraints: Vec<StraightLineConstraint<D, S>>,
   reg_constraints: Vec<RegularConstraint<D>>,
+  /** linear constraints over lengths and Int variables */
+  length_constraints: Vec<IntLinearConstraint>,
+  /** arguments of str.indexof, indexed by IntAtom::IndexOf */
+  index_patterns: Vec<IndexPattern>,
+  /** patterns of str.count, indexed by IntAtom::Count */
+  count_patterns: Vec<String>,
   vars: Variables,
   int_vars: Variables,
+  bool_vars: Variables,
+  /** RegLan constants bound by define-fun, already expanded */
+  reg_definitions: HashMap<String, Term>,
+  /** assertions referring to Bool variables, which are resolved by case_split */
+  conditional: Vec<(Span, Term)>,
+  /** terms of assertions named by (! term :named name), already expanded */
+  names: HashMap<String, Term>,
+  /** literals of check-sat-assuming, which are resolved by case_split as conditional ones */
+  assumptions: Vec<(Span, Term)>,
   option: SMTOption,
+  warnings: Vec<Warning>,
+  /** what preprocessing did, in order */
+  trace: Vec<TraceEntry>,
+  /** some assertion is false, so the problem is unsatisfiable without solving */
+  contradictory: bool,
+  /** assertions ignored by ignore_assertion, so a model may violate them */
+  ignored: Vec<String>,
+  /** position of the command being processed, which errors point at */
+  span: Span,
+  /** the problem at each (push), which (pop) goes back to */
+  scopes: Vec<Smt2<D, S>>,
+  /** the problem at each (check-sat) or (check-sat-assuming), see checks */
+  checks: Vec<Smt2<D, S>>,
+  /** how memberships are built into automata */
+  strategy: Strategy,
+  /** all strings are assumed ASCII, see with_ascii */
+  ascii: bool,
+  /** see with_bitsets */
+  bitsets: bool,
+  /** models are searched by shortest_path, see with_shortest */
+  shortest: bool,
 }
 impl<D: Domain, S: State> Smt2<D, S> {
-  pub fn parse(input: &str) -> Result<Self, Smt2ParserError> {
-    let commands = CommandStream::new(input.as_bytes(), SyntaxBuilder, None)
-      .collect::<Result<Vec<_>, _>>()
-      .map_err(|err| err)?;
-    let mut smt2 = Smt2::init();
-    for command in commands.into_iter() {
-      smt2.update(command);
-    }
-    Ok(smt2)
+  /** parse input, where commands unknown to the parser are skipped with warnings */
+  pub fn parse(input: &str) -> Result<Self, ParseError> {
+    Self::parse_with(input, false)
   }
 
-  fn init() -> Self {
+  /**
+   * parse input, which fails on commands unknown to the parser if strict.
+   * otherwise, each top-level command is parsed separately
+   * so that the unknown ones are collected into warnings.
+   * commands the solver can't take are errors in both cases.
+   */
+  pub fn parse_with(input: &str, strict: bool) -> Result<Self, ParseError> {
+    let mut smt2 = Smt2::init();
+    let input = &unwrap_get_value(input);
+    let chunks = split_commands(input);
+    if strict {
+      let commands = CommandStream::new(input.as_bytes(), SyntaxBuilder, None)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ParseError::Syntax {
+          span: chunks
+            .iter()
+            .find(|(_, chunk)| {
+              CommandStream::new(chunk.as_bytes(), SyntaxBuilder, None).any(|c| c.is_err())
+            })
+            .map_or_else(Span::default, |(offset, _)| Span::at(input, *offset)),
+          message: format!("{:?}", err),
+        })?;
+      for (i, command) in commands.into_iter().enumerate() {
+        smt2.span = chunks
+          .get(i)
+          .map_or_else(Span::default, |(offset, _)| Span::at(input, *offset));
+        smt2.update(command)?;
+      }
+    } else {
+      for (offset, chunk) in chunks {
+        smt2.span = Span::at(input, offset);
+        match CommandStream::new(chunk.as_bytes(), SyntaxBuilder, None)
+          .collect::<Result<Vec<_>, _>>()
+        {
+          Ok(commands) => {
+            for command in commands {
+              smt2.update(command)?;
+            }
+          }
+          Err(_) => smt2
+            .warnings
+            .push(Warning::UnsupportedCommand(chunk.to_owned())),
+        }
+      }
+    }
+    for idx in 0..smt2.int_vars.len() {
+      let atom = IntAtom::Var(idx);
+      if smt2
+        .length_constraints
+        .iter()
+        .all(|int_cons| !int_cons.atoms().contains(&atom))
+      {
+        let var = smt2.int_vars[idx].clone();
+        smt2.approximate(format!(
+          "integer variable {} is not constrained by lengths",
+          var
+        ));
+      }
+    }
+    /* otherwise, checked for each case */
+    if smt2.conditional.is_empty() && smt2.assumptions.is_empty() {
+      smt2.check_unconstrained();
+      smt2.check_sizes();
+    }
+    Ok(smt2)
+  }
+
+  pub(crate) fn init() -> Self {
     Smt2 {
       sl_constraints: vec![],
       reg_constraints: vec![],
+      length_constraints: vec![],
+      index_patterns: vec![],
+      count_patterns: vec![],
       vars: vec![],
       int_vars: vec![],
+      bool_vars: vec![],
+      reg_definitions: HashMap::new(),
+      conditional: vec![],
+      names: HashMap::new(),
+      assumptions: vec![],
       option: SMTOption::default(),
+      warnings: vec![],
+      trace: vec![],
+      contradictory: false,
+      ignored: vec![],
+      span: Span::default(),
+      scopes: vec![],
+      checks: vec![],
+      strategy: Strategy::default(),
+      ascii: false,
+      bitsets: false,
+      shortest: false,
     }
   }
 
-  fn update(&mut self, command: Command) {
-    match command {
-      Command::DeclareConst {
-        symbol: Symbol(var),
-        sort,
-      } => {
-        if let Sort::Simple {
-          identifier: Identifier::Simple { symbol: Symbol(s) },
-        } = sort
-        {
-          match &s[..] {
-            "String" | "string" => {
-              if self.vars.iter().find(|&x| x == &var).is_none() {
-                if self.int_vars.iter().find(|&x| x == &var).is_none() {
-                  self.vars.push(var);
-                } else {
-                  panic!(
-                    "Variable name confliction occured. Integer variable {} already defined.",
-                    var
-                  );
-                }
-              } else {
-                panic!("String variable {} is already defined.", var);
-              }
-            }
-            "Int" | "int" => {
-              if self.int_vars.iter().find(|&x| x == &var).is_none() {
-                if self.vars.iter().find(|&x| x == &var).is_none() {
-                  self.int_vars.push(var);
-                } else {
-                  panic!(
-                    "Variable name confliction occured. String variable {} already defined",
-                    var
-                  );
+  /** remove all the assertions, keeping declarations as (reset-assertions) does */
+  pub fn reset_assertions(&mut self) {
+    self.sl_constraints.clear();
+    self.reg_constraints.clear();
+    self.length_constraints.clear();
+    self.index_patterns.clear();
+    self.count_patterns.clear();
+    self.conditional.clear();
+    self.names.clear();
+    self.assumptions.clear();
+    self.contradictory = false;
+    self.ignored.clear();
+    self.scopes.clear();
+  }
+
+  /** open n scopes as (push n) does */
+  pub fn push(&mut self, n: usize) {
+    let scope = self.snapshot();
+    self.scopes.resize(self.scopes.len() + n, scope);
+  }
+
+  /**
+   * close n scopes as (pop n) does, removing the assertions and declarations made in them.
+   * options, warnings and trace are kept. it fails if fewer than n scopes are open.
+   */
+  pub fn pop(&mut self, n: usize) -> Result<(), ParseError> {
+    if n > self.scopes.len() {
+      return Err(ParseError::malformed(
+        &format!("(pop {})", n),
+        "at most as many levels as pushed",
+      ));
+    }
+    let depth = self.scopes.len() - n;
+    if let Some(scope) = self.scopes.drain(depth..).next() {
+      self.sl_constraints = scope.sl_constraints;
+      self.reg_constraints = scope.reg_constraints;
+      self.length_constraints = scope.length_constraints;
+      self.index_patterns = scope.index_patterns;
+      self.count_patterns = scope.count_patterns;
+      self.vars = scope.vars;
+      self.int_vars = scope.int_vars;
+      self.bool_vars = scope.bool_vars;
+      self.reg_definitions = scope.reg_definitions;
+      self.conditional = scope.conditional;
+      self.names = scope.names;
+      self.assumptions = scope.assumptions;
+      self.contradictory = scope.contradictory;
+      self.ignored = scope.ignored;
+    }
+    Ok(())
+  }
+
+  /** the number of scopes open */
+  pub fn depth(&self) -> usize {
+    self.scopes.len()
+  }
+
+  /** remove everything but warnings, trace and checks found so far, as (reset) does */
+  pub fn reset(&mut self) {
+    let warnings = std::mem::take(&mut self.warnings);
+    let trace = std::mem::take(&mut self.trace);
+    let checks = std::mem::take(&mut self.checks);
+    *self = Smt2::init();
+    self.warnings = warnings;
+    self.trace = trace;
+    self.checks = checks;
+  }
+
+  /** a copy of the problem without scopes and checks */
+  fn snapshot(&mut self) -> Self {
+    let scopes = std::mem::take(&mut self.scopes);
+    let checks = std::mem::take(&mut self.checks);
+    let snapshot = self.clone();
+    self.scopes = scopes;
+    self.checks = checks;
+    snapshot
+  }
+
+  /** take a snapshot to be answered by the check-sat command just given */
+  fn record_check(&mut self) {
+    let mut check = self.snapshot();
+    check.option.get_model = false;
+    check.option.get_values.clear();
+    self.checks.push(check);
+  }
+
+  fn record(&mut self, kind: TraceKind, detail: String) {
+    self.trace.push(TraceEntry {
+      span: Some(self.span),
+      kind,
+      detail,
+    });
+  }
+
+  /** approximate the problem with a warning */
+  fn approximate(&mut self, reason: String) {
+    self.record(TraceKind::Approximated, reason.clone());
+    self.warnings.push(Warning::Approximated(reason));
+  }
+
+  /** ignore the assertion with a warning */
+  fn ignore_assertion(&mut self, term: String) {
+    self.record(TraceKind::Approximated, format!("ignored {}", term));
+    self.ignored.push(term.clone());
+    self.warnings.push(Warning::UnsupportedAssertion(term));
+  }
+
+  fn check_unconstrained(&mut self) {
+    let unconstrained: Vec<_> = self
+      .unconstrained_vars()
+      .into_iter()
+      .map(|idx| Warning::UnconstrainedVariable(self.vars[idx].clone()))
+      .collect();
+    self.warnings.extend(unconstrained);
+  }
+
+  /**
+   * ResourceExhausted if some membership unrolls to more than STATE_LIMIT literals,
+   * before its automaton is built, e.g. for a large constant of re.loop or str.at.
+   */
+  pub fn check_limits(&self) -> Result<(), ResourceExhausted> {
+    for idx in 0..self.vars.len() {
+      if let Some(reg) = self.membership(idx) {
+        ResourceExhausted::check("to_sfa", reg.unrolled_size(), || {
+          format!("the membership of {}", self.vars[idx])
+        })?;
+      }
+    }
+    Ok(())
+  }
+
+  /** warn of memberships whose automata are estimated larger than SFA_SIZE_THRESHOLD */
+  fn check_sizes(&mut self) {
+    let large: Vec<_> = (0..self.vars.len())
+      .filter_map(|idx| {
+        let size = self.membership(idx)?.estimate_sfa_size();
+        (size > SFA_SIZE_THRESHOLD).then(|| Warning::LargeMembership(self.vars[idx].clone(), size))
+      })
+      .collect();
+    self.warnings.extend(large);
+  }
+
+  /** variables neither defined, restricted by regular constraints, nor referred to */
+  pub fn unconstrained_vars(&self) -> Vec<VarIndex> {
+    (0..self.vars.len())
+      .filter(|idx| {
+        self.filter_sl(*idx).is_none()
+          && self
+            .reg_constraints
+            .iter()
+            .all(|reg_cons| reg_cons.idx() != *idx)
+          && self
+            .sl_constraints
+            .iter()
+            .all(|sl_cons| !sl_cons.constraint().vars().contains(idx))
+          && self.length_constraints.iter().all(|int_cons| {
+            int_cons
+              .atoms()
+              .iter()
+              .all(|atom| atom.string_var() != Some(*idx))
+          })
+      })
+      .collect()
+  }
+
+  /**
+   * the problem for each assignment to Bool variables and each choice of disjuncts,
+   * where conditional assertions are normalized to disjunctive normal forms of constraints.
+   * the number of cases is exponential in the number of Bool variables and disjunctions,
+   * so it gives up with ResourceExhausted for more than MAX_BOOL_VARS Bool variables
+   * or MAX_CASES cases.
+   */
+  pub fn case_split(&self) -> Result<Vec<Case<D, S>>, ResourceExhausted> {
+    if self.bool_vars.is_empty() && self.conditional.is_empty() && self.assumptions.is_empty() {
+      return Ok(vec![(vec![], self.clone())]);
+    }
+    if self.bool_vars.len() > MAX_BOOL_VARS {
+      return Err(ResourceExhausted {
+        construction: "case split",
+        detail: format!("{} of them are declared", self.bool_vars.len()),
+        limit: MAX_BOOL_VARS,
+        unit: "Bool variables",
+      });
+    }
+
+    let too_many_cases = |detail: String| ResourceExhausted {
+      construction: "case split",
+      detail,
+      limit: MAX_CASES,
+      unit: "cases",
+    };
+
+    let mut result = vec![];
+    for bits in 0..1usize << self.bool_vars.len() {
+      let assignment: HashMap<String, bool> = self
+        .bool_vars
+        .iter()
+        .enumerate()
+        .map(|(i, var)| (var.clone(), (bits >> i) & 1 == 1))
+        .collect();
+
+      let mut cases = vec![Smt2 {
+        conditional: vec![],
+        assumptions: vec![],
+        ..self.clone()
+      }];
+      for (span, term) in self.conditional.iter().chain(&self.assumptions) {
+        let dnf = Self::dnf(term, true, &assignment)
+          .ok_or_else(|| too_many_cases(format!("normalizing {}", term)))?;
+        if dnf.is_empty() {
+          cases.iter_mut().for_each(|case| case.contradictory = true);
+          continue;
+        }
+        /* some disjunct always holds */
+        let dnf = if dnf.iter().any(|conjunction| conjunction.is_empty()) {
+          vec![vec![]]
+        } else {
+          dnf
+        };
+        if (result.len() + cases.len()).saturating_mul(dnf.len()) > MAX_CASES {
+          return Err(too_many_cases(format!("splitting the cases by {}", term)));
+        }
+        cases = cases
+          .iter()
+          .flat_map(|case| {
+            dnf.iter().map(move |conjunction| {
+              let mut case = case.clone();
+              case.span = *span;
+              case.record(
+                TraceKind::Rewritten,
+                format!(
+                  "{} split into the case {}",
+                  term,
+                  Self::show_conjunction(conjunction)
+                ),
+              );
+              /*
+               * literals are checked by parse, except for definitions meeting in a case,
+               * which are ignored if they define a variable twice or cyclically
+               */
+              for (atom, positive) in conjunction {
+                if case.assert_literal(atom, *positive).is_err() {
+                  case.ignore_assertion(if *positive {
+                    atom.to_string()
+                  } else {
+                    format!("(not {})", atom)
+                  });
                 }
-              } else {
-                panic!("Integer variable {} already defined", var);
               }
-            }
-            s => panic!("Unsupported type {}", s),
-          }
+              case
+            })
+          })
+          .collect();
+      }
+      if !self.conditional.is_empty() || !self.assumptions.is_empty() {
+        cases.iter_mut().for_each(|case| {
+          case.check_unconstrained();
+          case.check_sizes();
+        });
+      }
+
+      let assignment: Vec<_> = self
+        .bool_vars
+        .iter()
+        .map(|var| (var.clone(), assignment[var]))
+        .collect();
+      result.extend(cases.into_iter().map(|case| (assignment.clone(), case)));
+      if result.len() > MAX_CASES {
+        return Err(too_many_cases(format!(
+          "{} Bool variables are declared",
+          self.bool_vars.len()
+        )));
+      }
+    }
+    Ok(result)
+  }
+
+  /**
+   * disjunctive normal form of the term (or its negation if not positive),
+   * whose literals are constraints with their polarity.
+   * Bool variables are replaced with their values.
+   * None if it has more than MAX_CASES disjuncts.
+   */
+  fn dnf<'a>(
+    term: &'a Term,
+    positive: bool,
+    assignment: &HashMap<String, bool>,
+  ) -> Option<Vec<Vec<(&'a Term, bool)>>> {
+    if let Some(b) = Self::eval_bool(term, assignment) {
+      return Some(if b == positive { vec![vec![]] } else { vec![] });
+    }
+
+    if let Term::Application {
+      qual_identifier,
+      arguments,
+    } = term
+    {
+      match (get_symbol(qual_identifier), &arguments[..], positive) {
+        ("not", [t], _) => return Self::dnf(t, !positive, assignment),
+        ("and", _, true) | ("or", _, false) => {
+          return arguments.iter().try_fold(vec![vec![]], |result, t| {
+            Self::product(result, Self::dnf(t, positive, assignment)?)
+          })
+        }
+        ("or", _, true) | ("and", _, false) => {
+          return arguments.iter().try_fold(vec![], |result, t| {
+            Self::union(result, Self::dnf(t, positive, assignment)?)
+          })
+        }
+        ("=>", [t1, t2], true) => {
+          return Self::union(
+            Self::dnf(t1, false, assignment)?,
+            Self::dnf(t2, true, assignment)?,
+          )
+        }
+        ("=>", [t1, t2], false) => {
+          return Self::product(
+            Self::dnf(t1, true, assignment)?,
+            Self::dnf(t2, false, assignment)?,
+          )
+        }
+        ("xor", _, _) => return Self::dnf_xor(arguments, positive, assignment),
+        ("ite", [c, t, e], _) => {
+          return Self::union(
+            Self::product(
+              Self::dnf(c, true, assignment)?,
+              Self::dnf(t, positive, assignment)?,
+            )?,
+            Self::product(
+              Self::dnf(c, false, assignment)?,
+              Self::dnf(e, positive, assignment)?,
+            )?,
+          )
+        }
+        _ => {}
+      }
+    }
+
+    Some(vec![vec![(term, positive)]])
+  }
+
+  /** dnf of the xor of terms, i.e. an odd number of them hold */
+  fn dnf_xor<'a>(
+    terms: &'a [Term],
+    positive: bool,
+    assignment: &HashMap<String, bool>,
+  ) -> Option<Vec<Vec<(&'a Term, bool)>>> {
+    match terms {
+      [] => Some(if positive { vec![] } else { vec![vec![]] }),
+      [t, rest @ ..] => Self::union(
+        Self::product(
+          Self::dnf(t, true, assignment)?,
+          Self::dnf_xor(rest, !positive, assignment)?,
+        )?,
+        Self::product(
+          Self::dnf(t, false, assignment)?,
+          Self::dnf_xor(rest, positive, assignment)?,
+        )?,
+      ),
+    }
+  }
+
+  /** conjunction of literals in SMT-LIB syntax */
+  fn show_conjunction(conjunction: &[(&Term, bool)]) -> String {
+    let literals: Vec<_> = conjunction
+      .iter()
+      .map(|(atom, positive)| {
+        if *positive {
+          format!("{}", atom)
+        } else {
+          format!("(not {})", atom)
+        }
+      })
+      .collect();
+    match &literals[..] {
+      [] => String::from("true"),
+      [literal] => literal.clone(),
+      _ => format!("(and {})", literals.join(" ")),
+    }
+  }
+
+  /** conjunctions of each pair of disjuncts, None if more than MAX_CASES */
+  fn product<'a>(
+    left: Vec<Vec<(&'a Term, bool)>>,
+    right: Vec<Vec<(&'a Term, bool)>>,
+  ) -> Option<Vec<Vec<(&'a Term, bool)>>> {
+    if left.len().saturating_mul(right.len()) > MAX_CASES {
+      return None;
+    }
+    Some(
+      left
+        .iter()
+        .flat_map(|l| {
+          right.iter().map(move |r| {
+            let mut conjunction = l.clone();
+            conjunction.extend(r.iter().cloned());
+            conjunction
+          })
+        })
+        .collect(),
+    )
+  }
+
+  /** disjuncts of either, None if more than MAX_CASES */
+  fn union<'a>(
+    mut left: Vec<Vec<(&'a Term, bool)>>,
+    right: Vec<Vec<(&'a Term, bool)>>,
+  ) -> Option<Vec<Vec<(&'a Term, bool)>>> {
+    if left.len() + right.len() > MAX_CASES {
+      return None;
+    }
+    left.extend(right);
+    Some(left)
+  }
+
+  /**
+   * assert a constraint, or its negation if not positive.
+   * the negation of a membership is the membership in the complement,
+   * and the one of an integer inequality is the opposite strict or non-strict one.
+   * other negations are ignored, so that the problem is unknown unless the rest is unsat.
+   */
+  fn assert_literal(&mut self, term: &Term, positive: bool) -> Result<(), ParseError> {
+    if positive {
+      return self.update(Command::Assert { term: term.clone() });
+    }
+    if let Some(opposite) = self.opposite_inequality(term) {
+      self.record(
+        TraceKind::Rewritten,
+        format!("(not {}) into {}", term, opposite),
+      );
+      return self.update(Command::Assert { term: opposite });
+    }
+
+    let membership = match term {
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } => self
+        .predicate_membership(get_symbol(qual_identifier), arguments)
+        .map_err(|err| err.at(self.span))?,
+      _ => None,
+    };
+    match membership {
+      Some((idx, reg)) => {
+        self.record(
+          TraceKind::Rewritten,
+          format!("(not {}) into membership in the complement", term),
+        );
+        self
+          .reg_constraints
+          .push(RegularConstraint(idx, reg.not(), self.span))
+      }
+      None => self.ignore_assertion(format!("(not {})", term)),
+    }
+    Ok(())
+  }
+
+  /** (> a b) for (<= a b) over Int, and so on, which holds iff the inequality doesn't */
+  fn opposite_inequality(&self, term: &Term) -> Option<Term> {
+    let (qual_identifier, arguments) = match term {
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } if arguments.len() == 2 => (qual_identifier, arguments),
+      _ => return None,
+    };
+    let opposite = match get_symbol(qual_identifier) {
+      "<=" => ">",
+      "<" => ">=",
+      ">=" => "<",
+      ">" => "<=",
+      _ => return None,
+    };
+    if !arguments.iter().any(|argument| self.is_int_term(argument)) {
+      return None;
+    }
+    Some(Term::Application {
+      qual_identifier: QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+          symbol: Symbol(String::from(opposite)),
+        },
+      },
+      arguments: arguments.clone(),
+    })
+  }
+
+  /**
+   * the atom of (not atom) amounting to a membership without Bool variables,
+   * which is asserted as the membership in the complement without waiting for case_split
+   */
+  fn negated_membership<'a>(&self, term: &'a Term) -> Option<&'a Term> {
+    match term {
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } if get_symbol(qual_identifier) == "not" => match &arguments[..] {
+        [atom @ Term::Application {
+          qual_identifier,
+          arguments,
+        }] if !Self::mentions(atom, &self.bool_vars) => self
+          .predicate_membership(get_symbol(qual_identifier), arguments)
+          .ok()?
+          .map(|_| atom),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /**
+   * check the literals a conditional assertion is split into, in both polarities,
+   * so that malformed ones are reported by parse rather than by case_split.
+   */
+  fn check_conditional(&self, term: &Term) -> Result<(), ParseError> {
+    match term {
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } if matches!(
+        get_symbol(qual_identifier),
+        "not" | "and" | "or" | "=>" | "xor" | "ite"
+      ) =>
+      {
+        arguments
+          .iter()
+          .try_for_each(|argument| self.check_conditional(argument))
+      }
+      term
+        if Self::mentions(term, &self.bool_vars)
+          || Self::eval_bool(term, &HashMap::new()).is_some() =>
+      {
+        Ok(())
+      }
+      term => [true, false].iter().try_for_each(|positive| {
+        Smt2 {
+          conditional: vec![],
+          assumptions: vec![],
+          ..self.clone()
+        }
+        .assert_literal(term, *positive)
+      }),
+    }
+  }
+
+  /**
+   * the membership the predicate on a variable amounts to, i.e.
+   * (str.prefixof s x) is x in s.*, (str.suffixof s x) is x in *.s
+   * and (str.contains x s) is x in *.s.*, where s must be a string constant.
+   * with extensions, (str.subseqof s x) is x in .*s1.*s2 ... .*sn.*
+   * (= (str.at x k) s) for a constant k is also a membership by Regex::char_at,
+   * so that it never goes through transducers, and so is (= x s) for the negation of it.
+   */
+  fn predicate_membership(
+    &self,
+    function: &str,
+    arguments: &[Term],
+  ) -> Result<Option<(VarIndex, Regex<D>)>, ParseError> {
+    fn string(term: &Term) -> Option<&str> {
+      match term {
+        Term::Constant(Constant::String(s)) => Some(s),
+        _ => None,
+      }
+    }
+    let literal = |term: &Term| string(term).map(Regex::seq);
+    let any = || Regex::all().star();
+    let membership = match (function, arguments) {
+      ("str.in.re", [x, reg]) => Some((x, Regex::new(reg)?)),
+      ("str.prefixof", [s, x]) => literal(s).map(|s| (x, s.concat(any()))),
+      ("str.suffixof", [s, x]) => literal(s).map(|s| (x, any().concat(s))),
+      ("str.contains", [x, s]) => string(s).map(|s| (x, Regex::superstring(s))),
+      ("str.subseqof", [s, x]) if self.option.extensions => {
+        string(s).map(|s| (x, Regex::subsequence(s)))
+      }
+      ("=", [x @ Term::QualIdentifier(_), s]) | ("=", [s, x @ Term::QualIdentifier(_)])
+        if string(s).is_some() =>
+      {
+        literal(s).map(|s| (x, s))
+      }
+      ("=", _) => Self::char_at(arguments).map(|(x, k, s)| (x, Regex::char_at(k, s))),
+      _ => None,
+    };
+    match membership {
+      Some((Term::QualIdentifier(qi), reg)) => Ok(Some((get_var(qi, &self.vars)?, reg))),
+      _ => Ok(None),
+    }
+  }
+
+  /** the variable, position and string constant of (= (str.at x k) s) or (= s (str.at x k)) */
+  fn char_at(arguments: &[Term]) -> Option<(&Term, usize, &str)> {
+    let (at, s) = match arguments {
+      [at, Term::Constant(Constant::String(s))] | [Term::Constant(Constant::String(s)), at] => {
+        (at, s)
+      }
+      _ => return None,
+    };
+    match at {
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } if get_symbol(qual_identifier) == "str.at" => match &arguments[..] {
+        [x @ Term::QualIdentifier(_), Term::Constant(Constant::Numeral(k))] => {
+          Some((x, k.to_string().parse().ok()?, s))
+        }
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /** value of a formula only over Bool variables and constants */
+  fn eval_bool(term: &Term, assignment: &HashMap<String, bool>) -> Option<bool> {
+    match term {
+      Term::QualIdentifier(qi) => match get_symbol(qi) {
+        "true" => Some(true),
+        "false" => Some(false),
+        var => assignment.get(var).copied(),
+      },
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } => {
+        let values = arguments
+          .iter()
+          .map(|argument| Self::eval_bool(argument, assignment))
+          .collect::<Option<Vec<_>>>()?;
+        match (get_symbol(qual_identifier), &values[..]) {
+          ("not", [b]) => Some(!b),
+          ("and", _) => Some(values.iter().all(|b| *b)),
+          ("or", _) => Some(values.iter().any(|b| *b)),
+          ("=>", [b1, b2]) => Some(!b1 || *b2),
+          ("xor", _) => Some(values.iter().filter(|b| **b).count() % 2 == 1),
+          ("=", [b1, b2]) => Some(b1 == b2),
+          ("ite", [c, b1, b2]) => Some(if *c { *b1 } else { *b2 }),
+          _ => None,
+        }
+      }
+      _ => None,
+    }
+  }
+
+  /** whether the assertion is resolved by case_split */
+  fn is_conditional(&self, term: &Term) -> bool {
+    let connective = match term {
+      Term::Application {
+        qual_identifier, ..
+      } => matches!(
+        get_symbol(qual_identifier),
+        "not" | "and" | "or" | "=>" | "xor" | "ite"
+      ),
+      _ => false,
+    };
+    connective || Self::mentions(term, &self.bool_vars)
+  }
+
+  /**
+   * the formula with ite terms inside atoms lifted to connectives, e.g.
+   * (= y (ite c x "a")) into (ite c (= y x) (= y "a")), so that case_split resolves them
+   */
+  fn lift_ite(term: &Term) -> Term {
+    match term {
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } if matches!(
+        get_symbol(qual_identifier),
+        "not" | "and" | "or" | "=>" | "xor" | "ite"
+      ) =>
+      {
+        Term::Application {
+          qual_identifier: qual_identifier.clone(),
+          arguments: arguments.iter().map(Self::lift_ite).collect(),
         }
       }
-      Command::Assert { term } => match term {
+      atom => match Self::split_ite(atom) {
+        Some(ite) => Self::lift_ite(&ite),
+        None => atom.clone(),
+      },
+    }
+  }
+
+  /**
+   * (ite c t e) of the outermost leftmost ite subterm (ite c x y) of the term,
+   * where t and e are the term with the subterm replaced by x and y respectively
+   */
+  fn split_ite(term: &Term) -> Option<Term> {
+    let (qual_identifier, arguments) = match term {
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } => (qual_identifier, arguments),
+      _ => return None,
+    };
+    if get_symbol(qual_identifier) == "ite" && arguments.len() == 3 {
+      return Some(term.clone());
+    }
+    arguments.iter().enumerate().find_map(|(i, argument)| {
+      let (ite, c, branches) = match Self::split_ite(argument)? {
         Term::Application {
           qual_identifier,
+          mut arguments,
+        } => {
+          let branches = arguments.split_off(1);
+          (qual_identifier, arguments.remove(0), branches)
+        }
+        _ => unreachable!("split_ite gives an application"),
+      };
+      let mut arguments_ite = vec![c];
+      for branch in branches {
+        let mut arguments = arguments.clone();
+        arguments[i] = branch;
+        arguments_ite.push(Term::Application {
+          qual_identifier: qual_identifier.clone(),
           arguments,
-        } => match get_symbol(&qual_identifier) {
-          "=" => {
-            if let [qi, transduction] = &arguments[..] {
-              if let Term::QualIdentifier(qi) = qi {
-                self.sl_constraints.push(StraightLineConstraint(
-                  get_var(qi, &self.vars),
-                  Transduction::from(transduction, &self.vars),
-                ))
-              } else {
-                unimplemented!()
-              }
-            } else {
-              panic!("Syntax error")
+        });
+      }
+      Some(Term::Application {
+        qual_identifier: ite,
+        arguments: arguments_ite,
+      })
+    })
+  }
+
+  /** whether the term refers to some of vars */
+  fn mentions(term: &Term, vars: &Variables) -> bool {
+    match term {
+      Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+          symbol: Symbol(symbol),
+        },
+      }) => vars.contains(symbol),
+      Term::Application { arguments, .. } => arguments
+        .iter()
+        .any(|argument| Self::mentions(argument, vars)),
+      _ => false,
+    }
+  }
+
+  /** whether the term is an integer one, e.g. (str.len x) or an Int variable */
+  fn is_int_term(&self, term: &Term) -> bool {
+    match term {
+      Term::Constant(Constant::Numeral(_)) => true,
+      Term::QualIdentifier(qi) => self.int_vars.iter().any(|var| var == get_symbol(qi)),
+      Term::Application {
+        qual_identifier, ..
+      } => matches!(
+        get_symbol(qual_identifier),
+        "str.len" | "str.indexof" | "str.to_int" | "str.to.int" | "+" | "-" | "*"
+      ) || (self.option.extensions && get_symbol(qual_identifier) == "str.count"),
+      _ => false,
+    }
+  }
+
+  /** whether the term is (str.from_int i) */
+  fn is_from_int(term: &Term) -> bool {
+    match term {
+      Term::Application {
+        qual_identifier, ..
+      } => matches!(get_symbol(qual_identifier), "str.from_int" | "int.to.str"),
+      _ => false,
+    }
+  }
+
+  /**
+   * assert (= x (str.from_int i)) as (= (str.to_int x) i) where x is a decimal without leading zeros.
+   * i is assumed to be non-negative, dropping the models where x is empty.
+   */
+  fn assert_from_int(&mut self, term: &Term, arguments: &[Term]) -> Result<(), ParseError> {
+    let (idx, int_term) = match arguments {
+      [Term::QualIdentifier(qi), from_int @ Term::Application {
+        qual_identifier,
+        arguments,
+      }] => match &arguments[..] {
+        [int_term] => (get_var(qi, &self.vars)?, int_term),
+        _ => {
+          return Err(ParseError::arity(
+            get_symbol(qual_identifier),
+            from_int,
+            Arity::Exactly(1),
+          ))
+        }
+      },
+      _ => {
+        return Err(ParseError::unsupported(format!(
+          "definition of a non-variable {}",
+          term
+        )))
+      }
+    };
+    /* constants are given directly, since the search of values is bounded */
+    if let Term::Constant(Constant::Numeral(n)) = int_term {
+      let reg = Regex::seq(&n.to_string());
+      self
+        .reg_constraints
+        .push(RegularConstraint(idx, reg, self.span));
+      return Ok(());
+    }
+
+    let mut terms = vec![LinearTerm {
+      atom: Some(IntAtom::ToInt(idx)),
+      coefficient: 1,
+    }];
+    self.linear(int_term, -1, &mut terms)?;
+    self
+      .length_constraints
+      .push(IntLinearConstraint(Relation::Eq, terms, self.span));
+    self
+      .reg_constraints
+      .push(RegularConstraint(idx, from_int_regex(), self.span));
+    self.approximate(format!(
+      "{} in the definition of {} is assumed to be non-negative",
+      int_term, self.vars[idx]
+    ));
+    Ok(())
+  }
+
+  /** assert (op lhs rhs) over integer terms, normalized into lhs - rhs compared to 0 */
+  fn assert_linear(&mut self, term: &Term, op: &str, arguments: &[Term]) -> Result<(), ParseError> {
+    let (lhs, rhs) = match arguments {
+      [lhs, rhs] => (lhs, rhs),
+      _ => return Err(ParseError::arity(op, term, Arity::Exactly(2))),
+    };
+    let mut terms = vec![];
+    let (relation, lhs, rhs, offset) = match op {
+      "=" => (Relation::Eq, lhs, rhs, 0),
+      "<=" => (Relation::Le, lhs, rhs, 0),
+      ">=" => (Relation::Le, rhs, lhs, 0),
+      "<" => (Relation::Le, lhs, rhs, 1),
+      _ => (Relation::Le, rhs, lhs, 1),
+    };
+    self.linear(lhs, 1, &mut terms)?;
+    self.linear(rhs, -1, &mut terms)?;
+    if offset != 0 {
+      terms.push(LinearTerm {
+        atom: None,
+        coefficient: offset,
+      });
+    }
+    self
+      .length_constraints
+      .push(IntLinearConstraint(relation, terms, self.span));
+    Ok(())
+  }
+
+  /** terms of the linear integer term multiplied by coefficient */
+  fn linear(
+    &mut self,
+    term: &Term,
+    coefficient: i64,
+    terms: &mut Vec<LinearTerm>,
+  ) -> Result<(), ParseError> {
+    let unsupported = || ParseError::unsupported(format!("integer term {}", term));
+    match term {
+      Term::Constant(Constant::Numeral(n)) => terms.push(LinearTerm {
+        atom: None,
+        coefficient: coefficient * n.to_string().parse::<i64>().map_err(|_| unsupported())?,
+      }),
+      Term::QualIdentifier(qi) => {
+        let idx = get_var(qi, &self.int_vars)?;
+        terms.push(LinearTerm {
+          atom: Some(IntAtom::Var(idx)),
+          coefficient,
+        })
+      }
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } => match (get_symbol(qual_identifier), &arguments[..]) {
+        ("str.len", [Term::QualIdentifier(qi)]) => terms.push(LinearTerm {
+          atom: Some(IntAtom::Len(get_var(qi, &self.vars)?)),
+          coefficient,
+        }),
+        ("str.to_int" | "str.to.int", [Term::QualIdentifier(qi)]) => terms.push(LinearTerm {
+          atom: Some(IntAtom::ToInt(get_var(qi, &self.vars)?)),
+          coefficient,
+        }),
+        (
+          "str.indexof",
+          [Term::QualIdentifier(qi), Term::Constant(Constant::String(pattern)), start],
+        ) => {
+          let start = match IntArg::from(start) {
+            IntArg::Const(start) => start,
+            IntArg::Symbolic(_) => return Err(unsupported()),
+          };
+          let index_pattern = IndexPattern {
+            pattern: pattern.clone(),
+            start,
+          };
+          let i = match self.index_patterns.iter().position(|p| *p == index_pattern) {
+            Some(i) => i,
+            None => {
+              self.index_patterns.push(index_pattern);
+              self.index_patterns.len() - 1
             }
+          };
+          terms.push(LinearTerm {
+            atom: Some(IntAtom::IndexOf(get_var(qi, &self.vars)?, i)),
+            coefficient,
+          })
+        }
+        ("str.count", [Term::QualIdentifier(qi), Term::Constant(Constant::String(pattern))])
+          if self.option.extensions =>
+        {
+          if !is_border_free(pattern) {
+            return Err(ParseError::unsupported(format!(
+              "str.count of the overlapping pattern {}",
+              term
+            )));
           }
-          "str.in.re" => {
-            if let [qi, reg] = &arguments[..] {
-              if let Term::QualIdentifier(qi) = qi {
-                self
-                  .reg_constraints
-                  .push(RegularConstraint(get_var(qi, &self.vars), Regex::new(reg)))
-              } else {
-                panic!("Syntax error")
-              }
-            } else {
-              panic!("Syntax error")
+          let i = match self.count_patterns.iter().position(|p| p == pattern) {
+            Some(i) => i,
+            None => {
+              self.count_patterns.push(pattern.clone());
+              self.count_patterns.len() - 1
             }
-          }
-          s => eprintln!("Unsupported identifier: {}", s),
-        },
-        _ => eprintln!("Unsupported assertion: {:?}", term),
+          };
+          terms.push(LinearTerm {
+            atom: Some(IntAtom::Count(get_var(qi, &self.vars)?, i)),
+            coefficient,
+          })
+        }
+        ("+", _) => arguments
+          .iter()
+          .try_for_each(|argument| self.linear(argument, coefficient, terms))?,
+        ("-", [argument]) => self.linear(argument, -coefficient, terms)?,
+        ("-", [first, rest @ ..]) => {
+          self.linear(first, coefficient, terms)?;
+          rest
+            .iter()
+            .try_for_each(|argument| self.linear(argument, -coefficient, terms))?;
+        }
+        ("*", [Term::Constant(Constant::Numeral(k)), t])
+        | ("*", [t, Term::Constant(Constant::Numeral(k))]) => {
+          let k = k.to_string().parse::<i64>().map_err(|_| unsupported())?;
+          self.linear(t, coefficient * k, terms)?
+        }
+        _ => return Err(unsupported()),
       },
-      Command::CheckSat => self.option.check_sat = true,
-      Command::GetModel => self.option.get_model = true,
-      _ => eprintln!("Unsupported command: {:?}", command),
+      _ => return Err(unsupported()),
+    }
+    Ok(())
+  }
+
+  /** declare var of sort, which is String, Int or Bool */
+  fn is_declared(&self, var: &str) -> bool {
+    [&self.vars, &self.int_vars, &self.bool_vars]
+      .iter()
+      .any(|vars| vars.iter().any(|v| v == var))
+  }
+
+  fn declare(&mut self, var: String, sort: &Sort) -> Result<(), ParseError> {
+    if self.is_declared(&var) || self.names.contains_key(&var) {
+      return Err(ParseError::Redeclared {
+        span: self.span,
+        name: var,
+      });
+    }
+    let name = match sort {
+      Sort::Simple {
+        identifier: Identifier::Simple { symbol: Symbol(s) },
+      } => &s[..],
+      _ => "",
+    };
+    match name {
+      "String" | "string" => self.vars.push(var),
+      "Int" | "int" => self.int_vars.push(var),
+      "Bool" | "bool" => self.bool_vars.push(var),
+      _ => {
+        return Err(ParseError::unsupported(format!(
+          "sort {} of variable {}",
+          sort, var
+        )))
+      }
+    }
+    Ok(())
+  }
+
+  /**
+   * term where RegLan constants and names of assertions are replaced with their terms,
+   * and let-bound symbols are inlined
+   */
+  fn expand(&self, term: &Term) -> Term {
+    self.expand_in(term, &HashMap::new())
+  }
+
+  /** expand where env binds the symbols of the enclosing lets */
+  fn expand_in(&self, term: &Term, env: &HashMap<String, Term>) -> Term {
+    match term {
+      Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+          symbol: Symbol(symbol),
+        },
+      }) => match env
+        .get(symbol)
+        .or_else(|| self.reg_definitions.get(symbol))
+        .or_else(|| self.names.get(symbol))
+      {
+        Some(bound) => bound.clone(),
+        None => term.clone(),
+      },
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } => Term::Application {
+        qual_identifier: qual_identifier.clone(),
+        arguments: arguments
+          .iter()
+          .map(|term| self.expand_in(term, env))
+          .collect(),
+      },
+      /* bindings are parallel, i.e. each one is in the scope out of the let */
+      Term::Let { var_bindings, term } => {
+        let mut inner = env.clone();
+        for (Symbol(symbol), bound) in var_bindings {
+          inner.insert(symbol.clone(), self.expand_in(bound, env));
+        }
+        self.expand_in(term, &inner)
+      }
+      term => term.clone(),
+    }
+  }
+
+  /** bind a RegLan constant, e.g. (define-fun R () RegLan (re.* (str.to.re "a"))) */
+  fn define_regex(&mut self, name: String, term: &Term) -> Result<(), ParseError> {
+    if self.vars.contains(&name)
+      || self.int_vars.contains(&name)
+      || self.bool_vars.contains(&name)
+      || self.reg_definitions.contains_key(&name)
+    {
+      return Err(ParseError::Redeclared {
+        span: self.span,
+        name,
+      });
+    }
+    let term = self.expand(term);
+    self.reg_definitions.insert(name, term);
+    Ok(())
+  }
+
+  /** take the command, where errors point at the command being processed */
+  fn update(&mut self, command: Command) -> Result<(), ParseError> {
+    self.apply(command).map_err(|err| err.at(self.span))
+  }
+
+  fn apply(&mut self, command: Command) -> Result<(), ParseError> {
+    let command = match command {
+      Command::Assert {
+        term: Term::Attributes { term, attributes },
+      } => {
+        let term = self.expand(&term);
+        for (keyword, value) in attributes {
+          if let ("named", AttributeValue::Symbol(Symbol(name))) =
+            (keyword.0.trim_start_matches(':'), value)
+          {
+            if self.is_declared(&name) || self.names.contains_key(&name) {
+              return Err(ParseError::Redeclared {
+                span: self.span,
+                name,
+              });
+            }
+            self.names.insert(name, term.clone());
+          }
+        }
+        return self.apply(Command::Assert { term });
+      }
+      Command::Assert { term } => {
+        let expanded = self.expand(&term);
+        if expanded != term {
+          self.record(
+            TraceKind::Rewritten,
+            format!("definitions in {} expanded into {}", term, expanded),
+          );
+        }
+        let lifted = Self::lift_ite(&expanded);
+        if lifted != expanded {
+          self.record(
+            TraceKind::Rewritten,
+            format!("ite terms in {} lifted into {}", expanded, lifted),
+          );
+        }
+        Command::Assert { term: lifted }
+      }
+      command => command,
+    };
+    match command {
+      Command::DeclareConst {
+        symbol: Symbol(var),
+        sort,
+      } => self.declare(var, &sort)?,
+      Command::DeclareFun {
+        symbol: Symbol(var),
+        parameters,
+        sort,
+      } => {
+        if parameters.is_empty() {
+          self.declare(var, &sort)?
+        } else {
+          return Err(ParseError::unsupported(format!(
+            "function {} with parameters",
+            var
+          )));
+        }
+      }
+      Command::DefineFun {
+        sig:
+          FunctionDec {
+            name: Symbol(name),
+            parameters,
+            result:
+              Sort::Simple {
+                identifier:
+                  Identifier::Simple {
+                    symbol: Symbol(result),
+                  },
+              },
+          },
+        term,
+      } if parameters.is_empty() && result == "RegLan" => self.define_regex(name, &term)?,
+      Command::Assert { term } if self.is_conditional(&term) => match self.negated_membership(&term) {
+        Some(atom) => self.assert_literal(atom, false)?,
+        None => {
+          self.check_conditional(&term)?;
+          self.conditional.push((self.span, term))
+        }
+      },
+      Command::Assert { term } => match &term {
+        Term::QualIdentifier(qi) => match get_symbol(qi) {
+          "true" => {}
+          "false" => self.contradictory = true,
+          s => self.ignore_assertion(s.to_owned()),
+        },
+        Term::Application {
+          qual_identifier,
+          arguments,
+        } => match get_symbol(qual_identifier) {
+          op @ ("=" | "<=" | ">=" | "<" | ">")
+            if arguments.iter().any(|argument| self.is_int_term(argument)) =>
+          {
+            self.assert_linear(&term, op, arguments)?
+          }
+          "=" if arguments.len() == 2 && Self::is_from_int(&arguments[1]) => {
+            self.assert_from_int(&term, arguments)?
+          }
+          "=" if Self::char_at(arguments).is_some() => {
+            if let Some((idx, reg)) = self.predicate_membership("=", arguments)? {
+              self
+                .reg_constraints
+                .push(RegularConstraint(idx, reg, self.span))
+            }
+          }
+          "=" => {
+            let (qi, transduction) = match &arguments[..] {
+              [Term::QualIdentifier(qi), transduction] => (qi, transduction),
+              [lhs, _] => {
+                return Err(ParseError::unsupported(format!(
+                  "definition of a non-variable {}",
+                  lhs
+                )))
+              }
+              _ => return Err(ParseError::arity("=", &term, Arity::Exactly(2))),
+            };
+            let idx = get_var(qi, &self.vars)?;
+            if let Some(sl_cons) = self.filter_sl(idx) {
+              return Err(ParseError::Redefined {
+                span: self.span,
+                name: self.vars[idx].clone(),
+                first: sl_cons.span(),
+              });
+            }
+            let transduction = Transduction::from(transduction, &self.vars)?;
+            if self.refers_to(transduction.vars(), idx) {
+              return Err(ParseError::unsupported(format!(
+                "cyclic definition of {}",
+                self.vars[idx]
+              )));
+            }
+            if transduction.is_abstracted() {
+              self.approximate(format!(
+                "integer arguments in the definition of {} are abstracted",
+                self.vars[idx]
+              ));
+            }
+            self
+              .sl_constraints
+              .push(StraightLineConstraint(idx, transduction, self.span))
+          }
+          function @ ("str.in.re" | "str.prefixof" | "str.suffixof" | "str.contains"
+          | "str.subseqof") => match self.predicate_membership(function, arguments)? {
+            Some((idx, reg)) => self
+              .reg_constraints
+              .push(RegularConstraint(idx, reg, self.span)),
+            None if function == "str.in.re" => {
+              return Err(match &arguments[..] {
+                [x, _] => ParseError::unsupported(format!("membership of a non-variable {}", x)),
+                _ => ParseError::arity(function, &term, Arity::Exactly(2)),
+              })
+            }
+            None => self.ignore_assertion(format!(
+              "({} {})",
+              function,
+              arguments
+                .iter()
+                .map(|argument| argument.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+            )),
+          },
+          s => self.ignore_assertion(s.to_owned()),
+        },
+        _ => self.ignore_assertion(format!("{}", term)),
+      },
+      Command::SetOption { keyword, value } if keyword.0.trim_start_matches(':') == "alphabet" => {
+        self.set_alphabet(&value)?
+      }
+      Command::SetOption { keyword, value }
+        if keyword.0.trim_start_matches(':') == "str-extensions" =>
+      {
+        self.set_extensions(&value)?
+      }
+      Command::CheckSat => {
+        self.option.check_sat = true;
+        let assumptions = std::mem::take(&mut self.assumptions);
+        self.record_check();
+        self.assumptions = assumptions;
+      }
+      Command::CheckSatAssuming { literals } => {
+        /* the literals of a former check-sat-assuming are replaced */
+        self.assumptions.clear();
+        for (Symbol(name), positive) in literals {
+          let term = match self.names.get(&name) {
+            Some(term) => term.clone(),
+            None if self.bool_vars.contains(&name) => Term::QualIdentifier(QualIdentifier::Simple {
+              identifier: Identifier::Simple {
+                symbol: Symbol(name),
+              },
+            }),
+            None => {
+              return Err(ParseError::UnknownVariable {
+                span: self.span,
+                name,
+              })
+            }
+          };
+          let term = if positive {
+            term
+          } else {
+            Term::Application {
+              qual_identifier: QualIdentifier::Simple {
+                identifier: Identifier::Simple {
+                  symbol: Symbol(String::from("not")),
+                },
+              },
+              arguments: vec![term],
+            }
+          };
+          self.check_conditional(&term)?;
+          self.assumptions.push((self.span, term));
+        }
+        self.option.check_sat = true;
+        self.record_check();
+      }
+      Command::GetModel => {
+        self.option.get_model = true;
+        if let Some(check) = self.checks.last_mut() {
+          check.option.get_model = true;
+        }
+      }
+      Command::GetValue { terms } => {
+        /* evaluating over the model leaving out every variable finds malformed terms now */
+        let mut pairs = vec![];
+        for term in terms {
+          let expanded = self.expand(&term);
+          self.eval(&expanded, &HashMap::new())?;
+          pairs.push((term, expanded));
+        }
+        if let Some(check) = self.checks.last_mut() {
+          check.option.get_values.push(pairs.clone());
+        }
+        self.option.get_values.push(pairs);
+      }
+      Command::Push { level } => self.push(Self::level(&level)?),
+      Command::Pop { level } => self.pop(Self::level(&level)?)?,
+      Command::Reset => self.reset(),
+      Command::ResetAssertions => self.reset_assertions(),
+      _ => self
+        .warnings
+        .push(Warning::UnsupportedCommand(format!("{}", command))),
+    }
+    Ok(())
+  }
+
+  /** the level of (push n) or (pop n) */
+  fn level(level: &Numeral) -> Result<usize, ParseError> {
+    level
+      .to_string()
+      .parse()
+      .map_err(|_| ParseError::malformed(level, "a number of levels"))
+  }
+
+  /**
+   * (set-option :alphabet (x y "[0-9]")) restricts the variables to words of the characters
+   * of the pattern in the syntax of Regex::parse, e.g. digits-only IDs.
+   */
+  fn set_alphabet(&mut self, value: &AttributeValue) -> Result<(), ParseError> {
+    let expected = "variables followed by a pattern";
+    let (vars, pattern) = match value {
+      AttributeValue::SExpr(exprs) => match &exprs[..] {
+        [vars @ .., SExpr::Constant(Constant::String(pattern))] if !vars.is_empty() => {
+          (vars, pattern)
+        }
+        _ => return Err(ParseError::malformed(value, expected)),
+      },
+      _ => return Err(ParseError::malformed(value, expected)),
+    };
+    let alphabet = Regex::parse(pattern).map_err(|error| ParseError::Pattern {
+      span: self.span,
+      pattern: pattern.clone(),
+      error,
+    })?;
+    for var in vars {
+      match var {
+        SExpr::Symbol(Symbol(var)) => self.restrict_alphabet(var, alphabet.clone())?,
+        _ => return Err(ParseError::malformed(var, "a variable")),
+      }
+    }
+    Ok(())
+  }
+
+  /**
+   * (set-option :str-extensions true) accepts functions out of SMT-LIB in the following commands,
+   * i.e. (str.subseqof s x) for s being a scattered subsequence of x,
+   * and (str.count x s) for the number of occurrences of s in x in integer terms,
+   * where s must not overlap with itself, e.g. a single character.
+   */
+  fn set_extensions(&mut self, value: &AttributeValue) -> Result<(), ParseError> {
+    self.option.extensions = match value {
+      AttributeValue::Symbol(Symbol(value)) if value == "true" => true,
+      AttributeValue::Symbol(Symbol(value)) if value == "false" => false,
+      _ => return Err(ParseError::malformed(value, "true or false")),
+    };
+    Ok(())
+  }
+
+  /**
+   * restrict the variable to words of the characters of alphabet, by the membership of its star.
+   * the predicates of the product are narrowed by it from the start, since memberships
+   * are intersected before any pre-image. it is removed by (reset-assertions) as memberships are.
+   */
+  fn restrict_alphabet(&mut self, var: &str, alphabet: Regex<D>) -> Result<(), ParseError> {
+    self.assert_membership(var, Regex::Star(Box::new(alphabet)))
+  }
+
+  /** assert that the declared String variable is in reg */
+  pub(crate) fn assert_membership(&mut self, var: &str, reg: Regex<D>) -> Result<(), ParseError> {
+    let idx = get_var_from_str(var, &self.vars)?;
+    self.reg_constraints.push(RegularConstraint(idx, reg, self.span));
+    Ok(())
+  }
+
+  /**
+   * the number of constraints without_constraint can remove, i.e. memberships, definitions,
+   * linear constraints, conditional assertions and literals of check-sat-assuming
+   */
+  pub(crate) fn removable_constraints(&self) -> usize {
+    self.reg_constraints.len()
+      + self.sl_constraints.len()
+      + self.length_constraints.len()
+      + self.conditional.len()
+      + self.assumptions.len()
+  }
+
+  /** the problem without the i-th of the removable constraints, counted in the order above */
+  pub(crate) fn without_constraint(&self, mut i: usize) -> Self {
+    let mut smt2 = self.clone();
+    if i < smt2.reg_constraints.len() {
+      smt2.reg_constraints.remove(i);
+      return smt2;
+    }
+    i -= smt2.reg_constraints.len();
+    if i < smt2.sl_constraints.len() {
+      smt2.sl_constraints.remove(i);
+      return smt2;
+    }
+    i -= smt2.sl_constraints.len();
+    if i < smt2.length_constraints.len() {
+      smt2.length_constraints.remove(i);
+      return smt2;
+    }
+    i -= smt2.length_constraints.len();
+    if i < smt2.conditional.len() {
+      smt2.conditional.remove(i);
+      return smt2;
+    }
+    i -= smt2.conditional.len();
+    smt2.assumptions.remove(i);
+    smt2
+  }
+
+  /** take further commands, failing on the ones unknown to the parser as parse_with does if strict */
+  pub(crate) fn extend(&mut self, input: &str) -> Result<(), ParseError> {
+    let commands = CommandStream::new(input.as_bytes(), SyntaxBuilder, None)
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|err| ParseError::Syntax {
+        span: Span::default(),
+        message: format!("{:?}", err),
+      })?;
+    for command in commands {
+      self.update(command)?;
+    }
+    Ok(())
+  }
+
+  /**
+   * the problem with the variable restricted to words of the characters of alphabet,
+   * which fails if the String variable isn't declared.
+   */
+  pub fn with_alphabet(mut self, var: &str, alphabet: Regex<D>) -> Result<Self, ParseError> {
+    self.restrict_alphabet(var, alphabet)?;
+    Ok(self)
+  }
+
+  pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+    self.strategy = strategy;
+    self
+  }
+
+  /** the construction of the membership of each constrained variable under the strategy */
+  pub fn plan(&self) -> Vec<(String, Construction)> {
+    (0..self.vars.len())
+      .filter_map(|idx| {
+        let reg = self.membership(idx)?;
+        let construction = match self.strategy {
+          Strategy::Inductive => Construction::Inductive,
+          Strategy::Auto => reg.plan(),
+        };
+        Some((self.vars[idx].clone(), construction))
+      })
+      .collect()
+  }
+
+  /**
+   * assume all strings are ASCII, so that the product of memberships is built over AsciiSet
+   * and converted back afterwards. the problem is solved as usual if it mentions other characters.
+   */
+  pub fn with_ascii(mut self, ascii: bool) -> Self {
+    self.ascii = ascii;
+    self
+  }
+
+  /**
+   * build the product of memberships over bitsets of the classes of characters they distinguish,
+   * converted back afterwards, if there are at most MAX_CLASSES classes.
+   */
+  pub fn with_bitsets(mut self, bitsets: bool) -> Self {
+    self.bitsets = bitsets;
+    self
+  }
+
+  /**
+   * give models whose strings are the shortest in total,
+   * searching the final automaton breadth-first instead of depth-first.
+   */
+  pub fn with_shortest(mut self, shortest: bool) -> Self {
+    self.shortest = shortest;
+    self
+  }
+
+  pub fn shortest(&self) -> bool {
+    self.shortest
+  }
+
+  /** whether every character the constraints mention is ASCII */
+  pub fn is_ascii(&self) -> bool {
+    self
+      .reg_constraints
+      .iter()
+      .all(|reg_cons| reg_cons.constraint().is_ascii())
+      && self
+        .sl_constraints
+        .iter()
+        .all(|sl_cons| sl_cons.constraint().is_ascii())
+  }
+
+  /** the empty automaton if the problem is contradictory */
+  pub fn emit_sfa(&self) -> Sfa<D, S> {
+    if self.contradictory {
+      return Sfa::empty();
+    }
+    /* the product of no memberships, whose only word is the empty encoding */
+    if self.vars.is_empty() {
+      return Regex::Epsilon.to_sfa();
+    }
+    if self.ascii && self.is_ascii() {
+      return self
+        .emit_symfa::<AsciiSet<D>>()
+        .map_predicates(|phi| phi.to_predicate());
+    }
+    if self.bitsets {
+      let classes = self.classes();
+      if classes.len() <= MAX_CLASSES {
+        return classes.scope(|| {
+          self
+            .emit_symfa::<ClassSet<D>>()
+            .map_predicates(|phi| phi.to_predicate())
+        });
+      }
+    }
+    self.emit_symfa()
+  }
+
+  /** the classes of characters the memberships distinguish, and the separator */
+  pub fn classes(&self) -> Classes {
+    let separator: char = D::separator().into();
+    Classes::new(
+      self
+        .reg_constraints
+        .iter()
+        .flat_map(|reg_cons| reg_cons.constraint().boundaries())
+        .chain([separator as u32, separator as u32 + 1]),
+    )
+  }
+
+  fn emit_symfa<B>(&self) -> SymFa<D, B, S>
+  where
+    B: BoolAlg<Domain = D> + From<Predicate<D>>,
+  {
+    (0..self.vars.len())
+      .into_iter()
+      .map(|idx| {
+        self
+          .membership(idx)
+          .map(|reg| match self.strategy {
+            Strategy::Inductive => reg.to_symfa(),
+            Strategy::Auto => {
+              let construction = reg.plan();
+              reg.to_symfa_with(construction)
+            }
+          })
+          .unwrap_or_default()
+      })
+      .reduce(|result, sfa| result.chain(sfa))
+      .map(|sfa| sfa.finish())
+      .expect("no string constraint given")
+  }
+
+  /** whether some of vars is the variable or defined by it through definitions */
+  fn refers_to(&self, vars: Vec<VarIndex>, idx: VarIndex) -> bool {
+    let mut visited = HashSet::new();
+    let mut pending = vars;
+    while let Some(var) = pending.pop() {
+      if var == idx {
+        return true;
+      }
+      if visited.insert(var) {
+        if let Some(sl_cons) = self.filter_sl(var) {
+          pending.extend(sl_cons.constraint().vars());
+        }
+      }
+    }
+    false
+  }
+
+  pub fn filter_sl(&self, idx: VarIndex) -> Option<&StraightLineConstraint<D, S>> {
+    self
+      .sl_constraints
+      .iter()
+      .find(|sl_cons| sl_cons.idx() == idx)
+  }
+
+  pub fn filter_reg(&mut self, idx: VarIndex) -> Option<Regex<D>> {
+    let mut result: Option<Regex<D>> = None;
+
+    while let Some(i) = self
+      .reg_constraints
+      .iter()
+      .position(|reg_cons| reg_cons.idx() == idx)
+    {
+      let regex = self.reg_constraints.swap_remove(i).1;
+      result = Some(match result {
+        Some(result) => result.inter(regex),
+        None => regex,
+      });
+    }
+
+    result
+  }
+
+  /** intersection of all regular constraints on the variable, without consuming them */
+  pub fn membership(&self, idx: VarIndex) -> Option<Regex<D>> {
+    self
+      .reg_constraints
+      .iter()
+      .filter(|reg_cons| reg_cons.idx() == idx)
+      .map(|reg_cons| reg_cons.1.clone())
+      .reduce(|result, regex| result.inter(regex))
+  }
+
+  /**
+   * membership as an automaton,
+   * whose states are traced back to the regular constraints labeled by their positions.
+   */
+  pub fn membership_with_provenance(
+    &self,
+    idx: VarIndex,
+  ) -> Option<(Sfa<D, S>, Provenance<S, Span>)> {
+    self
+      .reg_constraints
+      .iter()
+      .filter(|reg_cons| reg_cons.idx() == idx)
+      .map(|reg_cons| {
+        let sfa = reg_cons.constraint().clone().to_sfa::<S>();
+        let provenance = Provenance::of(&sfa, reg_cons.span());
+        (sfa, provenance)
+      })
+      .reduce(|(sfa, provenance), (other, other_provenance)| {
+        sfa.inter_with_provenance(&provenance, other, &other_provenance)
+      })
+  }
+
+  /** the problem in straight-line form, see StraightLine */
+  pub fn straight_line(&self) -> StraightLine<'_, D, S> {
+    StraightLine {
+      definitions: self.sl_constraints.iter().collect(),
+      memberships: (0..self.vars.len())
+        .map(|idx| self.membership(idx))
+        .collect(),
+      free_vars: (0..self.vars.len())
+        .filter(|idx| self.filter_sl(*idx).is_none())
+        .collect(),
+    }
+  }
+
+  /**
+   * defined variables in the order their pre-images are applied,
+   * i.e. the reverse of an order where each definition follows the ones it refers to.
+   * among the variables no remaining definition refers to, the most constrained one goes first:
+   * the one with the smallest membership automaton, then the later declared one.
+   */
+  pub fn elimination_order(&self) -> Vec<VarIndex> {
+    let sizes: HashMap<VarIndex, usize> = self
+      .sl_constraints
+      .iter()
+      .map(|sl_cons| {
+        let size = self
+          .membership(sl_cons.idx())
+          .map_or(usize::MAX, |reg| reg.to_sfa::<S>().states.len());
+        (sl_cons.idx(), size)
+      })
+      .collect();
+
+    let mut remaining: Vec<&StraightLineConstraint<D, S>> = self.sl_constraints.iter().collect();
+    let mut order = vec![];
+    while !remaining.is_empty() {
+      let (i, next) = remaining
+        .iter()
+        .enumerate()
+        .filter(|(_, sl_cons)| {
+          remaining
+            .iter()
+            .all(|other| !other.constraint().vars().contains(&sl_cons.idx()))
+        })
+        .min_by_key(|(_, sl_cons)| (sizes[&sl_cons.idx()], std::cmp::Reverse(sl_cons.idx())))
+        .map(|(i, sl_cons)| (i, sl_cons.idx()))
+        .unwrap_or_else(|| panic!("definitions are cyclic at {}", remaining[0].span()));
+      remaining.remove(i);
+      order.push(next);
+    }
+    order
+  }
+
+  /**
+   * the same problem with variables renumbered so that
+   * undefined variables come first and defined ones are eliminated in the given order.
+   * definitions are sorted accordingly, as compile expects.
+   */
+  pub fn reorder(&self, elimination: &[VarIndex]) -> Self {
+    let mut order: Vec<VarIndex> = (0..self.vars.len())
+      .filter(|idx| !elimination.contains(idx))
+      .collect();
+    order.extend(elimination.iter().rev());
+    self.restrict(&order)
+  }
+
+  /**
+   * variables restricted by regular constraints,
+   * and the ones their definitions refer to transitively.
+   * the others can't affect satisfiability, since any value of them can be defined.
+   */
+  pub fn cone_of_influence(&self) -> Vec<VarIndex> {
+    let mut influenced = vec![false; self.vars.len()];
+    let mut stack: Vec<VarIndex> = self.reg_constraints.iter().map(|c| c.idx()).collect();
+    while let Some(idx) = stack.pop() {
+      if influenced[idx] {
+        continue;
+      }
+      influenced[idx] = true;
+      if let Some(sl_cons) = self.filter_sl(idx) {
+        stack.extend(sl_cons.constraint().vars());
+      }
+    }
+    (0..self.vars.len())
+      .filter(|idx| influenced[*idx])
+      .collect()
+  }
+
+  /**
+   * the problem restricted to cone_of_influence, and the variables sliced away.
+   * nothing is sliced if no variable has regular constraints.
+   */
+  pub fn slice(&self) -> (Self, Vec<VarIndex>) {
+    let cone = self.cone_of_influence();
+    if cone.is_empty() {
+      return (self.clone(), vec![]);
+    }
+    let sliced: Vec<_> = (0..self.vars.len())
+      .filter(|idx| !cone.contains(idx))
+      .collect();
+    let mut restricted = self.restrict(&cone);
+    for idx in &sliced {
+      restricted.trace.push(TraceEntry {
+        span: self.filter_sl(*idx).map(|sl_cons| sl_cons.span()),
+        kind: TraceKind::Eliminated,
+        detail: format!("{} is out of the cone of influence", self.vars[*idx]),
+      });
+    }
+    (restricted, sliced)
+  }
+
+  /**
+   * the problem only with the given variables in the given order.
+   * definitions of them must refer to no other variable.
+   */
+  fn restrict(&self, order: &[VarIndex]) -> Self {
+    let mut map = vec![usize::MAX; self.vars.len()];
+    for (new, old) in order.iter().enumerate() {
+      map[*old] = new;
+    }
+
+    let mut sl_constraints: Vec<_> = self
+      .sl_constraints
+      .iter()
+      .filter(|sl_cons| order.contains(&sl_cons.idx()))
+      .map(|sl_cons| {
+        StraightLineConstraint(map[sl_cons.idx()], sl_cons.1.renumber(&map), sl_cons.2)
+      })
+      .collect();
+    sl_constraints.sort_by_key(|sl_cons| sl_cons.idx());
+
+    Smt2 {
+      sl_constraints,
+      reg_constraints: self
+        .reg_constraints
+        .iter()
+        .filter(|reg_cons| order.contains(&reg_cons.idx()))
+        .map(|reg_cons| RegularConstraint(map[reg_cons.idx()], reg_cons.1.clone(), reg_cons.2))
+        .collect(),
+      length_constraints: self
+        .length_constraints
+        .iter()
+        .filter(|int_cons| {
+          int_cons
+            .atoms()
+            .iter()
+            .filter_map(|atom| atom.string_var())
+            .all(|idx| order.contains(&idx))
+        })
+        .map(|int_cons| {
+          let terms = int_cons
+            .1
+            .iter()
+            .map(|term| LinearTerm {
+              atom: term.atom.map(|atom| atom.renumber(&map)),
+              coefficient: term.coefficient,
+            })
+            .collect();
+          IntLinearConstraint(int_cons.0, terms, int_cons.2)
+        })
+        .collect(),
+      vars: order.iter().map(|idx| self.vars[*idx].clone()).collect(),
+      ..self.clone()
+    }
+  }
+
+  pub fn sl_constraints(&self) -> &Vec<StraightLineConstraint<D, S>> {
+    &self.sl_constraints
+  }
+
+  pub fn reg_constraints(&self) -> &Vec<RegularConstraint<D>> {
+    &self.reg_constraints
+  }
+
+  pub fn vars(&self) -> &Variables {
+    &self.vars
+  }
+
+  /**
+   * Graphviz DOT of the dependency graph of String variables.
+   * each definition is drawn as edges from the variables it refers to,
+   * labeled with its position and functions, and defined variables are boxes.
+   * positions of regular constraints are shown next to the variables.
+   */
+  pub fn dependency_dot(&self) -> String {
+    let mut dot = String::from("digraph deps {\n  rankdir=LR;\n");
+    for (idx, var) in self.vars.iter().enumerate() {
+      let shape = if self.filter_sl(idx).is_some() {
+        "box"
+      } else {
+        "ellipse"
+      };
+      let spans: Vec<_> = self
+        .reg_constraints
+        .iter()
+        .filter(|reg_cons| reg_cons.idx() == idx)
+        .map(|reg_cons| format!("in.re@{}", reg_cons.span()))
+        .collect();
+      if spans.is_empty() {
+        dot.push_str(&format!(
+          "  v{} [label=\"{}\", shape={}];\n",
+          idx,
+          dot_escape(var),
+          shape
+        ));
+      } else {
+        dot.push_str(&format!(
+          "  v{} [label=\"{}\", shape={}, xlabel=\"{}\"];\n",
+          idx,
+          dot_escape(var),
+          shape,
+          spans.join(", ")
+        ));
+      }
+    }
+    for sl_cons in &self.sl_constraints {
+      let label = format!(
+        "{} {}",
+        sl_cons.span(),
+        sl_cons.constraint().function_names().join(", ")
+      );
+      let mut sources = sl_cons.constraint().vars();
+      sources.sort();
+      sources.dedup();
+      if sources.is_empty() {
+        /* a constant definition */
+        dot.push_str(&format!(
+          "  c{0} [label=\"\", shape=point];\n  c{0} -> v{0} [label=\"{1}\"];\n",
+          sl_cons.idx(),
+          dot_escape(&label)
+        ));
+      }
+      for source in sources {
+        dot.push_str(&format!(
+          "  v{} -> v{} [label=\"{}\"];\n",
+          source,
+          sl_cons.idx(),
+          dot_escape(&label)
+        ));
+      }
+    }
+    dot.push_str("}\n");
+    dot
+  }
+
+  pub fn length_constraints(&self) -> &Vec<IntLinearConstraint> {
+    &self.length_constraints
+  }
+
+  pub fn index_patterns(&self) -> &Vec<IndexPattern> {
+    &self.index_patterns
+  }
+
+  pub fn count_patterns(&self) -> &Vec<String> {
+    &self.count_patterns
+  }
+
+  /**
+   * the same problem where the values of lengths, indexes, str.to_int and str.count are fixed
+   * by regular constraints, and those of Int variables are substituted for integer arguments.
+   */
+  pub fn with_values(&self, values: &HashMap<IntAtom, i64>) -> Self {
+    let mut lengths = vec![];
+    let mut indexes = vec![];
+    let mut smt2 = self.clone();
+    for (atom, value) in values {
+      match atom {
+        IntAtom::Var(idx) => {
+          for sl_cons in &mut smt2.sl_constraints {
+            sl_cons.1 = sl_cons.1.with_int(&self.int_vars[*idx], *value);
+          }
+        }
+        IntAtom::Len(idx) => lengths.push((*idx, *value as usize)),
+        IntAtom::IndexOf(idx, pattern) => indexes.push((*idx, *pattern, *value)),
+        IntAtom::ToInt(idx) => smt2.reg_constraints.push(RegularConstraint(
+          *idx,
+          to_int_regex(*value),
+          Span::default(),
+        )),
+        IntAtom::Count(idx, pattern) => smt2.reg_constraints.push(RegularConstraint(
+          *idx,
+          count_regex(&self.count_patterns[*pattern], *value),
+          Span::default(),
+        )),
+      }
+    }
+    smt2.with_lengths(&lengths).with_indexes(&indexes)
+  }
+
+  /**
+   * the same problem where each variable of lengths has exactly that length,
+   * given as regular constraints.
+   */
+  pub fn with_lengths(&self, lengths: &[(VarIndex, usize)]) -> Self {
+    let mut smt2 = self.clone();
+    for (idx, len) in lengths {
+      let reg = Regex::all().repeat(*len, Some(*len));
+      smt2
+        .reg_constraints
+        .push(RegularConstraint(*idx, reg, Span::default()));
+    }
+    smt2
+  }
+
+  /**
+   * the same problem where each variable of the values has the index of the pattern,
+   * given as regular constraints, where patterns are indexes of index_patterns.
+   */
+  pub fn with_indexes(&self, indexes: &[(VarIndex, usize, i64)]) -> Self {
+    let mut smt2 = self.clone();
+    for (idx, pattern, value) in indexes {
+      let reg = self.index_patterns[*pattern].regex(*value);
+      smt2
+        .reg_constraints
+        .push(RegularConstraint(*idx, reg, Span::default()));
+    }
+    smt2
+  }
+
+  /** whether some definition has an integer argument abstracted away */
+  pub fn is_abstracted(&self) -> bool {
+    self
+      .sl_constraints
+      .iter()
+      .any(|sl_cons| sl_cons.1.is_abstracted())
+  }
+
+  /**
+   * whether the model satisfies the definitions whose integer arguments are abstracted,
+   * since the abstraction takes any start and length of str.substr.
+   * Int variables in them which the model leaves out are searched up to the longest word,
+   * recomputing the defined variables by them, and added to the model if the memberships hold.
+   */
+  pub fn check_abstracted(&self, model: &mut HashMap<String, String>) -> bool {
+    let mut ints = HashMap::new();
+    let mut names: Vec<&str> = vec![];
+    for sl_cons in &self.sl_constraints {
+      for name in sl_cons.1.symbolic_args() {
+        if let Ok(value) = name.parse() {
+          /* a large constant */
+          ints.insert(name.to_owned(), value);
+          continue;
+        }
+        if !self.int_vars.iter().any(|var| var == name) {
+          /* a compound term can't be evaluated */
+          return false;
+        }
+        if !names.contains(&name) {
+          names.push(name);
+        }
+      }
+    }
+    let mut free = vec![];
+    for name in names {
+      match model.get(name).map(|value| value.parse()) {
+        Some(Ok(value)) => {
+          ints.insert(name.to_owned(), value);
+        }
+        Some(Err(_)) => return false,
+        None => free.push(name),
+      }
+    }
+    let bound = model.values().map(|value| value.chars().count()).max().unwrap_or(0) as i64;
+    if (bound as f64 + 1.0).powi(free.len() as i32) > INDEX_ASSIGNMENTS as f64 {
+      return false;
+    }
+    let memberships: HashMap<VarIndex, Sfa<D, S>> = (0..self.vars.len())
+      .filter_map(|idx| Some((idx, self.membership(idx)?.to_sfa())))
+      .collect();
+
+    let satisfies = |ints: &HashMap<String, i64>| -> Option<HashMap<VarIndex, String>> {
+      let mut words: HashMap<VarIndex, String> = (0..self.vars.len())
+        .filter(|idx| self.filter_sl(*idx).is_none())
+        .map(|idx| (idx, model.get(&self.vars[idx]).cloned().unwrap_or_default()))
+        .collect();
+      let mut pending: Vec<_> = self.sl_constraints.iter().collect();
+      while let Some(i) = pending
+        .iter()
+        .position(|sl_cons| sl_cons.1.vars().iter().all(|idx| words.contains_key(idx)))
+      {
+        let sl_cons = pending.remove(i);
+        words.insert(sl_cons.0, sl_cons.1.apply_with(&words, ints));
+      }
+      let holds = words.iter().all(|(idx, word)| {
+        memberships.get(idx).is_none_or(|sfa| {
+          let word: Vec<D> = word.chars().map(D::from).collect();
+          sfa.run(&word)
+        })
+      });
+      Some(words).filter(|_| holds)
+    };
+    let mut assignment = vec![0; free.len()];
+    loop {
+      for (name, value) in free.iter().zip(&assignment) {
+        ints.insert((*name).to_owned(), *value);
+      }
+      if let Some(words) = satisfies(&ints) {
+        for (idx, word) in words {
+          model.insert(self.vars[idx].clone(), word);
+        }
+        for name in free {
+          model.insert(name.to_owned(), ints[name].to_string());
+        }
+        return true;
+      }
+      /* the next assignment in the lexicographic order */
+      match assignment.iter().rposition(|value| *value < bound) {
+        Some(i) => {
+          assignment[i] += 1;
+          assignment[i + 1..].iter_mut().for_each(|value| *value = 0);
+        }
+        None => return false,
+      }
+    }
+  }
+
+  /** what preprocessing did, see TraceEntry */
+  pub fn trace(&self) -> &Vec<TraceEntry> {
+    &self.trace
+  }
+
+  pub fn int_vars(&self) -> &Variables {
+    &self.int_vars
+  }
+
+  pub fn bool_vars(&self) -> &Variables {
+    &self.bool_vars
+  }
+
+  pub fn check_sat(&self) -> bool {
+    self.option.check_sat
+  }
+
+  pub fn warnings(&self) -> &Vec<Warning> {
+    &self.warnings
+  }
+
+  pub fn is_contradictory(&self) -> bool {
+    self.contradictory
+  }
+
+  /**
+   * the assertions in scope which are ignored since they aren't supported,
+   * so that the problem is undecided if the rest is satisfiable
+   */
+  pub fn ignored(&self) -> &Vec<String> {
+    &self.ignored
+  }
+
+  pub fn get_model(&self) -> bool {
+    self.option.get_model
+  }
+
+  /**
+   * the response to (get-model) for a model of the problem,
+   * defining String, Int and then Bool variables in the order of declaration, e.g.
+   *   (model
+   *     (define-fun x0 () String "ab")
+   *   )
+   */
+  pub fn model_block(&self, model: &HashMap<String, String>) -> String {
+    let mut block = String::from("(model\n");
+    let sorts = [
+      ("String", &self.vars),
+      ("Int", &self.int_vars),
+      ("Bool", &self.bool_vars),
+    ];
+    for (sort, vars) in sorts {
+      for var in vars.iter() {
+        let value = match (model.get(var), sort) {
+          (None, _) => continue,
+          (Some(value), "String") => string_literal(value),
+          (Some(value), "Int") => match value.strip_prefix('-') {
+            Some(abs) => format!("(- {})", abs),
+            None => value.clone(),
+          },
+          (Some(value), _) => value.clone(),
+        };
+        block.push_str(&format!("  (define-fun {} () {} {})\n", var, sort, value));
+      }
+    }
+    block.push_str(")\n");
+    block
+  }
+
+  /** the terms of each (get-value), as given */
+  pub fn get_values(&self) -> Vec<Vec<&Term>> {
+    self
+      .option
+      .get_values
+      .iter()
+      .map(|pairs| pairs.iter().map(|(term, _)| term).collect())
+      .collect()
+  }
+
+  /** whether a model is needed to answer (get-model) or (get-value) */
+  pub fn needs_model(&self) -> bool {
+    self.option.get_model || !self.option.get_values.is_empty()
+  }
+
+  /** the responses to each (get-value), evaluating its terms over the model by eval */
+  pub fn value_blocks(&self, model: &HashMap<String, String>) -> Result<String, ParseError> {
+    let mut blocks = String::new();
+    for pairs in &self.option.get_values {
+      let mut values = vec![];
+      for (term, expanded) in pairs {
+        values.push(format!("({} {})", term, self.eval(expanded, model)?));
+      }
+      blocks.push_str(&format!("({})\n", values.join(" ")));
+    }
+    Ok(blocks)
+  }
+
+  /**
+   * the value of a term over the model, which maps variables to their words, decimals
+   * or true and false as SolverResult::Model does. variables the model leaves out are
+   * unconstrained, so they are the empty string, 0 or false.
+   * string functions follow SMT-LIB, e.g. (str.at x i) is empty out of x.
+   */
+  pub fn eval(&self, term: &Term, model: &HashMap<String, String>) -> Result<Value, ParseError> {
+    let unsupported = || ParseError::unsupported(format!("term {} in get-value", term));
+    let string = |term: &Term| match self.eval(term, model)? {
+      Value::Str(s) => Ok(s.chars().collect::<Vec<_>>()),
+      _ => Err(ParseError::malformed(term, "a string term")),
+    };
+    let int = |term: &Term| match self.eval(term, model)? {
+      Value::Int(n) => Ok(n),
+      _ => Err(ParseError::malformed(term, "an integer term")),
+    };
+    let boolean = |term: &Term| match self.eval(term, model)? {
+      Value::Bool(b) => Ok(b),
+      _ => Err(ParseError::malformed(term, "a Boolean term")),
+    };
+    /* the index of the first occurrence of pattern in s from start */
+    let find = |s: &[char], pattern: &[char], start: usize| {
+      (start..=s.len())
+        .find(|i| s[*i..].starts_with(pattern))
+        .map(|i| i as i64)
+    };
+    let word = |s: &[char]| Value::Str(s.iter().collect());
+
+    let (function, arguments) = match term {
+      Term::Constant(Constant::String(s)) => return Ok(Value::Str(s.clone())),
+      Term::Constant(Constant::Numeral(n)) => {
+        return n.to_string().parse().map(Value::Int).map_err(|_| unsupported())
+      }
+      Term::QualIdentifier(qi) => {
+        let name = get_symbol(qi);
+        let value = model.get(name);
+        return match name {
+          "true" => Ok(Value::Bool(true)),
+          "false" => Ok(Value::Bool(false)),
+          _ if self.vars.iter().any(|var| var == name) => {
+            Ok(Value::Str(value.cloned().unwrap_or_default()))
+          }
+          _ if self.int_vars.iter().any(|var| var == name) => Ok(Value::Int(
+            value.map_or(Ok(0), |value| value.parse().map_err(|_| unsupported()))?,
+          )),
+          _ if self.bool_vars.iter().any(|var| var == name) => {
+            Ok(Value::Bool(value.map(String::as_str) == Some("true")))
+          }
+          _ => Err(ParseError::UnknownVariable {
+            span: Span::default(),
+            name: name.to_owned(),
+          }),
+        };
+      }
+      Term::Application {
+        qual_identifier,
+        arguments,
+      } => (get_symbol(qual_identifier), &arguments[..]),
+      _ => return Err(unsupported()),
+    };
+    Ok(match (function, arguments) {
+      ("str.++", _) => {
+        let mut concat = vec![];
+        for argument in arguments {
+          concat.extend(string(argument)?);
+        }
+        word(&concat)
+      }
+      ("str.len", [x]) => Value::Int(string(x)?.len() as i64),
+      ("str.at", [x, i]) => {
+        let (x, i) = (string(x)?, int(i)?);
+        if i < 0 || i as usize >= x.len() {
+          Value::Str(String::new())
+        } else {
+          Value::Str(x[i as usize].to_string())
+        }
+      }
+      ("str.substr", [x, i, n]) => {
+        let (x, i, n) = (string(x)?, int(i)?, int(n)?);
+        if i < 0 || n <= 0 || i as usize >= x.len() {
+          Value::Str(String::new())
+        } else {
+          let i = i as usize;
+          word(&x[i..x.len().min(i.saturating_add(n as usize))])
+        }
+      }
+      ("str.prefixof", [s, x]) => Value::Bool(string(x)?.starts_with(&string(s)?)),
+      ("str.suffixof", [s, x]) => Value::Bool(string(x)?.ends_with(&string(s)?)),
+      ("str.contains", [x, s]) => Value::Bool(find(&string(x)?, &string(s)?, 0).is_some()),
+      ("str.indexof", [x, s, i]) => {
+        let (x, s, i) = (string(x)?, string(s)?, int(i)?);
+        if i < 0 || i as usize > x.len() {
+          Value::Int(-1)
+        } else {
+          Value::Int(find(&x, &s, i as usize).unwrap_or(-1))
+        }
+      }
+      ("str.replace", [x, s, t]) => {
+        let (x, s, t) = (string(x)?, string(s)?, string(t)?);
+        match find(&x, &s, 0) {
+          Some(i) => {
+            let i = i as usize;
+            word(&[&x[..i], &t[..], &x[i + s.len()..]].concat())
+          }
+          None => word(&x),
+        }
+      }
+      ("str.replace_all" | "str.replaceall", [x, s, t]) => {
+        let (x, s, t) = (string(x)?, string(s)?, string(t)?);
+        let mut replaced = vec![];
+        let mut i = 0;
+        while i < x.len() {
+          if !s.is_empty() && x[i..].starts_with(&s) {
+            replaced.extend(&t);
+            i += s.len();
+          } else {
+            replaced.push(x[i]);
+            i += 1;
+          }
+        }
+        word(&replaced)
+      }
+      ("str.reverse", [x]) => {
+        let mut x = string(x)?;
+        x.reverse();
+        word(&x)
+      }
+      ("str.to_int" | "str.to.int", [x]) => {
+        let x: String = string(x)?.into_iter().collect();
+        if !x.is_empty() && x.chars().all(|c| c.is_ascii_digit()) {
+          Value::Int(x.parse().map_err(|_| unsupported())?)
+        } else {
+          Value::Int(-1)
+        }
+      }
+      ("str.from_int" | "int.to.str", [n]) => match int(n)? {
+        n if n < 0 => Value::Str(String::new()),
+        n => Value::Str(n.to_string()),
+      },
+      ("str.in.re" | "str.in_re", [x, reg]) => {
+        let x: Vec<D> = string(x)?.into_iter().map(D::from).collect();
+        Value::Bool(Regex::<D>::new(reg)?.to_sfa::<S>().run(&x))
+      }
+      ("+", _) => {
+        let mut sum = 0i64;
+        for argument in arguments {
+          sum = sum.checked_add(int(argument)?).ok_or_else(unsupported)?;
+        }
+        Value::Int(sum)
+      }
+      ("-", [n]) => Value::Int(int(n)?.checked_neg().ok_or_else(unsupported)?),
+      ("-", [first, rest @ ..]) => {
+        let mut difference = int(first)?;
+        for argument in rest {
+          difference = difference
+            .checked_sub(int(argument)?)
+            .ok_or_else(unsupported)?;
+        }
+        Value::Int(difference)
+      }
+      ("*", _) => {
+        let mut product = 1i64;
+        for argument in arguments {
+          product = product
+            .checked_mul(int(argument)?)
+            .ok_or_else(unsupported)?;
+        }
+        Value::Int(product)
+      }
+      (op @ ("<=" | "<" | ">=" | ">"), [_, _, ..]) => {
+        let mut ints = vec![];
+        for argument in arguments {
+          ints.push(int(argument)?);
+        }
+        Value::Bool(ints.windows(2).all(|pair| match op {
+          "<=" => pair[0] <= pair[1],
+          "<" => pair[0] < pair[1],
+          ">=" => pair[0] >= pair[1],
+          _ => pair[0] > pair[1],
+        }))
+      }
+      ("=" | "distinct", [_, _, ..]) => {
+        let mut values = vec![];
+        for argument in arguments {
+          values.push(self.eval(argument, model)?);
+        }
+        Value::Bool(if function == "=" {
+          values.windows(2).all(|pair| pair[0] == pair[1])
+        } else {
+          (0..values.len()).all(|i| !values[i + 1..].contains(&values[i]))
+        })
+      }
+      ("not", [b]) => Value::Bool(!boolean(b)?),
+      ("and" | "or" | "xor", _) => {
+        let mut bools = vec![];
+        for argument in arguments {
+          bools.push(boolean(argument)?);
+        }
+        Value::Bool(match function {
+          "and" => bools.iter().all(|b| *b),
+          "or" => bools.iter().any(|b| *b),
+          _ => bools.iter().filter(|b| **b).count() % 2 == 1,
+        })
+      }
+      ("=>", [_, _, ..]) => {
+        let mut bools = vec![];
+        for argument in arguments {
+          bools.push(boolean(argument)?);
+        }
+        /* right associative, i.e. the last one holds if all the others do */
+        let (last, premises) = bools.split_last().expect("at least two arguments");
+        Value::Bool(*last || premises.iter().any(|b| !*b))
+      }
+      ("ite", [c, t, e]) => {
+        if boolean(c)? {
+          self.eval(t, model)?
+        } else {
+          self.eval(e, model)?
+        }
+      }
+      _ => return Err(unsupported()),
+    })
+  }
+
+  /**
+   * the problem at each (check-sat) or (check-sat-assuming) in order, i.e. the assertions
+   * in the scopes open at that point, asking for a model if (get-model) follows it.
+   * literals of check-sat-assuming are only in its own problem.
+   */
+  pub fn checks(&self) -> &Vec<Self> {
+    &self.checks
+  }
+
+  /** ask for a model as if (get-model) is given */
+  pub(crate) fn set_get_model(&mut self, get_model: bool) {
+    self.option.get_model = get_model;
+  }
+
+  pub fn logic(&self) -> &Logic {
+    &self.option.logic
+  }
+
+  pub fn to_model<B: BoolAlg>(&self, path: Vec<B>) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    let mut idx = 0usize;
+
+    path.into_iter().for_each(|predicate| {
+      assert!(idx < self.vars.len());
+      let s = result.entry(idx).or_insert(String::new());
+
+      if predicate == B::char(B::Domain::separator()) {
+        idx += 1;
+      } else {
+        s.push(predicate.get_one().unwrap().into());
+      }
+    });
+
+    while idx < self.vars.len() {
+      result.insert(idx, self.filter_sl(idx).unwrap().1.apply(&result));
+      idx += 1;
+    }
+
+    result
+      .into_iter()
+      .map(|(idx, s)| (self.vars[idx].clone(), s.clone()))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::helper::*;
+
+  #[test]
+  fn slice() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const x2 String)
+    (declare-const x3 String)
+    (declare-const x4 String)
+    (assert (= x1 (str.++ x0 "a")))
+    (assert (= x2 (str.reverse x1)))
+    (assert (= x4 (str.++ x3 x1)))
+    (assert (str.in.re x2 (str.to.re "ab")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(smt2.cone_of_influence(), vec![0, 1, 2]);
+
+    let (sliced, removed) = smt2.slice();
+    assert_eq!(removed, vec![3, 4]);
+    assert_eq!(sliced.vars(), &vec!["x0", "x1", "x2"]);
+    assert_eq!(sliced.sl_constraints().len(), 2);
+    assert_eq!(sliced.reg_constraints()[0].idx(), 2);
+
+    let smt2 = Smt2::<char, StateImpl>::parse(
+      r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "a")))
+      "#,
+    )
+    .unwrap();
+    assert_eq!(smt2.slice(), (smt2.clone(), vec![]));
+  }
+
+  #[test]
+  fn elimination_order() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const x2 String)
+    (declare-const x3 String)
+    (assert (= x3 (str.++ x0 "a")))
+    (assert (= x1 (str.reverse x3)))
+    (assert (= x2 (str.++ x0 "b")))
+    (assert (str.in.re x2 (str.to.re "ab")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    /* x1 and x2 are not referred to, and x2 is more constrained */
+    assert_eq!(smt2.elimination_order(), vec![2, 1, 3]);
+
+    let reordered = smt2.reorder(&smt2.elimination_order());
+    assert_eq!(reordered.vars(), &vec!["x0", "x3", "x1", "x2"]);
+    let defined: Vec<_> = reordered
+      .sl_constraints()
+      .iter()
+      .map(|sl_cons| (sl_cons.idx(), sl_cons.constraint().vars()))
+      .collect();
+    assert_eq!(defined, vec![(1, vec![0]), (2, vec![1]), (3, vec![0])]);
+    assert_eq!(reordered.reg_constraints()[0].idx(), 3);
+  }
+
+  #[test]
+  fn parse_correctly() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const x2 String)
+    (declare-const i2 Int)
+    (assert (= x1 (str.++ x0 x0)))
+    (assert (= x2 (str.++ x1 x0 x1)))
+    (assert (str.in.re x1 (re.+ (str.to.re "ab"))))
+    (assert (str.in.re x2 (re.* (str.to.re "aa"))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(
+      &vec!["x0".to_string(), "x1".to_string(), "x2".to_string()],
+      smt2.vars(),
+    );
+    assert_eq!(&vec!["i2".to_string()], smt2.int_vars(),);
+    assert!(smt2.check_sat());
+    assert!(!smt2.get_model());
+    let mut sl_iter = smt2.sl_constraints().clone().into_iter();
+    assert_eq!(
+      Some(StraightLineConstraint(
+        1,
+        Transduction(vec![TransductionOp::Var(0), TransductionOp::Var(0)]),
+        Span { line: 6, column: 5 }
+      )),
+      sl_iter.next()
+    );
+    assert_eq!(
+      Some(StraightLineConstraint(
+        2,
+        Transduction(vec![
+          TransductionOp::Var(1),
+          TransductionOp::Var(0),
+          TransductionOp::Var(1)
+        ]),
+        Span { line: 7, column: 5 }
+      )),
+      sl_iter.next()
+    );
+    assert_eq!(None, sl_iter.next());
+    let mut re_iter = smt2.reg_constraints().clone().into_iter();
+    assert_eq!(
+      Some(RegularConstraint(
+        1,
+        Regex::Element('a').concat(Regex::Element('b')).plus(),
+        Span { line: 8, column: 5 }
+      )),
+      re_iter.next()
+    );
+    assert_eq!(
+      Some(RegularConstraint(
+        2,
+        Regex::Element('a').concat(Regex::Element('a')).star(),
+        Span { line: 9, column: 5 }
+      )),
+      re_iter.next()
+    );
+    assert_eq!(None, re_iter.next());
+  }
+
+  #[test]
+  fn warnings() {
+    let input = r#"
+    (set-logic QF_S)
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const x2 String)
+    (declare-const i Int)
+    (assert (= x1 (str.reverse x0)))
+    (assert (str.in.re x1 (str.to.re "ab")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let warnings = smt2.warnings();
+    assert_eq!(warnings.len(), 3);
+    assert!(matches!(warnings[0], Warning::UnsupportedCommand(_)));
+    assert!(matches!(warnings[1], Warning::Approximated(_)));
+    assert_eq!(warnings[2], Warning::UnconstrainedVariable("x2".to_owned()));
+  }
+
+  #[test]
+  fn length_constraints() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const i Int)
+      (declare-const j Int)
+      (assert (= i (+ (str.len x0) 1)))
+      (assert (> (* 2 (str.len x1)) (- i)))
+      (assert (str.in.re x0 (str.to.re "ab")))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let term = |atom, coefficient| LinearTerm { atom, coefficient };
+    assert_eq!(
+      smt2.length_constraints(),
+      &vec![
+        IntLinearConstraint(
+          Relation::Eq,
+          vec![
+            term(Some(IntAtom::Var(0)), 1),
+            term(Some(IntAtom::Len(0)), -1),
+            term(None, -1)
+          ],
+          Span { line: 6, column: 7 }
+        ),
+        IntLinearConstraint(
+          Relation::Le,
+          vec![
+            term(Some(IntAtom::Var(0)), -1),
+            term(Some(IntAtom::Len(1)), -2),
+            term(None, 1)
+          ],
+          Span { line: 7, column: 7 }
+        ),
+      ]
+    );
+    /* j is not used by any length constraint */
+    assert_eq!(
+      smt2.warnings(),
+      &vec![Warning::Approximated(String::from(
+        "integer variable j is not constrained by lengths"
+      ))]
+    );
+
+    let values = HashMap::from([(IntAtom::Var(0), 3), (IntAtom::Len(0), 2)]);
+    assert_eq!(smt2.length_constraints()[0].eval(&values), Some(true));
+    assert_eq!(smt2.length_constraints()[1].eval(&values), None);
+    let values = HashMap::from([(IntAtom::Var(0), -3), (IntAtom::Len(1), 1)]);
+    assert_eq!(smt2.length_constraints()[1].eval(&values), Some(false));
+  }
+
+  #[test]
+  fn dependency_dot() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const x2 String)
+    (declare-const x3 String)
+    (assert (= x1 (str.++ x0 "a" x0)))
+    (assert (= x2 (str.replaceallre x1 (str.to.re "a") x0)))
+    (assert (= x3 "abc"))
+    (assert (str.in.re x2 (str.to.re "bab")))
+    (check-sat)
+    "#;
+    let dot = Smt2::<char, StateImpl>::parse(input)
+      .unwrap()
+      .dependency_dot();
+    assert!(dot.starts_with("digraph deps {"));
+    assert!(dot.contains(r#"v0 [label="x0", shape=ellipse];"#));
+    assert!(dot.contains(r#"v2 [label="x2", shape=box, xlabel="in.re@9:5"];"#));
+    assert!(dot.contains(r#"v0 -> v1 [label="6:5 str.++"];"#));
+    assert!(dot.contains(r#"v0 -> v2 [label="7:5 str.replace_re_all"];"#));
+    assert!(dot.contains(r#"v1 -> v2 [label="7:5 str.replace_re_all"];"#));
+    assert!(dot.contains(r#"c3 -> v3 [label="8:5 str.++"];"#));
+    assert_eq!(dot.matches(" -> ").count(), 4);
+  }
+
+  #[test]
+  fn replace_re_names() {
+    let legacy = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const x2 String)
+    (assert (= x1 (str.replacere x0 (str.to.re "a") "b")))
+    (assert (= x2 (str.replaceallre x1 (re.+ (str.to.re "a")) x0)))
+    (check-sat)
+    "#;
+    let standard = legacy
+      .replace("str.replacere", "str.replace_re")
+      .replace("str.replaceallre", "str.replace_re_all");
+    let legacy = Smt2::<char, StateImpl>::parse(legacy).unwrap();
+    let standard = Smt2::<char, StateImpl>::parse(&standard).unwrap();
+    assert!(standard.warnings().is_empty());
+    assert_eq!(standard.sl_constraints(), legacy.sl_constraints());
+    assert!(matches!(
+      standard.sl_constraints()[0].constraint().0[..],
+      [TransductionOp::Replace(..)]
+    ));
+    assert!(matches!(
+      standard.sl_constraints()[1].constraint().0[..],
+      [TransductionOp::ReplaceAll(..)]
+    ));
+  }
+
+  #[test]
+  fn substr() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const i Int)
+    (assert (= x1 (str.++ (str.substr x0 1 2) (str.substr x0 i 3))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let transduction = smt2.sl_constraints()[0].constraint();
+    assert_eq!(
+      transduction,
+      &Transduction(vec![
+        TransductionOp::Substr(0, IntArg::Const(1), IntArg::Const(2)),
+        TransductionOp::Substr(0, IntArg::Symbolic("i".to_owned()), IntArg::Const(3)),
+      ])
+    );
+    assert!(transduction.is_abstracted());
+    assert_eq!(smt2.trace().len(), 2);
+    assert_eq!(
+      transduction.apply(&HashMap::from([(0, "abcde".to_owned())])),
+      "bcabc"
+    );
+  }
+
+  #[test]
+  fn membership_with_provenance() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (str.in.re x0 (re.* (str.to.re "ab"))))
+    (assert (str.in.re x1 (str.to.re "c")))
+    (assert (str.in.re x0 (re.+ (str.to.re "ab"))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let (sfa, provenance) = smt2.membership_with_provenance(0).unwrap();
+    assert_eq!(sfa, smt2.membership(0).unwrap().to_sfa());
+    for s in &sfa.states {
+      assert_eq!(
+        provenance.labels(s),
+        vec![&Span { line: 4, column: 5 }, &Span { line: 6, column: 5 }]
+      );
+    }
+    assert!(smt2.membership_with_provenance(2).is_none());
+  }
+
+  #[test]
+  fn negated_membership() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const b Bool)
+    (assert (not (str.in.re x0 (str.to.re "a"))))
+    (assert (not (str.prefixof "b" x0)))
+    (assert (not (and b (str.in.re x0 (str.to.re "c")))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(
+      smt2.reg_constraints(),
+      &vec![
+        RegularConstraint(0, Regex::Element('a').not(), Span { line: 4, column: 5 }),
+        RegularConstraint(
+          0,
+          Regex::Element('b').concat(Regex::all().star()).not(),
+          Span { line: 5, column: 5 }
+        ),
+      ]
+    );
+    assert_eq!(smt2.conditional.len(), 1);
+    assert_eq!(
+      smt2.trace()[0].to_string(),
+      "4:5\trewritten\t(not (str.in.re x0 (str.to.re \"a\"))) into membership in the complement"
+    );
+  }
+
+  #[test]
+  fn char_at() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (= (str.at x0 2) "c"))
+    (assert (= "" (str.at x1 1)))
+    (assert (not (= (str.at x1 0) "a")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert!(smt2.sl_constraints().is_empty());
+    assert_eq!(
+      smt2.reg_constraints(),
+      &vec![
+        RegularConstraint(0, Regex::char_at(2, "c"), Span { line: 4, column: 5 }),
+        RegularConstraint(1, Regex::char_at(1, ""), Span { line: 5, column: 5 }),
+        RegularConstraint(1, Regex::char_at(0, "a").not(), Span { line: 6, column: 5 }),
+      ]
+    );
+
+    /* a symbolic position is not a membership */
+    let input = input.replace("(str.at x0 2)", "(str.at x0 (str.len x1))");
+    assert!(Smt2::<char, StateImpl>::parse(&input).is_err());
+  }
+
+  #[test]
+  fn push_pop() {
+    let input = r#"
+    (declare-const x0 String)
+    (assert (str.in.re x0 (str.to.re "a")))
+    (push 1)
+    (declare-const x1 String)
+    (assert (= x1 (str.++ x0 x0)))
+    (push 2)
+    (assert (str.in.re x1 (str.to.re "b")))
+    (pop 2)
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(smt2.depth(), 1);
+    assert_eq!(smt2.vars(), &vec!["x0".to_owned(), "x1".to_owned()]);
+    assert_eq!(smt2.reg_constraints().len(), 1);
+    assert_eq!(smt2.sl_constraints().len(), 1);
+
+    let smt2 = Smt2::<char, StateImpl>::parse(&input.replace("(check-sat)", "(pop 1)")).unwrap();
+    assert_eq!(smt2.depth(), 0);
+    assert_eq!(smt2.vars(), &vec!["x0".to_owned()]);
+    assert!(smt2.sl_constraints().is_empty());
+
+    let input = input.replace("(check-sat)", "(pop 2)");
+    assert!(matches!(
+      Smt2::<char, StateImpl>::parse(&input),
+      Err(ParseError::Malformed {
+        span: Span { line: 10, .. },
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn checks() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const b Bool)
+    (assert (str.in.re x0 (str.to.re "a")))
+    (check-sat)
+    (get-model)
+    (push 1)
+    (declare-const x1 String)
+    (assert (str.in.re x1 (str.to.re "b")))
+    (check-sat-assuming (b))
+    (pop 1)
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let checks = smt2.checks();
+    assert_eq!(checks.len(), 3);
+    let sizes: Vec<_> = checks
+      .iter()
+      .map(|check| (check.vars().len(), check.reg_constraints().len(), check.assumptions.len()))
+      .collect();
+    assert_eq!(sizes, vec![(1, 1, 0), (2, 2, 1), (1, 1, 0)]);
+    let get_model: Vec<_> = checks.iter().map(|check| check.get_model()).collect();
+    assert_eq!(get_model, vec![true, false, false]);
+    assert!(checks.iter().all(|check| check.checks().is_empty()));
+  }
+
+  #[test]
+  fn trace() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (declare-const x2 String)
+    (declare-const b Bool)
+    (define-fun A () RegLan (str.to.re "a"))
+    (assert (str.in.re x0 (re.* A)))
+    (assert (= x2 (str.reverse x1)))
+    (assert (or b (not (str.in.re x0 A))))
+    (assert (str.prefixof x0 x1))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let kinds: Vec<_> = smt2
+      .trace()
+      .iter()
+      .map(|entry| (entry.span.unwrap().line, entry.kind))
+      .collect();
+    assert_eq!(
+      kinds,
+      vec![
+        (7, TraceKind::Rewritten),
+        (9, TraceKind::Rewritten),
+        (10, TraceKind::Approximated)
+      ]
+    );
+    assert_eq!(
+      smt2.trace()[2].to_string(),
+      "10:5\tapproximated\tignored (str.prefixof x0 x1)"
+    );
+
+    let (_, case) = smt2.case_split().unwrap().into_iter().next().unwrap();
+    let (sliced, _) = case.slice();
+    let kinds: Vec<_> = sliced.trace()[3..]
+      .iter()
+      .map(|entry| (entry.span.map(|span| span.line), entry.kind))
+      .collect();
+    /* b is false in the first case, so the membership is negated */
+    assert_eq!(
+      kinds,
+      vec![
+        (Some(9), TraceKind::Rewritten),
+        (Some(9), TraceKind::Rewritten),
+        (None, TraceKind::Eliminated),
+        (Some(8), TraceKind::Eliminated),
+      ]
+    );
+  }
+
+  #[test]
+  fn reset() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (= x1 (str.reverse x0)))
+    (assert (str.in.re x1 (str.to.re "ab")))
+    (reset-assertions)
+    (assert (str.in.re x0 (str.to.re "a")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(smt2.vars(), &vec!["x0", "x1"]);
+    assert!(smt2.sl_constraints().is_empty());
+    assert_eq!(smt2.reg_constraints().len(), 1);
+
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const i Int)
+    (assert (str.in.re x0 (str.to.re "ab")))
+    (get-proof-stats)
+    (check-sat)
+    (reset)
+    (declare-const y String)
+    (assert (str.in.re y (str.to.re "a")))
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(smt2.vars(), &vec!["y"]);
+    assert!(smt2.int_vars().is_empty());
+    assert_eq!(smt2.reg_constraints().len(), 1);
+    assert!(!smt2.check_sat());
+    /* the warning before (reset) is kept */
+    assert_eq!(smt2.warnings().len(), 1);
+  }
+
+  #[test]
+  fn unknown_commands() {
+    let input = r#"
+    ; comments with ( are skipped
+    (set-info :source |exported (by another tool)|)
+    (declare-const x0 String)
+    (get-proof-stats :verbose)
+    (assert (str.in.re x0 (str.to.re "a)b")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let warnings = smt2.warnings();
+    assert_eq!(warnings.len(), 2);
+    assert!(matches!(warnings[0], Warning::UnsupportedCommand(_)));
+    assert_eq!(
+      warnings[1],
+      Warning::UnsupportedCommand("(get-proof-stats :verbose)".to_owned())
+    );
+    assert_eq!(smt2.reg_constraints().len(), 1);
+    assert!(Smt2::<char, StateImpl>::parse_with(input, true).is_err());
+
+    assert_eq!(
+      split_commands("(a \"(\") b ;(\n(c)")
+        .into_iter()
+        .map(|(_, chunk)| chunk)
+        .collect::<Vec<_>>(),
+      vec!["(a \"(\")", "b", "(c)"]
+    );
+  }
+
+  #[test]
+  fn case_split() {
+    let input = r#"
+    (declare-const b Bool)
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (= x1 (str.++ x0 "a")))
+    (assert (=> b (str.in.re x1 (str.to.re "ba"))))
+    (assert (=> (not b) (str.in.re x0 (str.to.re "c"))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(smt2.bool_vars(), &vec!["b"]);
+    let cases = smt2.case_split().unwrap();
+    assert_eq!(cases.len(), 2);
+    for (assignment, case) in cases {
+      let b = assignment[0].1;
+      assert_eq!(assignment, vec![("b".to_owned(), b)]);
+      assert_eq!(case.sl_constraints().len(), 1);
+      assert_eq!(case.reg_constraints().len(), 1);
+      assert_eq!(case.reg_constraints()[0].idx(), if b { 1 } else { 0 });
+      assert!(!case.is_contradictory());
+    }
+
+    let smt2 =
+      Smt2::<char, StateImpl>::parse(&format!("{} (assert b) (assert (not b))", input)).unwrap();
+    assert!(smt2
+      .case_split()
+      .unwrap()
+      .iter()
+      .all(|(_, case)| case.is_contradictory()));
+
+    /* the cases of many Bool variables are too many to enumerate */
+    let declarations: String = (0..64)
+      .map(|i| format!("(declare-const b{} Bool)", i))
+      .collect();
+    let assertions: String = (0..64).map(|i| format!("(assert b{})", i)).collect();
+    let smt2 = Smt2::<char, StateImpl>::parse(&format!("{} {}", declarations, assertions)).unwrap();
+    let err = smt2.case_split().unwrap_err();
+    assert_eq!(err.limit, MAX_BOOL_VARS);
+  }
+
+  #[test]
+  fn dnf() {
+    let input = r#"
+    (declare-const b Bool)
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (or (str.in.re x0 (str.to.re "a")) (str.in.re x1 (str.to.re "b"))))
+    (assert (xor b (str.in.re x0 (str.to.re "c"))))
+    (assert (not (not (= x1 x0))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let cases = smt2.case_split().unwrap();
+    /* two disjuncts of or for each value of b */
+    assert_eq!(cases.len(), 4);
+    for (assignment, case) in cases {
+      assert_eq!(case.sl_constraints().len(), 1);
+      assert_eq!(case.reg_constraints().len(), 2);
+      /* x0 is in "c" iff b doesn't hold */
+      let c = case
+        .reg_constraints()
+        .iter()
+        .find(|reg_cons| reg_cons.idx() == 0 && reg_cons.constraint() != &Regex::seq("a"))
+        .map(|reg_cons| reg_cons.constraint().clone());
+      let expected = Regex::seq("c");
+      if assignment[0].1 {
+        assert_eq!(c, Some(expected.not()));
+      } else {
+        assert_eq!(c, Some(expected));
+      }
+    }
+
+    /*
+     * negated equalities to constants and Int inequalities are taken exactly,
+     * while the other negated atoms are left as ignored in their case
+     */
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (=> (not (= x0 "a")) (not (<= (str.len x0) 2))))
+    (assert (or (not (= x1 (str.++ x0 x0))) (not (= x1 "b"))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let cases = smt2.case_split().unwrap();
+    assert_eq!(cases.len(), 4);
+    for (_, case) in &cases {
+      let not_b = case
+        .reg_constraints()
+        .iter()
+        .any(|reg_cons| reg_cons.idx() == 1 && reg_cons.constraint() == &Regex::seq("b").not());
+      if not_b {
+        assert!(case.ignored().is_empty());
+      } else {
+        assert_eq!(case.ignored(), &vec![String::from("(not (= x1 (str.++ x0 x0)))")]);
+      }
+      assert_eq!(
+        case.sl_constraints().len() + case.length_constraints().len(),
+        1
+      );
     }
   }
 
-  pub fn emit_sfa(&mut self) -> Sfa<D, S> {
-    assert_ne!(self.vars.len(), 0);
-    (0..self.vars.len())
-      .into_iter()
-      .map(|idx| {
-        self
-          .filter_reg(idx)
-          .map(|reg| reg.to_sfa())
-          .unwrap_or_default()
-      })
-      .reduce(|result, sfa| result.chain(sfa))
-      .map(|sfa| sfa.finish())
-      .expect("no string constraint given")
+  #[test]
+  fn boolean_constants() {
+    let input = r#"
+    (declare-const x0 String)
+    (assert true)
+    (assert (str.in.re x0 (str.to.re "ab")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert!(!smt2.is_contradictory());
+    assert!(smt2.warnings().is_empty());
+
+    let smt2 = Smt2::<char, StateImpl>::parse(&input.replace("true", "false")).unwrap();
+    assert!(smt2.is_contradictory());
+    assert!(smt2.emit_sfa().final_set().is_empty());
   }
 
-  pub fn filter_sl(&self, idx: VarIndex) -> Option<&StraightLineConstraint<D, S>> {
-    self
-      .sl_constraints
-      .iter()
-      .find(|sl_cons| sl_cons.idx() == idx)
+  #[test]
+  fn index_pattern() {
+    let ab = IndexPattern {
+      pattern: String::from("ab"),
+      start: 1,
+    };
+    let accepts = |pattern: &IndexPattern, value: i64, word: &str| {
+      pattern
+        .regex::<char>(value)
+        .to_sfa::<StateImpl>()
+        .run(&chars(word))
+    };
+    assert!(accepts(&ab, 1, "aab"));
+    assert!(accepts(&ab, 3, "abbab"));
+    assert!(!accepts(&ab, 3, "aabab"));
+    assert!(!accepts(&ab, 0, "abab"));
+    assert!(accepts(&ab, 2, "abab"));
+    assert!(accepts(&ab, -1, ""));
+    assert!(accepts(&ab, -1, "abba"));
+    assert!(!accepts(&ab, -1, "aab"));
+
+    let empty = IndexPattern {
+      pattern: String::new(),
+      start: 2,
+    };
+    assert!(accepts(&empty, 2, "ab"));
+    assert!(!accepts(&empty, 2, "a"));
+    assert!(accepts(&empty, -1, "a"));
+    assert!(!accepts(&empty, -1, "abc"));
+
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const i Int)
+      (assert (= x1 (str.at x0 i)))
+      (assert (= i (+ (str.indexof x0 "ab" 1) (str.indexof x1 "ab" 1) (str.indexof x0 "ab" 1))))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(
+      smt2.sl_constraints()[0].constraint(),
+      &Transduction(vec![TransductionOp::Substr(
+        0,
+        IntArg::Symbolic(String::from("i")),
+        IntArg::Const(1)
+      )])
+    );
+    assert_eq!(smt2.index_patterns(), &vec![ab]);
+    assert_eq!(
+      smt2.length_constraints()[0].atoms(),
+      vec![
+        IntAtom::Var(0),
+        IntAtom::IndexOf(0, 0),
+        IntAtom::IndexOf(1, 0)
+      ]
+    );
   }
 
-  pub fn filter_reg(&mut self, idx: VarIndex) -> Option<Regex<D>> {
-    let mut result: Option<Regex<D>> = None;
+  #[test]
+  fn int_conversions() {
+    let accepts = |reg: Regex<char>, word: &str| reg.to_sfa::<StateImpl>().run(&chars(word));
+    assert!(accepts(to_int_regex(12), "12"));
+    assert!(accepts(to_int_regex(12), "0012"));
+    assert!(!accepts(to_int_regex(12), "120"));
+    assert!(accepts(to_int_regex(0), "00"));
+    assert!(accepts(to_int_regex(-1), ""));
+    assert!(accepts(to_int_regex(-1), "1a"));
+    assert!(!accepts(to_int_regex(-1), "1"));
+    assert!(!accepts(to_int_regex(-1), "9"));
+    assert!(!accepts(to_int_regex(-2), "1"));
+    assert!(accepts(from_int_regex(), "0"));
+    assert!(accepts(from_int_regex(), "909"));
+    assert!(!accepts(from_int_regex(), "01"));
 
-    while let Some(i) = self
-      .reg_constraints
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (declare-const i Int)
+      (assert (= i (str.to_int x0)))
+      (assert (= x1 (str.from_int (+ i 1))))
+      (assert (= x2 (str.from_int 42)))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let atoms: Vec<_> = smt2
+      .length_constraints()
       .iter()
-      .position(|reg_cons| reg_cons.idx() == idx)
-    {
-      let regex = self.reg_constraints.swap_remove(i).1;
-      result = Some(match result {
-        Some(result) => result.inter(regex),
-        None => regex,
-      });
-    }
+      .map(|int_cons| int_cons.atoms())
+      .collect();
+    assert_eq!(
+      atoms,
+      vec![
+        vec![IntAtom::Var(0), IntAtom::ToInt(0)],
+        vec![IntAtom::Var(0), IntAtom::ToInt(1)]
+      ]
+    );
+    assert_eq!(smt2.membership(2), Some(Regex::seq("42")),);
+    assert_eq!(smt2.membership(1), Some(from_int_regex()));
+    assert_eq!(smt2.warnings().len(), 1);
+  }
 
-    result
+  #[test]
+  fn count() {
+    let accepts = |pattern: &str, value: i64, word: &str| {
+      count_regex::<char>(pattern, value)
+        .to_sfa::<StateImpl>()
+        .run(&chars(word))
+    };
+    assert!(accepts(",", 0, "abc"));
+    assert!(accepts(",", 2, ",a,"));
+    assert!(!accepts(",", 1, ",a,"));
+    assert!(accepts("ab", 2, "aabab"));
+    assert!(!accepts("ab", 1, "aabab"));
+    assert!(!accepts(",", -1, ""));
+    assert!(is_border_free("ab"));
+    assert!(!is_border_free("aba"));
+    assert!(!is_border_free(""));
+
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (set-option :str-extensions true)
+      (assert (<= (+ (str.count x0 ",") (str.count x1 ";") (str.count x1 ",")) 3))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(
+      smt2.count_patterns(),
+      &vec![String::from(","), String::from(";")]
+    );
+    assert_eq!(
+      smt2.length_constraints()[0].atoms(),
+      vec![IntAtom::Count(0, 0), IntAtom::Count(1, 0), IntAtom::Count(1, 1)]
+    );
+    assert!(Smt2::<char, StateImpl>::parse(&input.replace("\";\"", "\"aa\"")).is_err());
+    assert!(Smt2::<char, StateImpl>::parse(&input.replace("true", "false")).is_err());
   }
 
-  pub fn sl_constraints(&self) -> &Vec<StraightLineConstraint<D, S>> {
-    &self.sl_constraints
+  #[test]
+  fn alphabet() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (set-option :alphabet (x0 x1 "[0-9]"))
+      (assert (str.in.re x0 (re.+ re.allchar)))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let digits = Regex::Star(Box::new(Regex::inclusive_range('0', '9')));
+    assert_eq!(smt2.membership(1), Some(digits.clone()));
+    let x0 = smt2.membership(0).unwrap().to_sfa::<StateImpl>();
+    assert!(x0.run(&['4', '2']));
+    assert!(!x0.run(&['4', 'a']));
+    assert!(!x0.run(&[]));
+
+    let smt2 = smt2
+      .with_alphabet("x1", Regex::parse("[0-4]").unwrap())
+      .unwrap();
+    let x1 = smt2.membership(1).unwrap().to_sfa::<StateImpl>();
+    assert!(x1.run(&['4']));
+    assert!(!x1.run(&['5']));
   }
 
-  pub fn reg_constraints(&self) -> &Vec<RegularConstraint<D>> {
-    &self.reg_constraints
+  #[test]
+  fn extensions() {
+    let input = r#"
+      (declare-const x0 String)
+      (set-option :str-extensions true)
+      (assert (str.subseqof "abc" x0))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert!(smt2.warnings().is_empty());
+    assert_eq!(smt2.membership(0), Some(Regex::subsequence("abc")));
+
+    /* ignored without the option */
+    let smt2 = Smt2::<char, StateImpl>::parse(&input.replace("true", "false")).unwrap();
+    assert_eq!(smt2.membership(0), None);
+    assert_eq!(
+      smt2.warnings(),
+      &vec![
+        Warning::UnsupportedAssertion(String::from("(str.subseqof \"abc\" x0)")),
+        Warning::UnconstrainedVariable(String::from("x0"))
+      ]
+    );
   }
 
-  pub fn vars(&self) -> &Variables {
-    &self.vars
+  #[test]
+  fn large_membership() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (re.* (re.inter
+        (re.* (str.to.re "abcdefgh")) (re.* (str.to.re "bcdefgha")) (re.* (str.to.re "cdefghab"))
+        (re.* (str.to.re "defghabc")) (re.* (str.to.re "efghabcd"))))))
+      (assert (str.in.re x1 (re.inter (str.to.re "abcdefgh") (str.to.re "bcdefgha"))))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    /* each star has 17 states */
+    assert_eq!(
+      smt2.warnings(),
+      &vec![Warning::LargeMembership(
+        String::from("x0"),
+        17usize.pow(5) + 1
+      )]
+    );
   }
 
-  pub fn int_vars(&self) -> &Variables {
-    &self.int_vars
+  #[test]
+  fn string_predicates() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.prefixof "ab" x0))
+      (assert (str.suffixof "c" x0))
+      (assert (str.contains x1 "b"))
+      (assert (str.contains x1 x0))
+      (check-sat)
+      "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let any = || Regex::all().star();
+    let regexes: Vec<_> = smt2
+      .reg_constraints()
+      .iter()
+      .map(|reg_cons| (reg_cons.idx(), reg_cons.constraint().clone()))
+      .collect();
+    assert_eq!(
+      regexes,
+      vec![
+        (0, Regex::seq("ab").concat(any())),
+        (0, any().concat(Regex::seq("c"))),
+        (1, any().concat(Regex::seq("b")).concat(any())),
+      ]
+    );
+    /* the pattern must be a constant */
+    assert_eq!(
+      smt2.warnings()[0],
+      Warning::UnsupportedAssertion("(str.contains x1 x0)".to_owned())
+    );
+
+    let sfa = smt2.membership(0).unwrap().to_sfa::<StateImpl>();
+    assert!(sfa.run(&chars("abc")));
+    assert!(!sfa.run(&chars("bc")));
+    assert!(!sfa.run(&chars("abca")));
   }
 
-  pub fn check_sat(&self) -> bool {
-    self.option.check_sat
+  #[test]
+  fn spans() {
+    let input = r#"(declare-const x0 String) (declare-const x1 String)
+; comment with (parentheses)
+(assert (= x1 (str.++ x0 "(")))
+  (assert (str.in.re x1 (str.to.re "a(")))
+    "#;
+    for strict in [false, true] {
+      let smt2 = Smt2::<char, StateImpl>::parse_with(input, strict).unwrap();
+      assert_eq!(smt2.sl_constraints()[0].span(), Span { line: 3, column: 1 });
+      assert_eq!(
+        smt2.reg_constraints()[0].span(),
+        Span { line: 4, column: 3 }
+      );
+      let reordered = smt2.reorder(&smt2.elimination_order());
+      assert_eq!(reordered.sl_constraints()[0].span().to_string(), "3:1");
+    }
   }
 
-  pub fn get_model(&self) -> bool {
-    self.option.get_model
+  #[test]
+  fn reject_duplicate_definitions() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (= x1 (str.++ x0 "a")))
+    (assert (= x1 (str.reverse x0)))
+    (check-sat)
+    "#;
+    let err = Smt2::<char, StateImpl>::parse(input).unwrap_err();
+    assert_eq!(
+      err,
+      ParseError::Redefined {
+        span: Span { line: 5, column: 5 },
+        name: "x1".to_owned(),
+        first: Span { line: 4, column: 5 },
+      }
+    );
+    assert!(err
+      .to_string()
+      .starts_with("5:5: Variable x1 is defined twice, first at 4:5"));
   }
 
-  pub fn logic(&self) -> &Logic {
-    &self.option.logic
+  #[test]
+  fn model_block() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const i Int)
+    (declare-const b Bool)
+    (declare-const x1 String)
+    (assert (or b (str.in.re x0 (str.to.re "a"))))
+    (assert (= (str.len x1) i))
+    (get-model)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let model = HashMap::from([
+      ("x0".to_owned(), "a\"\\\u{e9}".to_owned()),
+      ("x1".to_owned(), String::new()),
+      ("i".to_owned(), "-1".to_owned()),
+      ("b".to_owned(), "true".to_owned()),
+    ]);
+    assert_eq!(
+      smt2.model_block(&model),
+      r#"(model
+  (define-fun x0 () String "a""\u{5c}\u{e9}")
+  (define-fun x1 () String "")
+  (define-fun i () Int (- 1))
+  (define-fun b () Bool true)
+)
+"#
+    );
   }
 
-  pub fn to_model<B: BoolAlg>(&self, path: Vec<B>) -> HashMap<String, String> {
-    let mut result = HashMap::new();
-    let mut idx = 0usize;
+  #[test]
+  fn get_value() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const i Int)
+    (declare-const b Bool)
+    (assert (str.in.re x0 (str.to.re "ab")))
+    (check-sat)
+    (get-value ((str.len x0) (str.++ x0 "c") i))
+    (get-value ( (str.at x0 (- i 1)) (ite b (str.indexof x0 "b" 0) (str.to_int "042")) ))
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert!(!smt2
+      .warnings()
+      .iter()
+      .any(|warning| matches!(warning, Warning::UnsupportedCommand(_))));
+    assert!(smt2.needs_model());
+    assert!(!smt2.get_model());
+    assert_eq!(smt2.get_values().len(), 2);
+    assert_eq!(smt2.checks()[0].get_values().len(), 2);
+    let model = HashMap::from([
+      ("x0".to_owned(), "ab".to_owned()),
+      ("i".to_owned(), "-1".to_owned()),
+    ]);
+    assert_eq!(
+      smt2.value_blocks(&model).unwrap(),
+      r#"(((str.len x0) 2) ((str.++ x0 "c") "abc") (i (- 1)))
+(((str.at x0 (- i 1)) "") ((ite b (str.indexof x0 "b" 0) (str.to_int "042")) 42))
+"#
+    );
 
-    path.into_iter().for_each(|predicate| {
-      assert!(idx < self.vars.len());
-      let s = result.entry(idx).or_insert(String::new());
+    let eval = |term: &str| {
+      let input = format!("(declare-const x0 String)(assert {})", term);
+      let term = match CommandStream::new(input.as_bytes(), SyntaxBuilder, None).nth(1) {
+        Some(Ok(Command::Assert { term })) => term,
+        _ => panic!("{}", term),
+      };
+      smt2.eval(&term, &model)
+    };
+    let string = |s: &str| Ok(Value::Str(s.to_owned()));
+    assert_eq!(eval(r#"(str.substr x0 1 5)"#), string("b"));
+    assert_eq!(eval(r#"(str.replace "abab" x0 "")"#), string("ab"));
+    assert_eq!(eval(r#"(str.replace_all "abab" x0 "c")"#), string("cc"));
+    assert_eq!(eval(r#"(str.replace x0 "" "c")"#), string("cab"));
+    assert_eq!(eval(r#"(str.from_int (* 3 (- 4)))"#), string(""));
+    assert_eq!(eval(r#"(str.indexof "abab" x0 1)"#), Ok(Value::Int(2)));
+    assert_eq!(eval(r#"(str.indexof x0 "" 3)"#), Ok(Value::Int(-1)));
+    assert_eq!(eval(r#"(str.in.re x0 (re.+ (str.to.re "ab")))"#), Ok(Value::Bool(true)));
+    assert_eq!(eval(r#"(=> (< 1 2 3) (distinct x0 "ab"))"#), Ok(Value::Bool(false)));
+    assert!(eval(r#"(str.len 1)"#).is_err());
+    assert!(eval(r#"(str.< x0 "b")"#).is_err());
+    assert_eq!(Value::Int(-3).to_string(), "(- 3)");
 
-      if predicate == B::char(B::Domain::separator()) {
-        idx += 1;
-      } else {
-        s.push(predicate.get_one().unwrap().into());
-      }
-    });
+    /* unknown variables are errors of parse */
+    let input = input.replace("(str.++ x0 \"c\")", "(str.++ x1 \"c\")");
+    assert!(matches!(
+      Smt2::<char, StateImpl>::parse(&input),
+      Err(ParseError::UnknownVariable { span: Span { line: 7, column: 5 }, .. })
+    ));
+    assert!(matches!(
+      Smt2::<char, StateImpl>::parse_with(&input, true),
+      Err(ParseError::UnknownVariable { span: Span { line: 7, column: 5 }, .. })
+    ));
+  }
 
-    while idx < self.vars.len() {
-      result.insert(idx, self.filter_sl(idx).unwrap().1.apply(&result));
-      idx += 1;
-    }
+  #[test]
+  fn reject_cyclic_definitions() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (= x1 (str.++ x0 "a")))
+    (assert (= x0 (str.reverse x1)))
+    (check-sat)
+    "#;
+    assert_eq!(
+      Smt2::<char, StateImpl>::parse(input).unwrap_err(),
+      ParseError::Unsupported {
+        span: Span { line: 5, column: 5 },
+        feature: "cyclic definition of x0".to_owned(),
+      }
+    );
 
-    result
-      .into_iter()
-      .map(|(idx, s)| (self.vars[idx].clone(), s.clone()))
-      .collect()
+    /* definitions meeting in a case are ignored instead */
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (and (= x1 (str.++ x0 "a")) (= x0 (str.reverse x1))))
+    (check-sat)
+    "#;
+    let cases = Smt2::<char, StateImpl>::parse(input)
+      .unwrap()
+      .case_split()
+      .unwrap();
+    assert_eq!(cases.len(), 1);
+    assert_eq!(cases[0].1.sl_constraints().len(), 1);
+    assert!(cases[0]
+      .1
+      .warnings()
+      .contains(&Warning::UnsupportedAssertion(
+        "(= x0 (str.reverse x1))".to_owned()
+      )));
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use crate::tests::helper::*;
+  #[test]
+  fn regex_definitions() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (define-fun A () RegLan (str.to.re "a"))
+    (define-fun R () RegLan (re.* (re.union A (str.to.re "b"))))
+    (assert (str.in.re x0 R))
+    (assert (= x1 (str.replaceallre x0 A "c")))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert!(smt2.warnings().is_empty());
+    assert_eq!(
+      smt2.reg_constraints(),
+      &vec![RegularConstraint(
+        0,
+        Regex::Element('a').or(Regex::Element('b')).star(),
+        Span { line: 6, column: 5 }
+      )]
+    );
+    assert_eq!(
+      smt2.sl_constraints()[0].constraint(),
+      &Transduction(vec![TransductionOp::ReplaceAll(
+        0,
+        Regex::Element('a'),
+        ReplaceTarget::Str("c".to_owned())
+      )])
+    );
+  }
 
   #[test]
-  fn parse_correctly() {
+  fn let_bindings() {
     let input = r#"
     (declare-const x0 String)
     (declare-const x1 String)
     (declare-const x2 String)
-    (declare-const i2 Int)
-    (assert (= x1 (str.++ x0 x0)))
-    (assert (= x2 (str.++ x1 x0 x1)))
-    (assert (str.in.re x1 (re.+ (str.to.re "ab"))))
-    (assert (str.in.re x2 (re.* (str.to.re "aa"))))
+    (assert (let ((t x0)) (let ((t x1) (u t)) (= x2 (str.++ t u)))))
+    (assert (let ((r (str.to.re "a"))) (str.in.re x0 (re.* r))))
     (check-sat)
     "#;
     let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
     assert_eq!(
-      &vec!["x0".to_string(), "x1".to_string(), "x2".to_string()],
-      smt2.vars(),
+      smt2.sl_constraints()[0].constraint(),
+      &Transduction(vec![TransductionOp::Var(1), TransductionOp::Var(0)])
     );
-    assert_eq!(&vec!["i2".to_string()], smt2.int_vars(),);
-    assert!(smt2.check_sat());
-    assert!(!smt2.get_model());
-    let mut sl_iter = smt2.sl_constraints().clone().into_iter();
+    assert_eq!(smt2.reg_constraints()[0].1, Regex::Element('a').star());
+  }
+
+  #[test]
+  fn declare_fun_without_parameters() {
+    let input = r#"
+    (declare-fun x0 () String)
+    (declare-fun b () Bool)
+    (assert (str.in.re x0 (str.to.re "a")))
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    assert_eq!(smt2.vars(), &vec!["x0"]);
+    assert_eq!(smt2.bool_vars(), &vec!["b"]);
+  }
+
+  #[test]
+  fn reject_unsupported_sorts() {
+    let input = r#"
+    (declare-const a (Array Int String))
+    (check-sat)
+    "#;
+    let err = Smt2::<char, StateImpl>::parse_with(input, true).unwrap_err();
     assert_eq!(
-      Some(StraightLineConstraint(
-        1,
-        Transduction(vec![TransductionOp::Var(0), TransductionOp::Var(0)])
-      )),
-      sl_iter.next()
+      err.to_string(),
+      "2:5: Unsupported sort (Array Int String) of variable a"
     );
+  }
+
+  #[test]
+  fn parse_errors() {
+    let parse = |assertion: &str| {
+      let input = format!(
+        "(declare-const x String)\n(declare-const b Bool)\n{}\n(check-sat)",
+        assertion
+      );
+      Smt2::<char, StateImpl>::parse(&input).unwrap_err()
+    };
     assert_eq!(
-      Some(StraightLineConstraint(
-        2,
-        Transduction(vec![
-          TransductionOp::Var(1),
-          TransductionOp::Var(0),
-          TransductionOp::Var(1)
-        ])
-      )),
-      sl_iter.next()
+      parse(r#"(assert (str.in.re x (re.* (str.to.re "a") (str.to.re "b"))))"#),
+      ParseError::Arity {
+        span: Span { line: 3, column: 1 },
+        operator: "re.*".to_owned(),
+        term: r#"(re.* (str.to.re "a") (str.to.re "b"))"#.to_owned(),
+        expected: Arity::Exactly(1),
+      }
     );
-    assert_eq!(None, sl_iter.next());
-    let mut re_iter = smt2.reg_constraints().clone().into_iter();
     assert_eq!(
-      Some(RegularConstraint(
-        1,
-        Regex::Element('a').concat(Regex::Element('b')).plus()
-      )),
-      re_iter.next()
+      parse("(assert (str.in.re x (str.to.re x)))"),
+      ParseError::Malformed {
+        span: Span { line: 3, column: 1 },
+        term: "x".to_owned(),
+        expected: "a string constant",
+      }
     );
     assert_eq!(
-      Some(RegularConstraint(
-        2,
-        Regex::Element('a').concat(Regex::Element('a')).star()
-      )),
-      re_iter.next()
+      parse("(assert (= x (str.reverse y)))").to_string(),
+      "3:1: Variable y is not declared"
     );
-    assert_eq!(None, re_iter.next());
+    assert_eq!(
+      parse("(assert (= x (str.replace x x \"a\")))").to_string(),
+      "3:1: Unsupported str.replace of a non-constant pattern x"
+    );
+    assert_eq!(
+      parse("(declare-const x Int)").to_string(),
+      "3:1: Variable x is already defined"
+    );
+    /* literals of conditional assertions are checked before case_split */
+    assert_eq!(
+      parse(r#"(assert (or b (str.in.re x (re.loop (str.to.re "a") "1"))))"#),
+      ParseError::Malformed {
+        span: Span { line: 3, column: 1 },
+        term: r#""1""#.to_owned(),
+        expected: "a numeral",
+      }
+    );
+    assert!(matches!(
+      parse(r#"(set-option :alphabet (x "[z-a]"))"#),
+      ParseError::Pattern {
+        error: regex::RegexParseError::InvalidRange(_),
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn straight_line_intersects_memberships() {
+    let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (= x1 (str.++ x0 "a")))
+    (assert (str.in.re x1 (re.* (str.to.re "ab"))))
+    (assert (str.in.re x0 (str.to.re "b")))
+    (assert (str.in.re x1 (re.+ re.allchar)))
+    (assert (str.in.re x1 (re.++ re.allchar (str.to.re "a"))))
+    (check-sat)
+    "#;
+    let smt2 = Smt2::<char, StateImpl>::parse(input).unwrap();
+    let straight_line = smt2.straight_line();
+    assert_eq!(straight_line.definitions().len(), 1);
+    assert_eq!(straight_line.definition(1).unwrap().idx(), 1);
+    assert!(straight_line.definition(0).is_none());
+    assert_eq!(straight_line.free_vars(), &vec![0]);
+    assert_eq!(straight_line.memberships().len(), 2);
+    assert_eq!(straight_line.membership(0), Some(&Regex::Element('b')));
+    assert_eq!(
+      straight_line.membership(1),
+      Some(
+        &Regex::Element('a')
+          .concat(Regex::Element('b'))
+          .star()
+          .inter(Regex::All.plus())
+          .inter(Regex::All.concat(Regex::Element('a')))
+      )
+    );
+
+    /* nothing is consumed */
+    assert_eq!(smt2.reg_constraints().len(), 4);
+    assert_eq!(smt2.straight_line(), straight_line);
   }
 }
@@ -0,0 +1,455 @@
+use crate::boolean_algebra::BoolAlg;
+use crate::regular::symbolic_automata::SymFa;
+use crate::state::State;
+use crate::transducer::{
+  sst::SymSst,
+  term::{FunctionTerm, OutputComp, UpdateComp, Variable},
+};
+use crate::util::Domain;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+/** environment variable which makes check_snapshot write the actual text instead of comparing */
+pub const UPDATE_SNAPSHOTS: &str = "UPDATE_SNAPSHOTS";
+
+/** unchanged lines shown around each change in diffs */
+const CONTEXT: usize = 2;
+
+/**
+ * a stable text of a machine for golden files. reachable states are numbered in the breadth-first
+ * order from the initial state, following transitions sorted by the labels of their predicates,
+ * and variables are numbered in their order. so it only changes with the machine up to renaming,
+ * and the header gives the size including unreachable states.
+ */
+pub trait Snapshot {
+  fn snapshot(&self) -> String;
+}
+
+/** states reachable from initial in the breadth-first order */
+fn breadth_first<'a, S: State>(
+  initial: &'a S,
+  successors: impl Fn(&'a S) -> Vec<&'a S>,
+) -> HashMap<&'a S, usize> {
+  let mut numbers = HashMap::from([(initial, 0)]);
+  let mut queue = VecDeque::from([initial]);
+  while let Some(p) = queue.pop_front() {
+    for q in successors(p) {
+      let n = numbers.len();
+      numbers.entry(q).or_insert_with(|| {
+        queue.push_back(q);
+        n
+      });
+    }
+  }
+  numbers
+}
+
+/** states in the order of their numbers */
+fn in_order<'a, S>(numbers: &HashMap<&'a S, usize>) -> Vec<&'a S> {
+  let mut states: Vec<_> = numbers.iter().map(|(p, n)| (*n, *p)).collect();
+  states.sort_by_key(|(n, _)| *n);
+  states.into_iter().map(|(_, p)| p).collect()
+}
+
+/** names of the sorted variables, i.e. prefix followed by the index */
+fn naming<'a, V: Ord + Hash>(variables: &'a HashSet<V>, prefix: &str) -> HashMap<&'a V, String> {
+  let mut variables: Vec<_> = variables.iter().collect();
+  variables.sort();
+  variables
+    .into_iter()
+    .enumerate()
+    .map(|(i, x)| (x, format!("{}{}", prefix, i)))
+    .collect()
+}
+
+/** a sequence joined with " · ", or ε if empty */
+fn sequence(labels: Vec<String>) -> String {
+  if labels.is_empty() {
+    String::from("ε")
+  } else {
+    labels.join(" · ")
+  }
+}
+
+impl<D: Domain, B: BoolAlg<Domain = D>, S: State> Snapshot for SymFa<D, B, S> {
+  fn snapshot(&self) -> String {
+    let mut successors: HashMap<&S, Vec<(String, &Vec<S>)>> = HashMap::new();
+    for ((p, phi), target) in &self.transition {
+      successors.entry(p).or_default().push((phi.label(), target));
+    }
+    successors
+      .values_mut()
+      .for_each(|transitions| transitions.sort_by(|(l1, _), (l2, _)| l1.cmp(l2)));
+    let numbers = breadth_first(&self.initial_state, |p| {
+      successors
+        .get(p)
+        .into_iter()
+        .flatten()
+        .flat_map(|(_, target)| target.iter())
+        .collect()
+    });
+
+    let mut text = format!(
+      "sfa: {} states, {} transitions\n",
+      self.states.len(),
+      self.transition.len()
+    );
+    for p in in_order(&numbers) {
+      for (label, target) in successors.get(p).into_iter().flatten() {
+        let target: Vec<_> = target.iter().map(|q| numbers[q].to_string()).collect();
+        text.push_str(&format!(
+          "{} {} -> {}\n",
+          numbers[p],
+          label,
+          target.join(" ")
+        ));
+      }
+    }
+    let mut finals: Vec<_> = self
+      .final_states
+      .iter()
+      .filter_map(|p| numbers.get(p))
+      .collect();
+    finals.sort();
+    let finals: Vec<_> = finals.iter().map(|n| n.to_string()).collect();
+    text.push_str(&format!("final: {}\n", finals.join(" ")));
+    text
+  }
+}
+
+impl<D, B, F, S, V> Snapshot for SymSst<D, B, F, S, V>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  F: FunctionTerm<Domain = D>,
+  S: State,
+  V: Variable,
+{
+  fn snapshot(&self) -> String {
+    let variables = naming(&self.variables, "x");
+    let registers = naming(&self.registers, "r");
+    let update_label = |alpha: &Vec<UpdateComp<F, V>>| {
+      sequence(
+        alpha
+          .iter()
+          .map(|comp| match comp {
+            UpdateComp::F(f) => format!("{:?}", f),
+            UpdateComp::X(x) => variables[x].clone(),
+          })
+          .collect(),
+      )
+    };
+
+    let mut successors: HashMap<&S, Vec<(String, &B, _)>> = HashMap::new();
+    for ((p, phi), targets) in &self.transition {
+      successors
+        .entry(p)
+        .or_default()
+        .push((phi.label(), phi, targets));
+    }
+    successors
+      .values_mut()
+      .for_each(|transitions| transitions.sort_by(|(l1, _, _), (l2, _, _)| l1.cmp(l2)));
+    let numbers = breadth_first(&self.initial_state, |p| {
+      successors
+        .get(p)
+        .into_iter()
+        .flatten()
+        .flat_map(|(_, _, targets)| targets.iter().map(|(q, _)| q))
+        .collect()
+    });
+
+    let mut text = format!(
+      "sst: {} states, {} transitions, {} variables, {} registers\n",
+      self.states.len(),
+      self.transition.len(),
+      self.variables.len(),
+      self.registers.len()
+    );
+    for p in in_order(&numbers) {
+      for (label, phi, targets) in successors.get(p).into_iter().flatten() {
+        for (q, alpha) in targets.iter() {
+          let mut updates: Vec<_> = alpha
+            .iter()
+            .map(|(x, alpha)| format!("{} := {}", variables[x], update_label(alpha)))
+            .collect();
+          let register_update =
+            self
+              .register_update
+              .get(&(S::clone(p), B::clone(phi), S::clone(q)));
+          for (r, affine) in register_update.into_iter().flatten() {
+            let (constant, coefficients) = affine.terms();
+            let mut terms: Vec<_> = coefficients
+              .iter()
+              .map(|(s, k)| match k {
+                1 => registers[s].clone(),
+                k => format!("{}*{}", k, registers[s]),
+              })
+              .collect();
+            terms.sort();
+            if constant != 0 || terms.is_empty() {
+              terms.push(constant.to_string());
+            }
+            updates.push(format!("{} := {}", registers[r], terms.join(" + ")));
+          }
+          updates.sort();
+          text.push_str(&format!(
+            "{} {} -> {} [{}]\n",
+            numbers[p],
+            label,
+            numbers[q],
+            updates.join(", ")
+          ));
+        }
+      }
+    }
+    let mut outputs: Vec<_> = self
+      .output_function
+      .iter()
+      .filter_map(|(p, output)| Some((numbers.get(p)?, output)))
+      .collect();
+    outputs.sort_by_key(|(n, _)| **n);
+    for (n, output) in outputs {
+      let output = output
+        .iter()
+        .map(|comp| match comp {
+          OutputComp::A(a) => format!("{:?}", a),
+          OutputComp::X(x) => variables[x].clone(),
+        })
+        .collect();
+      text.push_str(&format!("output {}: {}\n", n, sequence(output)));
+    }
+    text
+  }
+}
+
+/** a snapshot which doesn't match the actual text */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+  Missing(PathBuf),
+  /** the diff from the snapshot to the actual text, see diff */
+  Mismatch {
+    path: PathBuf,
+    diff: String,
+  },
+  Io {
+    path: PathBuf,
+    message: String,
+  },
+}
+impl Display for SnapshotError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SnapshotError::Missing(path) => write!(
+        f,
+        "snapshot {} is missing, run with {}=1 to write it",
+        path.display(),
+        UPDATE_SNAPSHOTS
+      ),
+      SnapshotError::Mismatch { path, diff } => write!(
+        f,
+        "snapshot {} differs (- snapshot, + actual), run with {}=1 to accept it\n{}",
+        path.display(),
+        UPDATE_SNAPSHOTS,
+        diff
+      ),
+      SnapshotError::Io { path, message } => {
+        write!(f, "failed to access {}: {}", path.display(), message)
+      }
+    }
+  }
+}
+impl std::error::Error for SnapshotError {}
+
+/**
+ * compare actual with the snapshot at path line by line,
+ * or write actual there if the environment variable UPDATE_SNAPSHOTS is set.
+ */
+pub fn check_snapshot(path: &Path, actual: &str) -> Result<(), SnapshotError> {
+  let io_error = |err: std::io::Error| SnapshotError::Io {
+    path: path.to_owned(),
+    message: err.to_string(),
+  };
+  if std::env::var_os(UPDATE_SNAPSHOTS).is_some() {
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    return std::fs::write(path, actual).map_err(io_error);
+  }
+  let expected = match std::fs::read_to_string(path) {
+    Ok(expected) => expected,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+      return Err(SnapshotError::Missing(path.to_owned()))
+    }
+    Err(err) => return Err(io_error(err)),
+  };
+  if expected.lines().eq(actual.lines()) {
+    Ok(())
+  } else {
+    Err(SnapshotError::Mismatch {
+      path: path.to_owned(),
+      diff: diff(&expected, actual),
+    })
+  }
+}
+
+/** check_snapshot of the machine, panicking with the diff so that tests fail with it */
+pub fn assert_snapshot(path: impl AsRef<Path>, machine: &impl Snapshot) {
+  if let Err(err) = check_snapshot(path.as_ref(), &machine.snapshot()) {
+    panic!("{}", err)
+  }
+}
+
+/**
+ * lines of expected and actual by their longest common subsequence, prefixed with "- " if only
+ * in expected and "+ " if only in actual. unchanged lines more than CONTEXT lines away from
+ * changes are elided into "  ...".
+ */
+pub fn diff(expected: &str, actual: &str) -> String {
+  let old: Vec<_> = expected.lines().collect();
+  let new: Vec<_> = actual.lines().collect();
+  /* lengths of the longest common subsequences of the suffixes */
+  let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+  for i in (0..old.len()).rev() {
+    for j in (0..new.len()).rev() {
+      lcs[i][j] = if old[i] == new[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        lcs[i + 1][j].max(lcs[i][j + 1])
+      };
+    }
+  }
+
+  let mut lines = vec![];
+  let (mut i, mut j) = (0, 0);
+  while i < old.len() || j < new.len() {
+    if i < old.len() && j < new.len() && old[i] == new[j] {
+      lines.push((' ', old[i]));
+      i += 1;
+      j += 1;
+    } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+      lines.push(('-', old[i]));
+      i += 1;
+    } else {
+      lines.push(('+', new[j]));
+      j += 1;
+    }
+  }
+
+  let changes: Vec<_> = (0..lines.len()).filter(|k| lines[*k].0 != ' ').collect();
+  let mut result = vec![];
+  let mut elided = false;
+  for (k, (mark, line)) in lines.iter().enumerate() {
+    if changes.iter().any(|c| c.abs_diff(k) <= CONTEXT) {
+      result.push(format!("{} {}", mark, line));
+      elided = false;
+    } else if !elided {
+      result.push(String::from("  ..."));
+      elided = true;
+    }
+  }
+  result.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::boolean_algebra::Predicate;
+  use crate::regular::symbolic_automata::Sfa;
+  use crate::state::StateImpl;
+  use crate::transducer::{
+    sst::Sst,
+    term::{Lambda, VariableImpl},
+  };
+
+  fn snapshots() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots")
+  }
+
+  fn sfa() -> Sfa<char, StateImpl> {
+    let (q0, q1, q2) = (StateImpl::new(), StateImpl::new(), StateImpl::new());
+    Sfa {
+      states: HashSet::from([q0.clone(), q1.clone(), q2.clone()]),
+      initial_state: q0.clone(),
+      final_states: HashSet::from([q2.clone()]),
+      transition: HashMap::from([
+        ((q0.clone(), Predicate::Eq('a')), vec![q1.clone()]),
+        ((q1.clone(), Predicate::Eq('b')), vec![q0]),
+        ((q1, Predicate::range(Some('0'), Some(':'))), vec![q2]),
+      ]),
+    }
+  }
+
+  #[test]
+  fn sfa_snapshot() {
+    assert_eq!(
+      sfa().snapshot(),
+      "sfa: 3 states, 3 transitions\n0 'a' -> 1\n1 'b' -> 0\n1 ['0'-'9'] -> 2\nfinal: 2\n"
+    );
+    /* states are renamed */
+    assert_eq!(sfa().snapshot(), sfa().snapshot());
+    assert_snapshot(snapshots().join("sfa.snap"), &sfa());
+  }
+
+  #[test]
+  fn sst_snapshot() {
+    let (q0, x) = (StateImpl::new(), VariableImpl::new());
+    let sst = Sst::<char, StateImpl, VariableImpl> {
+      states: HashSet::from([q0.clone()]),
+      variables: HashSet::from([x.clone()]),
+      initial_state: q0.clone(),
+      output_function: HashMap::from([(q0.clone(), vec![OutputComp::X(x.clone())])]),
+      transition: HashMap::from([(
+        (q0.clone(), Predicate::Eq('a')),
+        vec![(
+          q0,
+          HashMap::from([(
+            x.clone(),
+            vec![UpdateComp::X(x), UpdateComp::F(Lambda::Constant('b'))],
+          )]),
+        )],
+      )]),
+      registers: HashSet::new(),
+      register_update: HashMap::new(),
+    };
+    assert_eq!(
+      sst.snapshot(),
+      "sst: 1 states, 1 transitions, 1 variables, 0 registers\n\
+       0 'a' -> 0 [x0 := x0 · Constant('b')]\n\
+       output 0: x0\n"
+    );
+    assert_snapshot(snapshots().join("sst.snap"), &sst);
+  }
+
+  #[test]
+  fn check() {
+    let dir = std::env::temp_dir().join(format!("snapshot-{}", std::process::id()));
+    let path = dir.join("machine.snap");
+    assert_eq!(
+      check_snapshot(&path, "a"),
+      Err(SnapshotError::Missing(path.clone()))
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&path, "a\nb\n").unwrap();
+    assert_eq!(check_snapshot(&path, "a\nb"), Ok(()));
+    assert_eq!(
+      check_snapshot(&path, "a\nc\n"),
+      Err(SnapshotError::Mismatch {
+        path: path.clone(),
+        diff: String::from("  a\n- b\n+ c"),
+      })
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn elide_unchanged_lines() {
+    assert_eq!(
+      diff("a\nb\nc\nd\ne\nf", "a\nb\nc\nd\ne\nF"),
+      "  ...\n  d\n  e\n- f\n+ F"
+    );
+    assert_eq!(diff("a\nb", "b\nc"), "- a\n  b\n+ c");
+  }
+}
@@ -0,0 +1,739 @@
+use crate::limits::with_timeout;
+use crate::regular::{regex::Regex, symbolic_automata::Sfa};
+use crate::smt2::{ParseError, RegularConstraint, Smt2, Span};
+use crate::state::{State, StateImpl};
+use crate::util::{CharWrap, Domain};
+use crate::{solve_cases_with, MachineCache, SolveReport, SolverResult, UnconstrainedPolicy};
+use std::{collections::HashMap, marker::PhantomData, sync::Arc, time::Duration};
+
+/**
+ * a solver built without parsing a script, e.g.
+ *   let mut solver = SolverBuilder::new().with_timeout(Duration::from_secs(10)).build();
+ *   solver.declare("x")?;
+ *   solver.assert_regex("x", Regex::parse("a(b|c)*")?)?;
+ * the domain is CharWrap unless changed by with_domain.
+ */
+#[derive(Debug, Clone)]
+pub struct SolverBuilder<D: Domain = CharWrap> {
+  timeout: Option<Duration>,
+  unconstrained: UnconstrainedPolicy,
+  _domain: PhantomData<D>,
+}
+impl SolverBuilder {
+  pub fn new() -> Self {
+    SolverBuilder {
+      timeout: None,
+      unconstrained: UnconstrainedPolicy::default(),
+      _domain: PhantomData,
+    }
+  }
+}
+impl Default for SolverBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+impl<D: Domain> SolverBuilder<D> {
+  /**
+   * give up with SolverResult::Timeout in Solver::check after the duration,
+   * which needs the `std` feature to run it in another thread.
+   */
+  pub fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = Some(timeout);
+    self
+  }
+
+  pub fn with_unconstrained(mut self, policy: UnconstrainedPolicy) -> Self {
+    self.unconstrained = policy;
+    self
+  }
+
+  pub fn with_domain<E: Domain>(self) -> SolverBuilder<E> {
+    SolverBuilder {
+      timeout: self.timeout,
+      unconstrained: self.unconstrained,
+      _domain: PhantomData,
+    }
+  }
+
+  /** a solver of no variable and no assertion */
+  pub fn build(self) -> Solver<D, StateImpl> {
+    let mut solver = Solver::new(Smt2::init()).with_unconstrained(self.unconstrained);
+    solver.timeout = self.timeout;
+    solver
+  }
+}
+
+/** the problem keeping a satisfiable subset of the memberships, and the memberships dropped */
+pub type Relaxation<D, S> = (Smt2<D, S>, Vec<RegularConstraint<D>>);
+
+/**
+ * the end-to-end pipeline for a parsed problem.
+ * straight-line constraints are compiled into SSTs by SstBuilder,
+ * and the product of regular constraints is pulled back through them by pre-images.
+ */
+#[derive(Debug)]
+pub struct Solver<D: Domain, S: State> {
+  problem: Smt2<D, S>,
+  unconstrained: UnconstrainedPolicy,
+  /** the report of the last check_sat */
+  report: Option<SolveReport>,
+  /** concrete values of String variables pinned by assume_value */
+  assumed: Vec<(String, String)>,
+  /** how long check may take */
+  timeout: Option<Duration>,
+  /** machines built so far, shared with the threads of check */
+  cache: Arc<MachineCache<D, S>>,
+}
+impl<D: Domain, S: State> Solver<D, S> {
+  pub fn new(problem: Smt2<D, S>) -> Self {
+    Solver {
+      problem,
+      unconstrained: UnconstrainedPolicy::default(),
+      report: None,
+      assumed: vec![],
+      timeout: None,
+      cache: Arc::new(MachineCache::new()),
+    }
+  }
+
+  pub fn with_unconstrained(mut self, policy: UnconstrainedPolicy) -> Self {
+    self.unconstrained = policy;
+    self
+  }
+
+  pub fn problem(&self) -> &Smt2<D, S> {
+    &self.problem
+  }
+
+  /** remove all the assertions and the last result, keeping declarations */
+  pub fn reset_assertions(&mut self) {
+    self.problem.reset_assertions();
+    self.report = None;
+    self.assumed.clear();
+  }
+
+  /** remove declarations as well, so the solver can be reused for another problem */
+  pub fn reset(&mut self) {
+    self.problem.reset();
+    self.report = None;
+    self.assumed.clear();
+    self.cache = Arc::new(MachineCache::new());
+  }
+
+  /** open n scopes, see Smt2::push */
+  pub fn push(&mut self, n: usize) {
+    self.problem.push(n);
+  }
+
+  /**
+   * close n scopes, removing the assertions and declarations made in them.
+   * machines built for the constraints left are reused by the following check_sat.
+   */
+  pub fn pop(&mut self, n: usize) -> Result<(), ParseError> {
+    self.problem.pop(n)?;
+    self.report = None;
+    Ok(())
+  }
+
+  /** machines kept for the following check_sat */
+  pub fn cache(&self) -> &MachineCache<D, S> {
+    &self.cache
+  }
+
+  /**
+   * pin the String variable to the concrete value in the following check_sat,
+   * replacing the value assumed before, so that a concolic loop can try inputs one by one
+   * without parsing the problem again. the compiled product is intersected with the value,
+   * so var may be a defined one. it fails if var is not a declared String variable.
+   */
+  pub fn assume_value(&mut self, var: &str, value: &str) -> Result<(), ParseError> {
+    if !self.problem.vars().iter().any(|v| v == var) {
+      return Err(ParseError::UnknownVariable {
+        span: Span::default(),
+        name: var.to_owned(),
+      });
+    }
+    self.assumed.retain(|(v, _)| v != var);
+    self.assumed.push((var.to_owned(), value.to_owned()));
+    Ok(())
+  }
+
+  /** forget the values pinned by assume_value */
+  pub fn clear_assumptions(&mut self) {
+    self.assumed.clear();
+  }
+
+  /** the report of the last check_sat, if any */
+  pub fn report(&self) -> Option<&SolveReport> {
+    self.report.as_ref()
+  }
+
+  /** declare a String variable, which fails if the name is already declared */
+  pub fn declare(&mut self, var: &str) -> Result<(), ParseError> {
+    self.problem.extend(&format!("(declare-const {} String)", var))
+  }
+
+  /** assert that the declared String variable is in reg */
+  pub fn assert_regex(&mut self, var: &str, reg: Regex<D>) -> Result<(), ParseError> {
+    self.problem.assert_membership(var, reg)
+  }
+
+  /**
+   * assert that lhs is defined by rhs, a string term of SMT-LIB over the declared variables,
+   * e.g. assert_transduction("y", "(str.replace_re_all x (str.to.re \"a\") \"b\")").
+   */
+  pub fn assert_transduction(&mut self, lhs: &str, rhs: &str) -> Result<(), ParseError> {
+    self
+      .problem
+      .extend(&format!("(assert (= {} {}))", lhs, rhs))
+  }
+
+  /**
+   * decide the satisfiability of the assertions, i.e. SolverResult::Sat or SolverResult::Unsat,
+   * or SolverResult::ResourceExhausted if some construction diverged,
+   * and SolverResult::Unknown if some assertion is ignored or an abstraction can't be refined.
+   */
+  pub fn check_sat(&mut self) -> SolverResult {
+    let report = self.solve();
+    let result = match &report.result {
+      SolverResult::Model(_) => SolverResult::Sat,
+      result => self.completed(result),
+    };
+    self.report = Some(report);
+    result
+  }
+
+  /**
+   * answer each (check-sat) of the script in order against the assertions given before it,
+   * with SolverResult::Model if (get-model) follows it, see Smt2::checks.
+   * the machines built for a check-sat are reused by the following ones,
+   * and the report of the last one is kept.
+   */
+  pub fn check_sat_each(&mut self) -> Vec<SolverResult> {
+    let reports: Vec<_> = self
+      .problem
+      .checks()
+      .iter()
+      .map(|check| self.solve_problem(check.clone()))
+      .collect();
+    let results = reports
+      .iter()
+      .map(|report| self.completed(&report.result))
+      .collect();
+    self.report = reports.into_iter().last();
+    results
+  }
+
+  fn solve(&self) -> SolveReport {
+    let mut problem = self.problem.clone();
+    problem.set_get_model(true);
+    self.solve_problem(problem)
+  }
+
+  /**
+   * solve problem in place of the assertions, with the values pinned by assume_value
+   * of the variables declared in it
+   */
+  fn solve_problem(&self, mut problem: Smt2<D, S>) -> SolveReport {
+    let assumed: Vec<_> = self
+      .assumed
+      .iter()
+      .filter(|(var, _)| problem.vars().contains(var))
+      .collect();
+    /* the trivial memberships keep the pinned variables from being sliced away */
+    for (var, _) in &assumed {
+      problem = problem
+        .with_alphabet(var, Regex::all())
+        .expect("assumed variables are declared");
+    }
+    let values: Vec<_> = assumed
+      .iter()
+      .map(|(var, value)| (var, Regex::seq(value).to_sfa::<S>()))
+      .collect();
+    solve_cases_with(problem, self.unconstrained, &self.cache, |compiled| {
+      for (var, sfa) in &values {
+        compiled.restrict(var, sfa.clone());
+      }
+    })
+  }
+
+  /**
+   * a minimal subset of the constraints which is unsatisfiable by itself, as the problem
+   * keeping only them, or None unless the assertions are unsatisfiable.
+   * each constraint is dropped in turn if the rest is still unsatisfiable,
+   * so removing any one of the result makes it satisfiable or undecided.
+   * the spans of the constraints left point at their assertions, and
+   * values pinned by assume_value are taken as given rather than as part of the subset.
+   */
+  pub fn minimal_unsat_subset(&self) -> Option<Smt2<D, S>> {
+    if self.solve().result != SolverResult::Unsat {
+      return None;
+    }
+    let mut subset = self.problem.clone();
+    let mut i = 0;
+    while i < subset.removable_constraints() {
+      let candidate = subset.without_constraint(i);
+      if self.solve_problem(candidate.clone()).result == SolverResult::Unsat {
+        subset = candidate;
+      } else {
+        i += 1;
+      }
+    }
+    Some(subset)
+  }
+
+  /**
+   * a maximal subset of the memberships which is satisfiable with the other constraints,
+   * as the problem keeping only them, and the memberships dropped,
+   * or None if the other constraints aren't satisfiable by themselves.
+   * while the memberships are unsatisfiable, the last one of a minimal unsatisfiable core
+   * of them is dropped, and then each dropped one is taken back if the rest stays satisfiable.
+   * problems not decided within the resource limits count as unsatisfiable.
+   */
+  pub fn max_sat(&self) -> Option<Relaxation<D, S>> {
+    let satisfiable = |kept: &[usize]| {
+      matches!(
+        self.solve_problem(self.with_memberships(kept)).result,
+        SolverResult::Sat | SolverResult::Model(_)
+      )
+    };
+    let mut kept: Vec<_> = (0..self.problem.reg_constraints().len()).collect();
+    let mut dropped = vec![];
+    while !satisfiable(&kept) {
+      /* deletion-based as minimal_unsat_subset, over the memberships kept */
+      let mut core = kept.clone();
+      let mut i = 0;
+      while i < core.len() {
+        let mut candidate = core.clone();
+        candidate.remove(i);
+        if satisfiable(&candidate) {
+          i += 1;
+        } else {
+          core = candidate;
+        }
+      }
+      let last = *core.last()?;
+      kept.retain(|i| *i != last);
+      dropped.push(last);
+    }
+    let mut rest = vec![];
+    for i in dropped {
+      kept.push(i);
+      if !satisfiable(&kept) {
+        kept.pop();
+        rest.push(i);
+      }
+    }
+    rest.sort_unstable();
+    let rest = rest
+      .into_iter()
+      .map(|i| self.problem.reg_constraints()[i].clone())
+      .collect();
+    Some((self.with_memberships(&kept), rest))
+  }
+
+  /** the problem keeping only the memberships of the indices in reg_constraints */
+  fn with_memberships(&self, kept: &[usize]) -> Smt2<D, S> {
+    (0..self.problem.reg_constraints().len())
+      .rev()
+      .filter(|i| !kept.contains(i))
+      .fold(self.problem.clone(), |problem, i| problem.without_constraint(i))
+  }
+
+  /**
+   * values of all the declared variables satisfying the assertions of the last check_sat.
+   * the values of String ones are decoded from the accepted path of the final automaton
+   * and replayed through the SSTs. Int ones are the values the length search assigned,
+   * or the ones found for abstracted str.substr arguments, and 0 if no constraint refers to them.
+   */
+  pub fn get_model(&self) -> Option<HashMap<String, String>> {
+    match &self.report.as_ref()?.result {
+      SolverResult::Model(model) => Some(self.with_int_vars(model.clone())),
+      _ => None,
+    }
+  }
+
+  /**
+   * values satisfying the assertions where var is within max_edits edits of reference,
+   * counting insertions, deletions and substitutions of characters,
+   * so that constraint-satisfying variants of a seed input can be generated.
+   * the edit-distance automaton is intersected with the product of memberships,
+   * so var may be a defined one. the last check_sat is kept as it is.
+   * None if there is no such value or var is not a declared String variable.
+   */
+  pub fn model_near(
+    &self,
+    var: &str,
+    reference: &str,
+    max_edits: usize,
+  ) -> Option<HashMap<String, String>> {
+    /* the trivial membership keeps var from being sliced away */
+    let mut problem = self.problem.clone().with_alphabet(var, Regex::all()).ok()?;
+    problem.set_get_model(true);
+    let word: Vec<D> = reference.chars().map(D::from).collect();
+    let near = Sfa::within_edit_distance(&word, max_edits);
+    let report = solve_cases_with(problem, self.unconstrained, &self.cache, |compiled| {
+      compiled.restrict(var, near.clone())
+    });
+    match report.result {
+      SolverResult::Model(model) => Some(self.with_int_vars(model)),
+      _ => None,
+    }
+  }
+
+  /** the result with the model completed by with_int_vars */
+  fn completed(&self, result: &SolverResult) -> SolverResult {
+    match result {
+      SolverResult::Model(model) => SolverResult::Model(self.with_int_vars(model.clone())),
+      SolverResult::Sat => SolverResult::Sat,
+      SolverResult::Unsat => SolverResult::Unsat,
+      SolverResult::ResourceExhausted(err) => SolverResult::ResourceExhausted(err.clone()),
+      SolverResult::Timeout(timeout) => SolverResult::Timeout(*timeout),
+      SolverResult::Unknown(reason) => SolverResult::Unknown(reason.clone()),
+    }
+  }
+
+  /** Int variables are 0 unless assigned, since no constraint refers to them */
+  fn with_int_vars(&self, mut model: HashMap<String, String>) -> HashMap<String, String> {
+    for var in self.problem.int_vars() {
+      model
+        .entry(var.clone())
+        .or_insert_with(|| String::from("0"));
+    }
+    model
+  }
+}
+
+impl<D: Domain + Send + 'static, S: State + Send + 'static> Solver<D, S> {
+  /**
+   * decide the assertions within the timeout, giving SolverResult::Model with the values of all
+   * the declared variables if satisfiable, or SolverResult::Timeout.
+   * the problem is solved in another thread, which is cancelled and joined if it times out.
+   */
+  pub fn check(&mut self) -> SolverResult {
+    let timeout = match self.timeout {
+      Some(timeout) => timeout,
+      None => {
+        self.report = Some(self.solve());
+        return self.last_result();
+      }
+    };
+    let solver = Solver {
+      problem: self.problem.clone(),
+      unconstrained: self.unconstrained,
+      report: None,
+      assumed: self.assumed.clone(),
+      timeout: None,
+      cache: Arc::clone(&self.cache),
+    };
+    self.report = with_timeout(timeout, move || solver.solve());
+    match self.report {
+      Some(_) => self.last_result(),
+      None => SolverResult::Timeout(timeout),
+    }
+  }
+
+  /** the result of the last report with the model completed by with_int_vars */
+  fn last_result(&self) -> SolverResult {
+    self.completed(&self.report.as_ref().expect("solved").result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parse;
+
+  #[test]
+  fn check_sat() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (str.in.re x1 (str.to.re "abab")))
+      (check-sat)
+      "#;
+    let mut solver = Solver::new(parse(input).unwrap());
+    assert!(solver.report().is_none());
+    assert_eq!(solver.check_sat(), SolverResult::Sat);
+    assert!(solver.report().is_some());
+
+    let mut solver = Solver::new(parse(&input.replace("abab", "aba")).unwrap());
+    assert_eq!(solver.check_sat(), SolverResult::Unsat);
+
+    /* nothing is asserted after reset */
+    solver.reset_assertions();
+    assert!(solver.report().is_none());
+    assert_eq!(solver.problem().vars().len(), 2);
+    assert_eq!(solver.check_sat(), SolverResult::Sat);
+    solver.reset();
+    assert!(solver.problem().vars().is_empty());
+  }
+
+  #[test]
+  fn get_model() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (declare-const i Int)
+      (assert (= x1 (str.++ x0 "b")))
+      (assert (str.in.re x1 (str.to.re "ab")))
+      (check-sat)
+      "#;
+    let mut solver = Solver::new(parse(input).unwrap());
+    assert_eq!(solver.get_model(), None);
+    solver.check_sat();
+    let model = solver.get_model().unwrap();
+    assert_eq!(model.len(), 4);
+    assert_eq!(model["x0"], "a");
+    assert_eq!(model["x1"], "ab");
+    assert_eq!(model["x2"], "");
+    assert_eq!(model["i"], "0");
+
+    let mut solver = Solver::new(parse(&input.replace("\"ab\"", "\"a\"")).unwrap());
+    assert_eq!(solver.check_sat(), SolverResult::Unsat);
+    assert_eq!(solver.get_model(), None);
+  }
+
+  #[test]
+  fn assume_value() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (= x1 (str.++ x0 " HTTP/1.1")))
+      (assert (str.in.re x1 (re.++ (str.to.re "GET /") (re.* re.allchar))))
+      (check-sat)
+      "#;
+    let mut solver = Solver::new(parse(input).unwrap());
+    assert_eq!(
+      solver.assume_value("y", "GET /"),
+      Err(ParseError::UnknownVariable {
+        span: Span::default(),
+        name: "y".to_owned()
+      })
+    );
+
+    for (value, result) in [
+      ("GET /index.html", SolverResult::Sat),
+      ("POST /form", SolverResult::Unsat),
+      ("GET /", SolverResult::Sat),
+    ] {
+      solver.assume_value("x0", value).unwrap();
+      assert_eq!(solver.check_sat(), result);
+    }
+    assert_eq!(solver.get_model().unwrap()["x1"], "GET / HTTP/1.1");
+
+    /* a defined variable and an otherwise unconstrained one can be pinned as well */
+    solver.assume_value("x1", "GET / HTTP/1.1").unwrap();
+    solver.assume_value("x2", "abc").unwrap();
+    assert_eq!(solver.check_sat(), SolverResult::Sat);
+    assert_eq!(solver.get_model().unwrap()["x2"], "abc");
+    solver.assume_value("x1", "GET /").unwrap();
+    assert_eq!(solver.check_sat(), SolverResult::Unsat);
+
+    solver.clear_assumptions();
+    assert_eq!(solver.check_sat(), SolverResult::Sat);
+  }
+
+  #[test]
+  fn minimal_unsat_subset() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (declare-const x2 String)
+      (assert (str.in.re x0 (re.* (str.to.re "a"))))
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (str.in.re x2 (str.to.re "c")))
+      (assert (str.in.re x1 (str.to.re "aba")))
+      (check-sat)
+      "#;
+    let solver = Solver::new(parse(input).unwrap());
+    let subset = solver.minimal_unsat_subset().unwrap();
+    let spans: Vec<_> = subset.reg_constraints().iter().map(|c| c.span().line).collect();
+    assert_eq!(spans, vec![8]);
+    let spans: Vec<_> = subset.sl_constraints().iter().map(|c| c.span().line).collect();
+    assert_eq!(spans, vec![6]);
+    assert_eq!(Solver::new(subset).check_sat(), SolverResult::Unsat);
+
+    let solver = Solver::new(parse(&input.replace("aba", "aa")).unwrap());
+    assert!(solver.minimal_unsat_subset().is_none());
+  }
+
+  #[test]
+  fn builder() {
+    let mut solver = SolverBuilder::new()
+      .with_timeout(Duration::from_secs(60))
+      .with_domain::<CharWrap>()
+      .build();
+    solver.declare("x").unwrap();
+    solver.declare("y").unwrap();
+    assert!(matches!(
+      solver.declare("x"),
+      Err(ParseError::Redeclared { .. })
+    ));
+    solver
+      .assert_regex("y", Regex::parse("(ab)+").unwrap())
+      .unwrap();
+    solver.assert_transduction("y", "(str.++ x x)").unwrap();
+    assert!(matches!(
+      solver.assert_regex("z", Regex::all()),
+      Err(ParseError::UnknownVariable { .. })
+    ));
+    assert!(matches!(
+      solver.assert_transduction("y", "(str.++ x"),
+      Err(ParseError::Syntax { .. })
+    ));
+    assert_eq!(
+      solver.check(),
+      SolverResult::Model(HashMap::from([
+        ("x".to_owned(), "ab".to_owned()),
+        ("y".to_owned(), "abab".to_owned()),
+      ]))
+    );
+
+    solver.assert_regex("x", Regex::parse("b*").unwrap()).unwrap();
+    assert_eq!(solver.check(), SolverResult::Unsat);
+    assert_eq!(solver.get_model(), None);
+
+    /* too large to be solved at once */
+    #[cfg(feature = "std")]
+    {
+      let mut solver = SolverBuilder::new().with_timeout(Duration::ZERO).build();
+      solver.declare("x").unwrap();
+      solver.declare("y").unwrap();
+      solver.assert_regex("x", Regex::parse("[a-z]{100}").unwrap()).unwrap();
+      solver.assert_transduction("y", "(str.reverse x)").unwrap();
+      solver.assert_regex("y", Regex::parse("(a|b)*").unwrap()).unwrap();
+      assert_eq!(solver.check(), SolverResult::Timeout(Duration::ZERO));
+      assert!(solver.report().is_none());
+    }
+  }
+
+  #[test]
+  fn push_pop() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 x0)))
+      (assert (str.in.re x1 (re.+ (str.to.re "ab"))))
+      (check-sat)
+      "#;
+    let mut solver = Solver::new(parse(input).unwrap());
+    assert_eq!(solver.check_sat(), SolverResult::Sat);
+    assert_eq!(solver.cache().counts(), (1, 1));
+
+    solver.push(1);
+    solver.declare("x2").unwrap();
+    solver.assert_regex("x0", Regex::seq("b")).unwrap();
+    assert_eq!(solver.check_sat(), SolverResult::Unsat);
+    assert_eq!(solver.cache().counts(), (1, 2));
+
+    /* both machines of the outer scope are reused */
+    solver.pop(1).unwrap();
+    assert_eq!(solver.problem().vars().len(), 2);
+    assert_eq!(solver.check_sat(), SolverResult::Sat);
+    assert_eq!(solver.cache().counts(), (1, 2));
+    assert!(solver.pop(1).is_err());
+  }
+
+  #[test]
+  fn check_sat_each() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const b Bool)
+      (assert (str.in.re x0 (re.+ (str.to.re "a"))))
+      (check-sat)
+      (push 1)
+      (assert (=> b (str.in.re x0 (str.to.re "b"))))
+      (check-sat-assuming (b))
+      (pop 1)
+      (assert (str.in.re x0 (str.to.re "aa")))
+      (check-sat)
+      (get-model)
+      "#;
+    let mut solver = Solver::new(parse(input).unwrap());
+    let results = solver.check_sat_each();
+    assert_eq!(results[..2], [SolverResult::Sat, SolverResult::Unsat]);
+    match &results[2] {
+      SolverResult::Model(model) => assert_eq!(model["x0"], "aa"),
+      result => panic!("expected a model, got {:?}", result),
+    }
+    assert_eq!(results.len(), 3);
+    assert!(solver.report().is_some());
+  }
+
+  #[test]
+  fn max_sat() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (str.in.re x0 (re.+ (str.to.re "a"))))
+      (assert (str.in.re x0 (re.+ (str.to.re "b"))))
+      (assert (str.in.re x0 ((_ re.loop 2 2) (re.union (str.to.re "a") (str.to.re "b")))))
+      (assert (str.in.re x1 (str.to.re "c")))
+      (check-sat)
+      "#;
+    let solver = Solver::new(parse(input).unwrap());
+    let (subset, dropped) = solver.max_sat().unwrap();
+    let spans: Vec<_> = dropped.iter().map(|c| c.span().line).collect();
+    assert_eq!(spans, vec![5]);
+    assert_eq!(subset.reg_constraints().len(), 3);
+    let mut solver = Solver::new(subset);
+    assert_eq!(solver.check_sat(), SolverResult::Sat);
+    assert_eq!(solver.get_model().unwrap()["x0"], "aa");
+
+    let solver = Solver::new(parse(&input.replace("\"b\"", "\"a\"")).unwrap());
+    assert_eq!(solver.max_sat().unwrap().1, vec![]);
+    let solver = Solver::new(parse(&input.replace("(check-sat)", "(assert false)")).unwrap());
+    assert!(solver.max_sat().is_none());
+  }
+
+  #[test]
+  fn ignored_assertions() {
+    let input = r#"
+      (declare-const x0 String)
+      (assert (str.in.re x0 (str.to.re "b")))
+      (assert (str.< x0 "a"))
+      (check-sat)
+      "#;
+    let mut solver = Solver::new(parse(input).unwrap());
+    assert!(matches!(solver.check_sat(), SolverResult::Unknown(_)));
+    assert!(matches!(solver.check(), SolverResult::Unknown(_)));
+    assert_eq!(solver.get_model(), None);
+    assert!(solver.max_sat().is_none());
+    assert!(solver.minimal_unsat_subset().is_none());
+
+    let unsat = input.replace(
+      "(assert (str.<",
+      "(assert (str.in.re x0 (str.to.re \"c\"))) (assert (str.<",
+    );
+    let solver = Solver::new(parse(&unsat).unwrap());
+    assert_eq!(solver.minimal_unsat_subset().unwrap().reg_constraints().len(), 2);
+  }
+
+  #[test]
+  fn model_near() {
+    let input = r#"
+      (declare-const x0 String)
+      (declare-const x1 String)
+      (assert (= x1 (str.++ x0 "!")))
+      (assert (str.in.re x1 (re.++ (re.* (str.to.re "ab")) (str.to.re "!"))))
+      (check-sat)
+      "#;
+    let solver = Solver::new(parse(input).unwrap());
+
+    let model = solver.model_near("x0", "abxab", 1).unwrap();
+    assert_eq!(model["x0"], "abab");
+    assert_eq!(model["x1"], "abab!");
+    assert_eq!(solver.model_near("x0", "xxxxx", 1), None);
+    assert_eq!(solver.model_near("x0", "", 0).unwrap()["x0"], "");
+
+    /* the reference may be of a defined variable */
+    let model = solver.model_near("x1", "aab!", 1).unwrap();
+    assert!(model["x1"] == "ab!" || model["x1"] == "abab!");
+    assert_eq!(model["x1"], format!("{}!", model["x0"]));
+  }
+}
@@ -1,13 +1,15 @@
 use std::{
+  cell::{Cell, RefCell},
   collections::{HashMap, HashSet},
-  fmt::Debug,
+  fmt::{Debug, Display},
   hash::Hash,
   iter::FromIterator,
   rc::Rc,
-  sync::atomic::{AtomicUsize, Ordering},
+  sync::atomic::{AtomicU64, Ordering},
 };
 
-pub trait State: Debug + Eq + Ord + Hash + Clone {
+/** states are shown by Display in DOT and messages, e.g. q17 */
+pub trait State: Debug + Display + Eq + Ord + Hash + Clone {
   fn new() -> Self;
 }
 impl State for StateImpl {
@@ -21,18 +23,157 @@ impl State for Rc<StateImpl> {
   }
 }
 
-static STATE_CNT: AtomicUsize = AtomicUsize::new(0);
+static STATE_CNT: AtomicU64 = AtomicU64::new(0);
 
+/** source of the numbers of new StateImpl, which is GlobalCounter out of with_generator */
+pub trait StateGenerator {
+  fn next_id(&self) -> u64;
+}
+
+/** the atomic counter shared by all threads */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalCounter;
+impl StateGenerator for GlobalCounter {
+  fn next_id(&self) -> u64 {
+    STATE_CNT.fetch_add(1, Ordering::SeqCst)
+  }
+}
+
+/** the number of ids ThreadLocalCounter takes from the global counter at once */
+const BLOCK: u64 = 1 << 12;
+
+thread_local! {
+  /** the next id and the end of the block the thread took */
+  static THREAD_BLOCK: Cell<(u64, u64)> = const { Cell::new((0, 0)) };
+  static GENERATOR: RefCell<Option<Rc<dyn StateGenerator>>> = const { RefCell::new(None) };
+}
+
+/**
+ * counts in blocks taken from the global counter, so that threads touch the atomic
+ * once per BLOCK states while ids are still unique among all threads.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadLocalCounter;
+impl StateGenerator for ThreadLocalCounter {
+  fn next_id(&self) -> u64 {
+    THREAD_BLOCK.with(|block| {
+      let (mut next, mut end) = block.get();
+      if next == end {
+        next = STATE_CNT.fetch_add(BLOCK, Ordering::SeqCst);
+        end = next + BLOCK;
+      }
+      block.set((next + 1, end));
+      next
+    })
+  }
+}
+
+/**
+ * a counter of its own from 0, so that a run numbers its states the same whatever ran before.
+ * states made out of its scope may have the same ids, so they must not be mixed.
+ */
+#[derive(Debug, Default)]
+pub struct Counter(Cell<u64>);
+impl Counter {
+  pub fn new() -> Self {
+    Counter::default()
+  }
+}
+impl StateGenerator for Counter {
+  fn next_id(&self) -> u64 {
+    let id = self.0.get();
+    self.0.set(id + 1);
+    id
+  }
+}
+
+/** run f where new states of the current thread are numbered by generator */
+pub fn with_generator<T>(generator: impl StateGenerator + 'static, f: impl FnOnce() -> T) -> T {
+  struct Restore(Option<Rc<dyn StateGenerator>>);
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      let previous = self.0.take();
+      GENERATOR.with(|current| *current.borrow_mut() = previous);
+    }
+  }
+  let previous = GENERATOR.with(|current| current.replace(Some(Rc::new(generator))));
+  let _restore = Restore(previous);
+  f()
+}
+
+/** which StateGenerator numbers states, chosen by RunOption */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StateCounter {
+  /** GlobalCounter */
+  #[default]
+  Global,
+  /** ThreadLocalCounter */
+  ThreadLocal,
+  /** a Counter for each run */
+  PerRun,
+}
+impl StateCounter {
+  /** run f with the generator */
+  pub fn scope<T>(self, f: impl FnOnce() -> T) -> T {
+    match self {
+      StateCounter::Global => with_generator(GlobalCounter, f),
+      StateCounter::ThreadLocal => with_generator(ThreadLocalCounter, f),
+      StateCounter::PerRun => with_generator(Counter::new(), f),
+    }
+  }
+}
+
+/** a state numbered by the StateGenerator in scope, whose id stays the same in every dump */
 #[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
-pub struct StateImpl(usize);
+pub struct StateImpl(u64);
 impl StateImpl {
   pub fn new() -> StateImpl {
-    StateImpl(STATE_CNT.fetch_add(1, Ordering::SeqCst))
+    let id = GENERATOR.with(|current| {
+      current
+        .borrow()
+        .as_ref()
+        .map(|generator| generator.next_id())
+    });
+    StateImpl(id.unwrap_or_else(|| GlobalCounter.next_id()))
+  }
+
+  pub fn id(&self) -> u64 {
+    self.0
+  }
+}
+impl Default for StateImpl {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+/**
+ * loaded ids are never given to new states, since the global counter is moved past them.
+ * the block ThreadLocalCounter took is dropped only on the thread loading them,
+ * so machines should be loaded before other threads make states, and out of Counter.
+ */
+#[cfg(feature = "serde")]
+impl serde::Serialize for StateImpl {
+  fn serialize<Ser: serde::Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+    serde::Serialize::serialize(&self.0, serializer)
+  }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StateImpl {
+  fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+    let id: u64 = serde::Deserialize::deserialize(deserializer)?;
+    STATE_CNT.fetch_max(id + 1, Ordering::SeqCst);
+    THREAD_BLOCK.with(|block| block.set((0, 0)));
+    Ok(StateImpl(id))
+  }
+}
+impl Display for StateImpl {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!("q{}", self.0))
   }
 }
 impl Debug for StateImpl {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    f.write_fmt(format_args!("S({})", self.0))
+    Display::fmt(self, f)
   }
 }
 
@@ -173,6 +314,33 @@ pub trait StateMachine: Sized {
     }
   }
 
+  /** every transition as (source, predicate, target), one for each target */
+  fn transitions(&self) -> impl Iterator<Item = (&Self::StateType, &Self::BoolAlg, &Self::Target)> {
+    self
+      .transition()
+      .iter()
+      .flat_map(|((p, phi), targets)| targets.iter().map(move |t| (p, phi, t)))
+  }
+
+  /** transitions from source to target */
+  fn edges_between<'a>(
+    &'a self,
+    source: &'a Self::StateType,
+    target: &'a Self::StateType,
+  ) -> impl Iterator<Item = (&'a Self::StateType, &'a Self::BoolAlg, &'a Self::Target)> {
+    self
+      .transitions()
+      .filter(move |(p, _, t)| *p == source && t.to_state() == target)
+  }
+
+  /** transitions from source, whose predicates it reads */
+  fn predicates_from<'a>(
+    &'a self,
+    source: &'a Self::StateType,
+  ) -> impl Iterator<Item = (&'a Self::StateType, &'a Self::BoolAlg, &'a Self::Target)> {
+    self.transitions().filter(move |(p, _, _)| *p == source)
+  }
+
   fn state_predicate(&self, q: &Self::StateType) -> Self::BoolAlg {
     self
       .transition()
@@ -189,15 +357,9 @@ pub trait StateMachine: Sized {
       if reachables.insert(state) {
         stack.extend(
           self
-            .transition()
-            .into_iter()
-            .filter_map(|((p, _), target)| {
-              target
-                .into_iter()
-                .find(|t| *t.to_state() == *state)
-                .is_some()
-                .then(|| p)
-            }),
+            .transitions()
+            .filter(|(_, _, t)| t.to_state() == state)
+            .map(|(p, _, _)| p),
         );
       }
     }
@@ -211,14 +373,12 @@ pub trait StateMachine: Sized {
 
     while let Some(state) = stack.pop() {
       if reachables.insert(state) {
-        self
-          .transition()
-          .into_iter()
-          .for_each(|((p, phi), target)| {
-            if *p == *state && phi.satisfiable() {
-              stack.extend(target.into_iter().map(|t| t.to_state()));
-            }
-          });
+        stack.extend(
+          self
+            .predicates_from(state)
+            .filter(|(_, phi, _)| phi.satisfiable())
+            .map(|(_, _, t)| t.to_state()),
+        );
       }
     }
 
@@ -363,6 +523,44 @@ mod tests {
     assert_ne!(state_2, state_1);
   }
 
+  #[test]
+  fn display() {
+    let (s0, s1) = with_generator(Counter::new(), || (StateImpl::new(), StateImpl::new()));
+    assert_eq!(s1.id(), 1);
+    assert_eq!(s0.to_string(), "q0");
+    assert_eq!(format!("{:?}", s1), "q1");
+    assert_eq!(Rc::new(s1).to_string(), "q1");
+  }
+
+  #[test]
+  fn generators() {
+    let (s0, s1) = with_generator(Counter::new(), || (StateImpl::new(), StateImpl::new()));
+    assert_eq!((s0.0, s1.0), (0, 1));
+    let (t0, t1) = with_generator(Counter::new(), || {
+      let t0 = StateImpl::new();
+      with_generator(GlobalCounter, StateImpl::new);
+      (t0, StateImpl::new())
+    });
+    assert_eq!((t0, t1), (s0, s1));
+
+    let ids: Vec<Vec<u64>> = std::thread::scope(|scope| {
+      let workers: Vec<_> = (0..4)
+        .map(|_| {
+          scope.spawn(|| {
+            StateCounter::ThreadLocal
+              .scope(|| (0..100).map(|_| StateImpl::new().0).collect::<Vec<_>>())
+          })
+        })
+        .collect();
+      workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    let mut all = HashSet::new();
+    for id in ids.into_iter().flatten() {
+      assert!(all.insert(id));
+    }
+    assert!(!all.contains(&StateImpl::new().0));
+  }
+
   #[test]
   fn state_distingish_from_another() {
     fn new() -> StateImpl {
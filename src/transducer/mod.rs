@@ -404,4 +404,58 @@ pub(crate) mod tests {
       to_charwrap(prefixes.iter().map(|s| s.as_ref()))
     }
   }
+
+  #[test]
+  fn to_dot() {
+    let x = VariableImpl::new();
+    let sst = Builder::identity(&x);
+    let dot = sst.to_dot();
+    assert!(dot.starts_with("digraph sst {"));
+    assert!(dot.contains(&format!("start -> {};", sst.initial_state())));
+    assert!(dot.contains(&format!("xlabel=\"{:?}\"", x)));
+    assert!(dot.contains(&format!("label=\"¬'#' / {0:?} := {0:?} · Id\"", x)));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn serde_round_trip() {
+    let sst = Builder::map_chars(
+      term::Lambda::mapping(vec![(CharWrap::from('a'), CharWrap::from('b'))]),
+      &VariableImpl::new(),
+    );
+    let json = serde_json::to_string(&sst).unwrap();
+    let loaded: Sst<CharWrap, StateImpl, VariableImpl> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.states(), sst.states());
+    assert_eq!(loaded.transition(), sst.transition());
+    let input: Vec<CharWrap> = chars("abc");
+    assert_eq!(loaded.run(&input), vec![chars("bbc")]);
+  }
+
+  #[test]
+  fn length_relation() {
+    let builder = Builder::init();
+
+    let cons = Transduction(vec![
+      TransductionOp::Var(0),
+      TransductionOp::Reverse(1),
+      TransductionOp::Str("ab".to_owned()),
+    ]);
+    let sst = builder.generate(2, &cons);
+    let relation = sst.length_relation().unwrap();
+    /* x0#x1# -> x0#x1#x0 rev(x1) ab# */
+    for (x0, x1) in [("", ""), ("abc", "w"), ("0zero", "1one")] {
+      let output = sst.run(&to_charwrap([x0, x1]));
+      assert_eq!(output.len(), 1);
+      assert_eq!(relation.eval(&[x0.len(), x1.len()]), output[0].len() as i64);
+    }
+    assert_eq!(relation.eval(&[3, 4]), 2 * 3 + 2 * 4 + 5);
+
+    /* the length of replaced string depends on the occurrences */
+    let cons = Transduction(vec![TransductionOp::ReplaceAll(
+      0,
+      Regex::seq("a"),
+      ReplaceTarget::Str("xyz".to_owned()),
+    )]);
+    assert!(builder.generate(1, &cons).length_relation().is_none());
+  }
 }
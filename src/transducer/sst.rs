@@ -1,13 +1,17 @@
-use super::term::{FunctionTerm, FunctionTermImpl, OutputComp, UpdateComp, Variable};
+use super::term::{Affine, FunctionTerm, FunctionTermImpl, OutputComp, UpdateComp, Variable};
 use crate::boolean_algebra::{BoolAlg, Predicate};
+use crate::regular::symbolic_automata::SymFa;
 use crate::state::{self, State, StateMachine};
 use crate::util::{
   Domain,
-  extention::{ImmutableValueMap}
+  dot_escape,
+  extention::{ImmutableValueMap, MultiMap},
+  hash_of
 };
 use std::{
-  collections::{HashMap, HashSet},
+  collections::{HashMap, HashSet, VecDeque},
   fmt::Debug,
+  hash::{Hash, Hasher},
 };
 
 type UpdateFunction<F, V> = HashMap<V, Vec<UpdateComp<F, V>>>;
@@ -15,9 +19,45 @@ type Source<B, S> = (S, B);
 type Target<F, S, V> = (S, UpdateFunction<F, V>);
 type Output<D, V> = Vec<OutputComp<D, V>>;
 type Transition<B, F, S, V> = HashMap<Source<B, S>, Vec<Target<F, S, V>>>;
+type RegisterUpdate<B, S, V> = HashMap<(S, B, S), HashMap<V, Affine<V>>>;
+type CanonicalTarget<'a, F, V> = (
+  usize,
+  Vec<(&'a V, &'a Vec<UpdateComp<F, V>>)>,
+  Vec<(&'a V, &'a Affine<V>)>,
+);
+type CanonicalState<'a, D, B, F, V> = (
+  Option<&'a Output<D, V>>,
+  Vec<(&'a B, Vec<CanonicalTarget<'a, F, V>>)>,
+);
+type CanonicalForm<'a, D, B, F, V> = (
+  Vec<&'a V>,
+  Vec<&'a V>,
+  Vec<CanonicalState<'a, D, B, F, V>>,
+);
+
+/** a sequence of outputs or updates in DOT, e.g. X(0) · 'a', where ε is the empty one */
+fn sequence_label<T: Debug>(sequence: &[T]) -> String {
+  if sequence.is_empty() {
+    String::from("ε")
+  } else {
+    let labels: Vec<_> = sequence.iter().map(|x| format!("{:?}", x)).collect();
+    labels.join(" · ")
+  }
+}
 
 /** implementation of symbolic streaming string transducer (SSST) */
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(bound(
+    serialize = "D: serde::Serialize, B: serde::Serialize, F: serde::Serialize, \
+                 S: serde::Serialize, V: serde::Serialize",
+    deserialize = "D: serde::Deserialize<'de>, B: serde::Deserialize<'de>, \
+                   F: serde::Deserialize<'de>, S: serde::Deserialize<'de>, \
+                   V: serde::Deserialize<'de>"
+  ))
+)]
 pub struct SymSst<D, B, F, S, V>
 where
   D: Domain,
@@ -34,7 +74,54 @@ where
    * if a next transition has no correponding sequence for some variable, update with identity
    * i.e. update(var) = vec![UpdateComp::X(var)]
    */
+  #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_pooled"))]
   pub(crate) transition: Transition<B, F, S, V>,
+  /** integer registers, initialized with 0 */
+  pub(crate) registers: HashSet<V>,
+  /**
+   * affine update of registers for each transition (source, predicate, target).
+   * a register without corresponding expression keeps its value.
+   * registers only observe the run, so pre-image ignores them.
+   */
+  #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_pairs"))]
+  pub(crate) register_update: RegisterUpdate<B, S, V>,
+}
+/**
+ * structural equality up to renaming of states (variables are not renamed),
+ * which is exact for deterministic ones (see canonical_form).
+ */
+impl<D, B, F, S, V> PartialEq for SymSst<D, B, F, S, V>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  F: FunctionTerm<Domain = D>,
+  S: State,
+  V: Variable,
+{
+  fn eq(&self, other: &Self) -> bool {
+    self.canonical_form() == other.canonical_form()
+  }
+}
+impl<D, B, F, S, V> Eq for SymSst<D, B, F, S, V>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  F: FunctionTerm<Domain = D>,
+  S: State,
+  V: Variable,
+{
+}
+impl<D, B, F, S, V> Hash for SymSst<D, B, F, S, V>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  F: FunctionTerm<Domain = D>,
+  S: State,
+  V: Variable,
+{
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.canonical_form().hash(state)
+  }
 }
 impl<D, B, F, S, V> SymSst<D, B, F, S, V>
 where
@@ -57,11 +144,123 @@ where
       initial_state,
       output_function,
       transition,
+      registers: HashSet::new(),
+      register_update: HashMap::new(),
     };
     sst.minimize();
     sst
   }
 
+  pub fn with_registers(
+    mut self,
+    registers: HashSet<V>,
+    register_update: RegisterUpdate<B, S, V>,
+  ) -> Self {
+    self.registers.extend(registers);
+    self.register_update.extend(register_update);
+    self
+  }
+
+  /**
+   * sorted variables and registers, and reachable states renumbered
+   * in the breadth-first order from the initial state,
+   * following transitions in the order of the hash of their predicates.
+   * the order of targets is kept, so nondeterministic machines may differ by it.
+   */
+  fn canonical_form(&self) -> CanonicalForm<'_, D, B, F, V> {
+    let mut variables: Vec<_> = self.variables.iter().collect();
+    variables.sort();
+    let mut registers: Vec<_> = self.registers.iter().collect();
+    registers.sort();
+
+    let mut numbers = HashMap::from([(&self.initial_state, 0)]);
+    let mut queue = VecDeque::from([&self.initial_state]);
+    let mut form = vec![];
+
+    while let Some(p) = queue.pop_front() {
+      let mut transitions: Vec<_> = self
+        .transition
+        .iter()
+        .filter(|((q, _), _)| *q == *p)
+        .map(|((_, phi), targets)| (phi, targets))
+        .collect();
+      transitions.sort_by_key(|(phi, _)| hash_of(*phi));
+
+      let transitions = transitions
+        .into_iter()
+        .map(|(phi, targets)| {
+          let targets = targets
+            .iter()
+            .map(|(q, alpha)| {
+              let n = numbers.len();
+              let number = *numbers.entry(q).or_insert_with(|| {
+                queue.push_back(q);
+                n
+              });
+              let mut update: Vec<_> = alpha.iter().collect();
+              update.sort_by_key(|(x, _)| *x);
+              let mut register_update: Vec<_> = self
+                .register_update
+                .get(&(S::clone(p), B::clone(phi), S::clone(q)))
+                .into_iter()
+                .flatten()
+                .collect();
+              register_update.sort_by_key(|(x, _)| *x);
+              (number, update, register_update)
+            })
+            .collect();
+          (phi, targets)
+        })
+        .collect();
+      form.push((self.output_function.get(p), transitions));
+    }
+
+    (variables, registers, form)
+  }
+
+  /**
+   * Graphviz DOT of the transducer, whose states are named by Display.
+   * transitions are labeled with their predicates and updates,
+   * and final states with their outputs.
+   */
+  pub fn to_dot(&self) -> String {
+    let mut dot = String::from("digraph sst {\n  rankdir=LR;\n  start [shape=point];\n");
+    let mut nodes: Vec<_> = self.states.iter().collect();
+    nodes.sort();
+    for s in nodes {
+      match self.output_function.get(s) {
+        Some(output) => dot.push_str(&format!(
+          "  {} [shape=doublecircle, xlabel=\"{}\"];\n",
+          s,
+          dot_escape(&sequence_label(output))
+        )),
+        None => dot.push_str(&format!("  {} [shape=circle];\n", s)),
+      }
+    }
+    dot.push_str(&format!("  start -> {};\n", self.initial_state));
+
+    let mut edges: Vec<_> = self
+      .transition
+      .iter()
+      .flat_map(|((p, phi), targets)| {
+        targets.iter().map(move |(q, update)| {
+          let mut update: Vec<_> = update.iter().collect();
+          update.sort_by_key(|(x, _)| *x);
+          let update: Vec<_> = update
+            .into_iter()
+            .map(|(x, seq)| format!("{:?} := {}", x, sequence_label(seq)))
+            .collect();
+          let label = dot_escape(&format!("{} / {}", phi.label(), update.join(", ")));
+          format!("  {} -> {} [label=\"{}\"];\n", p, q, label)
+        })
+      })
+      .collect();
+    edges.sort();
+    dot.extend(edges);
+    dot.push_str("}\n");
+    dot
+  }
+
   /**
    * execute sst with given input.
    * if a next transition has no correponding sequence for some variable,
@@ -80,40 +279,11 @@ where
     self.generalized_run(
       input.into_iter(),
       vec![(S::clone(&self.initial_state), initial_map)],
-      |(_, map), c, (q, alpha)| {
-        let var_map = self
-          .variables
-          .iter()
-          .map(|var| {
-            (
-              V::clone(var),
-              alpha
-                .get(var)
-                .unwrap_or(&vec![UpdateComp::X(V::clone(var))])
-                .into_iter()
-                .flat_map(|out| match out {
-                  UpdateComp::F(f) => vec![D::clone(f.apply(c))],
-                  UpdateComp::X(var) => map.get(var).unwrap_or(&vec![]).clone(),
-                })
-                .collect(),
-            )
-          })
-          .collect();
-
-        (S::clone(q), var_map)
-      },
+      |(_, map), c, (q, alpha)| (S::clone(q), self.update_variables(map, c, alpha)),
       |possibilities| {
         let mut results = vec![];
         possibilities.into_iter().for_each(|(q, f)| {
-          if let Some(output) = self.output_function.get(&q) {
-            let result = output
-              .into_iter()
-              .flat_map(|o| match o {
-                OutputComp::A(a) => vec![D::clone(a)],
-                OutputComp::X(x) => f.get(x).unwrap_or(&vec![]).clone(),
-              })
-              .collect();
-
+          if let Some(result) = self.output(&q, &f) {
             if !results.contains(&result) {
               results.push(result);
             }
@@ -124,6 +294,170 @@ where
     )
   }
 
+  /** execute sst with given input, and also return the final values of registers */
+  pub fn run_with_registers<'a>(
+    &self,
+    input: impl IntoIterator<Item = &'a D>,
+  ) -> Vec<(Vec<D>, HashMap<V, i64>)>
+  where
+    D: 'a,
+  {
+    let initial_map: HashMap<V, Vec<D>> = self
+      .variables
+      .iter()
+      .map(|var| (V::clone(var), vec![]))
+      .collect();
+    let initial_registers: HashMap<V, i64> = self
+      .registers
+      .iter()
+      .map(|register| (V::clone(register), 0))
+      .collect();
+
+    let mut possibilities = vec![(
+      S::clone(&self.initial_state),
+      initial_map,
+      initial_registers,
+    )];
+    for c in input {
+      possibilities = self.step(
+        possibilities,
+        |(curr, map, registers), ((p, phi), (q, alpha))| {
+          (*p == *curr && phi.denote(c)).then(|| {
+            let mut registers_ = registers.clone();
+            if let Some(update) =
+              self
+                .register_update
+                .get(&(S::clone(p), B::clone(phi), S::clone(q)))
+            {
+              update.iter().for_each(|(register, expr)| {
+                registers_.insert(V::clone(register), expr.eval(registers));
+              });
+            }
+
+            (
+              S::clone(q),
+              self.update_variables(map, c, alpha),
+              registers_,
+            )
+          })
+        },
+      );
+    }
+
+    let mut results = vec![];
+    possibilities.into_iter().for_each(|(q, f, registers)| {
+      if let Some(output) = self.output(&q, &f) {
+        let result = (output, registers);
+        if !results.contains(&result) {
+          results.push(result);
+        }
+      }
+    });
+    results
+  }
+
+  fn update_variables(
+    &self,
+    map: &HashMap<V, Vec<D>>,
+    c: &D,
+    alpha: &UpdateFunction<F, V>,
+  ) -> HashMap<V, Vec<D>> {
+    self
+      .variables
+      .iter()
+      .map(|var| {
+        (
+          V::clone(var),
+          alpha
+            .get(var)
+            .unwrap_or(&vec![UpdateComp::X(V::clone(var))])
+            .iter()
+            .flat_map(|out| match out {
+              UpdateComp::F(f) => vec![D::clone(f.apply(c))],
+              UpdateComp::X(var) => map.get(var).unwrap_or(&vec![]).clone(),
+            })
+            .collect(),
+        )
+      })
+      .collect()
+  }
+
+  fn output(&self, q: &S, map: &HashMap<V, Vec<D>>) -> Option<Vec<D>> {
+    self.output_function.get(q).map(|output| {
+      output
+        .iter()
+        .flat_map(|o| match o {
+          OutputComp::A(a) => vec![D::clone(a)],
+          OutputComp::X(x) => map.get(x).unwrap_or(&vec![]).clone(),
+        })
+        .collect()
+    })
+  }
+
+  pub fn registers(&self) -> &HashSet<V> {
+    &self.registers
+  }
+
+  /**
+   * the values the counter register ends with on the words of sfa, up to bound,
+   * and whether those are all the values it takes.
+   * None unless it's a counter, i.e. it's only incremented by non-negative constants,
+   * which keeps the runs exceeding bound from coming back below it.
+   */
+  pub fn counter_values(
+    &self,
+    register: &V,
+    sfa: &SymFa<D, B, S>,
+    bound: usize,
+  ) -> Option<(Vec<i64>, bool)> {
+    let mut increments = HashMap::new();
+    for (transition, update) in &self.register_update {
+      if let Some(expr) = update.get(register) {
+        match expr.terms() {
+          (k, [(r, 1)]) if r == register && k >= 0 => increments.insert(transition, k),
+          _ => return None,
+        };
+      }
+    }
+
+    let initial = (&self.initial_state, &sfa.initial_state, 0);
+    let mut visited = HashSet::from([initial]);
+    let mut queue = VecDeque::from([initial]);
+    let mut values = std::collections::BTreeSet::new();
+    let mut complete = true;
+    while let Some((p, s, value)) = queue.pop_front() {
+      if self.output_function.contains_key(p) && sfa.final_states.contains(s) {
+        values.insert(value);
+      }
+      let moves = self
+        .transition
+        .iter()
+        .filter(|((p_, _), _)| p_ == p)
+        .flat_map(|((_, phi), targets)| targets.iter().map(move |(q, _)| (phi, q)));
+      for (phi, q) in moves {
+        let k = increments
+          .get(&(S::clone(p), B::clone(phi), S::clone(q)))
+          .copied()
+          .unwrap_or(0);
+        if value + k > bound as i64 {
+          complete = false;
+          continue;
+        }
+        for ((_, psi), ts) in sfa.transition.iter().filter(|((s_, _), _)| s_ == s) {
+          if !phi.and(psi).satisfiable() {
+            continue;
+          }
+          for t in ts {
+            if visited.insert((q, t, value + k)) {
+              queue.push_back((q, t, value + k));
+            }
+          }
+        }
+      }
+    }
+    Some((values.into_iter().collect(), complete))
+  }
+
   pub fn variables(&self) -> &HashSet<V> {
     &self.variables
   }
@@ -132,6 +466,188 @@ where
     &mut self.variables
   }
 
+  fn run_pipeline(pipeline: &[&Self], input: &[D]) -> HashSet<Vec<D>> {
+    pipeline
+      .iter()
+      .fold(HashSet::from([input.to_vec()]), |inputs, sst| {
+        inputs.iter().flat_map(|input| sst.run(input)).collect()
+      })
+  }
+
+  /**
+   * bounded equivalence check of two pipelines of ssts, applied from the head.
+   * compare the outputs for all inputs over alphabet up to max_len,
+   * and return the shortest input on which they differ.
+   */
+  pub fn counterexample_upto(
+    lhs: &[&Self],
+    rhs: &[&Self],
+    alphabet: &[D],
+    max_len: usize,
+  ) -> Option<Vec<D>> {
+    let mut words: Vec<Vec<D>> = vec![vec![]];
+    for len in 0..=max_len {
+      if let Some(word) = words
+        .iter()
+        .find(|word| Self::run_pipeline(lhs, word) != Self::run_pipeline(rhs, word))
+      {
+        return Some(word.clone());
+      }
+
+      if len < max_len {
+        words = words
+          .iter()
+          .flat_map(|word| {
+            alphabet.iter().map(move |c| {
+              let mut word = word.clone();
+              word.push(D::clone(c));
+              word
+            })
+          })
+          .collect();
+      }
+    }
+    None
+  }
+
+  pub fn equivalent_upto(lhs: &[&Self], rhs: &[&Self], alphabet: &[D], max_len: usize) -> bool {
+    Self::counterexample_upto(lhs, rhs, alphabet, max_len).is_none()
+  }
+
+  /**
+   * affine relation between lengths of input segments split by separator and the output length.
+   * it exists when each segment updates variables with translation only,
+   * i.e. a variable is updated with itself and constants, like copyless ssts built by SstBuilder.
+   * return None if the output length depends on other than lengths.
+   */
+  pub fn length_relation(&self) -> Option<LengthRelation> {
+    let separator = D::separator();
+    let is_char = |phi: &B| phi.and(&B::separator().not()).satisfiable();
+
+    let initial_lens: HashMap<V, LengthRelation> = self
+      .variables
+      .iter()
+      .map(|var| (V::clone(var), LengthRelation::default()))
+      .collect();
+    let mut visited = HashMap::new();
+    let mut stack = vec![(&self.initial_state, 0usize, initial_lens)];
+    let mut result: Option<LengthRelation> = None;
+
+    while let Some((entry, segment, lens)) = stack.pop() {
+      if segment > self.states.len() {
+        /* separator can be read infinitely */
+        return None;
+      }
+      match visited.get(&(entry, segment)) {
+        Some(lens_) if *lens_ == lens => continue,
+        Some(_) => return None,
+        None => {
+          visited.insert((entry, segment), lens.clone());
+        }
+      }
+
+      /* states reachable in the segment, and the increment of each variable per character */
+      let mut region = HashSet::from([entry]);
+      let mut region_stack = vec![entry];
+      let mut increment: Option<HashMap<&V, i64>> = None;
+      while let Some(p) = region_stack.pop() {
+        for ((p_, phi), target) in &self.transition {
+          if *p_ != *p || !is_char(phi) {
+            continue;
+          }
+
+          for (q, alpha) in target {
+            let mut inc = HashMap::new();
+            for var in &self.variables {
+              let mut c = 0;
+              if let Some(seq) = alpha.get(var) {
+                let mut itself = 0;
+                for uc in seq {
+                  match uc {
+                    UpdateComp::X(x) if *x == *var => itself += 1,
+                    UpdateComp::X(_) => return None,
+                    UpdateComp::F(_) => c += 1,
+                  }
+                }
+                if itself != 1 {
+                  return None;
+                }
+              }
+              inc.insert(var, c);
+            }
+
+            match &increment {
+              Some(inc_) if *inc_ != inc => return None,
+              Some(_) => {}
+              None => increment = Some(inc),
+            }
+            if region.insert(q) {
+              region_stack.push(q);
+            }
+          }
+        }
+      }
+
+      let lens: HashMap<V, LengthRelation> = lens
+        .into_iter()
+        .map(|(var, len)| {
+          let c = increment
+            .as_ref()
+            .and_then(|inc| inc.get(&var).cloned())
+            .unwrap_or(0);
+          (var, len.add(&LengthRelation::segment(segment, c)))
+        })
+        .collect();
+
+      for q in &region {
+        if let Some(output) = self.output_function.get(*q) {
+          let len = output
+            .iter()
+            .fold(LengthRelation::default(), |acc, oc| match oc {
+              OutputComp::A(_) => acc.add(&LengthRelation::constant(1)),
+              OutputComp::X(x) => acc.add(&lens[x]),
+            });
+
+          match &result {
+            Some(result) if *result != len => return None,
+            Some(_) => {}
+            None => result = Some(len),
+          }
+        }
+      }
+
+      for ((p, phi), target) in &self.transition {
+        if !region.contains(p) || !phi.denote(&separator) {
+          continue;
+        }
+
+        for (q, alpha) in target {
+          let lens_ = self
+            .variables
+            .iter()
+            .map(|var| {
+              let len = alpha
+                .get(var)
+                .map(|seq| {
+                  seq
+                    .iter()
+                    .fold(LengthRelation::default(), |acc, uc| match uc {
+                      UpdateComp::F(_) => acc.add(&LengthRelation::constant(1)),
+                      UpdateComp::X(x) => acc.add(&lens[x]),
+                    })
+                })
+                .unwrap_or_else(|| lens[var].clone());
+              (V::clone(var), len)
+            })
+            .collect();
+          stack.push((q, segment + 1, lens_));
+        }
+      }
+    }
+
+    result
+  }
+
   /**
    * merging two sst.
    * output function is first one's,
@@ -150,6 +666,8 @@ where
       initial_state: i2,
       output_function: o2,
       transition: t2,
+      registers: r2,
+      register_update: ru2,
     } = other;
 
     let cartesian: HashMap<_, _> = self
@@ -197,6 +715,40 @@ where
       })
       .collect();
 
+    let mut register_update = HashMap::new();
+    if !self.registers.is_empty() || !r2.is_empty() {
+      for ((p1, phi1), target1) in &self.transition {
+        for ((p2, phi2), target2) in &t2 {
+          let phi = phi1.and(phi2);
+          if !phi.satisfiable() {
+            continue;
+          }
+
+          let p = cartesian.get(&(p1, p2)).expect(error_msg);
+          for (q1, _) in target1 {
+            for (q2, _) in target2 {
+              let mut update = self
+                .register_update
+                .get(&(S::clone(p1), B::clone(phi1), S::clone(q1)))
+                .cloned()
+                .unwrap_or_default();
+              update.extend(
+                ru2
+                  .get(&(S::clone(p2), B::clone(phi2), S::clone(q2)))
+                  .cloned()
+                  .unwrap_or_default(),
+              );
+
+              if !update.is_empty() {
+                let q = cartesian.get(&(q1, q2)).expect(error_msg);
+                register_update.insert((S::clone(p), phi.clone(), S::clone(q)), update);
+              }
+            }
+          }
+        }
+      }
+    }
+
     let mut output_function = HashMap::new();
     self.output_function.iter().for_each(|(fs1, output1)| {
       o2.iter().for_each(|(fs2, output2)| {
@@ -228,6 +780,8 @@ where
     self.variables.insert(V::clone(result));
     self.transition = transition;
     self.output_function = output_function;
+    self.registers.extend(r2);
+    self.register_update = register_update;
   }
 
   /* mainly focus to use in my theory. */
@@ -238,6 +792,8 @@ where
       initial_state,
       output_function: o1,
       mut transition,
+      mut registers,
+      mut register_update,
     } = self;
 
     let Self {
@@ -246,11 +802,15 @@ where
       initial_state: i2,
       output_function,
       transition: t2,
+      registers: r2,
+      register_update: ru2,
     } = other;
 
     states.extend(s2.into_iter());
     variables.extend(v2.into_iter());
     variables.insert(V::clone(var));
+    registers.extend(r2);
+    register_update.extend(ru2);
 
     t2.into_iter().for_each(|((p, phi), target)| {
       transition.safe_insert((p, phi), target)
@@ -274,6 +834,7 @@ where
       output_function,
       transition,
     )
+    .with_registers(registers, register_update)
   }
 
   pub fn chain_output(self, output: Output<D, V>) -> Self {
@@ -283,6 +844,8 @@ where
       initial_state,
       output_function: transition_,
       mut transition,
+      registers,
+      register_update,
     } = self;
 
     let end = S::new();
@@ -306,6 +869,279 @@ where
       output_function,
       transition,
     )
+    .with_registers(registers, register_update)
+  }
+}
+impl<D, B, S, V> SymSst<D, B, B::Term, S, V>
+where
+  D: Domain,
+  B: BoolAlg<Domain = D>,
+  S: State,
+  V: Variable,
+{
+  /**
+   * automaton over-approximating the outputs for the inputs accepted by sfa, i.e. dual of pre-image.
+   * a variable at each state of the product with sfa is expanded into its updates,
+   * forgetting where to return after the expansion.
+   * so it is exact when variables are only appended or prepended, like identity and reverse.
+   */
+  pub fn image(&self, sfa: &SymFa<D, B, S>) -> SymFa<D, B, S> {
+    /* states of the product reachable from the initial one, and transitions among them */
+    let mut product = vec![(&self.initial_state, &sfa.initial_state)];
+    let mut indices = HashMap::from([((&self.initial_state, &sfa.initial_state), 0)]);
+    let mut rules = vec![];
+    let mut idx = 0;
+    while idx < product.len() {
+      let (q, p) = product[idx];
+      for ((q_, phi), targets) in &self.transition {
+        if *q_ != *q {
+          continue;
+        }
+
+        for ((p_, psi), ps) in &sfa.transition {
+          let chi = phi.and(psi);
+          if *p_ != *p || !chi.satisfiable() {
+            continue;
+          }
+
+          for (q2, alpha) in targets {
+            for p2 in ps {
+              let idx2 = *indices.entry((q2, p2)).or_insert_with(|| {
+                product.push((q2, p2));
+                product.len() - 1
+              });
+              rules.push((idx, chi.clone(), idx2, alpha));
+            }
+          }
+        }
+      }
+      idx += 1;
+    }
+
+    /* nfa with epsilon transitions, where a variable at a state is delimited by start and end */
+    let mut states = HashSet::new();
+    let mut eps: HashMap<S, Vec<S>> = HashMap::new();
+    let mut edges: HashMap<S, Vec<(B, S)>> = HashMap::new();
+    let mut starts = HashMap::new();
+    let mut ends = HashMap::new();
+
+    macro_rules! node {
+      () => {{
+        let s = S::new();
+        states.insert(S::clone(&s));
+        s
+      }};
+      ($nodes:ident, $var:expr, $r:expr) => {
+        S::clone($nodes.entry(($var, $r)).or_insert_with(|| node!()))
+      };
+    }
+    macro_rules! expand {
+      ($curr:expr, $var:expr, $r:expr, $next:expr) => {
+        eps.insert_with_check($curr, [node!(starts, $var, $r)]);
+        eps.insert_with_check(node!(ends, $var, $r), [$next]);
+      };
+    }
+
+    /* variables are empty at first */
+    for x in &self.variables {
+      eps.insert_with_check(node!(starts, x, 0), [node!(ends, x, 0)]);
+    }
+    for (r, chi, r2, alpha) in &rules {
+      for x in &self.variables {
+        match alpha.get(x) {
+          Some(seq) => {
+            let mut curr = node!(starts, x, *r2);
+            for uc in seq {
+              let next = node!();
+              match uc {
+                UpdateComp::F(f) => {
+                  edges.insert_with_check(curr, [(chi.image(f), S::clone(&next))])
+                }
+                UpdateComp::X(y) => {
+                  expand!(curr, y, *r, S::clone(&next));
+                }
+              }
+              curr = next;
+            }
+            eps.insert_with_check(curr, [node!(ends, x, *r2)]);
+          }
+          None => {
+            expand!(node!(starts, x, *r2), x, *r, node!(ends, x, *r2));
+          }
+        }
+      }
+    }
+
+    let initial_state = node!();
+    let last = node!();
+    for (r, (q, p)) in product.iter().enumerate() {
+      if let (Some(output), true) = (self.output_function.get(*q), sfa.final_states.contains(*p)) {
+        let mut curr = S::clone(&initial_state);
+        for oc in output {
+          let next = node!();
+          match oc {
+            OutputComp::A(a) => {
+              edges.insert_with_check(curr, [(B::char(D::clone(a)), S::clone(&next))])
+            }
+            OutputComp::X(x) => {
+              expand!(curr, x, r, S::clone(&next));
+            }
+          }
+          curr = next;
+        }
+        eps.insert_with_check(curr, [S::clone(&last)]);
+      }
+    }
+
+    /* eliminate epsilon transitions */
+    let closure = |s: &S| {
+      let mut visited = HashSet::from([S::clone(s)]);
+      let mut stack = vec![S::clone(s)];
+      while let Some(t) = stack.pop() {
+        for u in eps.get(&t).into_iter().flatten() {
+          if visited.insert(S::clone(u)) {
+            stack.push(S::clone(u));
+          }
+        }
+      }
+      visited
+    };
+    let mut transition = HashMap::new();
+    let mut final_states = HashSet::new();
+    for s in &states {
+      let reachables = closure(s);
+      if reachables.contains(&last) {
+        final_states.insert(S::clone(s));
+      }
+      for t in &reachables {
+        for (phi, u) in edges.get(t).into_iter().flatten() {
+          transition.insert_with_check((S::clone(s), phi.clone()), [S::clone(u)]);
+        }
+      }
+    }
+
+    SymFa::new(states, initial_state, final_states, transition)
+  }
+
+  /**
+   * compose a character filter on the output side.
+   * characters failing pred are dropped, or the whole run is rejected, depending on mode.
+   * the separator always passes so that chained machines keep their segments.
+   * each transition is split by whether its function terms output characters satisfying pred.
+   */
+  pub fn filter_output(self, pred: &B, mode: FilterMode) -> Self {
+    let Self {
+      states,
+      variables,
+      initial_state,
+      output_function,
+      transition,
+      registers,
+      register_update,
+    } = self;
+    let pred = pred.or(&B::separator());
+
+    let output_function = output_function
+      .into_iter()
+      .filter_map(|(q, output)| {
+        let passes = |out: &OutputComp<D, V>| match out {
+          OutputComp::A(a) => pred.denote(a),
+          OutputComp::X(_) => true,
+        };
+        match mode {
+          FilterMode::Drop => Some((q, output.into_iter().filter(passes).collect())),
+          FilterMode::Reject => output.iter().all(passes).then_some((q, output)),
+        }
+      })
+      .collect::<HashMap<_, _>>();
+    if output_function.is_empty() {
+      /* every run is rejected, which minimize would turn into Self::empty() outputting nothing */
+      return Self {
+        states: HashSet::from([S::clone(&initial_state)]),
+        variables,
+        initial_state,
+        output_function,
+        transition: HashMap::new(),
+        registers,
+        register_update: HashMap::new(),
+      };
+    }
+
+    let mut transition_ = HashMap::new();
+    let mut register_update_ = HashMap::new();
+    for ((p, phi), targets) in transition {
+      let mut terms: Vec<&B::Term> = vec![];
+      for (_, update) in &targets {
+        for comp in update.values().flatten() {
+          if let UpdateComp::F(f) = comp {
+            if !terms.contains(&f) {
+              terms.push(f);
+            }
+          }
+        }
+      }
+
+      /* guards over which each term passes or not */
+      let mut guards: Vec<(B, Vec<bool>)> = vec![(phi.clone(), vec![])];
+      for f in &terms {
+        let passing = pred.with_lambda(f);
+        guards = guards
+          .into_iter()
+          .flat_map(|(guard, passes)| {
+            let mut split = vec![];
+            for pass in [true, false] {
+              if mode == FilterMode::Reject && !pass {
+                continue;
+              }
+              let condition = if pass { passing.clone() } else { passing.not() };
+              let guard = guard.and(&condition);
+              if guard.satisfiable() {
+                let mut assignment = passes.clone();
+                assignment.push(pass);
+                split.push((guard, assignment));
+              }
+            }
+            split
+          })
+          .collect();
+      }
+
+      for (guard, passes) in guards {
+        let targets = targets
+          .iter()
+          .map(|(q, update)| {
+            let update = update
+              .iter()
+              .map(|(x, seq)| {
+                let seq = seq
+                  .iter()
+                  .filter(|comp| match comp {
+                    UpdateComp::F(f) => passes[terms.iter().position(|g| *g == f).unwrap()],
+                    UpdateComp::X(_) => true,
+                  })
+                  .cloned()
+                  .collect();
+                (V::clone(x), seq)
+              })
+              .collect();
+            if let Some(alpha) = register_update.get(&(S::clone(&p), phi.clone(), S::clone(q))) {
+              register_update_.insert((S::clone(&p), guard.clone(), S::clone(q)), alpha.clone());
+            }
+            (S::clone(q), update)
+          })
+          .collect();
+        transition_.insert((S::clone(&p), guard), targets);
+      }
+    }
+
+    Self::new(
+      states,
+      variables,
+      initial_state,
+      output_function,
+      transition_,
+    )
+    .with_registers(registers, register_update_)
   }
 }
 impl<D, B, F, S, V> StateMachine for SymSst<D, B, F, S, V>
@@ -330,10 +1166,76 @@ where
       initial_state: S::clone(&state),
       output_function: HashMap::from([(S::clone(&state), vec![])]),
       transition: HashMap::from([((S::clone(&state), B::top()), vec![(state, HashMap::new())])]),
+      registers: HashSet::new(),
+      register_update: HashMap::new(),
     }
   }
 
   state::macros::impl_state_machine!(states, initial_state, output_function, transition);
 }
 
+/** what filter_output does with characters failing the predicate */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+  /** remove them from the output */
+  Drop,
+  /** reject the input producing them */
+  Reject,
+}
+
+/**
+ * affine relation of lengths,
+ * |output| = constant + sum of coefficients[i] * |i-th input segment|
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct LengthRelation {
+  pub constant: i64,
+  pub coefficients: Vec<i64>,
+}
+impl LengthRelation {
+  fn constant(c: i64) -> Self {
+    LengthRelation {
+      constant: c,
+      coefficients: vec![],
+    }
+  }
+
+  fn segment(idx: usize, c: i64) -> Self {
+    let mut coefficients = vec![0; idx + 1];
+    coefficients[idx] = c;
+    LengthRelation {
+      constant: 0,
+      coefficients,
+    }
+    .add(&Self::default())
+  }
+
+  fn add(mut self, other: &Self) -> Self {
+    self.constant += other.constant;
+    if self.coefficients.len() < other.coefficients.len() {
+      self.coefficients.resize(other.coefficients.len(), 0);
+    }
+    other
+      .coefficients
+      .iter()
+      .enumerate()
+      .for_each(|(i, c)| self.coefficients[i] += c);
+    /* trailing zeros are trimmed to compare relations */
+    while self.coefficients.last() == Some(&0) {
+      self.coefficients.pop();
+    }
+    self
+  }
+
+  pub fn eval(&self, lengths: &[usize]) -> i64 {
+    self
+      .coefficients
+      .iter()
+      .enumerate()
+      .fold(self.constant, |acc, (i, c)| {
+        acc + c * lengths.get(i).cloned().unwrap_or(0) as i64
+      })
+  }
+}
+
 pub type Sst<T, S, V> = SymSst<T, Predicate<T>, FunctionTermImpl<T>, S, V>;
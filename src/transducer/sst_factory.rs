@@ -1,6 +1,6 @@
 use super::{
   sst::Sst,
-  term::{FunctionTerm, Lambda, OutputComp, UpdateComp, Variable},
+  term::{Affine, FunctionTerm, Lambda, OutputComp, UpdateComp, Variable},
 };
 use crate::boolean_algebra::{BoolAlg, Predicate};
 use crate::regular::regex::Regex;
@@ -11,10 +11,172 @@ use crate::util::{
   Domain,
 };
 use std::{
-  collections::{HashMap, HashSet},
+  collections::{HashMap, HashSet, VecDeque},
   marker::PhantomData,
 };
 
+type Update<D, V> = HashMap<V, Vec<UpdateComp<Lambda<Predicate<D>>, V>>>;
+
+/** how replace-all treats occurrences overlapping a replaced one */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlap {
+  /** scanning continues after the end of the match, so "aa" in "aaa" is replaced once */
+  #[default]
+  Skip,
+  /**
+   * scanning restarts right after the start of the match, so every overlapping
+   * occurrence is replaced and "aa" in "aaa" gets replaced twice.
+   * characters covered by any occurrence are not emitted.
+   */
+  Restart,
+}
+
+/** the state of scanning words, a pending prefix whose head `covered` ones were matched */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Scan<D: Domain> {
+  pending: Vec<D>,
+  covered: usize,
+}
+impl<D: Domain> Scan<D> {
+  fn init() -> Self {
+    Scan {
+      pending: vec![],
+      covered: 0,
+    }
+  }
+}
+
+/** what replace_all_literals emits while scanning */
+enum Literal<D: Domain> {
+  Char(D),
+  Replacement,
+  /** the read character, which is none of the words' ones */
+  Input,
+}
+impl<D: Domain> Literal<D> {
+  /**
+   * scan input after scan, where None stands for a character not in words.
+   * returns what to emit and the new scan state.
+   */
+  fn feed(
+    words: &[Vec<D>],
+    overlap: Overlap,
+    scan: Scan<D>,
+    input: Vec<Option<D>>,
+  ) -> (Vec<Self>, Scan<D>) {
+    let Scan {
+      mut pending,
+      mut covered,
+    } = scan;
+    let mut input = VecDeque::from(input);
+    let mut emitted = vec![];
+    while let Some(c) = input.pop_front() {
+      match c {
+        Some(c) => {
+          pending.push(c);
+          if words.contains(&pending) {
+            emitted.push(Literal::Replacement);
+            match overlap {
+              Overlap::Skip => {
+                pending.clear();
+                covered = 0;
+              }
+              Overlap::Restart => {
+                /* the rest of the match is covered, and is scanned again */
+                let mut rest = pending.split_off(1);
+                pending.clear();
+                covered = usize::max(covered.saturating_sub(1), rest.len());
+                while let Some(c) = rest.pop() {
+                  input.push_front(Some(c));
+                }
+              }
+            }
+          } else if !words.iter().any(|word| word.starts_with(&pending)) {
+            /* no occurrence starts at the head, so scan the rest again */
+            let mut rest = pending.split_off(1);
+            let head = pending.pop().unwrap();
+            if covered > 0 {
+              covered -= 1;
+            } else {
+              emitted.push(Literal::Char(head));
+            }
+            while let Some(c) = rest.pop() {
+              input.push_front(Some(c));
+            }
+          }
+        }
+        None => {
+          emitted.extend(Self::flush(words, overlap, Scan { pending, covered }));
+          pending = vec![];
+          covered = 0;
+          emitted.push(Literal::Input);
+        }
+      }
+    }
+    (emitted, Scan { pending, covered })
+  }
+
+  /** append what is emitted to out */
+  fn update<V: Variable>(
+    out: &V,
+    replace: &[OutputComp<D, V>],
+    emitted: Vec<Self>,
+  ) -> Update<D, V> {
+    let mut seq = vec![UpdateComp::X(V::clone(out))];
+    for item in emitted {
+      match item {
+        Literal::Char(c) => seq.push(UpdateComp::F(Lambda::constant(c))),
+        Literal::Replacement => seq.extend(replace.iter().cloned().map(UpdateComp::from)),
+        Literal::Input => seq.push(UpdateComp::F(Lambda::identity())),
+      }
+    }
+    HashMap::from([(V::clone(out), seq)])
+  }
+
+  /** output of out followed by what is emitted at the end of input */
+  fn output<V: Variable>(
+    out: &V,
+    replace: &[OutputComp<D, V>],
+    emitted: Vec<Self>,
+  ) -> Vec<OutputComp<D, V>> {
+    let mut output = vec![OutputComp::X(V::clone(out))];
+    for item in emitted {
+      match item {
+        Literal::Char(c) => output.push(OutputComp::A(c)),
+        Literal::Replacement => output.extend(replace.iter().cloned()),
+        Literal::Input => unreachable!(),
+      }
+    }
+    output
+  }
+
+  /** emit pending at the end of input, where it can't be completed */
+  fn flush(words: &[Vec<D>], overlap: Overlap, mut scan: Scan<D>) -> Vec<Self> {
+    let mut emitted = vec![];
+    while !scan.pending.is_empty() {
+      let rest = scan.pending.split_off(1);
+      let head = scan.pending.pop().unwrap();
+      if scan.covered > 0 {
+        scan.covered -= 1;
+      } else {
+        emitted.push(Literal::Char(head));
+      }
+      let (e, s) = Self::feed(
+        words,
+        overlap,
+        Scan {
+          pending: vec![],
+          covered: scan.covered,
+        },
+        rest.into_iter().map(Some).collect(),
+      );
+      emitted.extend(e);
+      scan = s;
+    }
+    emitted
+  }
+}
+
 pub struct SstBuilder<D: Domain, S: State, V: Variable> {
   _domain: PhantomData<D>,
   _state: PhantomData<S>,
@@ -30,9 +192,7 @@ impl<D: Domain, S: State, V: Variable> SstBuilder<D, S, V> {
   }
 
   pub fn generate(&self, idx: usize, transduction: &Transduction<D, S>) -> Sst<D, S, V> {
-    assert!(transduction.0.len() != 0 && idx != 0);
-
-    let mut ssts = Vec::with_capacity(idx - 1);
+    let mut ssts = Vec::with_capacity(idx);
     let mut identities = HashMap::new();
     let mut reverses = HashMap::new();
     let prefix = V::new();
@@ -136,22 +296,114 @@ impl<D: Domain, S: State, V: Variable> SstBuilder<D, S, V> {
             .merge(SstBuilder::replace_all_reg(reg.clone(), replace), &var);
           result.push(OutputComp::X(var));
         }
+        TransductionOp::ReplaceFirst(id, word, target) => {
+          assert!(*id < idx);
+
+          let replace = match target {
+            ReplaceTarget::Str(s) => s.chars().map(|c| OutputComp::A(D::from(c))).collect(),
+            ReplaceTarget::Var(target_id) => {
+              assert!(target_id < id);
+              if let Some(id_var) = identities.get(target_id) {
+                vec![OutputComp::X(V::clone(id_var))]
+              } else {
+                let var = V::new();
+                ssts
+                  .get_mut(*target_id)
+                  .unwrap()
+                  .merge(SstBuilder::identity(&var), &var);
+                identities.insert(*target_id, V::clone(&var));
+                vec![OutputComp::X(var)]
+              }
+            }
+          };
+          let word: Vec<_> = word.chars().map(D::from).collect();
+          let var = V::new();
+          ssts
+            .get_mut(*id)
+            .unwrap()
+            .merge(SstBuilder::replace_first(&word, replace), &var);
+          result.push(OutputComp::X(var));
+        }
+        TransductionOp::Substr(id, start, len) => {
+          assert!(*id < idx);
+
+          let var = V::new();
+          ssts.get_mut(*id).unwrap().merge(
+            SstBuilder::substr(start.constant(), len.constant(), &var),
+            &var,
+          );
+          result.push(OutputComp::X(var));
+        }
         TransductionOp::UserDef(_) => unimplemented!(),
       });
 
     result.push(OutputComp::A(D::separator()));
 
-    ssts
+    match ssts
       .into_iter()
       .reduce(|result, sst| result.chain(sst, &prefix))
-      .unwrap()
-      .chain_output(result)
+    {
+      Some(sst) => sst.chain_output(result),
+      /* a constant defining the first variable is output on the empty input */
+      None => {
+        let state = S::new();
+        Sst::new(
+          HashSet::from([S::clone(&state)]),
+          HashSet::new(),
+          S::clone(&state),
+          HashMap::from([(state, result.split_off(2))]),
+          HashMap::new(),
+        )
+      }
+    }
   }
 
   pub fn replace_all_reg(reg: Regex<D>, replace: Vec<OutputComp<D, V>>) -> Sst<D, S, V> {
+    Self::replace_all_reg_with(reg, replace, Overlap::Skip)
+  }
+
+  /**
+   * whether a mismatch in the middle of the word leaves a partial match of two or more
+   * characters, like aab in aaab, which the construction from the automaton misses
+   * since it restarts from the mismatched character alone.
+   */
+  fn falls_back_deeply(word: &[D]) -> bool {
+    (1..word.len()).any(|i| {
+      word.iter().filter(|c| **c != word[i]).any(|c| {
+        (2..=i).any(|k| word[i + 1 - k..i] == word[..k - 1] && *c == word[k - 1])
+      })
+    })
+  }
+
+  /**
+   * replace_all_reg with the given overlap semantics.
+   * Overlap::Restart is supported only for (unions of) literals.
+   */
+  pub fn replace_all_reg_with(
+    reg: Regex<D>,
+    replace: Vec<OutputComp<D, V>>,
+    overlap: Overlap,
+  ) -> Sst<D, S, V> {
     assert_ne!(reg, Regex::Empty);
     assert_ne!(reg, Regex::Epsilon);
 
+    /* unions of literals get much smaller by the dedicated construction */
+    match reg.literals() {
+      Some(words) if words.iter().all(|word| !word.is_empty()) => {
+        if overlap == Overlap::Restart
+          || matches!(reg, Regex::Or(_))
+          || words.iter().any(|word| Self::falls_back_deeply(word))
+        {
+          return Self::replace_all_literals(&words, replace, overlap);
+        }
+      }
+      _ => assert_eq!(
+        overlap,
+        Overlap::Skip,
+        "Overlap::Restart is supported only for literals"
+      ),
+    }
+
     let replace_update = super::to_update(&replace);
 
     let sfa = reg.to_sfa();
@@ -310,6 +562,152 @@ impl<D: Domain, S: State, V: Variable> SstBuilder<D, S, V> {
     )
   }
 
+  /**
+   * replace all the leftmost shortest occurrences of words, like Aho-Corasick.
+   * each state is a pending proper prefix of words (with how much of it is already
+   * replaced under Overlap::Restart), so only one variable accumulates the output.
+   */
+  pub fn replace_all_literals(
+    words: &[Vec<D>],
+    replace: Vec<OutputComp<D, V>>,
+    overlap: Overlap,
+  ) -> Sst<D, S, V> {
+    assert!(!words.is_empty() && words.iter().all(|word| !word.is_empty()));
+
+    let mut chars: Vec<D> = words.iter().flatten().cloned().collect();
+    chars.sort();
+    chars.dedup();
+    let others = Predicate::in_set(chars.iter().cloned()).not();
+
+    let out = V::new();
+
+    let mut states: HashMap<Scan<D>, S> = HashMap::from([(Scan::init(), S::new())]);
+    let mut queue = VecDeque::from([Scan::init()]);
+    let mut transition = HashMap::new();
+    let mut output_function = HashMap::new();
+    while let Some(scan) = queue.pop_front() {
+      let p = S::clone(&states[&scan]);
+      let inputs = chars
+        .iter()
+        .map(|c| (Predicate::char(D::clone(c)), Some(D::clone(c))))
+        .chain(std::iter::once((others.clone(), None)));
+      for (phi, input) in inputs {
+        let (emitted, next) = Literal::feed(words, overlap, scan.clone(), vec![input]);
+        let q = states.entry(next.clone()).or_insert_with(|| {
+          queue.push_back(next);
+          S::new()
+        });
+        transition.insert(
+          (S::clone(&p), phi),
+          vec![(S::clone(q), Literal::update(&out, &replace, emitted))],
+        );
+      }
+
+      let emitted = Literal::flush(words, overlap, scan);
+      output_function.insert(p, Literal::output(&out, &replace, emitted));
+    }
+
+    Sst::new(
+      states.values().cloned().collect(),
+      HashSet::from([out]),
+      S::clone(&states[&Scan::init()]),
+      output_function,
+      transition,
+    )
+  }
+
+  /**
+   * replace the first occurrence of word, as str.replace.
+   * scanning is the same as replace_all_literals, but the rest is copied after a match.
+   * the empty word occurs at the head, so replace is prepended to the input.
+   */
+  pub fn replace_first(word: &[D], replace: Vec<OutputComp<D, V>>) -> Sst<D, S, V> {
+    let out = V::new();
+    /* after the first match */
+    let done = S::new();
+    let copy = super::macros::make_update! {
+      out -> vec![UpdateComp::X(V::clone(&out)), UpdateComp::F(Lambda::identity())]
+    };
+
+    let mut transition = HashMap::from([(
+      (S::clone(&done), Predicate::all_char()),
+      vec![(S::clone(&done), copy)],
+    )]);
+    let mut output_function =
+      HashMap::from([(S::clone(&done), vec![OutputComp::X(V::clone(&out))])]);
+
+    if word.is_empty() {
+      let initial_state = S::new();
+      transition.insert(
+        (S::clone(&initial_state), Predicate::all_char()),
+        vec![(
+          S::clone(&done),
+          Literal::update(&out, &replace, vec![Literal::Replacement, Literal::Input]),
+        )],
+      );
+      output_function.insert(
+        S::clone(&initial_state),
+        Literal::output(&out, &replace, vec![Literal::Replacement]),
+      );
+      return Sst::new(
+        HashSet::from([S::clone(&initial_state), done]),
+        HashSet::from([out]),
+        initial_state,
+        output_function,
+        transition,
+      );
+    }
+
+    let words = [word.to_vec()];
+    let mut chars = word.to_vec();
+    chars.sort();
+    chars.dedup();
+    let others = Predicate::in_set(chars.iter().cloned()).not();
+
+    let mut states: HashMap<Scan<D>, S> = HashMap::from([(Scan::init(), S::new())]);
+    let mut queue = VecDeque::from([Scan::init()]);
+    while let Some(scan) = queue.pop_front() {
+      let p = S::clone(&states[&scan]);
+      let inputs = chars
+        .iter()
+        .map(|c| (Predicate::char(D::clone(c)), Some(D::clone(c))))
+        .chain(std::iter::once((others.clone(), None)));
+      for (phi, input) in inputs {
+        let (emitted, next) = Literal::feed(&words, Overlap::Skip, scan.clone(), vec![input]);
+        let q = if emitted
+          .iter()
+          .any(|item| matches!(item, Literal::Replacement))
+        {
+          S::clone(&done)
+        } else {
+          S::clone(states.entry(next.clone()).or_insert_with(|| {
+            queue.push_back(next);
+            S::new()
+          }))
+        };
+        transition.insert(
+          (S::clone(&p), phi),
+          vec![(q, Literal::update(&out, &replace, emitted))],
+        );
+      }
+
+      let emitted = Literal::flush(&words, Overlap::Skip, scan);
+      output_function.insert(p, Literal::output(&out, &replace, emitted));
+    }
+
+    let initial_state = S::clone(&states[&Scan::init()]);
+    let mut states: HashSet<_> = states.into_values().collect();
+    states.insert(done);
+
+    Sst::new(
+      states,
+      HashSet::from([out]),
+      initial_state,
+      output_function,
+      transition,
+    )
+  }
+
   pub fn replace_reg(reg: Regex<D>, replace: Vec<OutputComp<D, V>>) -> Sst<D, S, V> {
     assert_ne!(reg, Regex::Empty);
     assert_ne!(reg, Regex::Epsilon);
@@ -448,6 +846,122 @@ impl<D: Domain, S: State, V: Variable> SstBuilder<D, S, V> {
     )
   }
 
+  /**
+   * replace all matches of reg lying inside the window [start, end) of the input.
+   * a match crossing either edge of the window is left as is,
+   * and the window is empty when end is before start.
+   * states of replace_all_reg are paired with the number of characters read in the window.
+   */
+  pub fn replace_in_range(
+    reg: Regex<D>,
+    replace: Vec<OutputComp<D, V>>,
+    start: usize,
+    end: usize,
+  ) -> Sst<D, S, V> {
+    let inner = Self::replace_all_reg(reg, replace);
+    let len = end.saturating_sub(start);
+
+    /* before the window */
+    let pre = V::new();
+    /* result of the window */
+    let win = V::new();
+    /* after the window */
+    let post = V::new();
+    let mut variables = inner.variables().clone();
+    variables.extend([V::clone(&pre), V::clone(&win), V::clone(&post)]);
+
+    let window: HashMap<_, _> = inner
+      .states()
+      .iter()
+      .flat_map(|q| (0..=len).map(move |k| ((q, k), S::new())))
+      .collect();
+    let suffix = S::new();
+    let mut prefix: Vec<_> = (0..start).map(|_| S::new()).collect();
+    prefix.push(S::clone(window.get(&(inner.initial_state(), 0)).unwrap()));
+    let initial_state = S::clone(&prefix[0]);
+
+    let mut transition = HashMap::new();
+    let mut output_function = HashMap::new();
+
+    prefix.windows(2).for_each(|pair| {
+      transition.safe_insert(
+        (S::clone(&pair[0]), Predicate::all_char()),
+        vec![(
+          S::clone(&pair[1]),
+          super::macros::make_update! {
+            pre -> vec![UpdateComp::X(V::clone(&pre)), UpdateComp::F(Lambda::identity())]
+          },
+        )],
+      );
+      output_function.safe_insert(S::clone(&pair[0]), vec![OutputComp::X(V::clone(&pre))]);
+    });
+
+    inner.transition().iter().for_each(|((q, phi), target)| {
+      (0..len).for_each(|k| {
+        transition.insert_with_check(
+          (S::clone(window.get(&(q, k)).unwrap()), phi.clone()),
+          target
+            .iter()
+            .map(|(q_, alpha)| (S::clone(window.get(&(q_, k + 1)).unwrap()), alpha.clone())),
+        );
+      });
+    });
+
+    inner.final_set().iter().for_each(|(q, output)| {
+      (0..=len).for_each(|k| {
+        let mut v = vec![OutputComp::X(V::clone(&pre))];
+        v.extend(output.iter().cloned());
+        output_function.safe_insert(S::clone(window.get(&(q, k)).unwrap()), v);
+      });
+
+      /* leaving the window, dump the output of replace_all_reg */
+      transition.safe_insert(
+        (
+          S::clone(window.get(&(q, len)).unwrap()),
+          Predicate::all_char(),
+        ),
+        vec![(
+          S::clone(&suffix),
+          super::macros::make_update! {
+            win -> super::to_update(output),
+            post -> vec![UpdateComp::F(Lambda::identity())]
+          },
+        )],
+      );
+    });
+
+    transition.safe_insert(
+      (S::clone(&suffix), Predicate::all_char()),
+      vec![(
+        S::clone(&suffix),
+        super::macros::make_update! {
+          post -> vec![UpdateComp::X(V::clone(&post)), UpdateComp::F(Lambda::identity())]
+        },
+      )],
+    );
+    output_function.safe_insert(
+      S::clone(&suffix),
+      vec![
+        OutputComp::X(V::clone(&pre)),
+        OutputComp::X(V::clone(&win)),
+        OutputComp::X(V::clone(&post)),
+      ],
+    );
+
+    let mut states: HashSet<_> = window.into_values().collect();
+    states.extend(prefix);
+    states.insert(suffix);
+
+    Sst::new(
+      states,
+      variables,
+      initial_state,
+      output_function,
+      transition,
+    )
+  }
+
+  /** output the input as is */
   pub fn identity(var: &V) -> Sst<D, S, V> {
     super::macros::sst! {
       { initial },
@@ -465,6 +979,127 @@ impl<D: Domain, S: State, V: Variable> SstBuilder<D, S, V> {
     }
   }
 
+  /** apply lambda to each character, i.e. x1..xn -> lambda(x1)..lambda(xn) */
+  pub fn map_chars(lambda: Lambda<Predicate<D>>, var: &V) -> Sst<D, S, V> {
+    super::macros::sst! {
+      { initial },
+      HashSet::from([V::clone(var)]),
+      {
+        -> initial,
+        (initial, Predicate::all_char()) -> [(
+          initial,
+          super::macros::make_update! {
+            var -> vec![UpdateComp::X(V::clone(var)), UpdateComp::F(lambda)]
+          }
+        )]
+      },
+      { initial -> vec![OutputComp::X(V::clone(var))] }
+    }
+  }
+
+  /**
+   * output the substring of len characters from start, as str.substr.
+   * the output is shorter if the input ends before, and empty if start is beyond the input.
+   * states count characters for constant arguments,
+   * and unknown ones (None) are abstracted by nondeterministic choice of where to start or stop.
+   */
+  pub fn substr(start: Option<usize>, len: Option<usize>, var: &V) -> Sst<D, S, V> {
+    let append = super::macros::make_update! {
+      var -> vec![UpdateComp::X(V::clone(var)), UpdateComp::F(Lambda::identity())]
+    };
+    let skip = HashMap::new();
+
+    /* skipping[k] has skipped k characters, and the last one is ready to take */
+    let skipping: Vec<_> = (0..=start.unwrap_or(0)).map(|_| S::new()).collect();
+    let ready = S::clone(skipping.last().unwrap());
+    /* after taking all characters to output */
+    let done = S::new();
+
+    let mut transition: HashMap<_, Vec<_>> = HashMap::new();
+    skipping.windows(2).for_each(|pair| {
+      transition.insert_with_check(
+        (S::clone(&pair[0]), Predicate::all_char()),
+        vec![(S::clone(&pair[1]), skip.clone())],
+      );
+    });
+    if start.is_none() {
+      transition.insert_with_check(
+        (S::clone(&ready), Predicate::all_char()),
+        vec![(S::clone(&ready), skip.clone())],
+      );
+    }
+    let taking: Vec<_> = match len {
+      Some(len) => {
+        /* taking[k - 1] has taken k characters */
+        let taking: Vec<_> = (1..len).map(|_| S::new()).collect();
+        let mut chain = vec![S::clone(&ready)];
+        chain.extend(taking.iter().cloned());
+        chain.push(S::clone(&done));
+        chain.windows(2).for_each(|pair| {
+          let update = if len == 0 { &skip } else { &append };
+          transition.insert_with_check(
+            (S::clone(&pair[0]), Predicate::all_char()),
+            vec![(S::clone(&pair[1]), update.clone())],
+          );
+        });
+        taking
+      }
+      None => {
+        let take = S::new();
+        for from in [&ready, &take] {
+          transition.insert_with_check(
+            (S::clone(from), Predicate::all_char()),
+            vec![
+              (S::clone(&take), append.clone()),
+              (S::clone(&done), skip.clone()),
+            ],
+          );
+        }
+        vec![take]
+      }
+    };
+    transition.insert_with_check(
+      (S::clone(&done), Predicate::all_char()),
+      vec![(S::clone(&done), skip)],
+    );
+
+    let initial_state = S::clone(&skipping[0]);
+    let mut states: HashSet<_> = skipping.into_iter().collect();
+    states.extend(taking);
+    states.insert(done);
+    let output_function = states
+      .iter()
+      .map(|q| (S::clone(q), vec![OutputComp::X(V::clone(var))]))
+      .collect();
+
+    Sst::new(
+      states,
+      HashSet::from([V::clone(var)]),
+      initial_state,
+      output_function,
+      transition,
+    )
+  }
+
+  /** output the input twice, i.e. x -> xx */
+  pub fn duplicate(var: &V) -> Sst<D, S, V> {
+    super::macros::sst! {
+      { initial },
+      HashSet::from([V::clone(var)]),
+      {
+        -> initial,
+        (initial, Predicate::all_char()) -> [(
+          initial,
+          super::macros::make_update! {
+            var -> vec![UpdateComp::X(V::clone(var)), UpdateComp::F(Lambda::identity())]
+          }
+        )]
+      },
+      { initial -> vec![OutputComp::X(V::clone(var)), OutputComp::X(V::clone(var))] }
+    }
+  }
+
+  /** output the input reversed, used for str.reverse */
   pub fn reverse(var: &V) -> Sst<D, S, V> {
     super::macros::sst! {
       { initial },
@@ -482,6 +1117,99 @@ impl<D: Domain, S: State, V: Variable> SstBuilder<D, S, V> {
     }
   }
 
+  /**
+   * identity on var, counting characters satisfying pred in register.
+   * count(Predicate::top(), ..) tracks the position.
+   */
+  pub fn count(pred: Predicate<D>, var: &V, register: &V) -> Sst<D, S, V> {
+    let hit = Predicate::all_char().and(&pred);
+    let miss = Predicate::all_char().and(&pred.not());
+    let update = super::macros::make_update! {
+      var -> vec![UpdateComp::X(V::clone(var)), UpdateComp::F(Lambda::identity())]
+    };
+
+    let sst = super::macros::sst! {
+      { initial },
+      HashSet::from([V::clone(var)]),
+      {
+        -> initial,
+        (initial, hit.clone()) -> [(initial, update.clone())],
+        (initial, miss) -> [(initial, update)]
+      },
+      { initial -> vec![OutputComp::X(V::clone(var))] }
+    };
+
+    let q = S::clone(sst.initial_state());
+    sst.with_registers(
+      HashSet::from([V::clone(register)]),
+      HashMap::from([(
+        (S::clone(&q), hit, q),
+        HashMap::from([(V::clone(register), Affine::increment(V::clone(register), 1))]),
+      )]),
+    )
+  }
+
+  /**
+   * identity on var, counting the occurrences of the nonempty border-free pattern in register.
+   * the i-th state has matched the first i characters of pattern,
+   * and the transition completing an occurrence goes back to the first one.
+   */
+  pub fn count_occurrences(pattern: &str, var: &V, register: &V) -> Sst<D, S, V> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let states: Vec<S> = chars.iter().map(|_| S::new()).collect();
+    let update = super::macros::make_update! {
+      var -> vec![UpdateComp::X(V::clone(var)), UpdateComp::F(Lambda::identity())]
+    };
+    /* the longest prefix of pattern which the matched one followed by c ends with */
+    let next = |i: usize, c: char| {
+      let mut read = chars[..i].to_vec();
+      read.push(c);
+      (0..=read.len())
+        .rev()
+        .find(|k| read[read.len() - k..] == chars[..*k])
+        .unwrap()
+    };
+    let mut distinct = chars.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let in_pattern = Predicate::InSet(distinct.iter().map(|c| D::from(*c)).collect());
+    let others = Predicate::all_char().and(&in_pattern.not());
+
+    let mut transition = HashMap::new();
+    let mut register_update = HashMap::new();
+    for (i, p) in states.iter().enumerate() {
+      for c in distinct.iter().copied() {
+        let phi = Predicate::char(D::from(c));
+        let k = next(i, c);
+        let q = S::clone(&states[k % chars.len()]);
+        if k == chars.len() {
+          register_update.insert(
+            (S::clone(p), phi.clone(), S::clone(&q)),
+            HashMap::from([(V::clone(register), Affine::increment(V::clone(register), 1))]),
+          );
+        }
+        transition.insert((S::clone(p), phi), vec![(q, update.clone())]);
+      }
+      transition.insert(
+        (S::clone(p), others.clone()),
+        vec![(S::clone(&states[0]), update.clone())],
+      );
+    }
+    let output_function = states
+      .iter()
+      .map(|q| (S::clone(q), vec![OutputComp::X(V::clone(var))]))
+      .collect();
+    Sst::new(
+      states.iter().cloned().collect(),
+      HashSet::from([V::clone(var)]),
+      S::clone(&states[0]),
+      output_function,
+      transition,
+    )
+    .with_registers(HashSet::from([V::clone(register)]), register_update)
+  }
+
+  /** output the given string ignoring the input */
   pub fn constant(output: &str) -> Sst<D, S, V> {
     super::macros::sst! {
       { initial },
@@ -493,6 +1221,61 @@ impl<D: Domain, S: State, V: Variable> SstBuilder<D, S, V> {
       { initial -> output.chars().map(|c| OutputComp::A(D::from(c))).collect() }
     }
   }
+
+  /**
+   * output only the idx-th segment of the input x0#x1#...#xn#.
+   * the segments after it are read but ignored.
+   */
+  pub fn project(idx: usize, var: &V) -> Sst<D, S, V> {
+    let segments: Vec<_> = (0..=idx).map(|_| S::new()).collect();
+    let rest = S::new();
+
+    let mut transition = HashMap::new();
+    segments.windows(2).for_each(|pair| {
+      transition.safe_insert(
+        (S::clone(&pair[0]), Predicate::all_char()),
+        vec![(S::clone(&pair[0]), HashMap::new())],
+      );
+      transition.safe_insert(
+        (S::clone(&pair[0]), Predicate::separator()),
+        vec![(S::clone(&pair[1]), HashMap::new())],
+      );
+    });
+    transition.safe_insert(
+      (S::clone(&segments[idx]), Predicate::all_char()),
+      vec![(
+        S::clone(&segments[idx]),
+        super::macros::make_update! {
+          var -> vec![UpdateComp::X(V::clone(var)), UpdateComp::F(Lambda::identity())]
+        },
+      )],
+    );
+    transition.safe_insert(
+      (S::clone(&segments[idx]), Predicate::separator()),
+      vec![(S::clone(&rest), HashMap::new())],
+    );
+    transition.safe_insert(
+      (S::clone(&rest), Predicate::top()),
+      vec![(S::clone(&rest), HashMap::new())],
+    );
+
+    let output_function = HashMap::from([
+      (S::clone(&segments[idx]), vec![OutputComp::X(V::clone(var))]),
+      (S::clone(&rest), vec![OutputComp::X(V::clone(var))]),
+    ]);
+
+    let initial_state = S::clone(&segments[0]);
+    let mut states: HashSet<_> = segments.into_iter().collect();
+    states.insert(rest);
+
+    Sst::new(
+      states,
+      HashSet::from([V::clone(var)]),
+      initial_state,
+      output_function,
+      transition,
+    )
+  }
 }
 
 #[cfg(test)]
@@ -583,6 +1366,241 @@ mod tests {
     cases: ["", "xyz", "abcdefg", "palindromemordnilap", "baaaaaaaaaaaaaaaa"]
   }
 
+  #[test]
+  fn project() {
+    let sst = Builder::project(1, &VariableImpl::new());
+    assert_eq!(sst.variables().len(), 1);
+    for (case, expected) in [
+      ("zero#one#", "one"),
+      ("zero#one#two#", "one"),
+      ("#one", "one"),
+      ("##", ""),
+    ] {
+      assert!(run!(sst, [case]).contains(&chars(expected)));
+    }
+    assert!(run!(sst, ["zero"]).is_empty());
+
+    let sst = Builder::project(0, &VariableImpl::new());
+    assert!(run!(sst, ["zero#one#"]).contains(&chars("zero")));
+  }
+
+  #[test]
+  fn image() {
+    let sfa = Regex::seq("ab").or(Regex::seq("c")).to_sfa::<StateImpl>();
+
+    let image = Builder::identity(&VariableImpl::new()).image(&sfa);
+    for (case, expected) in [("ab", true), ("c", true), ("", false), ("ba", false)] {
+      assert_eq!(image.run(&chars(case)), expected);
+    }
+
+    let image = Builder::reverse(&VariableImpl::new()).image(&sfa);
+    for (case, expected) in [("ba", true), ("c", true), ("ab", false)] {
+      assert_eq!(image.run(&chars(case)), expected);
+    }
+
+    let image = Builder::constant("xyz").image(&sfa);
+    assert!(image.run(&chars("xyz")));
+    assert!(!image.run(&chars("ab")));
+    let image = Builder::constant("xyz").image(&Regex::Empty.to_sfa());
+    assert!(!image.run(&chars("xyz")));
+
+    /* over-approximated, but still contains the exact image */
+    let image = Builder::duplicate(&VariableImpl::new()).image(&sfa);
+    for case in ["abab", "cc"] {
+      assert!(image.run(&chars(case)));
+    }
+    assert!(!image.run(&chars("a")));
+  }
+
+  #[test]
+  fn laws() {
+    let alphabet = chars("ab");
+    let id = Builder::identity(&VariableImpl::new());
+    let rev = Builder::reverse(&VariableImpl::new());
+    let dup = Builder::duplicate(&VariableImpl::new());
+
+    /* reverse . reverse = id */
+    assert!(Sst::equivalent_upto(&[&rev, &rev], &[&id], &alphabet, 6));
+    assert!(Sst::equivalent_upto(&[&id, &id], &[&id], &alphabet, 6));
+    /* reverse . duplicate = duplicate . reverse */
+    assert!(Sst::equivalent_upto(
+      &[&dup, &rev],
+      &[&rev, &dup],
+      &alphabet,
+      6
+    ));
+    assert_eq!(
+      Sst::counterexample_upto(&[&rev], &[&id], &alphabet, 6),
+      Some(chars("ab"))
+    );
+    assert_eq!(
+      Sst::counterexample_upto(&[&dup], &[&id], &alphabet, 6),
+      Some(chars("a"))
+    );
+  }
+
+  #[test]
+  fn replace_all_literals() {
+    let from = Regex::seq("abc").or(Regex::seq("kkk"));
+    let sst = Builder::replace_all_reg(from, to_replacer("xyz"));
+    /* pending prefixes "", "a", "ab", "k" and "kk" */
+    assert_eq!(sst.states().len(), 5);
+    assert_eq!(sst.variables().len(), 1);
+    for (case, expected) in [
+      ("", ""),
+      ("abcababcbcc", "xyzabxyzbcc"),
+      ("kkkk", "xyzk"),
+      ("akkkabc", "axyzxyz"),
+      ("kkabkkk", "kkabxyz"),
+    ] {
+      assert_eq!(run!(sst, [case]), vec![chars(expected)]);
+    }
+
+    /* the shortest one among occurrences starting at the leftmost position */
+    let from = Regex::seq("a").or(Regex::seq("ab"));
+    let sst = Builder::replace_all_reg(from, to_replacer("_"));
+    assert_eq!(run!(sst, ["abab"]), vec![chars("_b_b")]);
+
+    /* an occurrence inside a failed longer one */
+    let from = Regex::seq("abc").or(Regex::seq("b"));
+    let sst = Builder::replace_all_reg(from, to_replacer("_"));
+    for (case, expected) in [("abx", "a_x"), ("ab", "a_"), ("abc", "_")] {
+      assert_eq!(run!(sst, [case]), vec![chars(expected)]);
+    }
+  }
+
+  #[test]
+  fn replace_all_overlap() {
+    for (from, case, skip, restart) in [
+      (vec!["aa"], "aaa", "_a", "__"),
+      (vec!["aa"], "aaaa", "__", "___"),
+      (vec!["aa"], "baab", "b_b", "b_b"),
+      (vec!["aba"], "ababa", "_ba", "__"),
+      (vec!["aba", "b"], "abab", "__", "___"),
+      (vec!["aab"], "aaab", "a_", "a_"),
+    ] {
+      let from = from
+        .into_iter()
+        .map(Regex::seq)
+        .reduce(|acc, reg| acc.or(reg))
+        .unwrap();
+      let sst = Builder::replace_all_reg_with(from.clone(), to_replacer("_"), Overlap::Skip);
+      assert_eq!(run!(sst, [case]), vec![chars(skip)]);
+      let sst = Builder::replace_all_reg_with(from, to_replacer("_"), Overlap::Restart);
+      assert_eq!(run!(sst, [case]), vec![chars(restart)]);
+    }
+  }
+
+  #[test]
+  #[should_panic]
+  fn reject_restart_for_non_literals() {
+    let from = Regex::seq("a").concat(Regex::All);
+    let _rep = Builder::replace_all_reg_with(from, vec![], Overlap::Restart);
+  }
+
+  #[test]
+  fn map_chars() {
+    let rot = Lambda::mapping(vec![('a', 'b'), ('b', 'c')]);
+    let capitals = Lambda::function(vec![(Predicate::range(Some('A'), Some('[')), 'x')]);
+    let sst = Builder::map_chars(rot.clone().then(capitals.clone()), &VariableImpl::new());
+    for case in ["", "abc", "aB-Z"] {
+      let expected = rot.clone().then(capitals.clone()).apply_str(case);
+      assert_eq!(run!(sst, [case]), vec![chars(&expected)]);
+    }
+    assert_eq!(run!(sst, ["aB-Z"]), vec![chars("bx-x")]);
+  }
+
+  #[test]
+  fn replace_first() {
+    let sst = Builder::replace_first(&chars("aab"), to_replacer("X"));
+    for (case, expected) in [
+      ("", ""),
+      ("aa", "aa"),
+      ("aab", "X"),
+      ("aaab", "aX"),
+      ("aabaab", "Xaab"),
+      ("caabc", "cXc"),
+    ] {
+      assert_eq!(run!(sst, [case]), vec![chars(expected)]);
+    }
+
+    let sst = Builder::replace_first(&[], to_replacer("X"));
+    assert_eq!(run!(sst, [""]), vec![chars("X")]);
+    assert_eq!(run!(sst, ["ab"]), vec![chars("Xab")]);
+  }
+
+  #[test]
+  fn substr() {
+    let var = VariableImpl::new();
+    let outputs = |sst: &Sst<char, StateImpl, VariableImpl>, input: &str| {
+      let mut outputs: Vec<String> = run!(sst, [input])
+        .into_iter()
+        .map(|output| output.into_iter().collect())
+        .collect();
+      outputs.sort();
+      outputs.dedup();
+      outputs
+    };
+
+    let sst = Builder::substr(Some(1), Some(2), &var);
+    assert_eq!(outputs(&sst, "abcd"), vec!["bc"]);
+    assert_eq!(outputs(&sst, "ab"), vec!["b"]);
+    assert_eq!(outputs(&sst, "a"), vec![""]);
+    assert_eq!(
+      outputs(&Builder::substr(Some(0), Some(0), &var), "ab"),
+      vec![""]
+    );
+
+    let sst = Builder::substr(None, Some(2), &var);
+    assert_eq!(outputs(&sst, "abc"), vec!["", "ab", "bc", "c"]);
+    let sst = Builder::substr(Some(1), None, &var);
+    assert_eq!(outputs(&sst, "abc"), vec!["", "b", "bc"]);
+    let sst = Builder::substr(None, None, &var);
+    assert_eq!(
+      outputs(&sst, "abc"),
+      vec!["", "a", "ab", "abc", "b", "bc", "c"]
+    );
+  }
+
+  #[test]
+  fn filter_output() {
+    use crate::transducer::sst::FilterMode;
+
+    let alnum = Predicate::range(Some('a'), Some('{')).or(&Predicate::range(Some('0'), Some(':')));
+    let x = VariableImpl::new();
+
+    let sst = Builder::identity(&x).filter_output(&alnum, FilterMode::Drop);
+    for (case, expected) in [("", ""), ("a-b_1!", "ab1"), ("<>", "")] {
+      assert_eq!(run!(sst, [case]), vec![chars(expected)]);
+    }
+    let sst = Builder::identity(&x).filter_output(&alnum, FilterMode::Reject);
+    assert_eq!(run!(sst, ["ab1"]), vec![chars("ab1")]);
+    assert!(run!(sst, ["a-b"]).is_empty());
+
+    /* constants are filtered as well */
+    let sst = Builder::constant("a-b").filter_output(&alnum, FilterMode::Drop);
+    assert_eq!(run!(sst, ["xyz"]), vec![chars("ab")]);
+    let sst = Builder::constant("a-b").filter_output(&alnum, FilterMode::Reject);
+    assert!(run!(sst, ["xyz"]).is_empty());
+  }
+
+  #[test]
+  fn equality_up_to_renaming() {
+    use crate::util::hash_of;
+    use std::collections::HashSet;
+
+    let x = VariableImpl::new();
+    let (id1, id2) = (Builder::identity(&x), Builder::identity(&x));
+    assert_eq!(id1, id2);
+    assert_eq!(hash_of(&id1), hash_of(&id2));
+    assert_ne!(id1, Builder::reverse(&x));
+    assert_ne!(id1, Builder::identity(&VariableImpl::new()));
+    assert_ne!(Builder::constant("ab"), Builder::constant("ba"));
+
+    let set = HashSet::from([id1, id2, Builder::reverse(&x), Builder::reverse(&x)]);
+    assert_eq!(set.len(), 2);
+  }
+
   #[test]
   #[should_panic]
   fn reject_empty_substr_all_reg() {
@@ -611,6 +1629,87 @@ mod tests {
     eprintln!("unreachable");
   }
 
+  #[test]
+  fn replace_in_range() {
+    let sst = Builder::replace_in_range(Regex::seq("ab"), to_replacer("x"), 2, 6);
+    for (case, expected) in [
+      ("", ""),
+      ("a", "a"),
+      ("abab", "abx"),
+      ("ababa", "abxa"),
+      ("abababab", "abxxab"),
+      /* matches crossing the edges are left */
+      ("xabab", "xabx"),
+      ("xxxababab", "xxxxabab"),
+    ] {
+      assert!(run!(sst, [case]).contains(&chars(expected)));
+    }
+
+    let sst = Builder::replace_in_range(Regex::seq("ab"), to_replacer("x"), 0, 0);
+    assert!(run!(sst, ["abab"]).contains(&chars("abab")));
+    let sst = Builder::replace_in_range(Regex::seq("ab"), to_replacer("x"), 3, 1);
+    assert!(run!(sst, ["abab"]).contains(&chars("abab")));
+  }
+
+  #[test]
+  fn count() {
+    let register = VariableImpl::new();
+    let sst = Builder::count(Predicate::char('a'), &VariableImpl::new(), &register);
+    assert_eq!(sst.registers().len(), 1);
+    for (case, expected) in [("", 0), ("xyz", 0), ("abcabca", 3)] {
+      assert_eq!(
+        sst.run_with_registers(&chars(case)),
+        vec![(chars(case), HashMap::from([(register.clone(), expected)]))]
+      );
+    }
+
+    let sst = Builder::count(Predicate::top(), &VariableImpl::new(), &register);
+    for case in ["", "xyz", "abcabca"] {
+      assert_eq!(
+        sst.run_with_registers(&chars(case)),
+        vec![(
+          chars(case),
+          HashMap::from([(register.clone(), case.len() as i64)])
+        )]
+      );
+    }
+  }
+
+  #[test]
+  fn count_occurrences() {
+    let register = VariableImpl::new();
+    let sst = Builder::count_occurrences("aab", &VariableImpl::new(), &register);
+    for (case, expected) in [("", 0), ("aab", 1), ("aaab", 1), ("abaabxaabaab", 3)] {
+      assert_eq!(
+        sst.run_with_registers(&chars(case)),
+        vec![(chars(case), HashMap::from([(register.clone(), expected)]))]
+      );
+    }
+
+    /* the counts over (aab)*c(aab)? are any, but only the ones up to the bound are found */
+    let sfa = Regex::seq("aab")
+      .star()
+      .concat(Regex::element('c'))
+      .concat(Regex::seq("aab").opt())
+      .to_sfa();
+    assert_eq!(
+      sst.counter_values(&register, &sfa, 3),
+      Some((vec![0, 1, 2, 3], false))
+    );
+    let sfa = Regex::seq("aab").concat(Regex::element('c').star()).to_sfa();
+    assert_eq!(sst.counter_values(&register, &sfa, 3), Some((vec![1], true)));
+
+    /* registers not only incremented aren't counters */
+    let doubled = Affine::register(register.clone()).scale(2);
+    let register_update = sst
+      .register_update
+      .keys()
+      .map(|transition| (transition.clone(), HashMap::from([(register.clone(), doubled.clone())])))
+      .collect();
+    let sst = sst.with_registers(HashSet::new(), register_update);
+    assert_eq!(sst.counter_values(&register, &sfa, 3), None);
+  }
+
   replace_test! {
     names: [abc_to_xyz, abc_to_xyz_all],
     from: "abc",
@@ -1,6 +1,7 @@
 use crate::boolean_algebra::{BoolAlg, Predicate};
 use crate::util::Domain;
 use std::{
+  collections::HashMap,
   fmt::Debug,
   hash::Hash,
   rc::Rc,
@@ -24,18 +25,62 @@ pub trait FunctionTerm: Debug + Eq + Hash + Clone {
   fn compose(self, other: Self) -> Self;
 }
 
-/** for Primitive Function Term */
+/**
+ * for Primitive Function Term, a character-wise transformation.
+ * build one with FunctionTerm::identity/constant, mapping or function,
+ * combine them with FunctionTerm::compose or then,
+ * and lift it to an SST by SstBuilder::map_chars.
+ */
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Lambda<B: BoolAlg + ?Sized> {
+  /** x -> x */
   Id,
+  /** x -> c */
   Constant(B::Domain),
+  /** x -> the value paired with x, or x itself if it isn't in the table */
   Mapping(Vec<(B::Domain, B::Domain)>),
+  /** x -> the value of the first case x satisfies, or x itself if it satisfies none */
   Function(Vec<(Box<B>, B::Domain)>),
 }
 impl<B: BoolAlg> Lambda<B> {
   pub fn mapping(m: Vec<(B::Domain, B::Domain)>) -> Lambda<B> {
     Lambda::Mapping(m)
   }
+
+  pub fn function(cases: Vec<(B, B::Domain)>) -> Lambda<B> {
+    Lambda::Function(
+      cases
+        .into_iter()
+        .map(|(cond, value)| (Box::new(cond), value))
+        .collect(),
+    )
+  }
+
+  /** functional composition of next (self (x)) */
+  pub fn then(self, next: Self) -> Self {
+    next.compose(self)
+  }
+
+  /** apply self to each character of input */
+  pub fn apply_str(&self, input: &str) -> String {
+    input
+      .chars()
+      .map(|c| self.apply(&c.into()).clone().into())
+      .collect()
+  }
+
+  /** the cases of Function equivalent to self, for Mapping or Function */
+  fn cases(&self) -> Vec<(Box<B>, B::Domain)> {
+    match self {
+      Lambda::Mapping(map) => map
+        .iter()
+        .map(|(k, v)| (Box::new(B::char(k.clone())), v.clone()))
+        .collect(),
+      Lambda::Function(cases) => cases.clone(),
+      _ => unreachable!(),
+    }
+  }
 }
 impl<B> FunctionTerm for Lambda<B>
 where
@@ -67,21 +112,34 @@ where
     }
   }
 
+  /**
+   * characters other absorbs as identity are still transformed by self,
+   * so the cases of self follow the ones of other.
+   */
   fn compose(self, other: Self) -> Self {
     match (&self, &other) {
       (_, Lambda::Id) => self,
       (Lambda::Id, _) => other,
       (Lambda::Constant(_), _) => self,
       (f, Lambda::Constant(c)) => Lambda::Constant(f.apply(c).clone()),
-      (f, Lambda::Mapping(map)) => Lambda::Mapping(
-        map
-          .into_iter()
-          .map(|(k, v)| (k.clone(), f.apply(v).clone()))
+      (Lambda::Mapping(f), Lambda::Mapping(g)) => Lambda::Mapping(
+        g.iter()
+          .map(|(k, v)| (k.clone(), self.apply(v).clone()))
+          .chain(
+            f.iter()
+              .filter(|(k, _)| g.iter().all(|(k_, _)| k_ != k))
+              .cloned(),
+          )
           .collect(),
       ),
-      (f, Lambda::Function(g)) => Lambda::Function(
-        g.into_iter()
-          .map(|(phi, val)| (phi.clone(), f.apply(val).clone()))
+      (f, g) => Lambda::Function(
+        g.cases()
+          .into_iter()
+          .map(|(phi, val)| {
+            let val = f.apply(&val).clone();
+            (phi, val)
+          })
+          .chain(f.cases())
           .collect(),
       ),
     }
@@ -111,13 +169,29 @@ impl VariableImpl {
     VariableImpl(VAR_CNT.fetch_add(1, Ordering::SeqCst))
   }
 }
+/** loaded ids are never given to new variables, as for StateImpl */
+#[cfg(feature = "serde")]
+impl serde::Serialize for VariableImpl {
+  fn serialize<Ser: serde::Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+    serde::Serialize::serialize(&self.0, serializer)
+  }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VariableImpl {
+  fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+    let id: usize = serde::Deserialize::deserialize(deserializer)?;
+    VAR_CNT.fetch_max(id + 1, Ordering::SeqCst);
+    Ok(VariableImpl(id))
+  }
+}
 impl Debug for VariableImpl {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.write_fmt(format_args!("X({})", self.0))
   }
 }
 
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UpdateComp<F: FunctionTerm, V: Variable> {
   /** function term representation */
   F(F),
@@ -142,7 +216,8 @@ impl<D: Domain, V: Variable, F: FunctionTerm<Domain = D>> From<OutputComp<D, V>>
     }
   }
 }
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OutputComp<D: Domain, V: Variable> {
   /** domain character representation */
   A(D),
@@ -158,6 +233,72 @@ impl<D: Domain, V: Variable> Debug for OutputComp<D, V> {
   }
 }
 
+/** affine expression over integer registers, constant + sum of coefficient * register */
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Affine<V: Variable> {
+  constant: i64,
+  coefficients: Vec<(V, i64)>,
+}
+impl<V: Variable> Affine<V> {
+  pub fn constant(c: i64) -> Self {
+    Affine {
+      constant: c,
+      coefficients: vec![],
+    }
+  }
+
+  pub fn register(register: V) -> Self {
+    Affine {
+      constant: 0,
+      coefficients: vec![(register, 1)],
+    }
+  }
+
+  /** register + k */
+  pub fn increment(register: V, k: i64) -> Self {
+    Self::register(register) + Self::constant(k)
+  }
+
+  pub fn scale(mut self, k: i64) -> Self {
+    self.constant *= k;
+    self.coefficients.iter_mut().for_each(|(_, k_)| *k_ *= k);
+    self.coefficients.retain(|(_, k)| *k != 0);
+    self
+  }
+
+  /** the constant and the coefficients of registers */
+  pub(crate) fn terms(&self) -> (i64, &[(V, i64)]) {
+    (self.constant, &self.coefficients)
+  }
+
+  /** registers not in values are deal with as 0 */
+  pub fn eval(&self, values: &HashMap<V, i64>) -> i64 {
+    self
+      .coefficients
+      .iter()
+      .fold(self.constant, |acc, (var, k)| {
+        acc + k * values.get(var).unwrap_or(&0)
+      })
+  }
+}
+impl<V: Variable> std::ops::Add for Affine<V> {
+  type Output = Self;
+
+  fn add(mut self, other: Self) -> Self {
+    self.constant += other.constant;
+    for (var, k) in other.coefficients {
+      match self.coefficients.iter_mut().find(|(v, _)| *v == var) {
+        Some((_, k_)) => *k_ += k,
+        None => self.coefficients.push((var, k)),
+      }
+    }
+    self.coefficients.retain(|(_, k)| *k != 0);
+    self.coefficients.sort();
+    self
+  }
+}
+
 pub type FunctionTermImpl<T> = Lambda<Predicate<T>>;
 
 #[cfg(test)]
@@ -172,6 +313,45 @@ mod tests {
     iter::FromIterator
   };
 
+  #[test]
+  fn lambda() {
+    type L = FunctionTermImpl<char>;
+    let upper = L::function(vec![(Predicate::range(Some('a'), Some('{')), '*')]);
+    let swap = L::mapping(vec![('a', 'b'), ('b', 'a')]);
+
+    assert_eq!(L::identity().apply_str("ab#"), "ab#");
+    assert_eq!(L::constant('z').apply_str("ab"), "zz");
+    assert_eq!(swap.apply_str("abc"), "bac");
+    assert_eq!(upper.apply_str("aZ9"), "*Z9");
+
+    /* characters untouched by the first are still transformed by the next */
+    let swap_then_rot = swap.clone().then(L::mapping(vec![('b', 'c'), ('c', 'd')]));
+    assert_eq!(swap_then_rot.apply_str("abcd"), "cadd");
+    let swap_then_upper = swap.clone().then(upper.clone());
+    assert_eq!(swap_then_upper.apply_str("abZ"), "**Z");
+    let upper_then_swap = upper.then(swap);
+    assert_eq!(upper_then_swap.apply_str("a*Z"), "**Z");
+  }
+
+  #[test]
+  fn affine() {
+    let (r1, r2) = (VariableImpl::new(), VariableImpl::new());
+    let values = HashMap::from([(r1.clone(), 3), (r2.clone(), -2)]);
+
+    assert_eq!(Affine::<VariableImpl>::constant(5).eval(&values), 5);
+    assert_eq!(Affine::increment(r1.clone(), 1).eval(&values), 4);
+    assert_eq!(
+      (Affine::register(r1.clone()).scale(2) + Affine::register(r2.clone()) + Affine::constant(1))
+        .eval(&values),
+      5
+    );
+    assert_eq!(
+      Affine::register(r1.clone()) + Affine::register(r1.clone()).scale(-1),
+      Affine::constant(0)
+    );
+    assert_eq!(Affine::register(VariableImpl::new()).eval(&values), 0);
+  }
+
   #[test]
   fn new_var_is_new() {
     let var_1 = VariableImpl::new();
@@ -1,4 +1,8 @@
-use std::{fmt::Debug, hash::Hash};
+use std::{
+  collections::{hash_map::DefaultHasher, HashMap},
+  fmt::Debug,
+  hash::{Hash, Hasher},
+};
 
 pub trait Domain: Debug + Eq + Ord + Clone + Hash + From<char> + Into<char> {
   fn separator() -> Self;
@@ -10,6 +14,7 @@ impl Domain for char {
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CharWrap {
   Char(char),
   Separator,
@@ -42,9 +47,124 @@ impl Domain for CharWrap {
   }
 }
 
+/** hash deterministic in a process, used to order values without Ord */
+pub(crate) fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  value.hash(&mut hasher);
+  hasher.finish()
+}
+
+/** numbering of states in their order, used to serialize them */
+pub(crate) fn numbering<S: Ord + Hash>(states: impl IntoIterator<Item = S>) -> HashMap<S, usize> {
+  let mut states: Vec<S> = states.into_iter().collect();
+  states.sort();
+  states
+    .into_iter()
+    .enumerate()
+    .map(|(i, s)| (s, i))
+    .collect()
+}
+
+/**
+ * serde of a map as a sequence of pairs, for maps keyed by tuples which
+ * formats like JSON can't take as keys, e.g. #[serde(with = "crate::util::serde_pairs")]
+ */
+#[cfg(feature = "serde")]
+pub(crate) mod serde_pairs {
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+  use std::{collections::HashMap, hash::Hash};
+
+  pub(crate) fn serialize<K, V, Ser>(
+    map: &HashMap<K, V>,
+    serializer: Ser,
+  ) -> Result<Ser::Ok, Ser::Error>
+  where
+    K: Serialize,
+    V: Serialize,
+    Ser: Serializer,
+  {
+    serializer.collect_seq(map.iter())
+  }
+
+  pub(crate) fn deserialize<'de, K, V, De>(deserializer: De) -> Result<HashMap<K, V>, De::Error>
+  where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    De: Deserializer<'de>,
+  {
+    Vec::<(K, V)>::deserialize(deserializer).map(|pairs| pairs.into_iter().collect())
+  }
+}
+
+/**
+ * serde of transitions keyed by (state, predicate) through a PredicatePool,
+ * so that a predicate repeated over many transitions, e.g. all_char, is written once.
+ */
+#[cfg(feature = "serde")]
+pub(crate) mod serde_pooled {
+  use crate::boolean_algebra::{BoolAlg, PredicatePool};
+  use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+  use std::{collections::HashMap, hash::Hash};
+
+  #[derive(Serialize, Deserialize)]
+  struct Pooled<B, S, T> {
+    predicates: Vec<B>,
+    transition: Vec<(S, usize, T)>,
+  }
+
+  pub(crate) fn serialize<S, B, T, Ser>(
+    transition: &HashMap<(S, B), T>,
+    serializer: Ser,
+  ) -> Result<Ser::Ok, Ser::Error>
+  where
+    S: Serialize,
+    B: BoolAlg + Serialize,
+    T: Serialize,
+    Ser: Serializer,
+  {
+    let mut pool = PredicatePool::new();
+    let transition = transition
+      .iter()
+      .map(|((p, phi), target)| (p, pool.intern(B::clone(phi)), target))
+      .collect();
+    Pooled {
+      predicates: pool.iter().collect(),
+      transition,
+    }
+    .serialize(serializer)
+  }
+
+  pub(crate) fn deserialize<'de, S, B, T, De>(
+    deserializer: De,
+  ) -> Result<HashMap<(S, B), T>, De::Error>
+  where
+    S: Deserialize<'de> + Eq + Hash,
+    B: BoolAlg + Deserialize<'de>,
+    T: Deserialize<'de>,
+    De: Deserializer<'de>,
+  {
+    let Pooled {
+      predicates,
+      transition,
+    } = Pooled::<B, S, T>::deserialize(deserializer)?;
+    transition
+      .into_iter()
+      .map(|(p, idx, target)| match predicates.get(idx) {
+        Some(phi) => Ok(((p, B::clone(phi)), target)),
+        None => Err(De::Error::custom(format!("no predicate {} in the pool", idx))),
+      })
+      .collect()
+  }
+}
+
+/** escape a label of DOT */
+pub(crate) fn dot_escape(label: &str) -> String {
+  label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
 pub(crate) mod extention {
   use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, HashMap},
     default::Default,
     hash::Hash,
     iter::Extend,
@@ -113,28 +233,4 @@ pub(crate) mod extention {
       assert!(self.insert(key, value).is_none());
     }
   }
-
-  pub(crate) trait HashSetExt: std::marker::Sized {
-    /** expensive method */
-    fn subsets(&self) -> Vec<Self>;
-  }
-  impl<V: Clone + Hash + Eq> HashSetExt for HashSet<V> {
-    fn subsets(&self) -> Vec<Self> {
-      use std::convert::TryInto;
-      let mut subsets = vec![];
-
-      for i in 0..2u64.pow(self.len().try_into().unwrap()) {
-        let mut subset = HashSet::new();
-        self.iter().enumerate().for_each(|(idx, v)| {
-          if i & (1 << idx) != 0 {
-            subset.insert(v.clone());
-          }
-        });
-
-        subsets.push(subset);
-      }
-
-      subsets
-    }
-  }
 }
@@ -0,0 +1,95 @@
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_solver_with_symbolic"))
+    .args(args)
+    .output()
+    .unwrap()
+}
+
+#[test]
+fn invalid_arguments() {
+  for args in [
+    &["--bogus"][..],
+    &["--timeout", "soon"],
+    &["--strategy=none"],
+    &["demo", "--regex"],
+    &["demo", "--define", "x"],
+  ] {
+    let output = run(args);
+    assert_eq!(output.status.code(), Some(1), "{:?}", args);
+    assert!(output.stdout.is_empty(), "{:?}", args);
+    assert!(!output.stderr.is_empty(), "{:?}", args);
+  }
+}
+
+#[test]
+fn unreadable_file() {
+  let output = run(&["no/such/file.smt2"]);
+  assert_eq!(output.status.code(), Some(1));
+  assert!(output.stdout.is_empty());
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr.contains("no/such/file.smt2"), "{}", stderr);
+
+  /* a directory can be opened but not read */
+  assert_eq!(run(&["src"]).status.code(), Some(1));
+}
+
+#[test]
+fn ignored_assertion() {
+  let dir = std::env::temp_dir();
+  let input_path = dir.join(format!("ignored-{}.smt2", std::process::id()));
+  let input = r#"
+    (declare-const x String)
+    (assert (str.in.re x (str.to.re "b")))
+    (assert (str.< x "a"))
+    (check-sat)
+    "#;
+  std::fs::write(&input_path, input).unwrap();
+  let output = run(&[input_path.to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), "unknown\n");
+
+  std::fs::remove_file(&input_path).unwrap();
+}
+
+#[test]
+fn checkpoint_resume() {
+  let dir = std::env::temp_dir();
+  let input_path = dir.join(format!("resume-{}.smt2", std::process::id()));
+  let path = dir.join(format!("resume-{}.txt", std::process::id()));
+  let input = r#"
+    (declare-const x0 String)
+    (declare-const x1 String)
+    (assert (= x1 (str.++ x0 x0)))
+    (assert (str.in.re x1 (str.to.re "abab")))
+    (check-sat)
+    (get-model)
+    "#;
+  std::fs::write(&input_path, input).unwrap();
+  let args = [
+    "--checkpoint",
+    path.to_str().unwrap(),
+    input_path.to_str().unwrap(),
+  ];
+  let output = run(&args);
+  assert_eq!(output.status.code(), Some(10));
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert!(stdout.starts_with("sat\n"), "{}", stdout);
+  assert!(stdout.contains(r#"(define-fun x0 () String "ab")"#), "{}", stdout);
+
+  /* the key written by one process is the one of the script in another */
+  let text = std::fs::read_to_string(&path).unwrap();
+  let header = text.lines().next().unwrap();
+  let key = solver_with_symbolic::checkpoint::key(input);
+  assert!(header.starts_with(&format!("checkpoint {:x} 0 ", key)), "{}", header);
+
+  /* so the next run resumes from the checkpoint, here of no words */
+  std::fs::write(&path, format!("{}\nstates 1 0\nfinal\n", header)).unwrap();
+  let output = run(&args);
+  assert_eq!(output.status.code(), Some(20));
+  assert!(String::from_utf8(output.stdout).unwrap().starts_with("unsat\n"));
+
+  std::fs::remove_file(&input_path).unwrap();
+  std::fs::remove_file(&path).unwrap();
+}